@@ -0,0 +1,151 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use wgpu::*;
+
+/// The name of the config file the graphics config is loaded from.
+pub const GRAPHICS_CONFIG_FILE: &str = "graphics.toml";
+
+/// A graphics API the renderer can draw with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphicsBackend {
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+impl GraphicsBackend {
+    /// Returns the wgpu backends of the API.
+    pub fn backends(self) -> Backends {
+        match self {
+            Self::Vulkan => Backends::VULKAN,
+            Self::Dx12 => Backends::DX12,
+            Self::Metal => Backends::METAL,
+            Self::Gl => Backends::GL,
+        }
+    }
+}
+
+impl FromStr for GraphicsBackend {
+    type Err = anyhow::Error;
+
+    /// Parses a backend from its name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vulkan" => Ok(Self::Vulkan),
+            "dx12" => Ok(Self::Dx12),
+            "metal" => Ok(Self::Metal),
+            "gl" => Ok(Self::Gl),
+            _ => bail!("unknown backend, '{s}' (expected vulkan, dx12, metal or gl)"),
+        }
+    }
+}
+
+/// Which kind of GPU is preferred when several are available.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GpuPreference {
+    /// The fastest GPU, usually a discrete one.
+    #[default]
+    HighPerformance,
+    /// The GPU using the least power, usually one integrated into the CPU.
+    LowPower,
+}
+
+impl FromStr for GpuPreference {
+    type Err = anyhow::Error;
+
+    /// Parses a preference from its name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "high-performance" => Ok(Self::HighPerformance),
+            "low-power" => Ok(Self::LowPower),
+            _ => bail!("unknown power preference, '{s}' (expected high-performance or low-power)"),
+        }
+    }
+}
+
+/// Options for which GPU the renderer draws with, loaded from `GRAPHICS_CONFIG_FILE`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsConfig {
+    /// The graphics API to draw with, or None to use whichever is available.
+    pub backend: Option<GraphicsBackend>,
+    /// Which kind of GPU is picked, unless an `adapter` is chosen.
+    pub power_preference: GpuPreference,
+    /// The index of the GPU to draw with, as listed by `--list-gpus`.
+    pub adapter: Option<usize>,
+}
+
+impl GraphicsConfig {
+    /// Returns the wgpu backends adapters may be picked from.
+    pub fn backends(&self) -> Backends {
+        self.backend
+            .map_or(Backends::all(), GraphicsBackend::backends)
+    }
+}
+
+/// Prints every GPU available to the backends of the config, along with the index it is
+/// chosen by.
+pub fn list_adapters(config: &GraphicsConfig) {
+    let instance = Instance::new(InstanceDescriptor {
+        backends: config.backends(),
+        ..Default::default()
+    });
+
+    let adapters = instance.enumerate_adapters(config.backends());
+
+    if adapters.is_empty() {
+        println!("no gpus found");
+    }
+
+    for (index, adapter) in adapters.iter().enumerate() {
+        let info = adapter.get_info();
+        println!(
+            "{index}: {} ({:?}, {:?})",
+            info.name, info.device_type, info.backend
+        );
+    }
+}
+
+/// Picks the adapter the renderer draws onto the surface with: the one at the config's index,
+/// or otherwise the one best matching its power preference.
+pub async fn select_adapter(
+    instance: &Instance,
+    surface: &Surface<'_>,
+    config: &GraphicsConfig,
+) -> anyhow::Result<Adapter> {
+    if let Some(index) = config.adapter {
+        let adapter = instance
+            .enumerate_adapters(config.backends())
+            .into_iter()
+            .nth(index)
+            .with_context(|| format!("there is no gpu {index} (see --list-gpus)"))?;
+
+        if !adapter.is_surface_supported(surface) {
+            bail!(
+                "gpu {index} ({}) can't draw onto the window",
+                adapter.get_info().name
+            );
+        }
+
+        return Ok(adapter);
+    }
+
+    let power_preference = match config.power_preference {
+        GpuPreference::HighPerformance => PowerPreference::HighPerformance,
+        GpuPreference::LowPower => PowerPreference::LowPower,
+    };
+
+    instance
+        .request_adapter(&RequestAdapterOptions {
+            power_preference,
+            force_fallback_adapter: false,
+            compatible_surface: Some(surface),
+        })
+        .await
+        .context("no gpu can draw onto the window")
+}
@@ -0,0 +1,441 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
+};
+
+use anyhow::{bail, Context};
+use fastnbt::LongArray;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use glam::ivec2;
+use rayon::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    chunk::*,
+    dimension::Dimension,
+    storage::{self, Compression},
+};
+
+/// The size of a sector of a region file, in bytes. Chunks are stored in whole sectors.
+const SECTOR_SIZE: usize = 4096;
+
+/// How many chunks wide a region file is.
+const REGION_WIDTH: usize = 32;
+
+/// The first data version whose packed block states don't span across longs (20w17a).
+const NON_SPANNING_DATA_VERSION: i32 = 2527;
+
+/// The first data version to store block states in palettes (17w47a). Older chunks use numeric
+/// block ids, which aren't supported.
+const PALETTE_DATA_VERSION: i32 = 1451;
+
+/// How many blocks there are in a section of a Minecraft chunk.
+const SECTION_VOLUME: usize = CHUNK_WIDTH * CHUNK_WIDTH * SECTION_HEIGHT;
+
+/// The NBT data of a Minecraft chunk, in either the current (1.18+) layout, or the older one
+/// with everything nested in `Level`.
+#[derive(Debug, Deserialize)]
+struct ChunkData {
+    #[serde(rename = "DataVersion")]
+    data_version: Option<i32>,
+    #[serde(rename = "xPos")]
+    x: Option<i32>,
+    #[serde(rename = "zPos")]
+    z: Option<i32>,
+    #[serde(rename = "Status")]
+    status: Option<String>,
+    sections: Option<Vec<SectionData>>,
+    #[serde(rename = "Level")]
+    level: Option<LevelData>,
+}
+
+/// The contents of a chunk in the pre-1.18 layout.
+#[derive(Debug, Deserialize)]
+struct LevelData {
+    #[serde(rename = "xPos")]
+    x: i32,
+    #[serde(rename = "zPos")]
+    z: i32,
+    #[serde(rename = "Status")]
+    status: Option<String>,
+    #[serde(rename = "Sections", default)]
+    sections: Vec<LegacySectionData>,
+}
+
+/// A 16 block tall section of a chunk, in the 1.18+ layout.
+#[derive(Debug, Deserialize)]
+struct SectionData {
+    #[serde(rename = "Y")]
+    y: i8,
+    block_states: Option<BlockStates>,
+}
+
+/// The blocks of a section, in the 1.18+ layout.
+#[derive(Debug, Deserialize)]
+struct BlockStates {
+    palette: Vec<BlockState>,
+    data: Option<LongArray>,
+}
+
+/// A 16 block tall section of a chunk, in the pre-1.18 layout.
+#[derive(Debug, Deserialize)]
+struct LegacySectionData {
+    #[serde(rename = "Y")]
+    y: i8,
+    #[serde(rename = "Palette")]
+    palette: Option<Vec<BlockState>>,
+    #[serde(rename = "BlockStates")]
+    block_states: Option<LongArray>,
+}
+
+/// An entry of a section's block palette.
+#[derive(Debug, Deserialize)]
+struct BlockState {
+    #[serde(rename = "Name")]
+    name: String,
+}
+
+/// The blocks of a Minecraft chunk section, as indices into its palette.
+struct Section<'a> {
+    /// The y coordinate of the section, in sections.
+    y: i32,
+    /// The blocks that can appear in the section.
+    palette: &'a [BlockState],
+    /// The packed palette indices of the section's blocks, or None if there is only one block
+    /// in the palette.
+    data: Option<&'a [i64]>,
+}
+
+/// The number of chunks converted and skipped, and how often each unknown block was seen.
+#[derive(Default)]
+struct ImportStats {
+    converted: usize,
+    skipped: usize,
+    unknown_blocks: HashMap<String, usize>,
+}
+
+/// Converts every chunk of the Minecraft region (`.mca`) files in `region_directory` and saves
+/// them as chunks of the given dimension of the world in `world_directory`, printing progress
+/// along the way. Minecraft blocks are mapped onto the closest voxel, with blocks that have no
+/// counterpart becoming stone, and are shifted up by `y_offset` (blocks that end up outside of
+/// the chunk are cut off). Chunks that Minecraft hadn't finished generating are skipped.
+pub fn import(
+    region_directory: &Path,
+    world_directory: &Path,
+    dimension: Dimension,
+    compression: Compression,
+    y_offset: i32,
+) -> anyhow::Result<()> {
+    let entries =
+        fs::read_dir(region_directory).context(format!("reading {region_directory:?}"))?;
+
+    let regions = entries
+        .filter_map(|entry| entry.map(|entry| entry.path()).ok())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "mca"))
+        .collect::<Vec<PathBuf>>();
+
+    let start = Instant::now();
+    let stats = Mutex::new(ImportStats::default());
+
+    println!("importing {} regions", regions.len());
+
+    regions.par_iter().try_for_each(|path| {
+        let chunks = read_region(path).context(format!("reading {path:?}"))?;
+
+        for nbt in chunks {
+            let chunk = convert_chunk(&nbt, y_offset, &stats);
+
+            match chunk {
+                Ok(Some(chunk)) => {
+                    storage::write_chunk(world_directory, dimension, &chunk, compression)?;
+                    stats.lock().unwrap().converted += 1;
+                }
+                Ok(None) => stats.lock().unwrap().skipped += 1,
+                Err(error) => {
+                    eprintln!("could not convert chunk in {path:?}: {error:?}");
+                    stats.lock().unwrap().skipped += 1;
+                }
+            }
+        }
+
+        println!("imported {path:?}");
+
+        anyhow::Ok(())
+    })?;
+
+    let stats = stats.into_inner().unwrap();
+
+    println!(
+        "converted {} chunks ({} skipped) in {:.2}s",
+        stats.converted,
+        stats.skipped,
+        start.elapsed().as_secs_f32()
+    );
+
+    if !stats.unknown_blocks.is_empty() {
+        let mut unknown_blocks = stats.unknown_blocks.into_iter().collect::<Vec<_>>();
+        unknown_blocks.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+        println!("unknown blocks (converted to stone):");
+
+        for (name, count) in unknown_blocks {
+            println!("  {name}: {count}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the decompressed NBT data of every chunk stored in a region file.
+fn read_region(path: &Path) -> anyhow::Result<Vec<Vec<u8>>> {
+    let bytes = fs::read(path)?;
+
+    if bytes.len() < SECTOR_SIZE {
+        bail!("region file is missing its header");
+    }
+
+    let mut chunks = Vec::new();
+
+    for index in 0..REGION_WIDTH * REGION_WIDTH {
+        let location = &bytes[index * 4..index * 4 + 4];
+        let offset = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize;
+
+        // chunks that have never been generated have no location
+        if offset == 0 || location[3] == 0 {
+            continue;
+        }
+
+        let start = offset * SECTOR_SIZE;
+        let Some(header) = bytes.get(start..start + 5) else {
+            bail!("chunk {index} lies past the end of the file");
+        };
+
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let Some(data) = bytes.get(start + 5..start + 4 + length) else {
+            bail!("chunk {index} is cut off");
+        };
+
+        let mut nbt = Vec::new();
+
+        match header[4] {
+            1 => GzDecoder::new(data).read_to_end(&mut nbt)?,
+            2 => ZlibDecoder::new(data).read_to_end(&mut nbt)?,
+            3 => {
+                nbt.extend_from_slice(data);
+                nbt.len()
+            }
+            compression => bail!("chunk {index} uses unsupported compression type {compression}"),
+        };
+
+        chunks.push(nbt);
+    }
+
+    Ok(chunks)
+}
+
+/// Converts the NBT data of a Minecraft chunk into a chunk, or returns None if the chunk hadn't
+/// finished generating. Unknown blocks are counted in `stats`. The chunk is marked as decorated,
+/// as its terrain is already final.
+fn convert_chunk(
+    nbt: &[u8],
+    y_offset: i32,
+    stats: &Mutex<ImportStats>,
+) -> anyhow::Result<Option<Chunk>> {
+    let data: ChunkData = fastnbt::from_bytes(nbt)?;
+    let data_version = data.data_version.unwrap_or(0);
+
+    if data_version < PALETTE_DATA_VERSION {
+        bail!("chunks older than Minecraft 1.13 are not supported");
+    }
+
+    let (x, z, status, sections) = match &data.level {
+        Some(level) => (
+            level.x,
+            level.z,
+            level.status.as_deref(),
+            level
+                .sections
+                .iter()
+                .filter_map(|section| {
+                    Some(Section {
+                        y: section.y as i32,
+                        palette: section.palette.as_deref()?,
+                        data: section.block_states.as_deref(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        ),
+        None => (
+            data.x.context("chunk has no x position")?,
+            data.z.context("chunk has no z position")?,
+            data.status.as_deref(),
+            data.sections
+                .iter()
+                .flatten()
+                .filter_map(|section| {
+                    let block_states = section.block_states.as_ref()?;
+
+                    Some(Section {
+                        y: section.y as i32,
+                        palette: &block_states.palette,
+                        data: block_states.data.as_deref(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        ),
+    };
+
+    if status.is_some_and(|status| status.trim_start_matches("minecraft:") != "full") {
+        return Ok(None);
+    }
+
+    let mut chunk = Chunk::new(ivec2(x, z));
+    // trees and structures would otherwise be placed over the imported builds once loaded
+    chunk.decorated = true;
+
+    let mut unknown_blocks = HashMap::<&str, usize>::new();
+
+    for section in &sections {
+        let voxels = section
+            .palette
+            .iter()
+            .map(|state| voxel_for_block(&state.name))
+            .collect::<Vec<_>>();
+
+        let indices = unpack_indices(section, data_version)?;
+
+        for (index, palette_index) in indices.into_iter().enumerate() {
+            let Some(voxel) = voxels.get(palette_index) else {
+                bail!("block state index {palette_index} is outside of the palette");
+            };
+
+            let voxel = voxel.unwrap_or_else(|| {
+                *unknown_blocks
+                    .entry(&section.palette[palette_index].name)
+                    .or_default() += 1;
+
+                Voxel::Stone
+            });
+
+            if voxel == Voxel::Air {
+                continue;
+            }
+
+            let y = section.y * SECTION_HEIGHT as i32 + (index / 256) as i32 + y_offset;
+
+            if !(0..CHUNK_HEIGHT as i32).contains(&y) {
+                continue;
+            }
+
+            chunk.set_voxel([index % 16, y as usize, index / 16 % 16], voxel);
+        }
+    }
+
+    if !unknown_blocks.is_empty() {
+        let mut stats = stats.lock().unwrap();
+
+        for (name, count) in unknown_blocks {
+            *stats.unknown_blocks.entry(name.to_string()).or_default() += count;
+        }
+    }
+
+    Ok(Some(chunk))
+}
+
+/// Unpacks the palette index of each block of a section, indexed by `(y * 16 + z) * 16 + x`.
+/// Before `NON_SPANNING_DATA_VERSION`, indices could be split across two longs.
+fn unpack_indices(section: &Section, data_version: i32) -> anyhow::Result<Vec<usize>> {
+    let Some(data) = section.data else {
+        return Ok(vec![0; SECTION_VOLUME]);
+    };
+
+    let bits = (usize::BITS - (section.palette.len().max(1) - 1).leading_zeros()).max(4) as usize;
+    let mask = (1u64 << bits) - 1;
+
+    if data_version >= NON_SPANNING_DATA_VERSION {
+        let per_long = 64 / bits;
+
+        if data.len() < SECTION_VOLUME.div_ceil(per_long) {
+            bail!("section has too few block states");
+        }
+
+        return Ok((0..SECTION_VOLUME)
+            .map(|index| {
+                let long = data[index / per_long] as u64;
+                ((long >> (index % per_long * bits)) & mask) as usize
+            })
+            .collect());
+    }
+
+    if data.len() * 64 < SECTION_VOLUME * bits {
+        bail!("section has too few block states");
+    }
+
+    Ok((0..SECTION_VOLUME)
+        .map(|index| {
+            let bit = index * bits;
+            let (long, shift) = (bit / 64, bit % 64);
+            let mut value = data[long] as u64 >> shift;
+
+            if shift + bits > 64 {
+                value |= (data[long + 1] as u64) << (64 - shift);
+            }
+
+            (value & mask) as usize
+        })
+        .collect())
+}
+
+/// Returns the voxel closest to the Minecraft block with the given name, or None if there isn't
+/// one. Plants and other small decorations are dropped, becoming air.
+fn voxel_for_block(name: &str) -> Option<Voxel> {
+    let name = name.trim_start_matches("minecraft:");
+
+    let voxel = match name {
+        "air" | "cave_air" | "void_air" | "grass" | "short_grass" | "tall_grass" | "fern"
+        | "large_fern" | "dead_bush" | "snow" | "vine" | "sugar_cane" | "cactus" | "torch"
+        | "wall_torch" => Voxel::Air,
+        "grass_block" | "mycelium" | "moss_block" => Voxel::Grass,
+        "dirt" | "coarse_dirt" | "rooted_dirt" | "podzol" | "farmland" | "dirt_path" | "mud"
+        | "clay" => Voxel::Dirt,
+        "stone" | "granite" | "diorite" | "andesite" | "deepslate" | "tuff" | "calcite"
+        | "cobblestone" | "mossy_cobblestone" | "cobbled_deepslate" | "gravel" | "obsidian"
+        | "smooth_stone" | "stone_bricks" | "mossy_stone_bricks" | "bricks" => Voxel::Stone,
+        "snow_block" | "powder_snow" | "ice" | "packed_ice" | "blue_ice" => Voxel::Snow,
+        "water" | "bubble_column" | "seagrass" | "tall_seagrass" | "kelp" | "kelp_plant" => {
+            Voxel::Water
+        }
+        "sand" | "red_sand" | "suspicious_sand" => Voxel::Sand,
+        "bedrock" => Voxel::Bedrock,
+        name if name.ends_with("_ore") => Voxel::Stone,
+        name if name.ends_with("sandstone") => Voxel::Sandstone,
+        name if name.ends_with("_leaves") => Voxel::Leaves,
+        "crimson_stem" | "warped_stem" => Voxel::Log,
+        name if ["_log", "_wood", "_hyphae", "_planks"]
+            .iter()
+            .any(|suffix| name.ends_with(suffix)) =>
+        {
+            Voxel::Log
+        }
+        name if [
+            "_flower",
+            "_sapling",
+            "_mushroom",
+            "_tulip",
+            "_carpet",
+            "_button",
+        ]
+        .iter()
+        .any(|suffix| name.ends_with(suffix)) =>
+        {
+            Voxel::Air
+        }
+        _ => return None,
+    };
+
+    Some(voxel)
+}
@@ -1,69 +1,430 @@
-use std::{collections::HashSet, sync::Arc, time::Instant};
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use egui::Context;
 use glam::*;
 use wgpu::SurfaceError;
 use winit::{
-    event::{DeviceEvent, ElementState, Event, KeyEvent, WindowEvent},
+    event::{
+        DeviceEvent, ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent,
+    },
     event_loop::EventLoopWindowTarget,
     keyboard::{KeyCode, PhysicalKey},
-    window::{CursorGrabMode, Window},
+    window::{CursorGrabMode, Fullscreen, Window},
 };
 
-use crate::{camera::Camera, chunk_manager::ChunkManager, renderer::Renderer};
+use crate::{
+    adapter::GraphicsConfig,
+    camera::{Camera, CAMERA_CONFIG_FILE, FOV_RANGE, REACH_RANGE},
+    chunk::{Voxel, CHUNK_HEIGHT, CHUNK_WIDTH},
+    chunk_manager::{ChunkManager, ChunkManagerConfig, CHUNK_MANAGER_CONFIG_FILE},
+    config,
+    console::{self, Console, ConsoleCommand},
+    debug_lines,
+    dimension::Dimension,
+    environment::Environment,
+    game_mode::GameMode,
+    generator::WorldgenConfig,
+    gpu_memory::MemoryCategory,
+    keybindings::{Action, Keybindings},
+    lighting::PointLight,
+    mesher::{self, MeshLayer},
+    model::{InstancedMesh, ModelInstance},
+    physics::{Player, PLAYER_CONFIG_FILE, SNEAK_SPEED, SPRINT_SPEED, SWIM_SPEED, WALK_SPEED},
+    post,
+    preview::WorldgenPreview,
+    raycast::RaycastHit,
+    recording::{RecordingConfig, RECORDING_CONFIG_FILE},
+    renderer::{RenderSettings, RenderStats, Renderer},
+    storage::{Compression, WorldInfo},
+    window_state::{WindowState, WINDOW_STATE_FILE},
+};
 
 use anyhow::Result;
 
+/// The radius (in chunks) around the spawn point that must be generated and meshed before the
+/// player can start moving.
+pub const SPAWN_AREA_RADIUS: usize = 3;
+
+/// How often chunks modified since they were last saved are written to disk.
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(120);
+
+/// The tint of the ghost of the block about to be placed: a generic foliage green, with an
+/// alpha of how opaque the ghost is.
+const GHOST_TINT: u32 = u32::from_le_bytes([110, 170, 70, 128]);
+/// How large the ghost is drawn relative to a voxel. Slightly smaller, so its faces don't
+/// flicker against those of the voxels next to it.
+const GHOST_SCALE: f32 = 0.98;
+
+/// The light carried by the camera when the lantern is lit from the render settings.
+const LANTERN_COLOR: Vec3 = vec3(1.5, 1.1, 0.6);
+const LANTERN_RADIUS: f32 = 12.0;
+
 /// The main application struct that holds all the data and state of the
 /// application.
 pub struct App {
     /// The window being rendered onto.
     window: Arc<winit::window::Window>,
+    /// The state of the window and the world open in it, saved on exit to be restored on the
+    /// next launch.
+    window_state: WindowState,
     /// The renderer responsible for interacting with wgpu and setting up the
     /// rendering device, and drawing out a scene.
     renderer: crate::renderer::Renderer,
     /// The camera in 3d space representing the player.
     camera: crate::camera::Camera,
+    /// The body of the player, which the camera follows unless they are flying.
+    player: Player,
+    /// Whether the player can fly and break voxels instantly, switched from the console.
+    game_mode: GameMode,
+    /// The voxel placed with right click, chosen from the hotbar.
+    selected_voxel: Voxel,
+    /// A translucent model of the selected voxel at the position right click would place it,
+    /// along with that position and voxel, while there is one.
+    ghost: Option<(IVec3, Voxel, InstancedMesh)>,
+    /// Whether left click is held to break voxels, which takes time outside of creative.
+    breaking: bool,
+    /// The voxel being broken by holding left click, along with how long it has been held on
+    /// it for, in seconds.
+    break_progress: Option<(IVec3, f32)>,
+    /// The lighting of the world.
+    environment: Environment,
+    /// The effects the renderer draws, changed from the render settings panel.
+    render_settings: RenderSettings,
 
     /// Represents whether the app is currently in focus and locked or not.
     has_focus: bool,
+    /// Whether the window is minimized (sized to zero), during which nothing is drawn, as there
+    /// is no surface to draw onto.
+    minimized: bool,
+    /// The fraction of the spawn area that has been loaded, while the player is waiting for it
+    /// to load. None once the spawn area has finished loading.
+    spawn_progress: Option<f32>,
 
     /// All the keys currently being held down.
     keys_held: HashSet<KeyCode>,
+    /// The keys bound to each action.
+    keybindings: Keybindings,
 
     /// The time of the last rendering frame.
     last_frame: std::time::Instant,
+    /// The options frames are recorded with, when recording is started with F9.
+    recording_config: RecordingConfig,
+    /// The time modified chunks were last saved.
+    last_autosave: std::time::Instant,
 
     /// The chunk manager used to manage chunks around the player.
     chunk_manager: crate::chunk_manager::ChunkManager,
+    /// The preview of the terrain around the origin, while it is open.
+    preview: Option<WorldgenPreview>,
+    /// The console commands are typed into, while the cursor is free.
+    console: Console,
+}
+
+/// The measurements of the frame being drawn, shown in the debug window.
+struct FrameStats<'a> {
+    /// The number of frames drawn per second.
+    fps: f32,
+    /// What the renderer measured while drawing the frame.
+    render_stats: &'a RenderStats<'a>,
 }
 
 impl App {
-    /// Sets up the renderer and camera.
-    pub async fn new(window: Arc<Window>) -> Result<Self> {
+    /// Sets up the renderer (drawing with the GPU chosen by the graphics config) and camera, and
+    /// the chunk manager generating the world described by the given metadata, saved in the
+    /// world directory of the window state.
+    pub async fn new(
+        window: Arc<Window>,
+        graphics: GraphicsConfig,
+        world: WorldInfo,
+        window_state: WindowState,
+    ) -> Result<Self> {
         let camera = Camera::new(
-            vec3(-33.0, 20.0, 50.0),
+            dvec3(-33.0, 20.0, 50.0),
             180.0f32.to_radians(),
             0.0,
             window.inner_size(),
+            config::load_or_default(CAMERA_CONFIG_FILE)?,
         );
 
-        let renderer = Renderer::new(Arc::clone(&window), &camera).await?;
+        let chunk_manager_config: ChunkManagerConfig =
+            config::load_or_default(CHUNK_MANAGER_CONFIG_FILE)?;
+
+        // the fog thickens towards the edge of the loaded chunks, hiding where they stop
+        let environment = Environment {
+            fog_density: Environment::fog_density_for(
+                (chunk_manager_config.load_radius * CHUNK_WIDTH) as f32,
+            ),
+            ..Default::default()
+        };
+        let renderer = Renderer::new(Arc::clone(&window), graphics, &camera, &environment).await?;
+        let render_settings = RenderSettings {
+            supported_present_modes: renderer.present_modes().to_vec(),
+            ..Default::default()
+        };
+
+        let chunk_manager = ChunkManager::new(
+            chunk_manager_config,
+            world,
+            WorldgenConfig::load()?,
+            window_state.world.clone(),
+            Compression::default(),
+        );
 
         Ok(Self {
             window,
+            window_state,
             renderer,
+            player: Player::new(camera.eye, config::load_or_default(PLAYER_CONFIG_FILE)?),
+            game_mode: GameMode::default(),
+            selected_voxel: Voxel::PLACEABLE[0],
+            ghost: None,
+            breaking: false,
+            break_progress: None,
             camera,
+            environment,
+            render_settings,
             has_focus: false,
+            minimized: false,
+            spawn_progress: Some(0.0),
             keys_held: HashSet::new(),
+            keybindings: Keybindings::load()?,
             last_frame: Instant::now(),
-            chunk_manager: ChunkManager::new(),
+            recording_config: config::load_or_default(RECORDING_CONFIG_FILE)?,
+            last_autosave: Instant::now(),
+            chunk_manager,
+            preview: None,
+            console: Console::default(),
         })
     }
 
-    /// Returns the time elapsed since the last frame, in seconds
+    /// Sends the point lights around the camera to the renderer.
+    fn update_lights(&mut self) {
+        let lantern = self.render_settings.lantern.then_some(PointLight {
+            position: self.camera.eye,
+            color: LANTERN_COLOR,
+            radius: LANTERN_RADIUS,
+        });
+
+        self.renderer
+            .update_lights(lantern.as_slice(), self.camera.eye);
+    }
+
+    /// Returns the voxel the camera is looking at, if there is one within reach.
+    fn targeted_block(&self) -> Option<RaycastHit> {
+        self.chunk_manager.raycast(
+            self.camera.eye,
+            self.camera.forward,
+            self.camera.config.reach(),
+        )
+    }
+
+    /// Outlines the voxel the camera is looking at, if there is one within reach.
+    fn update_outline(&mut self) {
+        let target = match self.spawn_progress {
+            Some(_) => None,
+            None => self.targeted_block().map(|hit| hit.position),
+        };
+
+        self.renderer.update_outline(target, self.camera.eye);
+    }
+
+    /// Breaks the voxel the camera is looking at, if there is one within reach.
+    fn break_targeted_block(&mut self) {
+        if let Some(hit) = self.targeted_block() {
+            self.chunk_manager.break_block(hit.position);
+        }
+    }
+
+    /// Breaks the voxel the camera is looking at once left click has been held on it for as long
+    /// as its hardness, starting over whenever the player looks at another voxel.
+    fn update_breaking(&mut self, dt: f32) {
+        let target = match self.breaking {
+            true => self.targeted_block().map(|hit| hit.position),
+            false => None,
+        };
+
+        let Some(position) = target else {
+            self.break_progress = None;
+            return;
+        };
+
+        let held = match self.break_progress {
+            Some((previous, held)) if previous == position => held + dt,
+            _ => dt,
+        };
+        let hardness = self
+            .chunk_manager
+            .get_block(position)
+            .map_or(f32::INFINITY, |voxel| voxel.hardness());
+
+        if held >= hardness {
+            self.chunk_manager.break_block(position);
+            self.break_progress = None;
+        } else {
+            self.break_progress = Some((position, held));
+        }
+    }
+
+    /// Returns the fraction of the targeted voxel broken so far by holding left click, if it is
+    /// being broken.
+    fn break_fraction(&self) -> Option<f32> {
+        let (position, held) = self.break_progress?;
+        let hardness = self.chunk_manager.get_block(position)?.hardness();
+
+        Some(held / hardness)
+    }
+
+    /// Places the selected voxel at the `App::placement_target`, if there is one.
+    fn place_selected_block(&mut self) {
+        if let Some(position) = self.placement_target() {
+            self.chunk_manager.set_block(position, self.selected_voxel);
+        }
+    }
+
+    /// Returns where the selected voxel would be placed: against the face of the voxel the
+    /// camera is looking at, if there is one within reach, unless it would overlap the player.
+    fn placement_target(&self) -> Option<IVec3> {
+        let position = self.targeted_block().and_then(|hit| hit.adjacent())?;
+
+        let replaceable = self
+            .chunk_manager
+            .get_block(position)
+            .is_some_and(|voxel| voxel.is_replaceable());
+
+        // water doesn't block the player, so it can be placed anywhere
+        let blocks_player =
+            self.selected_voxel.is_solid() && self.player.intersects_voxel(position);
+
+        (replaceable && !blocks_player).then_some(position)
+    }
+
+    /// Moves the ghost of the selected voxel to where it would be placed, building its mesh
+    /// again only when the selected voxel changes.
+    fn update_ghost(&mut self) {
+        let target = match self.spawn_progress {
+            Some(_) => None,
+            None => self
+                .placement_target()
+                .map(|position| (position, self.selected_voxel)),
+        };
+
+        if let (Some((position, voxel, _)), Some((target_position, target_voxel))) =
+            (&mut self.ghost, target)
+        {
+            if *voxel == target_voxel {
+                *position = target_position;
+                return;
+            }
+        }
+
+        self.ghost = target.and_then(|(position, voxel)| {
+            let (vertices, indices) = mesher::single_voxel_mesh(voxel, 1.0, GHOST_TINT);
+            let renderer = &self.renderer;
+
+            match InstancedMesh::new(
+                &vertices,
+                &indices,
+                &renderer.mesh_pool,
+                &renderer.device,
+                &renderer.queue,
+            ) {
+                Ok(mesh) => Some((position, voxel, mesh)),
+                Err(error) => {
+                    eprintln!("could not upload the ghost block: {error:?}");
+                    None
+                }
+            }
+        });
+    }
+
+    /// Selects the voxel the camera is looking at in the hotbar, if there is one within reach
+    /// that can be placed.
+    fn pick_targeted_block(&mut self) {
+        let target = self
+            .targeted_block()
+            .and_then(|hit| self.chunk_manager.get_block(hit.position));
+
+        if let Some(voxel) = target.filter(|voxel| Voxel::PLACEABLE.contains(voxel)) {
+            self.selected_voxel = voxel;
+        }
+    }
+
+    /// Selects the voxel `steps` places along the hotbar from the selected one, wrapping around
+    /// at either end.
+    fn cycle_selected_voxel(&mut self, steps: i32) {
+        let count = Voxel::PLACEABLE.len() as i32;
+        let index = Voxel::PLACEABLE
+            .iter()
+            .position(|&voxel| voxel == self.selected_voxel)
+            .unwrap_or(0) as i32;
+
+        self.selected_voxel = Voxel::PLACEABLE[(index + steps).rem_euclid(count) as usize];
+    }
+
+    /// Sends the debug lines enabled in the render settings to the renderer.
+    fn update_debug_lines(&mut self) {
+        let eye = self.camera.eye;
+        let player_voxel = eye.round().as_ivec3();
+        let player_chunk = player_voxel
+            .xz()
+            .div_euclid(IVec2::splat(CHUNK_WIDTH as i32));
+
+        let mut lines = Vec::new();
+
+        if self.render_settings.chunk_borders {
+            lines.extend(debug_lines::chunk_borders(player_chunk));
+        }
+
+        if self.render_settings.chunk_load_states {
+            // just below the camera, so the squares are seen from above
+            let y = (player_voxel.y - 1).max(0) as f32;
+
+            lines.extend(debug_lines::chunk_load_states(player_chunk, y, |chunk| {
+                self.chunk_manager.load_state(chunk)
+            }));
+        }
+
+        self.renderer.update_debug_lines(&lines, eye);
+    }
+
+    /// Saves the state of the window, so it is opened the same way on the next launch.
+    fn save_window_state(&mut self) {
+        self.window_state.update(&self.window);
+
+        if let Err(error) = config::save(WINDOW_STATE_FILE, &self.window_state) {
+            eprintln!("could not save the window state: {error:?}");
+        }
+    }
+
+    /// Replaces the renderer with one on a new device once its device has been lost, uploading
+    /// the meshes of the loaded chunks again.
+    fn recover_lost_device(&mut self) -> Result<()> {
+        eprintln!("recreating the renderer on a new device");
+
+        self.renderer = pollster::block_on(self.renderer.recreate(
+            Arc::clone(&self.window),
+            &self.camera,
+            &self.environment,
+        ))?;
+        self.render_settings.supported_present_modes = self.renderer.present_modes().to_vec();
+        self.chunk_manager.discard_meshes();
+        self.ghost = None;
+
+        Ok(())
+    }
+
+    /// Returns the time elapsed since the last frame, in seconds. While recording, every frame
+    /// is a fixed step apart instead, so motion in the recording is smooth however long frames
+    /// take to capture.
     fn delta_time(&self) -> f32 {
-        (Instant::now() - self.last_frame).as_secs_f32()
+        match self.renderer.is_recording() {
+            true => 1.0 / self.recording_config.frame_rate as f32,
+            false => (Instant::now() - self.last_frame).as_secs_f32(),
+        }
     }
 
     /// Updates the app with the latest input state, and renders
@@ -75,25 +436,69 @@ impl App {
             }
 
             Event::WindowEvent { event, .. } => match event {
+                // minimizing resizes the window to zero, which the surface can't be configured
+                // to, so drawing is suspended until it is restored to its size
+                WindowEvent::Resized(size) if size.width == 0 || size.height == 0 => {
+                    self.minimized = true;
+                }
+
                 WindowEvent::Resized(size) => {
+                    self.minimized = false;
                     self.renderer.resize(size);
                     self.camera.resize(size);
                 }
 
-                WindowEvent::KeyboardInput {
-                    event:
-                        KeyEvent {
-                            physical_key: PhysicalKey::Code(KeyCode::Escape),
-                            state: ElementState::Pressed,
-                            ..
-                        },
+                // clicks and keys used by the ui (such as typing into the console) are left to
+                // it, while the cursor is free
+                event if !self.has_focus && self.renderer.handle_ui_input(&event) => {}
+
+                WindowEvent::MouseInput { .. } if !self.has_focus => {
+                    self.toggle_focus();
+                }
+
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Left,
+                    ..
+                } if self.spawn_progress.is_none() => match self.game_mode.breaks_instantly() {
+                    true => self.break_targeted_block(),
+                    false => self.breaking = true,
+                },
+
+                WindowEvent::MouseInput {
+                    state: ElementState::Released,
+                    button: MouseButton::Left,
                     ..
                 } => {
-                    self.toggle_focus();
+                    self.breaking = false;
                 }
 
-                WindowEvent::MouseInput { .. } if !self.has_focus => {
-                    self.toggle_focus();
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Right,
+                    ..
+                } if self.spawn_progress.is_none() => {
+                    self.place_selected_block();
+                }
+
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Middle,
+                    ..
+                } if self.spawn_progress.is_none() => {
+                    self.pick_targeted_block();
+                }
+
+                WindowEvent::MouseWheel { delta, .. } if self.has_focus => {
+                    let scroll = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(position) => position.y as f32,
+                    };
+
+                    // scrolling down moves right along the hotbar
+                    if scroll != 0.0 {
+                        self.cycle_selected_voxel(if scroll < 0.0 { 1 } else { -1 });
+                    }
                 }
 
                 WindowEvent::KeyboardInput {
@@ -101,6 +506,7 @@ impl App {
                         KeyEvent {
                             physical_key: key,
                             state,
+                            repeat,
                             ..
                         },
                     ..
@@ -114,23 +520,66 @@ impl App {
                         ElementState::Pressed => self.keys_held.insert(code),
                         ElementState::Released => self.keys_held.remove(&code),
                     };
+
+                    if state == ElementState::Pressed && !repeat {
+                        let actions = self.keybindings.actions(code).collect::<Vec<_>>();
+
+                        for action in actions {
+                            self.perform(action);
+                        }
+                    }
                 }
 
-                WindowEvent::CloseRequested => elwt.exit(),
+                WindowEvent::CloseRequested => {
+                    self.chunk_manager.save_modified();
+                    self.save_window_state();
+                    elwt.exit();
+                }
 
                 WindowEvent::RedrawRequested => {
-                    if self.has_focus {
-                        self.camera
-                            .update_position(&self.keys_held, self.delta_time());
+                    if self.renderer.is_device_lost() {
+                        self.recover_lost_device()?;
                     }
 
+                    if self.spawn_progress.is_none() {
+                        self.update_player(self.delta_time());
+                        self.update_breaking(self.delta_time());
+                    }
+
+                    self.environment.advance_time(self.delta_time());
+                    self.environment.underwater = self
+                        .chunk_manager
+                        .get_block(self.camera.eye.round().as_ivec3())
+                        == Some(Voxel::Water);
                     self.last_frame = Instant::now();
 
-                    self.chunk_manager.update(self.camera.eye);
                     self.chunk_manager
-                        .resolve_mesh_uploads(&self.renderer.device);
+                        .update(self.camera.eye, self.camera.forward);
+                    self.chunk_manager.resolve_mesh_uploads(
+                        &self.renderer.device,
+                        &self.renderer.queue,
+                        &self.renderer.mesh_pool,
+                    );
+
+                    if self.spawn_progress.is_some() {
+                        self.update_spawn_progress();
+                    }
+
+                    if self.last_autosave.elapsed() >= AUTOSAVE_INTERVAL {
+                        self.chunk_manager.save_modified();
+                        self.last_autosave = Instant::now();
+                    }
+
+                    if self.minimized {
+                        return Ok(());
+                    }
 
-                    self.renderer.update_camera_buffer(self.camera.view_proj());
+                    self.renderer.update_camera_buffer(&self.camera);
+                    self.renderer.update_environment_buffer(&self.environment);
+                    self.update_lights();
+                    self.update_outline();
+                    self.update_ghost();
+                    self.update_debug_lines();
                     self.render();
                 }
 
@@ -140,8 +589,8 @@ impl App {
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion { delta },
                 ..
-            } if self.has_focus => {
-                self.camera.update_orientation(delta, self.delta_time());
+            } if self.has_focus && self.spawn_progress.is_none() => {
+                self.camera.update_orientation(delta);
             }
 
             _ => {}
@@ -150,6 +599,187 @@ impl App {
         Ok(())
     }
 
+    /// Performs the action of a key that was just pressed. Actions that happen while their keys
+    /// are held are left to the frames they are held in.
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::ToggleFocus => self.toggle_focus(),
+            Action::SwitchDimension if self.spawn_progress.is_none() => {
+                self.switch_dimension(self.chunk_manager.dimension().next());
+            }
+            Action::TogglePreview => self.toggle_preview(),
+            Action::Screenshot => {
+                let high_resolution = self.is_held(Action::HighResolution);
+                let scale = match high_resolution {
+                    true => self.render_settings.screenshot_scale,
+                    false => 1,
+                };
+
+                self.renderer.take_screenshot(scale);
+            }
+            Action::ToggleWireframe => {
+                self.render_settings.wireframe = !self.render_settings.wireframe;
+            }
+            Action::ToggleChunkBorders => {
+                self.render_settings.chunk_borders = !self.render_settings.chunk_borders;
+            }
+            Action::ToggleRecording => {
+                if self.renderer.is_recording() {
+                    self.renderer.stop_recording();
+                } else if let Err(error) = self.renderer.start_recording(&self.recording_config) {
+                    eprintln!("could not start recording: {error:?}");
+                }
+            }
+            Action::ToggleFullscreen => {
+                let fullscreen = match self.window.fullscreen() {
+                    Some(_) => None,
+                    None => Some(Fullscreen::Borderless(None)),
+                };
+
+                self.window.set_fullscreen(fullscreen);
+            }
+            Action::OpenConsole => {
+                if self.has_focus {
+                    self.toggle_focus();
+                }
+
+                self.console.open();
+            }
+            Action::ToggleFlight if self.has_focus && self.game_mode.allows_flight() => {
+                self.player.flying = !self.player.flying;
+                self.player.teleport(self.camera.target_eye());
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs a command typed into the console, printing what it did (or why it couldn't) to it.
+    fn run_command(&mut self, line: &str) {
+        self.console.print(format!("> {line}"));
+
+        match line.parse() {
+            Ok(ConsoleCommand::Help) => {
+                for line in console::HELP.lines() {
+                    self.console.print(line);
+                }
+            }
+            Ok(ConsoleCommand::GameMode(game_mode)) => self.set_game_mode(game_mode),
+            Err(error) => self.console.print(format!("{error}")),
+        }
+    }
+
+    /// Switches the game mode, landing the player if they are flying where it isn't allowed.
+    fn set_game_mode(&mut self, game_mode: GameMode) {
+        self.game_mode = game_mode;
+        self.break_progress = None;
+
+        if !game_mode.allows_flight() {
+            self.player.flying = false;
+        }
+
+        self.console
+            .print(format!("game mode set to {}", game_mode.name()));
+    }
+
+    /// Returns whether any key bound to the action is held.
+    fn is_held(&self, action: Action) -> bool {
+        self.keybindings.is_held(action, &self.keys_held)
+    }
+
+    /// Moves the player with the keys held: walking and jumping against the terrain, or flying
+    /// freely with the camera.
+    fn update_player(&mut self, dt: f32) {
+        let is_held = |action| self.keybindings.is_held(action, &self.keys_held);
+
+        let direction = self.camera.walk_direction(is_held).as_dvec3();
+        // the keys of these are bound to flying downwards and faster by default, which take
+        // their place while flying
+        self.player.sneaking = !self.player.flying && is_held(Action::Sneak);
+        let sprinting = !self.player.flying
+            && !self.player.swimming
+            && !self.player.sneaking
+            && direction != DVec3::ZERO
+            && is_held(Action::Sprint);
+
+        self.camera.set_sprinting(sprinting);
+        self.camera.set_zooming(is_held(Action::Zoom));
+
+        if self.player.flying {
+            self.camera.update_position(is_held, dt);
+            self.player.teleport(self.camera.target_eye());
+            self.camera.update_animations(dt);
+            return;
+        }
+
+        let speed = match (sprinting, self.player.sneaking) {
+            _ if self.player.swimming => SWIM_SPEED,
+            (true, _) => SPRINT_SPEED,
+            (_, true) => SNEAK_SPEED,
+            _ => WALK_SPEED,
+        };
+        let walk_velocity = direction * speed;
+        let jump = is_held(Action::Jump);
+        let swim_down = is_held(Action::SwimDown);
+
+        // unloaded chunks are solid, so the player can't fall out of the loaded world, while
+        // nothing above the top of the world is
+        self.player.update(
+            walk_velocity,
+            jump,
+            swim_down,
+            dt,
+            |position| {
+                position.y < CHUNK_HEIGHT as i32
+                    && self
+                        .chunk_manager
+                        .get_block(position)
+                        .is_none_or(|voxel| voxel.is_solid())
+            },
+            |position| self.chunk_manager.get_block(position) == Some(Voxel::Water),
+        );
+
+        self.camera.move_to(self.player.eye());
+        self.camera.update_animations(dt);
+    }
+
+    /// Checks how much of the spawn area has been loaded, letting the player move once all of it
+    /// has.
+    fn update_spawn_progress(&mut self) {
+        let (uploaded, total) = self
+            .chunk_manager
+            .meshes_uploaded_around_player(SPAWN_AREA_RADIUS);
+
+        self.spawn_progress = (uploaded < total).then(|| uploaded as f32 / total as f32);
+    }
+
+    /// Moves the player into another dimension, waiting for the area around them to load again
+    /// before they can move.
+    fn switch_dimension(&mut self, dimension: Dimension) {
+        self.chunk_manager.set_dimension(dimension);
+        self.spawn_progress = Some(0.0);
+    }
+
+    /// Opens the worldgen preview, sampled with the worldgen config currently on disk, or closes
+    /// it if it is open.
+    fn toggle_preview(&mut self) {
+        self.preview = match self.preview {
+            Some(_) => None,
+            None => Self::sample_preview(self.chunk_manager.seed()),
+        };
+    }
+
+    /// Samples the worldgen preview with the worldgen config currently on disk, so changes to
+    /// it show up without restarting.
+    fn sample_preview(seed: u32) -> Option<WorldgenPreview> {
+        match WorldgenConfig::load() {
+            Ok(worldgen) => Some(WorldgenPreview::new(seed, &worldgen)),
+            Err(error) => {
+                eprintln!("could not load worldgen config: {error:?}");
+                None
+            }
+        }
+    }
+
     /// Toggles the current focus state of the app.
     fn toggle_focus(&mut self) {
         self.has_focus = !self.has_focus;
@@ -162,37 +792,319 @@ impl App {
             self.window.set_cursor_grab(CursorGrabMode::None).unwrap();
 
             self.keys_held.clear();
+            self.breaking = false;
         }
     }
 
     /// Renders everything onto the surface.
     fn render(&mut self) {
-        let mut meshes = self.chunk_manager.loaded_meshes();
+        let loading = self.spawn_progress.is_some();
+        let frustum = self.camera.frustum();
+
+        // models are placed relative to the camera, so the ghost moves with it every frame
+        if let Some((position, _, ghost)) = &mut self.ghost {
+            let instance = ModelInstance::new(*position, GHOST_SCALE, self.camera.eye);
+            let renderer = &self.renderer;
+
+            ghost
+                .instances
+                .write(&renderer.device, &renderer.queue, &[instance]);
+        }
+
+        // sections hidden behind solid terrain (caves while on the surface, and vice versa) are
+        // skipped
+        let visible = self
+            .chunk_manager
+            .visible_sections(self.camera.eye, &frustum);
+        let is_visible = |(section, _): &(IVec3, _)| {
+            !loading
+                && visible
+                    .as_ref()
+                    .is_none_or(|visible| visible.contains(section))
+        };
+
+        let meshes = self
+            .chunk_manager
+            .loaded_meshes(MeshLayer::Opaque)
+            .filter(is_visible)
+            .map(|(_, mesh)| mesh);
+        let transparent_meshes = self
+            .chunk_manager
+            .loaded_meshes(MeshLayer::Transparent)
+            .filter(is_visible)
+            .map(|(_, mesh)| mesh);
         let fps = 1.0 / self.delta_time();
+        let break_fraction = self.break_fraction();
+        // copied, as the ui changes the settings while the frame is being drawn
+        let render_settings = self.render_settings.clone();
+        let mut command = None;
 
-        match self.renderer.render(&mut meshes, |ui| {
-            Self::ui(ui, &self.camera, &self.chunk_manager, fps)
-        }) {
+        match self.renderer.render(
+            meshes,
+            transparent_meshes,
+            self.ghost
+                .as_ref()
+                .map(|(_, _, ghost)| (MeshLayer::Transparent, ghost))
+                .into_iter(),
+            self.camera.eye,
+            &frustum,
+            &render_settings,
+            |ui, render_stats| {
+                Self::ui(
+                    ui,
+                    &mut self.camera,
+                    &self.chunk_manager,
+                    &mut self.preview,
+                    &mut self.render_settings,
+                    self.spawn_progress,
+                    &FrameStats { fps, render_stats },
+                );
+                Self::validation_errors_ui(ui, render_stats.validation_errors);
+
+                if self.spawn_progress.is_none() {
+                    Self::hotbar_ui(ui, &mut self.selected_voxel, break_fraction);
+                }
+
+                command = self.console.show(ui);
+            },
+        ) {
             Ok(_) => {}
             // If we are out of memory, just quit the app
             Err(SurfaceError::OutOfMemory) => panic!("out of memory - stopping application"),
+            // the surface no longer matches the window, so the frame is drawn once it is
+            // configured again
+            Err(SurfaceError::Lost | SurfaceError::Outdated) => self.renderer.reconfigure(),
             // For other errors, they will be gone by the next frame
             Err(error) => eprintln!("{error}"),
         };
+
+        if let Some(command) = command {
+            self.run_command(&command);
+        }
+    }
+
+    /// Lists the validation errors the renderer caught while creating GPU resources, if any.
+    fn validation_errors_ui(ui: &Context, errors: &[String]) {
+        if errors.is_empty() {
+            return;
+        }
+
+        egui::Window::new("gpu errors").show(ui, |ui| {
+            for error in errors {
+                ui.colored_label(egui::Color32::LIGHT_RED, error);
+            }
+        });
+    }
+
+    /// Lists the voxels that can be placed along the bottom of the screen, highlighting the
+    /// selected one. Scrolling changes the selection, as does clicking one while the cursor is
+    /// free. Above it is how much of the voxel being broken has been, while one is.
+    fn hotbar_ui(ui: &Context, selected: &mut Voxel, break_fraction: Option<f32>) {
+        egui::Window::new("hotbar")
+            .title_bar(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -8.0))
+            .show(ui, |ui| {
+                if let Some(fraction) = break_fraction {
+                    ui.add(egui::ProgressBar::new(fraction).desired_height(4.0));
+                }
+
+                ui.horizontal(|ui| {
+                    for voxel in Voxel::PLACEABLE {
+                        ui.selectable_value(selected, voxel, format!("{voxel:?}"));
+                    }
+                });
+            });
     }
 
     /// Renders all egui windows.
-    fn ui(ui: &Context, camera: &Camera, chunk_manager: &ChunkManager, fps: f32) {
+    fn ui(
+        ui: &Context,
+        camera: &mut Camera,
+        chunk_manager: &ChunkManager,
+        preview: &mut Option<WorldgenPreview>,
+        render_settings: &mut RenderSettings,
+        spawn_progress: Option<f32>,
+        stats: &FrameStats,
+    ) {
         use egui::*;
 
+        if let Some(progress) = spawn_progress {
+            Window::new("loading")
+                .title_bar(false)
+                .resizable(false)
+                .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ui, |ui| {
+                    ui.label("loading area...");
+                    ui.add(ProgressBar::new(progress).show_percentage());
+                });
+
+            return;
+        }
+
+        if let Some(worldgen_preview) = preview {
+            let mut resample = false;
+
+            Window::new("worldgen preview").show(ui, |window| {
+                worldgen_preview.show(ui, window);
+                resample = window.button("reload worldgen config").clicked();
+            });
+
+            if resample {
+                if let Some(resampled) = Self::sample_preview(chunk_manager.seed()) {
+                    *preview = Some(resampled);
+                }
+            }
+        }
+
+        Window::new("render settings")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.checkbox(&mut render_settings.god_rays, "god rays");
+                ui.checkbox(&mut render_settings.ssao, "ambient occlusion (ssao)");
+                ui.checkbox(&mut render_settings.depth_prepass, "depth prepass");
+                ui.checkbox(&mut render_settings.lantern, "lantern");
+                ui.checkbox(&mut render_settings.wireframe, "wireframe (F3)");
+                ui.checkbox(&mut render_settings.chunk_borders, "chunk borders (F4)");
+                ui.checkbox(&mut render_settings.chunk_load_states, "chunk load states");
+                ui.checkbox(&mut render_settings.screenshot_ui, "ui in screenshots (F2)");
+                ui.add(
+                    Slider::new(&mut render_settings.screenshot_scale, 2..=8)
+                        .suffix("x")
+                        .text("high resolution screenshots (shift+F2)"),
+                );
+                ui.horizontal(|ui| {
+                    ui.label("anisotropic filtering");
+
+                    for anisotropy in [1, 2, 4, 8, 16] {
+                        let label = match anisotropy {
+                            1 => "off".to_string(),
+                            _ => format!("{anisotropy}x"),
+                        };
+
+                        ui.radio_value(&mut render_settings.anisotropy, anisotropy, label);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("present mode");
+
+                    for &present_mode in &render_settings.supported_present_modes {
+                        let label = match present_mode {
+                            wgpu::PresentMode::Immediate => "uncapped",
+                            wgpu::PresentMode::Mailbox => "mailbox",
+                            _ => "vsync",
+                        };
+
+                        ui.radio_value(&mut render_settings.present_mode, present_mode, label);
+                    }
+                });
+
+                ui.separator();
+                ui.label("post-processing");
+                post::show_stack(ui, &mut render_settings.post);
+            });
+
+        Window::new("camera settings")
+            .default_open(false)
+            .show(ui, |ui| {
+                let config = &mut camera.config;
+
+                ui.add(
+                    Slider::new(&mut config.fov, FOV_RANGE)
+                        .suffix("°")
+                        .text("field of view (hold Z to zoom)"),
+                );
+                ui.add(
+                    Slider::new(&mut config.sensitivity, 0.01..=1.0)
+                        .logarithmic(true)
+                        .suffix("°")
+                        .text("mouse sensitivity"),
+                );
+                ui.checkbox(&mut config.invert_y, "invert mouse y");
+                ui.add(
+                    Slider::new(&mut config.reach, REACH_RANGE)
+                        .logarithmic(true)
+                        .suffix(" voxels")
+                        .text("reach"),
+                );
+                ui.add(
+                    Slider::new(&mut config.rotation_smoothing, 0.0..=0.5)
+                        .suffix(" s")
+                        .text("rotation smoothing"),
+                );
+                ui.add(
+                    Slider::new(&mut config.position_smoothing, 0.0..=0.5)
+                        .suffix(" s")
+                        .text("position smoothing"),
+                );
+            });
+
         Window::new("debug").show(ui, |ui| {
             ui.label(format!("position: {:?}", camera.eye));
             ui.label(format!("facing: {:?}", camera.forward));
+            ui.label(format!("dimension: {}", chunk_manager.dimension().name()));
+
+            let target = chunk_manager
+                .raycast(camera.eye, camera.forward, camera.config.reach())
+                .and_then(|hit| Some((chunk_manager.get_block(hit.position)?, hit.position)));
+
+            if let Some((voxel, position)) = target {
+                ui.label(format!("looking at: {voxel:?} at {position}"));
+            }
 
             ui.label(format!("chunks loaded: {}", chunk_manager.chunks_loaded()));
             ui.label(format!("meshes built: {}", chunk_manager.meshes_loaded()));
+            ui.label(format!("pending saves: {}", chunk_manager.pending_saves()));
+
+            ui.label(format!("fps: {}", (stats.fps as u32 / 10) * 10));
+
+            CollapsingHeader::new("chunk pipeline").show(ui, |ui| {
+                let metrics = chunk_manager.metrics();
+
+                for (name, stage) in [
+                    ("generation", &metrics.generation),
+                    ("meshing", &metrics.meshing),
+                    ("upload", &metrics.upload),
+                ] {
+                    ui.label(format!(
+                        "{name}: avg {:.2?}, p95 {:.2?}, {}/frame, {} queued, {} in flight",
+                        stage.average(),
+                        stage.percentile(0.95),
+                        stage.completed_last_frame,
+                        stage.queued,
+                        stage.in_flight,
+                    ));
+                }
+            });
+
+            CollapsingHeader::new("gpu passes").show(ui, |ui| {
+                let Some(timings) = stats.render_stats.pass_timings else {
+                    ui.label("timing passes isn't supported by this gpu");
+                    return;
+                };
+
+                for (name, time) in timings {
+                    ui.label(format!("{name}: {:.3} ms", time.as_secs_f32() * 1000.0));
+                }
+
+                let total = timings.iter().map(|(_, time)| *time).sum::<Duration>();
+                ui.label(format!("total: {:.3} ms", total.as_secs_f32() * 1000.0));
+            });
+
+            CollapsingHeader::new("gpu memory").show(ui, |ui| {
+                const MIB: f32 = 1024.0 * 1024.0;
+
+                for category in MemoryCategory::ALL {
+                    let allocated = category.allocated() as f32 / MIB;
+                    ui.label(format!("{}: {allocated:.1} MiB", category.name()));
+                }
 
-            ui.label(format!("fps: {}", (fps as u32 / 10) * 10));
+                let total = MemoryCategory::ALL
+                    .into_iter()
+                    .map(MemoryCategory::allocated)
+                    .sum::<u64>();
+                ui.label(format!("total: {:.1} MiB", total as f32 / MIB));
+            });
         });
     }
 }
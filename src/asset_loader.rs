@@ -1,12 +1,24 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
 use regex::Regex;
-use std::{fs, str::FromStr, sync::OnceLock};
-use wgpu::*;
+use serde::Deserialize;
+use std::{collections::HashMap, fs, io, path::Path, str::FromStr, sync::OnceLock};
+use wgpu::{util::DeviceExt, *};
 
 use anyhow::{bail, Context};
 
 use crate::{chunk::Voxel, texture::Texture};
 
-static TEXTURE_UPLOAD_ORDER: OnceLock<Vec<(Voxel, Face)>> = OnceLock::new();
+/// The layer of each voxel face's texture (its first frame, if it is animated).
+static TEXTURE_UPLOAD_ORDER: OnceLock<Vec<(Voxel, Face, u16)>> = OnceLock::new();
+
+/// The manifest describing the materials of the textures, keyed by the name of a texture (e.g.
+/// `stone_side`), or of a voxel to describe all of its faces at once (e.g. `stone`).
+const TEXTURE_MANIFEST: &str = "assets/textures.toml";
+
+/// The endings of the file names of normal maps and emissive masks, following the name of the
+/// texture they belong to.
+const NORMAL_MAP_SUFFIX: &str = "_normal.png";
+const EMISSIVE_MASK_SUFFIX: &str = "_emissive.png";
 
 /// The side to which this face is oriented towards.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -19,20 +31,175 @@ pub enum Face {
     Side,
 }
 
+/// How the surface of a texture reflects light, as described by the texture manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Material {
+    /// How rough the surface is, from 0 (mirror-like, with sharp highlights) to 1 (with broad,
+    /// faint highlights).
+    pub roughness: f32,
+    /// How metallic the surface is, from 0 (dielectric) to 1 (metal, tinting its reflections).
+    pub metallic: f32,
+    /// How brightly the surface glows, regardless of the light reaching it, as a multiple of
+    /// its color. Glowing brighter than 1 lets the bloom pick it up. Only the regions of the
+    /// texture set in its emissive mask glow, or all of it without one.
+    pub emissive: f32,
+    /// The number of frames the texture is animated through, stacked from top to bottom in its
+    /// image (along with its companions), each as tall as the image is wide.
+    pub frames: u32,
+    /// The seconds each frame is shown for.
+    pub frame_time: f32,
+    /// Whether the (grayscale) texture is tinted by the climate of the biome it is in, as grass
+    /// and leaves are.
+    pub tinted: bool,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            roughness: 0.9,
+            metallic: 0.0,
+            emissive: 0.0,
+            frames: 1,
+            frame_time: 1.0,
+            tinted: false,
+        }
+    }
+}
+
+/// A material as laid out in the shader's material buffer.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct MaterialData {
+    roughness: f32,
+    metallic: f32,
+    emissive: f32,
+    frames: u32,
+    frame_time: f32,
+    tinted: u32,
+}
+
+/// The textures of every voxel face, uploaded as the layers of array textures, in the order
+/// stored in `TEXTURE_UPLOAD_ORDER`.
+pub struct VoxelTextures {
+    /// The color of each face.
+    pub color: Texture,
+    /// The normal map of each face, which is flat for faces without one.
+    pub normal: Texture,
+    /// The emissive mask of each face (in the red channel), which is entirely set for faces
+    /// without one.
+    pub emissive_mask: Texture,
+    /// The material of each face, as `MaterialData`.
+    pub materials: Buffer,
+}
+
+impl VoxelTextures {
+    /// Creates the layout of the bind groups the textures are accessed through in the shader.
+    pub fn create_bind_group_layout(device: &Device) -> BindGroupLayout {
+        let texture_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2Array,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Texture Bind Group Layout"),
+            entries: &[
+                texture_entry(0),
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture_entry(2),
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(4),
+            ],
+        })
+    }
+
+    /// Creates a bind group with the given layout to access the textures in the shader.
+    pub fn create_bind_group(&self, device: &Device, layout: &BindGroupLayout) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Texture Bind Group"),
+            layout,
+            entries: &[
+                // texture
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&self.color.view),
+                },
+                // sampler
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.color.sampler),
+                },
+                // normal map
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&self.normal.view),
+                },
+                // materials
+                BindGroupEntry {
+                    binding: 3,
+                    resource: self.materials.as_entire_binding(),
+                },
+                // emissive mask
+                BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::TextureView(&self.emissive_mask.view),
+                },
+            ],
+        })
+    }
+
+    /// Samples the textures with up to the given amount of anisotropic filtering from now on,
+    /// where 1 turns it off. Bind groups must be recreated to pick up the change.
+    pub fn set_anisotropy(&mut self, device: &Device, anisotropy: u16) {
+        self.color.sampler = Texture::create_mipmap_sampler(device, anisotropy);
+    }
+}
+
 /// Loads all textures from the `assets` directory and uploads them to the GPU.
 /// All textures are uploaded onto the same texture, in seperate layers. The order
-/// in which the images are stored are saved in `TEXTURE_UPLOAD_ORDER`.
-pub fn load_textures(device: &Device, queue: &Queue) -> anyhow::Result<Texture> {
+/// in which the images are stored are saved in `TEXTURE_UPLOAD_ORDER`. A texture's normal map
+/// is loaded from its `*_normal.png` companion (e.g. `stone_side_normal.png`), if it has one,
+/// into the same layer of a second texture, and likewise for its emissive mask from its
+/// `*_emissive.png` companion. The material of each texture is read from the
+/// `TEXTURE_MANIFEST`, and the frames of animated textures are uploaded into consecutive layers.
+pub fn load_textures(device: &Device, queue: &Queue) -> anyhow::Result<VoxelTextures> {
     let assets = fs::read_dir("assets").context("loading voxel textures")?;
 
     let assets = assets
         .filter_map(|entry| entry.map(|entry| entry.path()).ok())
         .filter(|path| path.is_file())
-        .filter_map(|path| path.file_name()?.to_str().map(String::from));
+        .filter_map(|path| path.file_name()?.to_str().map(String::from))
+        .filter(|file_name| {
+            !file_name.ends_with(NORMAL_MAP_SUFFIX) && !file_name.ends_with(EMISSIVE_MASK_SUFFIX)
+        });
 
     let re = Regex::new(r"(\w+)_(\w+).png")?;
+    let manifest = load_manifest()?;
 
     let mut images = Vec::new();
+    let mut normal_maps = Vec::new();
+    let mut emissive_masks = Vec::new();
+    let mut materials = Vec::new();
     let mut order = Vec::new();
 
     for asset_file in assets {
@@ -41,20 +208,148 @@ pub fn load_textures(device: &Device, queue: &Queue) -> anyhow::Result<Texture>
 
             let image = image::open(format!("assets/{asset_file}"))
                 .context(format!("loading {asset_file}"))?;
+            // pointing straight out of the face, and glowing all over
+            let normal_map = load_companion(
+                &asset_file,
+                NORMAL_MAP_SUFFIX,
+                image.dimensions(),
+                Rgba([128, 128, 255, 255]),
+            )?;
+            let emissive_mask = load_companion(
+                &asset_file,
+                EMISSIVE_MASK_SUFFIX,
+                image.dimensions(),
+                Rgba([255, 255, 255, 255]),
+            )?;
+
+            let material = manifest
+                .get(&format!("{voxel}_{face}"))
+                .or_else(|| manifest.get(voxel))
+                .copied()
+                .unwrap_or_default();
 
             let voxel = Voxel::from_str(voxel)?;
             let face = Face::from_str(face)?;
 
-            images.push(image);
-            order.push((voxel, face));
+            let frames = material.frames.max(1);
+            let data = MaterialData {
+                roughness: material.roughness,
+                metallic: material.metallic,
+                emissive: material.emissive,
+                frames,
+                frame_time: material.frame_time.max(0.001),
+                tinted: material.tinted as u32,
+            };
+
+            order.push((voxel, face, images.len() as u16));
+
+            images.extend(split_frames(&image, frames, &asset_file)?);
+            normal_maps.extend(split_frames(&normal_map, frames, &asset_file)?);
+            emissive_masks.extend(split_frames(&emissive_mask, frames, &asset_file)?);
+            materials.extend(std::iter::repeat_n(data, frames as usize));
         }
     }
 
-    let texture = Texture::from_images(device, queue, &images, Some("Voxel Textures"))?;
+    let color = Texture::from_images(
+        device,
+        queue,
+        &images,
+        TextureFormat::Rgba8UnormSrgb,
+        Some("Voxel Textures"),
+    )?;
+
+    // normals are stored linearly, unlike colors
+    let normal = Texture::from_images(
+        device,
+        queue,
+        &normal_maps,
+        TextureFormat::Rgba8Unorm,
+        Some("Voxel Normal Maps"),
+    )?;
+
+    let emissive_mask = Texture::from_images(
+        device,
+        queue,
+        &emissive_masks,
+        TextureFormat::Rgba8Unorm,
+        Some("Voxel Emissive Masks"),
+    )?;
+
+    let materials = device.create_buffer_init(&util::BufferInitDescriptor {
+        label: Some("Voxel Material Buffer"),
+        contents: bytemuck::cast_slice(&materials),
+        usage: BufferUsages::STORAGE,
+    });
 
     TEXTURE_UPLOAD_ORDER.get_or_init(|| order);
 
-    Ok(texture)
+    Ok(VoxelTextures {
+        color,
+        normal,
+        emissive_mask,
+        materials,
+    })
+}
+
+/// Loads the materials described by the `TEXTURE_MANIFEST`, which describes none if it is
+/// missing.
+fn load_manifest() -> anyhow::Result<HashMap<String, Material>> {
+    match fs::read_to_string(TEXTURE_MANIFEST) {
+        Ok(contents) => toml::from_str(&contents).context(format!("parsing {TEXTURE_MANIFEST}")),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(error) => Err(error).context(format!("reading {TEXTURE_MANIFEST}")),
+    }
+}
+
+/// Splits the image of an animated texture into its frames, stacked from top to bottom.
+fn split_frames(
+    image: &DynamicImage,
+    frames: u32,
+    asset_file: &str,
+) -> anyhow::Result<Vec<DynamicImage>> {
+    if frames == 1 {
+        return Ok(vec![image.clone()]);
+    }
+
+    let (width, height) = image.dimensions();
+
+    if height != width * frames {
+        bail!(
+            "{asset_file} must be {frames} frames of {width}x{width} stacked on top of each other"
+        );
+    }
+
+    Ok((0..frames)
+        .map(|frame| image.crop_imm(0, frame * width, width, width))
+        .collect())
+}
+
+/// Loads the companion of the texture in the given asset file whose name ends in the given
+/// suffix (such as its normal map), which must be the same size as the texture, or an image
+/// filled with the `fallback` if it doesn't have one.
+fn load_companion(
+    asset_file: &str,
+    suffix: &str,
+    (width, height): (u32, u32),
+    fallback: Rgba<u8>,
+) -> anyhow::Result<DynamicImage> {
+    let companion_file = asset_file.replace(".png", suffix);
+    let path = format!("assets/{companion_file}");
+
+    if !Path::new(&path).is_file() {
+        let filled = RgbaImage::from_pixel(width, height, fallback);
+        return Ok(DynamicImage::ImageRgba8(filled));
+    }
+
+    println!("loading {path:?}");
+
+    let companion = image::open(&path).context(format!("loading {companion_file}"))?;
+
+    if companion.dimensions() != (width, height) {
+        bail!("{companion_file} is not the same size as {asset_file}");
+    }
+
+    Ok(companion)
 }
 
 /// Gets the appropriate texture index for a given voxel oriented in this face direction.
@@ -62,8 +357,8 @@ pub fn get_texture_index(voxel: &Voxel, face: &Face) -> Option<u16> {
     TEXTURE_UPLOAD_ORDER
         .get()?
         .iter()
-        .position(|(v, f)| voxel == v && face == f)
-        .map(|index| index as u16)
+        .find(|(v, f, _)| voxel == v && face == f)
+        .map(|(_, _, layer)| *layer)
 }
 
 impl FromStr for Face {
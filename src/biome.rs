@@ -0,0 +1,273 @@
+use glam::{ivec2, vec3, IVec2, Vec3};
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::{Voxel, SEA_LEVEL};
+
+/// The scale factor used to sample the climate noise. Climates change far slower than the
+/// terrain does, so biomes stretch over many chunks.
+const CLIMATE_SCALE: f64 = 1.0 / 1200.0;
+
+/// How much colder the climate gets for every voxel the terrain rises above sea level, so the
+/// peaks of tall mountains end up in colder biomes than their foothills.
+const TEMPERATURE_LAPSE_RATE: f64 = 1.0 / 160.0;
+
+/// The colors grass and leaves are tinted in the corners of the climate, as
+/// `[[cold and dry, cold and wet], [hot and dry, hot and wet]]`. Climates in between blend them, so
+/// hot, dry land turns yellow, rainforests a lush green, and cold swamps a murky dark green.
+const FOLIAGE_TINTS: [[Vec3; 2]; 2] = [
+    [vec3(0.5, 0.62, 0.45), vec3(0.3, 0.45, 0.3)],
+    [vec3(0.75, 0.7, 0.3), vec3(0.25, 0.65, 0.15)],
+];
+
+/// The distance (in voxels) between the points sampled when blending biome heights together.
+const BLEND_STEP: usize = 4;
+
+/// Options for how biomes are laid out, part of the worldgen config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiomeConfig {
+    /// The radius (in voxels) over which the heights of neighboring biomes are blended together,
+    /// so the terrain ramps smoothly between them instead of forming walls at their borders.
+    pub blend_radius: usize,
+}
+
+impl Default for BiomeConfig {
+    fn default() -> Self {
+        Self { blend_radius: 24 }
+    }
+}
+
+/// How a biome reshapes the heightmap terrain within it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiomeHeight {
+    /// How many voxels the terrain is raised by.
+    pub offset: f64,
+    /// How much the terrain's distance above or below sea level is stretched by.
+    pub scale: f64,
+}
+
+impl BiomeHeight {
+    /// Applies the biome height to a terrain height.
+    pub fn apply(&self, height: f64) -> f64 {
+        SEA_LEVEL as f64 + (height - SEA_LEVEL as f64) * self.scale + self.offset
+    }
+}
+
+/// The layers of voxels making up the surface of a biome's terrain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurfaceProfile {
+    /// The voxel covering the top of the terrain.
+    pub top: Voxel,
+    /// The voxel filling in the terrain right below the top voxel.
+    pub filler: Voxel,
+    /// How many voxels of filler there are below the top voxel, with stone below that.
+    pub depth: usize,
+}
+
+/// A region of the world with its own climate and surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    /// Temperate grasslands.
+    Plains,
+    /// Hot, dry dunes of sand.
+    Desert,
+    /// Cold, snow covered land.
+    Tundra,
+}
+
+impl Biome {
+    /// Returns the layers of voxels covering the terrain of the biome.
+    pub fn surface(&self) -> SurfaceProfile {
+        match self {
+            Self::Plains => SurfaceProfile {
+                top: Voxel::Grass,
+                filler: Voxel::Dirt,
+                depth: 3,
+            },
+            Self::Desert => SurfaceProfile {
+                top: Voxel::Sand,
+                filler: Voxel::Sandstone,
+                depth: 5,
+            },
+            Self::Tundra => SurfaceProfile {
+                top: Voxel::Snow,
+                filler: Voxel::Dirt,
+                depth: 3,
+            },
+        }
+    }
+
+    /// Returns how the biome reshapes the terrain within it. Deserts are flattened into low
+    /// dunes, while the tundra is more rugged than the plains.
+    pub fn height(&self) -> BiomeHeight {
+        match self {
+            Self::Plains => BiomeHeight {
+                offset: 0.0,
+                scale: 1.0,
+            },
+            Self::Desert => BiomeHeight {
+                offset: 2.0,
+                scale: 0.5,
+            },
+            Self::Tundra => BiomeHeight {
+                offset: 0.0,
+                scale: 1.25,
+            },
+        }
+    }
+
+    /// Returns the biome with the given climate, with both temperature and humidity roughly
+    /// between -1 and 1.
+    pub fn from_climate(temperature: f64, humidity: f64) -> Self {
+        match (temperature, humidity) {
+            (temperature, _) if temperature < -0.5 => Self::Tundra,
+            (temperature, humidity) if temperature > 0.3 && humidity < 0.0 => Self::Desert,
+            _ => Self::Plains,
+        }
+    }
+}
+
+/// Decides which biome every column of the world belongs to, from its climate.
+pub struct BiomeMap {
+    /// The noise function the temperature of the climate is sampled from.
+    temperature: Fbm<Perlin>,
+    /// The noise function the humidity of the climate is sampled from.
+    humidity: Fbm<Perlin>,
+    /// The radius (in voxels) biome heights are blended over.
+    blend_radius: usize,
+}
+
+impl BiomeMap {
+    /// Creates a new biome map from the given seed, laid out with the given config.
+    pub fn new(seed: u32, config: &BiomeConfig) -> Self {
+        Self {
+            temperature: Fbm::<Perlin>::new(seed.wrapping_add(5)).set_octaves(3),
+            humidity: Fbm::<Perlin>::new(seed.wrapping_add(6)).set_octaves(3),
+            blend_radius: config.blend_radius,
+        }
+    }
+
+    /// Returns the `(temperature, humidity)` of the climate of the column at the given world
+    /// position, whose terrain rises to the given height.
+    pub fn climate(&self, x: i32, z: i32, height: usize) -> (f64, f64) {
+        let position = [x as f64 * CLIMATE_SCALE, z as f64 * CLIMATE_SCALE];
+        let altitude = height.saturating_sub(SEA_LEVEL) as f64;
+
+        let temperature = self.temperature.get(position) * 2.0 - altitude * TEMPERATURE_LAPSE_RATE;
+        let humidity = self.humidity.get(position) * 2.0;
+
+        (temperature, humidity)
+    }
+
+    /// Returns the color grass and leaves are tinted with in the column at the given world
+    /// position, whose terrain rises to the given height.
+    pub fn foliage_tint(&self, x: i32, z: i32, height: usize) -> Vec3 {
+        let (temperature, humidity) = self.climate(x, z, height);
+        foliage_tint(temperature, humidity)
+    }
+
+    /// Returns the biome of the column at the given world position, whose terrain rises to the
+    /// given height.
+    pub fn biome_at(&self, x: i32, z: i32, height: usize) -> Biome {
+        let (temperature, humidity) = self.climate(x, z, height);
+        Biome::from_climate(temperature, humidity)
+    }
+
+    /// Returns the biome the column at the given world position lies in before the terrain is
+    /// shaped, ignoring how high it rises. This decides how the biome reshapes the terrain.
+    pub fn base_biome_at(&self, x: i32, z: i32) -> Biome {
+        self.biome_at(x, z, SEA_LEVEL)
+    }
+
+    /// Returns how the terrain of the column at the given world position is reshaped, averaging
+    /// the heights of the base biomes within the blend radius, weighted by how close they are.
+    pub fn blended_height(&self, x: i32, z: i32) -> BiomeHeight {
+        self.blend(x, z, |x, z| self.base_biome_at(x, z))
+    }
+
+    /// Returns `BiomeMap::blended_height` for every column of the `size` wide area whose first
+    /// column is at the given world position, indexed by `z * size.x + x`. Neighboring columns
+    /// share most of the biomes they blend, so each one is only sampled once.
+    pub fn blended_heights(&self, origin: IVec2, size: IVec2) -> Vec<BiomeHeight> {
+        let radius = self.blend_radius as i32;
+        let step = BLEND_STEP as i32;
+        let start = align_down(origin - IVec2::splat(radius), step);
+        let cells =
+            (align_down(origin + size + IVec2::splat(radius), step) - start) / step + IVec2::ONE;
+
+        let biomes = (0..cells.y)
+            .flat_map(|z| (0..cells.x).map(move |x| start + ivec2(x, z) * step))
+            .map(|position| self.base_biome_at(position.x, position.y))
+            .collect::<Vec<_>>();
+
+        let base_biome = |x: i32, z: i32| {
+            let cell = (ivec2(x, z) - start) / step;
+            biomes[(cell.y * cells.x + cell.x) as usize]
+        };
+
+        (0..size.y)
+            .flat_map(|z| (0..size.x).map(move |x| origin + ivec2(x, z)))
+            .map(|position| self.blend(position.x, position.y, base_biome))
+            .collect()
+    }
+
+    /// Averages the heights of the base biomes (as given by `base_biome`) around the given world
+    /// column. Biomes are sampled on a grid `BLEND_STEP` voxels wide that is aligned to the
+    /// world, so every column sees the same samples no matter which chunk asks for it.
+    fn blend(&self, x: i32, z: i32, base_biome: impl Fn(i32, i32) -> Biome) -> BiomeHeight {
+        let radius = self.blend_radius as i32;
+        let step = BLEND_STEP as i32;
+
+        if radius < step {
+            let sample = align_down(ivec2(x, z), step);
+            return base_biome(sample.x, sample.y).height();
+        }
+
+        let start = align_down(ivec2(x, z) - IVec2::splat(radius), step);
+        let end = ivec2(x, z) + IVec2::splat(radius);
+
+        let mut offset = 0.0;
+        let mut scale = 0.0;
+        let mut total_weight = 0.0;
+
+        for sample_z in (start.y..=end.y).step_by(BLEND_STEP) {
+            for sample_x in (start.x..=end.x).step_by(BLEND_STEP) {
+                let distance = ivec2(sample_x - x, sample_z - z).as_vec2().length() as f64;
+                let weight = 1.0 - distance / (radius + 1) as f64;
+
+                if weight <= 0.0 {
+                    continue;
+                }
+
+                let height = base_biome(sample_x, sample_z).height();
+                offset += height.offset * weight;
+                scale += height.scale * weight;
+                total_weight += weight;
+            }
+        }
+
+        BiomeHeight {
+            offset: offset / total_weight,
+            scale: scale / total_weight,
+        }
+    }
+}
+
+/// Returns the color grass and leaves are tinted with in the given climate, with both temperature
+/// and humidity roughly between -1 and 1.
+pub fn foliage_tint(temperature: f64, humidity: f64) -> Vec3 {
+    let temperature = ((temperature as f32 + 1.0) / 2.0).clamp(0.0, 1.0);
+    let humidity = ((humidity as f32 + 1.0) / 2.0).clamp(0.0, 1.0);
+
+    let [cold, hot] = FOLIAGE_TINTS.map(|[dry, wet]| dry.lerp(wet, humidity));
+    cold.lerp(hot, temperature)
+}
+
+/// Rounds every component of a position down to a multiple of `step`.
+fn align_down(position: IVec2, step: i32) -> IVec2 {
+    ivec2(
+        position.x.div_euclid(step) * step,
+        position.y.div_euclid(step) * step,
+    )
+}
@@ -0,0 +1,48 @@
+use glam::IVec3;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Extra data attached to a single voxel, for blocks that need more state than their voxel type
+/// (chests, signs, spawners, etc.).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockEntity {
+    /// The kind of block entity, used to find the handler responsible for it.
+    pub kind: String,
+    /// The block entity's data, serialized with bincode.
+    data: Vec<u8>,
+}
+
+impl BlockEntity {
+    /// Creates a new block entity of the given kind, holding the given data.
+    pub fn new<T: Serialize>(kind: impl Into<String>, data: &T) -> anyhow::Result<Self> {
+        Ok(Self {
+            kind: kind.into(),
+            data: bincode::serialize(data)?,
+        })
+    }
+
+    /// Deserializes the data held by the block entity.
+    pub fn data<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        Ok(bincode::deserialize(&self.data)?)
+    }
+
+    /// Replaces the data held by the block entity.
+    pub fn set_data<T: Serialize>(&mut self, data: &T) -> anyhow::Result<()> {
+        self.data = bincode::serialize(data)?;
+
+        Ok(())
+    }
+}
+
+/// Hooks run on the block entities of a single kind as the chunk containing them moves through
+/// its lifecycle. Every hook is given the world position of the block entity.
+pub trait BlockEntityHandler: Send + Sync {
+    /// Called once the chunk containing the block entity has been loaded or generated.
+    fn on_load(&self, _position: IVec3, _entity: &mut BlockEntity) {}
+
+    /// Called just before the chunk containing the block entity is unloaded.
+    fn on_unload(&self, _position: IVec3, _entity: &BlockEntity) {}
+
+    /// Called just before the chunk containing the block entity is saved, giving the handler a
+    /// chance to write any state it holds into the block entity.
+    fn on_save(&self, _position: IVec3, _entity: &mut BlockEntity) {}
+}
@@ -1,39 +1,134 @@
-use std::{collections::HashSet, f32::consts::FRAC_PI_2};
+use std::{f32::consts::FRAC_PI_2, ops::RangeInclusive};
 
 use glam::*;
+use serde::{Deserialize, Serialize};
 use wgpu::{util::*, *};
 
-use winit::{dpi::PhysicalSize, keyboard::KeyCode};
+use winit::dpi::PhysicalSize;
+
+use crate::keybindings::Action;
 
 /// The normal speed of the camera in space.
 pub const CAMERA_NORMAL_SPEED: f32 = 20.0;
-/// The speed of the camera when the boost speed key (L_CTRL) is pressed.
+/// The speed of the camera when the `FlyFast` action (L_CTRL) is held.
 pub const CAMERA_BOOST_SPEED: f32 = 350.0;
-/// The speed of the camera when the slow modifier key (L_ALT) is pressed.
+/// The speed of the camera when the `FlySlow` action (L_ALT) is held.
 pub const CAMERA_SLOW_SPEED: f32 = 10.0;
 
-/// The sensitivity of the camera.
-pub const CAMERA_SENSITIVITY: f32 = 0.15;
+/// The name of the config file the camera options are loaded from.
+pub const CAMERA_CONFIG_FILE: &str = "camera.toml";
+
+/// The range (in degrees) the field of view can be set within.
+pub const FOV_RANGE: RangeInclusive<f32> = 30.0..=110.0;
+/// The range (in voxels) the reach can be set within.
+pub const REACH_RANGE: RangeInclusive<f32> = 1.0..=512.0;
+/// The field of view (in degrees) zoomed into while the zoom key is held.
+pub const ZOOM_FOV: f32 = 20.0;
+/// How much wider (in degrees) the field of view gets while sprinting.
+pub const SPRINT_FOV_KICK: f32 = 8.0;
+/// How quickly animated camera parameters approach their targets. After `1 / rate` seconds,
+/// about two thirds of the way has been covered.
+const ANIMATION_RATE: f32 = 12.0;
+
+/// Options for how the camera moves, loaded from `CAMERA_CONFIG_FILE`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    /// The vertical field of view of the camera, in degrees, within `FOV_RANGE`.
+    pub fov: f32,
+    /// How far (in degrees) the camera turns for each unit the mouse moves.
+    pub sensitivity: f32,
+    /// Whether moving the mouse up looks down, and vice versa.
+    pub invert_y: bool,
+    /// How far away (in voxels) the voxel the camera is looking at can be, to be broken, built
+    /// against or inspected. Within `REACH_RANGE`.
+    pub reach: f32,
+    /// How long (in seconds) the camera takes to turn about two thirds of the way towards where
+    /// the mouse has turned it, smoothing out the jumps between mouse events. 0 turns it
+    /// instantly.
+    pub rotation_smoothing: f32,
+    /// How long (in seconds) the camera takes to move about two thirds of the way towards the
+    /// player's eyes. 0 moves it instantly.
+    pub position_smoothing: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            fov: 45.0,
+            sensitivity: 0.15,
+            invert_y: false,
+            reach: 5.0,
+            rotation_smoothing: 0.0,
+            position_smoothing: 0.0,
+        }
+    }
+}
+
+impl CameraConfig {
+    /// Returns the field of view, clamped to `FOV_RANGE`.
+    pub fn fov(&self) -> f32 {
+        self.fov.clamp(*FOV_RANGE.start(), *FOV_RANGE.end())
+    }
+
+    /// Returns the reach, clamped to `REACH_RANGE`.
+    pub fn reach(&self) -> f32 {
+        self.reach.clamp(*REACH_RANGE.start(), *REACH_RANGE.end())
+    }
+}
+
+/// The camera as laid out in the shaders' uniform buffer.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    /// The view-projection matrix, as given by `Camera::view_proj`.
+    view_proj: Mat4,
+    /// The position of the camera (rounded to single precision), with a w of 1. Vertices are
+    /// already relative to it, so it is only used for effects that depend on absolute positions
+    /// (like height fog).
+    eye: Vec4,
+    /// The inverse of `view_proj`, turning positions on the screen back into directions.
+    inverse_view_proj: Mat4,
+}
 
 /// A perspective camera with a position and orientation in 3D space.
 #[derive(Debug)]
 pub struct Camera {
-    /// The actual position of the camera.
-    pub eye: glam::Vec3,
+    /// The actual position of the camera. It is kept in double precision, as offsets from it are
+    /// computed on the CPU, and a single precision position far from the origin would snap the
+    /// camera between representable positions, making the whole world wobble as it moves.
+    /// Moved with `Camera::move_to`, which it eases towards when smoothed.
+    pub eye: glam::DVec3,
     /// The "forward" vector, representing the direction the camera is looking to.
     pub forward: glam::Vec3,
     /// The vector representing the up direction of the camera
     pub up: glam::Vec3,
 
+    /// The options the camera moves with.
+    pub config: CameraConfig,
+
     /// The yaw of the camera in radians.
     yaw: f32,
     /// The pitch of the camera in radians. Clamped to [-pi/2, pi/2].
     pitch: f32,
+    /// The yaw and pitch the mouse has turned the camera to, which `yaw` and `pitch` ease
+    /// towards when smoothed.
+    target_yaw: f32,
+    target_pitch: f32,
+    /// The position the camera has been moved to, which `eye` eases towards when smoothed.
+    target_eye: DVec3,
 
     /// The aspect ratio of the surface.
     aspect: f32,
-    /// The vertical field of view of the camera in radians.
-    fovy: f32,
+    /// The vertical field of view of the camera in radians, as configured.
+    fovy: Animated,
+    /// How much wider than `fovy` the field of view is drawn, in radians, which widens while
+    /// sprinting.
+    fov_kick: Animated,
+    /// Whether the player is sprinting, widening the field of view.
+    sprinting: bool,
+    /// Whether the zoom key is held, narrowing the field of view to `ZOOM_FOV`.
+    zooming: bool,
     /// The near clipping plane of the camera's frustum.
     znear: f32,
 }
@@ -50,7 +145,13 @@ impl Camera {
 
     /// Creates a new camera at the given position, looking at the target, and window size to
     /// calculate the aspect ratio.
-    pub fn new(eye: Vec3, yaw: f32, pitch: f32, window_size: PhysicalSize<u32>) -> Self {
+    pub fn new(
+        eye: DVec3,
+        yaw: f32,
+        pitch: f32,
+        window_size: PhysicalSize<u32>,
+        config: CameraConfig,
+    ) -> Self {
         let PhysicalSize { width, height } = window_size;
 
         let forward = Self::calculate_forward(yaw, pitch);
@@ -60,74 +161,180 @@ impl Camera {
             forward,
             up: Vec3::Y,
             aspect: width as f32 / height as f32,
-            fovy: 45.0f32.to_radians(),
+            fovy: Animated::new(config.fov().to_radians()),
+            fov_kick: Animated::new(0.0),
+            sprinting: false,
+            zooming: false,
             znear: 0.01,
+            config,
             yaw,
             pitch,
+            target_yaw: yaw,
+            target_pitch: pitch,
+            target_eye: eye,
         }
     }
 
-    /// Returns the view-projection matrix of the camera to transform vertices.
+    /// Returns the view-projection matrix of the camera to transform vertices. The camera is
+    /// treated as sitting at the origin, so vertices must be positioned relative to `eye`.
     /// Follows the canonical WebGPU coordinate depth in range [0, 1], unlike OpenGL's [-1, 1]
     /// range.
     pub fn view_proj(&self) -> Mat4 {
-        let view = Mat4::look_at_rh(self.eye, self.forward + self.eye, self.up);
-        let proj = Mat4::perspective_infinite_rh(self.fovy, self.aspect, self.znear);
+        let view = Mat4::look_at_rh(Vec3::ZERO, self.forward, self.up);
+        let proj = Mat4::perspective_infinite_rh(
+            self.fovy.value + self.fov_kick.value,
+            self.aspect,
+            self.znear,
+        );
 
         proj * view
     }
 
-    /// Updates the camera's orientation (yaw/pitch) based on the mouse move delta
-    pub fn update_orientation(&mut self, delta: (f64, f64), dt: f32) {
+    /// Returns the uniform data of the camera, to be sent to the GPU.
+    pub fn uniform(&self) -> CameraUniform {
+        let view_proj = self.view_proj();
+
+        CameraUniform {
+            view_proj,
+            eye: self.eye.as_vec3().extend(1.0),
+            inverse_view_proj: view_proj.inverse(),
+        }
+    }
+
+    /// Returns the frustum of the space visible to the camera. Like `Camera::view_proj`, the
+    /// camera is treated as sitting at the origin, so positions tested against it must be
+    /// relative to `eye`.
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_proj(self.view_proj())
+    }
+
+    /// Updates the camera's orientation (yaw/pitch) based on the mouse move delta. The delta is
+    /// how far the mouse moved, however long that took, so the camera turns the same amount at
+    /// any frame rate. When rotation is smoothed, the camera turns towards it over the next
+    /// frames.
+    pub fn update_orientation(&mut self, delta: (f64, f64)) {
         let (dx, dy) = delta;
+        let sensitivity = self.config.sensitivity.to_radians();
+        let dy = match self.config.invert_y {
+            true => -dy,
+            false => dy,
+        };
 
-        self.yaw += dt * dx as f32 * CAMERA_SENSITIVITY;
-        self.pitch -= dt * dy as f32 * CAMERA_SENSITIVITY;
+        self.target_yaw += dx as f32 * sensitivity;
+        self.target_pitch -= dy as f32 * sensitivity;
 
-        self.pitch = self.pitch.clamp(-FRAC_PI_2 + 0.001, FRAC_PI_2 - 0.001);
-        self.forward = Self::calculate_forward(self.yaw, self.pitch);
+        self.target_pitch = self
+            .target_pitch
+            .clamp(-FRAC_PI_2 + 0.001, FRAC_PI_2 - 0.001);
+
+        if self.config.rotation_smoothing <= 0.0 {
+            self.yaw = self.target_yaw;
+            self.pitch = self.target_pitch;
+            self.forward = Self::calculate_forward(self.yaw, self.pitch);
+        }
     }
 
-    /// Updates the camera's position based on the keys held.
-    pub fn update_position(&mut self, keys_held: &HashSet<KeyCode>, dt: f32) {
-        let forward = self.forward;
+    /// Moves the camera to the given position. When position is smoothed, the camera moves
+    /// towards it over the next frames.
+    pub fn move_to(&mut self, eye: DVec3) {
+        self.target_eye = eye;
+
+        if self.config.position_smoothing <= 0.0 {
+            self.eye = eye;
+        }
+    }
+
+    /// Returns the position the camera has been moved to, which it may still be easing
+    /// towards.
+    pub fn target_eye(&self) -> DVec3 {
+        self.target_eye
+    }
+
+    /// Returns the horizontal direction the actions held move the camera in, with a length of one
+    /// (or zero if they don't move it).
+    pub fn walk_direction(&self, is_held: impl Fn(Action) -> bool) -> Vec3 {
+        // the forward vector flattened onto the ground, so looking up or down doesn't slow
+        // walking
+        let forward = vec3(self.forward.x, 0.0, self.forward.z).normalize_or_zero();
         let right = forward.cross(self.up);
 
-        let mut delta_pos = Vec3::ZERO;
+        let mut direction = Vec3::ZERO;
 
-        if keys_held.contains(&KeyCode::KeyW) {
-            delta_pos += forward;
+        if is_held(Action::MoveForward) {
+            direction += forward;
         }
-        if keys_held.contains(&KeyCode::KeyS) {
-            delta_pos -= forward;
+        if is_held(Action::MoveBackward) {
+            direction -= forward;
         }
-        if keys_held.contains(&KeyCode::KeyD) {
-            delta_pos += right;
+        if is_held(Action::MoveRight) {
+            direction += right;
         }
-        if keys_held.contains(&KeyCode::KeyA) {
-            delta_pos -= right;
+        if is_held(Action::MoveLeft) {
+            direction -= right;
         }
 
-        delta_pos.y = 0.0;
+        direction.normalize_or_zero()
+    }
+
+    /// Updates the camera's position based on the actions held, flying freely.
+    pub fn update_position(&mut self, is_held: impl Fn(Action) -> bool, dt: f32) {
+        let mut delta_pos = self.walk_direction(&is_held);
 
-        if keys_held.contains(&KeyCode::Space) {
+        if is_held(Action::Jump) {
             delta_pos += Vec3::Y;
         }
-        if keys_held.contains(&KeyCode::ShiftLeft) {
+        if is_held(Action::FlyDown) {
             delta_pos -= Vec3::Y;
         }
 
         delta_pos = delta_pos.normalize_or_zero();
 
-        let speed = if keys_held.contains(&KeyCode::ControlLeft) {
+        let speed = if is_held(Action::FlyFast) {
             CAMERA_BOOST_SPEED
-        } else if keys_held.contains(&KeyCode::AltLeft) {
+        } else if is_held(Action::FlySlow) {
             CAMERA_SLOW_SPEED
         } else {
             CAMERA_NORMAL_SPEED
         };
 
-        self.eye += dt * speed * delta_pos;
+        self.move_to(self.target_eye + (dt * speed * delta_pos).as_dvec3());
+    }
+
+    /// Widens the field of view while the player is sprinting, easing it back once they stop.
+    pub fn set_sprinting(&mut self, sprinting: bool) {
+        self.sprinting = sprinting;
+    }
+
+    /// Narrows the field of view to `ZOOM_FOV` while zooming, easing it back once the zoom key
+    /// is released.
+    pub fn set_zooming(&mut self, zooming: bool) {
+        self.zooming = zooming;
+    }
+
+    /// Moves the animated parameters of the camera, and its smoothed orientation and position,
+    /// `dt` seconds closer to their targets.
+    pub fn update_animations(&mut self, dt: f32) {
+        self.fovy.target = match self.zooming {
+            true => ZOOM_FOV,
+            false => self.config.fov(),
+        }
+        .to_radians();
+        // sprinting doesn't widen the view while zoomed in, which would undo the zoom
+        self.fov_kick.target = match self.sprinting && !self.zooming {
+            true => SPRINT_FOV_KICK.to_radians(),
+            false => 0.0,
+        };
+
+        self.fovy.update(dt);
+        self.fov_kick.update(dt);
+
+        let rotation = smoothing_factor(self.config.rotation_smoothing, dt);
+        self.yaw += (self.target_yaw - self.yaw) * rotation;
+        self.pitch += (self.target_pitch - self.pitch) * rotation;
+        self.forward = Self::calculate_forward(self.yaw, self.pitch);
+
+        let position = smoothing_factor(self.config.position_smoothing, dt) as f64;
+        self.eye += (self.target_eye - self.eye) * position;
     }
 
     /// Recalculates the aspect ratio given the new window size
@@ -141,7 +348,7 @@ impl Camera {
     pub fn create_buffers(&self, device: &Device) -> (Buffer, BindGroupLayout, BindGroup) {
         let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Camera Uniform Buffer"),
-            contents: bytemuck::cast_slice(&self.view_proj().to_cols_array()),
+            contents: bytemuck::bytes_of(&self.uniform()),
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
 
@@ -149,7 +356,7 @@ impl Camera {
             label: Some("Camera Bind Group Layout"),
             entries: &[BindGroupLayoutEntry {
                 binding: 0,
-                visibility: ShaderStages::VERTEX,
+                visibility: ShaderStages::VERTEX_FRAGMENT,
                 ty: BindingType::Buffer {
                     ty: BufferBindingType::Uniform,
                     has_dynamic_offset: false,
@@ -171,3 +378,77 @@ impl Camera {
         (uniform_buffer, bind_group_layout, bind_group)
     }
 }
+
+/// A camera parameter that eases towards its target, rather than jumping to it as soon as the
+/// target changes.
+#[derive(Debug, Clone, Copy)]
+pub struct Animated {
+    /// The value the parameter currently has.
+    pub value: f32,
+    /// The value the parameter is approaching.
+    pub target: f32,
+}
+
+impl Animated {
+    /// Creates a parameter resting at the given value.
+    pub fn new(value: f32) -> Self {
+        Self {
+            value,
+            target: value,
+        }
+    }
+
+    /// Moves the value `dt` seconds closer to the target. It approaches it exponentially, so the
+    /// motion is the same at any frame rate.
+    pub fn update(&mut self, dt: f32) {
+        self.value += (self.target - self.value) * (1.0 - (-ANIMATION_RATE * dt).exp());
+    }
+}
+
+/// Returns how much of the way towards its target a value smoothed over the given time (in
+/// seconds) moves in `dt` seconds. Values smoothed over no time move all the way.
+fn smoothing_factor(smoothing: f32, dt: f32) -> f32 {
+    match smoothing > 0.0 {
+        true => 1.0 - (-dt / smoothing).exp(),
+        false => 1.0,
+    }
+}
+
+/// The six clip planes bounding the space visible to a camera, each stored as `(normal, d)`
+/// with the normal pointing inwards, so points inside have `normal.dot(point) + d >= 0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// The left, right, bottom, top, near and far planes. Cameras with an infinite far plane
+    /// have a far plane that every point is inside of.
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the clip planes of a view-projection matrix with WebGPU's [0, 1] depth range.
+    pub fn from_view_proj(view_proj: Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|row| view_proj.row(row));
+
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            let length = plane.truncate().length();
+
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        });
+
+        Self { planes }
+    }
+
+    /// Returns whether any part of the axis aligned box between `min` and `max` could be inside
+    /// the frustum. Boxes near the corners of the frustum may be counted as inside when they
+    /// aren't, but boxes that are inside are never counted as outside.
+    pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner of the box furthest along the plane's normal
+            let corner = Vec3::select(plane.truncate().cmpge(Vec3::ZERO), max, min);
+            plane.truncate().dot(corner) + plane.w >= 0.0
+        })
+    }
+}
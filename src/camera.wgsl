@@ -0,0 +1,11 @@
+// The camera, as laid out in `CameraUniform`, bound as group 0 by every shader drawing the scene.
+struct CameraUniform {
+	// The view-projection matrix, treating the camera as sitting at the origin.
+	view_proj: mat4x4<f32>,
+	// The position of the camera, with a w of 1.
+	eye: vec4<f32>,
+	// The inverse of `view_proj`, turning positions on the screen back into directions.
+	inverse_view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> camera: CameraUniform;
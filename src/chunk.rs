@@ -1,22 +1,43 @@
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 
 use anyhow::bail;
 use glam::*;
 use noise::NoiseFn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    biome::{BiomeHeight, BiomeMap},
+    block_entity::BlockEntity,
+    row_noise::{Row, RowNoise},
+};
 
 /// The width of a chunk (xz length).
 pub const CHUNK_WIDTH: usize = 16;
 /// The height of a chunk (y length).
 pub const CHUNK_HEIGHT: usize = 256;
+/// The height of a single vertical section of a chunk.
+pub const SECTION_HEIGHT: usize = 16;
+/// The number of vertical sections stacked in a chunk.
+pub const SECTIONS_PER_CHUNK: usize = CHUNK_HEIGHT / SECTION_HEIGHT;
+
+/// The height that oceans are filled with water up to.
+pub const SEA_LEVEL: usize = 64;
+
+/// The thickest the bedrock layer at the bottom of the world gets.
+pub const MAX_BEDROCK_THICKNESS: usize = 3;
+
+/// The seed worlds are generated with.
+pub const DEFAULT_SEED: u32 = 129;
 
 /// The scale factor used to sample noise values for chunk generation.
 const NOISE_SCALE: f64 = 1.0 / 500.0;
 
-/// A 3d grid of voxels.
-pub type VoxelGrid = [[[Voxel; CHUNK_WIDTH]; CHUNK_WIDTH]; CHUNK_HEIGHT];
+/// A 3d grid of voxels making up a single section of a chunk.
+pub type VoxelGrid = [[[Voxel; CHUNK_WIDTH]; CHUNK_WIDTH]; SECTION_HEIGHT];
 
 /// A filled cube within a 3d grid.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum Voxel {
     #[default]
     Air,
@@ -24,55 +45,186 @@ pub enum Voxel {
     Dirt,
     Stone,
     Snow,
+    Log,
+    Leaves,
+    Water,
+    Sand,
+    Bedrock,
+    Sandstone,
 }
 
-/// Creates a noise function that can be used to create interesting terrain.
-pub fn create_noise_generator(seed: u32) -> impl NoiseFn<f64, 2> {
-    /// The rate at which the frequency of the noise increases with each octave.
-    const CONTINENT_LACUNARITY: f64 = 2.05;
+/// The shape of the noise heightmap terrain is generated from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerrainNoiseConfig {
+    /// The frequency of the first octave of the noise.
+    pub frequency: f64,
     /// The number of octaves used for noise generation.
-    const NUM_OCTAVES: usize = 8;
+    pub octaves: usize,
+    /// The rate at which the frequency of the noise increases with each octave.
+    pub lacunarity: f64,
+    /// The rate at which the amplitude of the noise decreases with each octave.
+    pub persistence: f64,
+    /// The `(input, output)` control points of the curve the raw noise is remapped with, which
+    /// decides how much of the world is lowlands, hills or mountains. At least four are needed.
+    pub curve: Vec<[f64; 2]>,
+}
 
-    use noise::*;
+impl Default for TerrainNoiseConfig {
+    fn default() -> Self {
+        Self {
+            frequency: 0.2,
+            octaves: 8,
+            lacunarity: 2.05,
+            persistence: 0.5,
+            curve: vec![
+                [-2.0, -1.625],
+                [-1.0, -1.375],
+                [0.0, -0.375],
+                [0.0625, 0.125],
+                [0.125, 0.25],
+                [0.25, 1.0],
+                [0.5, 0.25],
+                [0.75, 0.25],
+                [1.0, 0.5],
+                [2.0, 0.5],
+            ],
+        }
+    }
+}
 
-    const SEA_LEVEL: f64 = 0.0;
+/// Creates a noise function that can be used to create interesting terrain, shaped by the given
+/// config.
+pub fn create_noise_generator(seed: u32, config: &TerrainNoiseConfig) -> impl NoiseFn<f64, 2> {
+    use noise::*;
 
     let continents = Fbm::<Perlin>::new(seed)
-        .set_frequency(0.2)
-        .set_octaves(NUM_OCTAVES)
-        .set_lacunarity(CONTINENT_LACUNARITY);
-
-    let mountain_ranges_curuve = Curve::new(continents)
-        .add_control_point(-2.0 + SEA_LEVEL, -1.625 + SEA_LEVEL)
-        .add_control_point(-1.0 + SEA_LEVEL, -1.375 + SEA_LEVEL)
-        .add_control_point(SEA_LEVEL, -0.375 + SEA_LEVEL)
-        .add_control_point(0.0625 + SEA_LEVEL, 0.125 + SEA_LEVEL)
-        .add_control_point(0.125 + SEA_LEVEL, 0.25 + SEA_LEVEL)
-        .add_control_point(0.25 + SEA_LEVEL, 1.0 + SEA_LEVEL)
-        .add_control_point(0.5 + SEA_LEVEL, 0.25 + SEA_LEVEL)
-        .add_control_point(0.75 + SEA_LEVEL, 0.25 + SEA_LEVEL)
-        .add_control_point(1.0 + SEA_LEVEL, 0.5 + SEA_LEVEL)
-        .add_control_point(2.0 + SEA_LEVEL, 0.5 + SEA_LEVEL);
-
-    mountain_ranges_curuve
+        .set_frequency(config.frequency)
+        .set_octaves(config.octaves)
+        .set_lacunarity(config.lacunarity)
+        .set_persistence(config.persistence);
+
+    config
+        .curve
+        .iter()
+        .fold(Curve::new(continents), |curve, [input, output]| {
+            curve.add_control_point(*input, *output)
+        })
+}
+
+/// Returns the height of the heightmap terrain generated by `Chunk::fill_perlin` at the given
+/// world column, reshaped by the biomes around it.
+pub fn terrain_height(
+    noise: &(impl NoiseFn<f64, 2> + ?Sized),
+    biomes: &BiomeMap,
+    x: i32,
+    z: i32,
+) -> usize {
+    column_height(shape_height(noise, biomes.blended_height(x, z), x, z))
+}
+
+/// Returns the unrounded heights of the heightmap terrain for every column of the `size` wide
+/// area whose first column is at the given world position, indexed by `z * size.x + x`.
+pub fn terrain_heights(
+    noise: &(impl NoiseFn<f64, 2> + ?Sized),
+    biomes: &BiomeMap,
+    origin: IVec2,
+    size: IVec2,
+) -> Vec<f64> {
+    biomes
+        .blended_heights(origin, size)
+        .into_iter()
+        .enumerate()
+        .map(|(index, biome_height)| {
+            let index = index as i32;
+            let position = origin + ivec2(index % size.x, index / size.x);
+
+            shape_height(noise, biome_height, position.x, position.y)
+        })
+        .collect()
+}
+
+/// Rounds an unrounded terrain height down into the height of the topmost voxel of a column,
+/// keeping it inside the chunk.
+pub fn column_height(height: f64) -> usize {
+    height.clamp(0.0, CHUNK_HEIGHT as f64 - 2.0) as usize
+}
+
+/// Returns the unrounded height of the terrain at the given world column, sampled from the
+/// noise and reshaped by the given blended biome height.
+fn shape_height(
+    noise: &(impl NoiseFn<f64, 2> + ?Sized),
+    biome_height: BiomeHeight,
+    x: i32,
+    z: i32,
+) -> f64 {
+    let position = ivec2(x, z).as_dvec2() * NOISE_SCALE;
+
+    let height = (noise.get(position.to_array()) + 1.0) / 2.0 * CHUNK_HEIGHT as f64;
+    biome_height.apply(height)
 }
 
 /// A collection of voxels grouped within a AABB rectangle to increase performance
 /// with regards to rendering.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Chunk {
-    /// The list of voxels stored contiguously in memory.
-    pub voxels: Box<VoxelGrid>,
+    /// The vertical sections of the chunk, from bottom to top. Sections made up entirely of air
+    /// are not stored.
+    #[serde(with = "run_length")]
+    pub sections: [Option<Box<VoxelGrid>>; SECTIONS_PER_CHUNK],
     /// The position of the chunk within the world along the xz axis.
     pub position: glam::IVec2,
+    /// The block entities within the chunk, keyed by their local position.
+    pub block_entities: HashMap<[usize; 3], BlockEntity>,
+    /// Whether the chunk's decorations (trees, etc.) have been placed. Decorations can spill
+    /// into neighboring chunks, so they are placed after generation once the neighbors exist.
+    pub decorated: bool,
 }
 
 impl Chunk {
     /// Creates a new chunk at the given position.
     pub fn new(position: IVec2) -> Self {
-        let voxels = Box::new([[[Voxel::Air; CHUNK_WIDTH]; CHUNK_WIDTH]; CHUNK_HEIGHT]);
+        let sections = std::array::from_fn(|_| None);
+
+        Self {
+            sections,
+            position,
+            block_entities: HashMap::new(),
+            decorated: false,
+        }
+    }
+
+    /// Returns whether the section at the given index has no voxels stored.
+    pub fn is_section_empty(&self, section: usize) -> bool {
+        self.sections[section].is_none()
+    }
+
+    /// Returns the voxel at the given local position. Positions above or below the chunk are
+    /// considered to be air.
+    pub fn get_voxel(&self, [x, y, z]: [usize; 3]) -> Voxel {
+        if y >= CHUNK_HEIGHT {
+            return Voxel::Air;
+        }
+
+        match &self.sections[y / SECTION_HEIGHT] {
+            Some(section) => section[y % SECTION_HEIGHT][z][x],
+            None => Voxel::Air,
+        }
+    }
 
-        Self { voxels, position }
+    /// Sets the voxel at the given local position, allocating its section if needed.
+    pub fn set_voxel(&mut self, [x, y, z]: [usize; 3], voxel: Voxel) {
+        let section = &mut self.sections[y / SECTION_HEIGHT];
+
+        if section.is_none() && voxel == Voxel::Air {
+            return;
+        }
+
+        let section = section.get_or_insert_with(|| {
+            Box::new([[[Voxel::Air; CHUNK_WIDTH]; CHUNK_WIDTH]; SECTION_HEIGHT])
+        });
+
+        section[y % SECTION_HEIGHT][z][x] = voxel;
     }
 
     /// Returns whether the provided position is in the confines of the chunk,
@@ -83,9 +235,7 @@ impl Chunk {
 
     /// Returns if the voxel at the given position is non empty (not air).
     pub fn is_block_full(&self, block_pos: [usize; 3]) -> bool {
-        let [x, y, z] = block_pos;
-
-        self.voxels[y][z][x] != Voxel::Air
+        self.get_voxel(block_pos) != Voxel::Air
     }
 
     /// Utility to add a block position with some delta direction, and return
@@ -136,30 +286,548 @@ impl Chunk {
         }
     }
 
-    /// Fills the chunk in using noise values.
-    pub fn fill_perlin(&mut self, noise: impl NoiseFn<f64, 2>) {
-        let global_position = (self.position * CHUNK_WIDTH as i32).as_vec2();
+    /// Returns the world position of the voxel at the given local position.
+    pub fn world_position(&self, [x, y, z]: [usize; 3]) -> IVec3 {
+        let origin = self.position * CHUNK_WIDTH as i32;
+
+        ivec3(origin.x + x as i32, y as i32, origin.y + z as i32)
+    }
+
+    /// Splits a world space voxel position into the position of the chunk containing it, and the
+    /// local position within that chunk. Returns None if the position is above or below the
+    /// world.
+    pub fn locate(position: IVec3) -> Option<(IVec2, [usize; 3])> {
+        if position.y < 0 || position.y >= CHUNK_HEIGHT as i32 {
+            return None;
+        }
+
+        let width = CHUNK_WIDTH as i32;
+
+        let chunk = ivec2(position.x.div_euclid(width), position.z.div_euclid(width));
+        let local = [
+            position.x.rem_euclid(width) as usize,
+            position.y as usize,
+            position.z.rem_euclid(width) as usize,
+        ];
+
+        Some((chunk, local))
+    }
+
+    /// Serializes the chunk into a compact binary representation.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Deserializes a chunk previously serialized with `Chunk::to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    /// Fills the chunk in using noise values, covering every column with the surface of the
+    /// biome it is in.
+    pub fn fill_perlin(&mut self, noise: impl NoiseFn<f64, 2>, biomes: &BiomeMap) {
+        let heights = terrain_heights(
+            &noise,
+            biomes,
+            self.position * CHUNK_WIDTH as i32,
+            IVec2::splat(CHUNK_WIDTH as i32),
+        );
+
+        self.fill_heightmap(&heights, biomes);
+    }
+
+    /// Fills the chunk in from a heightmap of unrounded terrain heights, indexed by
+    /// `z * CHUNK_WIDTH + x`, covering every column with the surface of the biome it is in.
+    pub fn fill_heightmap(&mut self, heights: &[f64], biomes: &BiomeMap) {
+        let global_position = self.position * CHUNK_WIDTH as i32;
 
         for z in 0..CHUNK_WIDTH {
             for x in 0..CHUNK_WIDTH {
-                let local_position = vec2(x as f32, z as f32);
-                let position = (global_position + local_position).as_dvec2() * NOISE_SCALE;
+                let height = column_height(heights[z * CHUNK_WIDTH + x]);
 
-                //let height = (self.get_voxel(&noise, base_position) as usize).min(CHUNK_HEIGHT - 1);
-                let height = (noise.get(position.to_array()) + 1.0) / 2.0 * CHUNK_HEIGHT as f64;
-                let height = height.min(CHUNK_HEIGHT as f64 - 2.0) as usize;
+                let surface = biomes
+                    .biome_at(
+                        global_position.x + x as i32,
+                        global_position.y + z as i32,
+                        height,
+                    )
+                    .surface();
 
                 for y in 0..=height {
-                    self.voxels[y][z][x] = match y {
-                        200..=CHUNK_HEIGHT => Voxel::Snow,
-                        150.. => Voxel::Stone,
-                        _ if y == height => Voxel::Grass,
-                        _ => Voxel::Dirt,
+                    let voxel = match height - y {
+                        0 => surface.top,
+                        depth if depth <= surface.depth => surface.filler,
+                        _ => Voxel::Stone,
                     };
+
+                    self.set_voxel([x, y, z], voxel);
+                }
+            }
+        }
+    }
+
+    /// Fills the chunk in from a heightmap of column heights, indexed by `z * CHUNK_WIDTH + x`,
+    /// with grass on top, dirt below it, and stone down to a floor of bedrock. Columns without a
+    /// height are left empty, and the rest are flooded with water up to `water_level`, if given.
+    pub fn fill_columns(&mut self, heights: &[Option<usize>], water_level: Option<usize>) {
+        /// How many voxels of dirt lie beneath the grass.
+        const DIRT_DEPTH: usize = 3;
+
+        for z in 0..CHUNK_WIDTH {
+            for x in 0..CHUNK_WIDTH {
+                let Some(height) = heights[z * CHUNK_WIDTH + x] else {
+                    continue;
+                };
+
+                let height = height.min(CHUNK_HEIGHT - 1);
+
+                for y in 0..=height {
+                    let voxel = match (y, height - y) {
+                        (0, _) => Voxel::Bedrock,
+                        (_, 0) => Voxel::Grass,
+                        (_, depth) if depth <= DIRT_DEPTH => Voxel::Dirt,
+                        _ => Voxel::Stone,
+                    };
+
+                    self.set_voxel([x, y, z], voxel);
+                }
+
+                if let Some(water_level) = water_level {
+                    self.flood_column(x, z, height, water_level);
+                }
+            }
+        }
+    }
+
+    /// Fills the chunk in using 3d density noise, which (unlike `Chunk::fill_perlin`) can produce
+    /// overhangs, cliffs and floating islands. A voxel is solid if `density(x, y, z) - y_bias > 0`,
+    /// where the bias grows by one every `squash` voxels above `base_height`. The topmost solid
+    /// voxels of every column are covered in grass and dirt.
+    pub fn fill_density(&mut self, noise: impl RowNoise, base_height: f64, squash: f64) {
+        /// The scale factor used to sample the density noise.
+        const DENSITY_SCALE: f64 = 1.0 / 96.0;
+        /// How many voxels of dirt lie beneath the grass on each surface.
+        const DIRT_DEPTH: usize = 3;
+
+        let global_position = (self.position * CHUNK_WIDTH as i32).as_dvec2();
+        let xs = self.row_positions(DENSITY_SCALE);
+
+        for z in 0..CHUNK_WIDTH {
+            // the number of solid voxels since the last air voxel in each column, going downwards
+            let mut depths = [0; CHUNK_WIDTH];
+
+            for y in (0..CHUNK_HEIGHT).rev() {
+                let densities = noise.get_row(
+                    &xs,
+                    y as f64 * DENSITY_SCALE,
+                    (global_position.y + z as f64) * DENSITY_SCALE,
+                );
+
+                let y_bias = (y as f64 - base_height) / squash;
+
+                for (x, depth) in depths.iter_mut().enumerate() {
+                    if densities[x] - y_bias <= 0.0 {
+                        *depth = 0;
+                        continue;
+                    }
+
+                    let voxel = match (y, *depth) {
+                        (200.., _) => Voxel::Snow,
+                        (_, 0) => Voxel::Grass,
+                        (_, depth) if depth <= DIRT_DEPTH => Voxel::Dirt,
+                        _ => Voxel::Stone,
+                    };
+
+                    self.set_voxel([x, y, z], voxel);
+                    *depth += 1;
+                }
+            }
+        }
+    }
+
+    /// Carves caves out of the already generated terrain, turning every voxel where the 3d noise
+    /// value is above `threshold` into air. Voxels less than `min_depth` below the highest solid
+    /// voxel of their column, as well as the bottom layer of the world, are left untouched.
+    pub fn carve_caves(
+        &mut self,
+        noise: impl RowNoise,
+        frequency: f64,
+        threshold: f64,
+        min_depth: usize,
+    ) {
+        let global_position = (self.position * CHUNK_WIDTH as i32).as_dvec2();
+        let xs = self.row_positions(frequency);
+
+        for z in 0..CHUNK_WIDTH {
+            // the highest voxel that may be carved out of each column
+            let tops: [Option<usize>; CHUNK_WIDTH] = std::array::from_fn(|x| {
+                self.surface_height(x, z)
+                    .map(|surface| surface.saturating_sub(min_depth))
+            });
+
+            let Some(highest) = tops.iter().flatten().max() else {
+                continue;
+            };
+
+            for y in 1..=*highest {
+                let carvable: [bool; CHUNK_WIDTH] = std::array::from_fn(|x| {
+                    tops[x].is_some_and(|top| y <= top) && self.get_voxel([x, y, z]).is_opaque()
+                });
+
+                if !carvable.contains(&true) {
+                    continue;
+                }
+
+                let values = noise.get_row(
+                    &xs,
+                    y as f64 * frequency,
+                    (global_position.y + z as f64) * frequency,
+                );
+
+                for x in 0..CHUNK_WIDTH {
+                    if carvable[x] && values[x] > threshold {
+                        self.set_voxel([x, y, z], Voxel::Air);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the height of the highest non-air voxel in the given local column, or None if the
+    /// column is empty.
+    pub fn surface_height(&self, x: usize, z: usize) -> Option<usize> {
+        (0..CHUNK_HEIGHT).rfind(|y| self.get_voxel([x, *y, z]) != Voxel::Air)
+    }
+
+    /// Carves rivers into the terrain along the zero crossings of the 2d noise, where the
+    /// absolute noise value is below `width`. Channels are cut down to `depth` voxels below
+    /// `water_level` at their center, with their banks sloping up to the surrounding terrain,
+    /// and are filled with water up to `water_level`.
+    pub fn carve_rivers(
+        &mut self,
+        noise: impl NoiseFn<f64, 2>,
+        frequency: f64,
+        width: f64,
+        depth: usize,
+        water_level: usize,
+    ) {
+        let global_position = (self.position * CHUNK_WIDTH as i32).as_dvec2();
+
+        for z in 0..CHUNK_WIDTH {
+            for x in 0..CHUNK_WIDTH {
+                let position = (global_position + dvec2(x as f64, z as f64)) * frequency;
+                let distance = noise.get(position.to_array()).abs();
+
+                if distance >= width {
+                    continue;
+                }
+
+                let Some(surface) = self.surface_height(x, z) else {
+                    continue;
+                };
+
+                // 1 at the center of the river, falling off smoothly to 0 at its banks
+                let t = 1.0 - distance / width;
+                let blend = t * t * (3.0 - 2.0 * t);
+
+                let bed = water_level as f64 - depth as f64 * t;
+                let bed = (surface as f64 + (bed - surface as f64) * blend).max(1.0) as usize;
+
+                for y in bed + 1..=surface {
+                    self.set_voxel([x, y, z], Voxel::Air);
+                }
+
+                self.flood_column(x, z, bed.min(surface), water_level);
+            }
+        }
+    }
+
+    /// Fills the local height minima of the terrain with lakes. The world is split into a grid of
+    /// cells `spacing` voxels wide; a cell holds a lake if its center lies at least `min_depth`
+    /// voxels below every point on a ring around it, in which case the lake is filled up to just
+    /// below the lowest of those points. `height` returns the terrain height of a world column.
+    pub fn fill_lakes(
+        &mut self,
+        height: impl Fn(i32, i32) -> usize,
+        spacing: usize,
+        min_depth: usize,
+    ) {
+        let spacing = spacing as i32;
+        let radius = spacing / 2;
+        let global_position = self.position * CHUNK_WIDTH as i32;
+
+        let mut levels = HashMap::new();
+
+        for z in 0..CHUNK_WIDTH {
+            for x in 0..CHUNK_WIDTH {
+                let column = global_position + ivec2(x as i32, z as i32);
+                let cell = (column.as_vec2() / spacing as f32).round().as_ivec2() * spacing;
+
+                let level = *levels.entry(cell).or_insert_with(|| {
+                    let center = height(cell.x, cell.y);
+                    let rim = (0..8)
+                        .map(|i| {
+                            let angle = i as f32 * std::f32::consts::FRAC_PI_4;
+                            let offset = (Vec2::from_angle(angle) * radius as f32).as_ivec2();
+
+                            height(cell.x + offset.x, cell.y + offset.y)
+                        })
+                        .min()
+                        .unwrap_or(0);
+
+                    (rim >= center + min_depth).then(|| rim - 1)
+                });
+
+                let Some(level) = level else {
+                    continue;
+                };
+
+                if (column - cell).length_squared() > radius * radius {
+                    continue;
+                }
+
+                if let Some(surface) = self.surface_height(x, z) {
+                    self.flood_column(x, z, surface, level);
+                }
+            }
+        }
+    }
+
+    /// Fills all the air in the chunk at or below `sea_level` with water, above the highest
+    /// solid voxel of each column.
+    pub fn fill_ocean(&mut self, sea_level: usize) {
+        for z in 0..CHUNK_WIDTH {
+            for x in 0..CHUNK_WIDTH {
+                let floor = self.surface_height(x, z).unwrap_or(0);
+
+                self.flood_column(x, z, floor, sea_level);
+            }
+        }
+    }
+
+    /// Turns the grass and dirt at the surface of columns near water into sand, for columns whose
+    /// surface lies within a couple of voxels of `sea_level`.
+    pub fn place_beaches(&mut self, sea_level: usize) {
+        /// How far above sea level beaches reach.
+        const BEACH_HEIGHT: usize = 2;
+        /// How far below sea level the sea floor is covered in sand.
+        const BEACH_DEPTH: usize = 4;
+        /// How far (horizontally) from water a column can be to become beach.
+        const BEACH_WIDTH: isize = 3;
+        /// How many voxels deep the sand is.
+        const SAND_DEPTH: usize = 3;
+
+        let heights = sea_level.saturating_sub(BEACH_DEPTH)..=sea_level + BEACH_HEIGHT;
+
+        for z in 0..CHUNK_WIDTH {
+            for x in 0..CHUNK_WIDTH {
+                let Some(surface) =
+                    (0..CHUNK_HEIGHT).rfind(|y| self.get_voxel([x, *y, z]).is_opaque())
+                else {
+                    continue;
+                };
+
+                if !heights.contains(&surface) {
+                    continue;
+                }
+
+                // only neighbors within the chunk are checked, as the neighboring chunks may
+                // not have been generated yet
+                let near_water = (-BEACH_WIDTH..=BEACH_WIDTH).any(|dz| {
+                    (-BEACH_WIDTH..=BEACH_WIDTH).any(|dx| {
+                        let (x, z) = (x as isize + dx, z as isize + dz);
+
+                        (0..CHUNK_WIDTH as isize).contains(&x)
+                            && (0..CHUNK_WIDTH as isize).contains(&z)
+                            && self.get_voxel([x as usize, sea_level, z as usize]) == Voxel::Water
+                    })
+                });
+
+                if !near_water {
+                    continue;
+                }
+
+                for y in surface.saturating_sub(SAND_DEPTH - 1)..=surface {
+                    if matches!(self.get_voxel([x, y, z]), Voxel::Grass | Voxel::Dirt) {
+                        self.set_voxel([x, y, z], Voxel::Sand);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fills the air in a column above `floor` with water, up to and including `water_level`.
+    /// Grass left under the water is turned to dirt.
+    fn flood_column(&mut self, x: usize, z: usize, floor: usize, water_level: usize) {
+        if floor >= water_level {
+            return;
+        }
+
+        if self.get_voxel([x, floor, z]) == Voxel::Grass {
+            self.set_voxel([x, floor, z], Voxel::Dirt);
+        }
+
+        for y in floor + 1..=water_level.min(CHUNK_HEIGHT - 1) {
+            if self.get_voxel([x, y, z]) == Voxel::Air {
+                self.set_voxel([x, y, z], Voxel::Water);
+            }
+        }
+    }
+
+    /// Replaces the bottom of the chunk with a layer of bedrock between one and
+    /// `MAX_BEDROCK_THICKNESS` voxels thick, varying with the 2d noise.
+    pub fn fill_bedrock(&mut self, noise: impl NoiseFn<f64, 2>) {
+        /// The scale factor used to sample the bedrock noise.
+        const BEDROCK_SCALE: f64 = 0.37;
+
+        let global_position = (self.position * CHUNK_WIDTH as i32).as_dvec2();
+
+        for z in 0..CHUNK_WIDTH {
+            for x in 0..CHUNK_WIDTH {
+                let position = (global_position + dvec2(x as f64, z as f64)) * BEDROCK_SCALE;
+                let value = (noise.get(position.to_array()) + 1.0) / 2.0;
+                let thickness = (value * MAX_BEDROCK_THICKNESS as f64) as usize;
+
+                for y in 0..thickness.clamp(1, MAX_BEDROCK_THICKNESS) {
+                    self.set_voxel([x, y, z], Voxel::Bedrock);
                 }
             }
         }
     }
+
+    /// Fills the chunk with horizontal layers of voxels, given from the bottom up along with
+    /// their thickness.
+    pub fn fill_flat(&mut self, layers: &[(Voxel, usize)]) {
+        let voxels = layers
+            .iter()
+            .flat_map(|(voxel, thickness)| std::iter::repeat_n(*voxel, *thickness))
+            .take(CHUNK_HEIGHT);
+
+        for (y, voxel) in voxels.enumerate() {
+            for z in 0..CHUNK_WIDTH {
+                for x in 0..CHUNK_WIDTH {
+                    self.set_voxel([x, y, z], voxel);
+                }
+            }
+        }
+    }
+
+    /// Empties the chunk, leaving nothing but air.
+    pub fn fill_void(&mut self) {
+        self.sections = std::array::from_fn(|_| None);
+        self.block_entities.clear();
+    }
+
+    /// Fills the lower half of the chunk with stone, carving out caverns wherever the 3d noise
+    /// value is high enough. The floor and ceiling are always left solid.
+    pub fn fill_caves(&mut self, noise: impl RowNoise) {
+        /// The height of the cave world.
+        const CAVE_HEIGHT: usize = CHUNK_HEIGHT / 2;
+        /// The scale factor used to sample the cave noise.
+        const CAVE_SCALE: f64 = 1.0 / 48.0;
+        /// Voxels with a noise value above this are carved out.
+        const CAVE_THRESHOLD: f64 = 0.1;
+
+        let global_position = (self.position * CHUNK_WIDTH as i32).as_dvec2();
+        let xs = self.row_positions(CAVE_SCALE);
+
+        for y in 0..CAVE_HEIGHT {
+            for z in 0..CHUNK_WIDTH {
+                let is_boundary = y == 0 || y == CAVE_HEIGHT - 1;
+
+                let values = noise.get_row(
+                    &xs,
+                    y as f64 * CAVE_SCALE,
+                    (global_position.y + z as f64) * CAVE_SCALE,
+                );
+
+                for (x, value) in values.into_iter().enumerate() {
+                    if is_boundary || value < CAVE_THRESHOLD {
+                        self.set_voxel([x, y, z], Voxel::Stone);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the x coordinates of the voxels along a row of the chunk, in world space,
+    /// multiplied by `scale`, for sampling `RowNoise` with.
+    fn row_positions(&self, scale: f64) -> Row {
+        let global_x = (self.position.x * CHUNK_WIDTH as i32) as f64;
+        std::array::from_fn(|x| (global_x + x as f64) * scale)
+    }
+}
+
+impl Voxel {
+    /// The voxels the player can place, in the order they are listed in the hotbar.
+    pub const PLACEABLE: [Self; 9] = [
+        Self::Grass,
+        Self::Dirt,
+        Self::Stone,
+        Self::Snow,
+        Self::Log,
+        Self::Leaves,
+        Self::Water,
+        Self::Sand,
+        Self::Sandstone,
+    ];
+
+    /// Returns whether the voxel fully hides whatever is behind it.
+    pub fn is_opaque(&self) -> bool {
+        !matches!(self, Self::Air | Self::Water)
+    }
+
+    /// Returns whether the voxel blocks the player from moving through it.
+    pub fn is_solid(&self) -> bool {
+        !matches!(self, Self::Air | Self::Water)
+    }
+
+    /// Returns whether placing a voxel where this one is replaces it.
+    pub fn is_replaceable(&self) -> bool {
+        matches!(self, Self::Air | Self::Water)
+    }
+
+    /// Returns whether players are able to break the voxel. Bedrock is unbreakable, so players
+    /// can't dig out of the bottom of the world.
+    pub fn is_breakable(&self) -> bool {
+        !matches!(self, Self::Air | Self::Bedrock)
+    }
+
+    /// Returns how many seconds the voxel takes to break in survival. Unbreakable voxels take
+    /// forever.
+    pub fn hardness(&self) -> f32 {
+        match self {
+            Self::Air | Self::Water => 0.0,
+            Self::Snow | Self::Leaves => 0.3,
+            Self::Dirt | Self::Sand => 0.5,
+            Self::Grass => 0.6,
+            Self::Log => 2.0,
+            Self::Sandstone => 2.5,
+            Self::Stone => 3.0,
+            Self::Bedrock => f32::INFINITY,
+        }
+    }
+}
+
+impl TryFrom<u8> for Voxel {
+    type Error = anyhow::Error;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            0 => Ok(Self::Air),
+            1 => Ok(Self::Grass),
+            2 => Ok(Self::Dirt),
+            3 => Ok(Self::Stone),
+            4 => Ok(Self::Snow),
+            5 => Ok(Self::Log),
+            6 => Ok(Self::Leaves),
+            7 => Ok(Self::Water),
+            8 => Ok(Self::Sand),
+            9 => Ok(Self::Bedrock),
+            10 => Ok(Self::Sandstone),
+            _ => bail!("unknown voxel id, '{id}'"),
+        }
+    }
 }
 
 impl FromStr for Voxel {
@@ -172,7 +840,162 @@ impl FromStr for Voxel {
             "dirt" => Ok(Self::Dirt),
             "stone" => Ok(Self::Stone),
             "snow" => Ok(Self::Snow),
+            "log" => Ok(Self::Log),
+            "leaves" => Ok(Self::Leaves),
+            "water" => Ok(Self::Water),
+            "sand" => Ok(Self::Sand),
+            "bedrock" => Ok(Self::Bedrock),
+            "sandstone" => Ok(Self::Sandstone),
             _ => bail!("unkown voxel type, '{s}'"),
         }
     }
 }
+
+/// Serializes chunk sections as runs of identical voxels, which keeps the (mostly uniform)
+/// sections small instead of storing every voxel individually.
+mod run_length {
+    use anyhow::Context;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::*;
+
+    /// A voxel id along with how many times it is repeated.
+    type Run = (u8, u16);
+
+    pub fn serialize<S: Serializer>(
+        sections: &[Option<Box<VoxelGrid>>; SECTIONS_PER_CHUNK],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let encoded = sections
+            .iter()
+            .map(|section| section.as_deref().map(encode))
+            .collect::<Vec<_>>();
+
+        encoded.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[Option<Box<VoxelGrid>>; SECTIONS_PER_CHUNK], D::Error> {
+        let encoded = Vec::<Option<Vec<Run>>>::deserialize(deserializer)?;
+
+        if encoded.len() != SECTIONS_PER_CHUNK {
+            return Err(D::Error::invalid_length(
+                encoded.len(),
+                &"one entry per section",
+            ));
+        }
+
+        let mut sections = std::array::from_fn(|_| None);
+
+        for (section, runs) in sections.iter_mut().zip(encoded) {
+            if let Some(runs) = runs {
+                *section = Some(decode(&runs).map_err(D::Error::custom)?);
+            }
+        }
+
+        Ok(sections)
+    }
+
+    /// Encodes a section as a list of runs, in y, z, x order.
+    fn encode(section: &VoxelGrid) -> Vec<Run> {
+        let mut runs: Vec<Run> = Vec::new();
+
+        for voxel in section.iter().flatten().flatten() {
+            match runs.last_mut() {
+                Some((id, count)) if *id == *voxel as u8 => *count += 1,
+                _ => runs.push((*voxel as u8, 1)),
+            }
+        }
+
+        runs
+    }
+
+    /// Decodes a list of runs back into a section.
+    fn decode(runs: &[Run]) -> anyhow::Result<Box<VoxelGrid>> {
+        let mut section = Box::new([[[Voxel::Air; CHUNK_WIDTH]; CHUNK_WIDTH]; SECTION_HEIGHT]);
+        let mut voxels = section.iter_mut().flatten().flatten();
+
+        for (id, count) in runs {
+            let voxel = Voxel::try_from(*id)?;
+
+            for _ in 0..*count {
+                *voxels.next().context("too many voxels in section")? = voxel;
+            }
+        }
+
+        if voxels.next().is_some() {
+            bail!("too few voxels in section");
+        }
+
+        Ok(section)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a chunk with a section filled with a single voxel, a section holding a mix of
+    /// voxels (including air), and every other section empty.
+    fn test_chunk() -> Chunk {
+        let mut chunk = Chunk::new(ivec2(-3, 7));
+        let mixed = [
+            Voxel::Dirt,
+            Voxel::Air,
+            Voxel::Water,
+            Voxel::Sandstone,
+            Voxel::Bedrock,
+        ];
+
+        for y in 0..SECTION_HEIGHT {
+            for z in 0..CHUNK_WIDTH {
+                for x in 0..CHUNK_WIDTH {
+                    chunk.set_voxel([x, y, z], Voxel::Stone);
+
+                    let voxel = mixed[(x * 7 + y * 3 + z) % mixed.len()];
+                    chunk.set_voxel([x, SECTION_HEIGHT * 2 + y, z], voxel);
+                }
+            }
+        }
+
+        chunk
+    }
+
+    #[test]
+    fn sections_round_trip() {
+        let chunk = test_chunk();
+        let decoded = Chunk::from_bytes(&chunk.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(decoded.position, chunk.position);
+        assert_eq!(decoded.sections, chunk.sections);
+        assert!(decoded.is_section_empty(1));
+        assert!(!decoded.is_section_empty(2));
+    }
+
+    #[test]
+    fn empty_chunk_round_trips() {
+        let chunk = Chunk::new(ivec2(0, 0));
+        let decoded = Chunk::from_bytes(&chunk.to_bytes().unwrap()).unwrap();
+
+        assert!((0..SECTIONS_PER_CHUNK).all(|section| decoded.is_section_empty(section)));
+    }
+
+    #[test]
+    fn uniform_section_is_a_single_run() {
+        let mut chunk = Chunk::new(ivec2(0, 0));
+        let empty_size = chunk.to_bytes().unwrap().len();
+
+        for y in 0..SECTION_HEIGHT {
+            for z in 0..CHUNK_WIDTH {
+                for x in 0..CHUNK_WIDTH {
+                    chunk.set_voxel([x, y, z], Voxel::Stone);
+                }
+            }
+        }
+
+        // the number of runs in the section, followed by its single run of (id, count)
+        let run_size = size_of::<u64>() + size_of::<u8>() + size_of::<u16>();
+        assert_eq!(chunk.to_bytes().unwrap().len(), empty_size + run_size);
+    }
+}
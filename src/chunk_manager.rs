@@ -1,244 +1,1269 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    sync::mpsc,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
-use glam::{ivec2, IVec2, Vec3};
-use noise::NoiseFn;
+use glam::{ivec2, ivec3, DVec3, IVec2, IVec3, Vec2, Vec3, Vec3Swizzles};
 use rayon::ThreadPoolBuilder;
-use wgpu::Device;
+use serde::{Deserialize, Serialize};
+use wgpu::{Device, Queue};
 
-use crate::{chunk::*, mesher::ChunkMesher, model::*};
+use crate::{
+    block_entity::{BlockEntity, BlockEntityHandler},
+    camera::Frustum,
+    chunk::*,
+    dimension::Dimension,
+    generator::{ChunkGenerator, ChunkRegion, WorldgenConfig},
+    mesh_pool::MeshPool,
+    mesher::{ChunkMesher, MeshData, MeshLayer},
+    metrics::PipelineMetrics,
+    model::*,
+    raycast::{raycast, RaycastHit},
+    storage::{self, Compression, IoRequest, IoResult, WorldInfo},
+    structure::Structure,
+    visibility::{self, ChunkVisibility},
+};
+
+/// The name of the config file the chunk manager's config is loaded from.
+pub const CHUNK_MANAGER_CONFIG_FILE: &str = "chunk_manager.toml";
 
-/// The radius around the player in which chunks are loaded. One extra chunk
-/// in both the x and z axes are loaded as padding for mesh generation.
-pub const CHUNK_LOAD_RADIUS: usize = 16;
 /// The size of the padding around loaded chunks. These padding chunks only have
 /// their voxel data generated; without their meshes being built.
 pub const CHUNK_LOAD_PADDING: usize = 2;
+/// The number of extra chunks beyond the load radius (and padding) that chunks are kept around
+/// for before being unloaded. Prevents chunks from being repeatedly unloaded and reloaded when
+/// the player moves back and forth across a chunk boundary.
+pub const CHUNK_UNLOAD_HYSTERESIS: usize = 2;
 
-/// The maximum number of chunks whose voxel data can be generated per frame.
-pub const MAX_CHUNK_DATA_GENERATION_PER_FRAME: usize = 32;
-/// The maximum number of chunks whose meshes can be built per frame.
-pub const MAX_CHUNK_MESH_GENERATION_PER_FRAME: usize = 16;
+/// How strongly the view direction affects the order chunks are loaded in. A chunk directly
+/// behind the player is treated as if it were `1 + VIEW_PRIORITY_WEIGHT` times further away.
+pub const VIEW_PRIORITY_WEIGHT: f32 = 1.5;
+/// The queues are re-sorted once the dot product between the current view direction and the one
+/// the queues were last sorted with drops below this value.
+const VIEW_REPRIORITIZE_THRESHOLD: f32 = 0.9;
 
-type UnUploadedMesh = (Vec<MeshVertex>, Vec<u32>);
+/// The maximum number of chunks that can be generating voxel data at once. Also the capacity of
+/// the channel generated chunks are sent back through.
+pub const MAX_CHUNKS_IN_FLIGHT: usize = 256;
+/// The maximum number of meshes that can be built or waiting to be uploaded at once. Also the
+/// capacity of the channel built meshes are sent back through.
+pub const MAX_MESHES_IN_FLIGHT: usize = 128;
 
-/// Manages the loading and unloading of chunks around the player.
-pub struct ChunkManager {
-    /// The noise generator used to generate terrain, etc.
-    noise: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+type UnUploadedMesh = MeshData;
+
+/// The meshes of every section of a chunk that has any geometry, tagged with the index of the
+/// section and the layer they belong to.
+type SectionMeshes<M> = Vec<(usize, MeshLayer, M)>;
+
+/// A chunk generated by a worker, along with the dimension it belongs to, how long it took to
+/// generate and the worker's cancellation token (held until the result is received).
+type GeneratedChunk = (Dimension, Chunk, Duration, CancellationToken);
+/// The section meshes of a chunk built by a worker, along with the dimension it belongs to, the
+/// visibility of its sections, how long they took to build and the worker's cancellation token
+/// (held until the result is received).
+type BuiltMeshes = (
+    Dimension,
+    glam::IVec2,
+    SectionMeshes<UnUploadedMesh>,
+    ChunkVisibility,
+    Duration,
+    CancellationToken,
+);
+
+/// A flag shared with a worker, set once the worker's result is no longer wanted.
+type CancellationToken = Arc<AtomicBool>;
+
+/// The tokens of cancelled work whose workers may still be running, or whose results have not
+/// been received yet. Cancelled work keeps counting against the in-flight limits until then, so
+/// cancelling never lets more workers run than the limits (and the capacities of the result
+/// channels) allow.
+#[derive(Default)]
+struct CancelledWork(Vec<CancellationToken>);
+
+impl CancelledWork {
+    /// Cancels the work the token was handed out to.
+    fn cancel(&mut self, token: CancellationToken) {
+        token.store(true, Ordering::Relaxed);
+        self.0.push(token);
+    }
+
+    /// Returns the number of cancelled jobs that are still in flight, forgetting the rest. A job
+    /// is in flight as long as its worker or its result still holds a clone of the token.
+    fn in_flight(&mut self) -> usize {
+        self.0.retain(|token| Arc::strong_count(token) > 1);
+        self.0.len()
+    }
+}
+
+/// Tuning options for the chunk manager, loaded from `CHUNK_MANAGER_CONFIG_FILE`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChunkManagerConfig {
+    /// The radius around the player in which chunks are loaded. `CHUNK_LOAD_PADDING` extra
+    /// chunks in both the x and z axes are loaded as padding for mesh generation.
+    pub load_radius: usize,
+    /// The maximum number of chunks whose voxel data can be generated per frame.
+    pub max_generation_per_frame: usize,
+    /// The maximum number of chunks whose meshes can be built per frame.
+    pub max_meshing_per_frame: usize,
+    /// The most bytes of mesh data uploaded to the GPU per frame, past which built meshes wait
+    /// for later frames. At least one chunk's meshes are uploaded every frame.
+    pub max_upload_bytes_per_frame: usize,
+    /// The number of threads generating chunk voxel data. Sized from the available cores if
+    /// not set.
+    pub generation_threads: Option<usize>,
+    /// The number of threads building chunk meshes. Sized from the available cores if not set.
+    pub meshing_threads: Option<usize>,
+    /// The fraction of the available cores given to voxel generation when sizing the thread
+    /// pools automatically, with the rest being given to meshing.
+    pub generation_thread_ratio: f32,
+}
+
+impl Default for ChunkManagerConfig {
+    fn default() -> Self {
+        Self {
+            load_radius: 16,
+            max_generation_per_frame: 32,
+            max_meshing_per_frame: 16,
+            max_upload_bytes_per_frame: 8 << 20,
+            generation_threads: None,
+            meshing_threads: None,
+            generation_thread_ratio: 2.0 / 3.0,
+        }
+    }
+}
+
+impl ChunkManagerConfig {
+    /// Returns the number of generation and meshing threads to use. Pools without an explicit
+    /// size share the available cores (minus one, left for the render thread) according to
+    /// `generation_thread_ratio`.
+    pub fn thread_counts(&self) -> (usize, usize) {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        let workers = cores.saturating_sub(1).max(2);
+
+        let ratio = self.generation_thread_ratio.clamp(0.0, 1.0);
+        let generation = ((workers as f32 * ratio).round() as usize).clamp(1, workers - 1);
+        let meshing = workers - generation;
+
+        (
+            self.generation_threads.unwrap_or(generation),
+            self.meshing_threads.unwrap_or(meshing),
+        )
+    }
+}
+
+/// How far along the pipeline a chunk is, from being queued to load to having its meshes drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkLoadState {
+    /// Waiting to be loaded from disk or generated.
+    Queued,
+    /// Being loaded or generated.
+    Generating,
+    /// Generated, but waiting on its neighbors to be decorated.
+    Undecorated,
+    /// Loaded, and waiting for or having its meshes built.
+    Meshing,
+    /// Meshed, but waiting for its meshes to be uploaded.
+    Uploading,
+    /// Meshed and uploaded, so it is being drawn.
+    Uploaded,
+}
+
+/// The chunks and meshes of a single dimension, along with the state of the work being done on
+/// them.
+struct World {
+    /// The dimension this world holds.
+    dimension: Dimension,
+    /// The generator used to fill in the world's chunks.
+    generator: Arc<dyn ChunkGenerator>,
 
-    /// The chunks that are currently loaded.
-    chunks: HashMap<glam::IVec2, Chunk>,
+    /// The chunks that are currently loaded. Shared with mesh workers, which only ever read them.
+    chunks: HashMap<glam::IVec2, Arc<Chunk>>,
     /// The meshes of the chunks that have been made and uploaded to the GPU.
-    uploaded_meshes: HashMap<glam::IVec2, Mesh>,
+    uploaded_meshes: HashMap<glam::IVec2, SectionMeshes<Mesh>>,
     /// The meshes of the chunks that have been made but not yet been uploaded to the GPU.
-    unuploaded_meshes: HashMap<glam::IVec2, UnUploadedMesh>,
+    unuploaded_meshes: HashMap<glam::IVec2, SectionMeshes<UnUploadedMesh>>,
+    /// Which faces of each section can be seen through to which others, for the chunks that
+    /// have been meshed.
+    visibility: HashMap<glam::IVec2, ChunkVisibility>,
 
     /// A queue of chunks to load.
     load_queue: VecDeque<glam::IVec2>,
     /// A queue of chunks to build meshes for.
     build_queue: VecDeque<glam::IVec2>,
 
-    /// A list of chunks that are currently having their voxel data generated.
-    currently_generating: HashSet<glam::IVec2>,
-    /// A list of chunks that are currently having their meshes built.
-    currently_meshing: HashSet<glam::IVec2>,
+    /// The chunks that are currently having their voxel data generated, along with the tokens
+    /// used to cancel them.
+    currently_generating: HashMap<glam::IVec2, CancellationToken>,
+    /// The chunks that are currently having their meshes built, along with the tokens used to
+    /// cancel them.
+    currently_meshing: HashMap<glam::IVec2, CancellationToken>,
+
+    /// The chunks whose voxels have been modified since they were last saved.
+    modified_chunks: HashSet<glam::IVec2>,
+    /// The chunks that have been generated but not yet decorated, as some of their neighbors
+    /// have not been generated yet.
+    undecorated: HashSet<glam::IVec2>,
+
+    /// The (current) chunk the player is in.
+    current_chunk: Option<glam::IVec2>,
+}
+
+impl World {
+    /// Creates a new, empty world for the given dimension of the saved world described by `info`.
+    fn new(dimension: Dimension, info: &WorldInfo, worldgen: &WorldgenConfig) -> Self {
+        Self {
+            dimension,
+            generator: info.world_type.generator(dimension, info.seed, worldgen),
+            chunks: HashMap::new(),
+            uploaded_meshes: HashMap::new(),
+            unuploaded_meshes: HashMap::new(),
+            visibility: HashMap::new(),
+            load_queue: VecDeque::new(),
+            build_queue: VecDeque::new(),
+            currently_generating: HashMap::new(),
+            currently_meshing: HashMap::new(),
+            modified_chunks: HashSet::new(),
+            undecorated: HashSet::new(),
+            current_chunk: None,
+        }
+    }
+
+    /// Adds a newly loaded or generated chunk to the world, running the load hooks of its block
+    /// entities.
+    fn insert_chunk(
+        &mut self,
+        chunk: Chunk,
+        handlers: &HashMap<String, Box<dyn BlockEntityHandler>>,
+    ) {
+        let mut chunk = Arc::new(chunk);
+
+        ChunkManager::run_block_entity_hooks(handlers, &mut chunk, |handler, position, entity| {
+            handler.on_load(position, entity)
+        });
+
+        if !chunk.decorated {
+            self.undecorated.insert(chunk.position);
+        }
+
+        self.chunks.insert(chunk.position, chunk);
+    }
+
+    /// Cancels all in-flight work and empties the queues. The chunks and meshes that are already
+    /// loaded are kept, and the queues are refilled the next time the world is updated.
+    fn suspend(&mut self, generation: &mut CancelledWork, meshing: &mut CancelledWork) {
+        for (_, token) in self.currently_generating.drain() {
+            generation.cancel(token);
+        }
+
+        for (_, token) in self.currently_meshing.drain() {
+            meshing.cancel(token);
+        }
+
+        self.load_queue.clear();
+        self.build_queue.clear();
+        self.current_chunk = None;
+    }
+
+    /// Drops every uploaded mesh, queueing the chunks they were built from to be meshed again.
+    fn discard_meshes(&mut self) {
+        for (position, _) in self.uploaded_meshes.drain() {
+            if !(self.build_queue.contains(&position)
+                || self.currently_meshing.contains_key(&position))
+            {
+                self.build_queue.push_back(position);
+            }
+        }
+    }
+}
+
+/// Manages the loading and unloading of chunks around the player, across every dimension.
+pub struct ChunkManager {
+    /// The options the chunk manager was created with.
+    config: ChunkManagerConfig,
+    /// The metadata of the saved world, deciding how every dimension is generated.
+    info: WorldInfo,
+    /// The options shaping the terrain of every dimension.
+    worldgen: WorldgenConfig,
+
+    /// The world of the dimension the player is in, which is the only one being updated and
+    /// drawn.
+    world: World,
+    /// The worlds of the other dimensions that have been visited, kept around so switching back
+    /// to them is instant.
+    inactive_worlds: HashMap<Dimension, World>,
 
     /// A thread pool to manage chunks voxel data to be built.
     chunk_thread_pool: rayon::ThreadPool,
-    /// The producer end of the `std::sync::mpsc::channel` to communicate with workers.
-    chunk_tx: std::sync::mpsc::Sender<Chunk>,
-    /// The consumer end of the `std::sync::mpsc::channel` to communicate with workers.
-    chunk_rx: std::sync::mpsc::Receiver<Chunk>,
+    /// The producer end of the `std::sync::mpsc::sync_channel` to communicate with workers.
+    chunk_tx: std::sync::mpsc::SyncSender<GeneratedChunk>,
+    /// The consumer end of the `std::sync::mpsc::sync_channel` to communicate with workers.
+    chunk_rx: std::sync::mpsc::Receiver<GeneratedChunk>,
+    /// The voxel generation work that was cancelled but is still in flight.
+    cancelled_generation: CancelledWork,
+
+    /// The producer end of the `std::sync::mpsc::channel` to send requests to the disk I/O worker.
+    io_tx: std::sync::mpsc::Sender<IoRequest>,
+    /// The consumer end of the `std::sync::mpsc::channel` to receive chunks read from disk.
+    io_rx: std::sync::mpsc::Receiver<IoResult>,
+    /// The thread reading and writing chunks to disk.
+    io_thread: Option<JoinHandle<()>>,
+    /// The number of saves sent to the I/O worker that it has not finished writing yet.
+    pending_saves: Arc<AtomicUsize>,
 
     /// A thread pool to manage chunks meshes to be built.
     mesh_thread_pool: rayon::ThreadPool,
-    /// The producer end of the `std::sync::mpsc::channel` to communicate with workers.
-    mesh_tx: std::sync::mpsc::Sender<(glam::IVec2, UnUploadedMesh)>,
-    /// The consumer end of the `std::sync::mpsc::channel` to communicate with workers.
-    mesh_rx: std::sync::mpsc::Receiver<(glam::IVec2, UnUploadedMesh)>,
+    /// The producer end of the `std::sync::mpsc::sync_channel` to communicate with workers.
+    mesh_tx: std::sync::mpsc::SyncSender<BuiltMeshes>,
+    /// The consumer end of the `std::sync::mpsc::sync_channel` to communicate with workers.
+    mesh_rx: std::sync::mpsc::Receiver<BuiltMeshes>,
+    /// The meshing work that was cancelled but is still in flight.
+    cancelled_meshing: CancelledWork,
 
-    /// The (current) chunk the player is in.
-    current_chunk: Option<glam::IVec2>,
+    /// The handlers run on block entities as their chunks are loaded, unloaded and saved, keyed
+    /// by the kind of block entity they handle.
+    block_entity_handlers: HashMap<String, Box<dyn BlockEntityHandler>>,
+
+    /// Timing and throughput statistics of each stage of the pipeline.
+    metrics: PipelineMetrics,
+
+    /// The normalized direction the player is looking in along the xz plane.
+    view_direction: glam::Vec2,
+    /// The view direction the queues were last sorted with.
+    prioritized_direction: glam::Vec2,
 }
 
 impl ChunkManager {
-    /// Creates a new chunk manager.
-    pub fn new() -> Self {
-        let noise = Box::new(create_noise_generator(129));
+    /// Creates a new chunk manager, starting in the overworld, generating chunks the way the
+    /// world described by `info` was created, shaped by the given worldgen config. Chunks are
+    /// saved to and loaded from the given world directory, compressed with the given compression.
+    pub fn new(
+        config: ChunkManagerConfig,
+        info: WorldInfo,
+        worldgen: WorldgenConfig,
+        world_directory: PathBuf,
+        compression: Compression,
+    ) -> Self {
+        let (generation_threads, meshing_threads) = config.thread_counts();
 
         let chunk_thread_pool = ThreadPoolBuilder::new()
-            .num_threads(16)
+            .num_threads(generation_threads)
             .build()
             .expect("could not create chunk voxel builder thread pool");
 
-        let (chunk_tx, chunk_rx) = mpsc::channel();
+        let (chunk_tx, chunk_rx) = mpsc::sync_channel(MAX_CHUNKS_IN_FLIGHT);
 
         let mesh_thread_pool = ThreadPoolBuilder::new()
-            .num_threads(8)
+            .num_threads(meshing_threads)
             .build()
             .expect("could not create mesh builder thread pool");
 
-        let (mesh_tx, mesh_rx) = mpsc::channel();
+        let (mesh_tx, mesh_rx) = mpsc::sync_channel(MAX_MESHES_IN_FLIGHT);
+
+        let (io_tx, requests) = mpsc::channel();
+        let (results, io_rx) = mpsc::channel();
+        let pending_saves = Arc::new(AtomicUsize::new(0));
+        let worker_pending_saves = Arc::clone(&pending_saves);
+
+        let io_thread = std::thread::Builder::new()
+            .name("chunk io".into())
+            .spawn(move || {
+                storage::run_io_worker(
+                    world_directory,
+                    compression,
+                    requests,
+                    results,
+                    worker_pending_saves,
+                )
+            })
+            .expect("could not create chunk io thread");
 
         Self {
-            noise,
-            chunks: HashMap::new(),
-            unuploaded_meshes: HashMap::new(),
-            uploaded_meshes: HashMap::new(),
-            load_queue: VecDeque::new(),
-            build_queue: VecDeque::new(),
-            currently_generating: HashSet::new(),
-            currently_meshing: HashSet::new(),
+            config,
+            world: World::new(Dimension::Overworld, &info, &worldgen),
+            info,
+            worldgen,
+            inactive_worlds: HashMap::new(),
             chunk_thread_pool,
             chunk_tx,
             chunk_rx,
+            cancelled_generation: CancelledWork::default(),
+            io_tx,
+            io_rx,
+            io_thread: Some(io_thread),
+            pending_saves,
             mesh_thread_pool,
             mesh_tx,
             mesh_rx,
-            current_chunk: None,
+            cancelled_meshing: CancelledWork::default(),
+            block_entity_handlers: HashMap::new(),
+            metrics: PipelineMetrics::default(),
+            view_direction: Vec2::ZERO,
+            prioritized_direction: Vec2::ZERO,
         }
     }
 
-    /// Updates the chunk manager with the latest player position.
-    pub fn update(&mut self, player_position: Vec3) {
+    /// Updates the chunk manager with the latest player position and the direction they are
+    /// facing, which is used to load the chunks in front of the player first.
+    pub fn update(&mut self, player_position: DVec3, view_direction: Vec3) {
+        self.metrics.end_frame();
+
         self.load_chunks();
+        self.decorate_chunks();
         self.build_meshes();
+        self.update_queue_metrics();
+
+        self.view_direction = view_direction.xz().normalize_or_zero();
 
         let player_chunk = ivec2(
             (player_position.x as i32).div_euclid(CHUNK_WIDTH as i32),
             (player_position.z as i32).div_euclid(CHUNK_WIDTH as i32),
         );
 
-        if let Some(chunk) = self.current_chunk {
+        if let Some(chunk) = self.world.current_chunk {
             if chunk == player_chunk {
+                if self.view_direction.dot(self.prioritized_direction) < VIEW_REPRIORITIZE_THRESHOLD
+                {
+                    self.prioritize_queues();
+                }
+
                 return;
             };
         }
 
-        self.current_chunk = Some(player_chunk);
+        self.world.current_chunk = Some(player_chunk);
+        self.cancel_stale_work();
+        self.unload_distant_chunks();
         self.queue_surrounding_chunks();
     }
 
-    /// Uploads any meshes that have built but not uploaded.
-    pub fn resolve_mesh_uploads(&mut self, device: &Device) {
-        for (position, (vertices, indices)) in self.unuploaded_meshes.drain() {
-            let mesh = Mesh::new(&vertices, &indices, device);
-            self.uploaded_meshes.insert(position, mesh);
+    /// Returns the seed every dimension is generated with.
+    pub fn seed(&self) -> u32 {
+        self.info.seed
+    }
+
+    /// Returns the dimension the player is currently in.
+    pub fn dimension(&self) -> Dimension {
+        self.world.dimension
+    }
+
+    /// Moves the player into the given dimension, which becomes the one that is updated and
+    /// drawn. The previous dimension's modified chunks are saved and its in-flight work is
+    /// cancelled, but its loaded chunks and meshes are kept for when the player returns.
+    pub fn set_dimension(&mut self, dimension: Dimension) {
+        if dimension == self.world.dimension {
+            return;
         }
+
+        self.save_modified();
+        self.world
+            .suspend(&mut self.cancelled_generation, &mut self.cancelled_meshing);
+
+        let world = self
+            .inactive_worlds
+            .remove(&dimension)
+            .unwrap_or_else(|| World::new(dimension, &self.info, &self.worldgen));
+        let previous = std::mem::replace(&mut self.world, world);
+
+        self.inactive_worlds.insert(previous.dimension, previous);
+        self.prioritized_direction = Vec2::ZERO;
     }
 
-    /// Adds the chunks that are not currently being built or have not already been generated (mesh
-    /// or voxel data) onto the respective queues.
-    fn queue_surrounding_chunks(&mut self) {
-        let Some(player_chunk) = self.current_chunk else {
+    /// Drops every uploaded mesh, in every dimension, and queues their chunks to be meshed
+    /// again. Called once the GPU device the meshes were uploaded to has been lost, as the
+    /// meshes can only be rebuilt from the voxels of the chunks.
+    pub fn discard_meshes(&mut self) {
+        self.world.discard_meshes();
+
+        for world in self.inactive_worlds.values_mut() {
+            world.discard_meshes();
+        }
+
+        self.prioritize_queues();
+    }
+
+    /// Removes queued chunks that have fallen outside of the load radius, and cancels the
+    /// workers currently generating or meshing them.
+    fn cancel_stale_work(&mut self) {
+        let Some(player_chunk) = self.world.current_chunk else {
             return;
         };
 
-        let mut neighbors =
-            Self::get_chunks_around(player_chunk, CHUNK_LOAD_RADIUS + CHUNK_LOAD_PADDING)
-                .map(|chunk| {
-                    // the manhattan distance between this chunk and the player
-                    let distance = (player_chunk.x - chunk.x)
-                        .abs()
-                        .max((player_chunk.y - chunk.y).abs())
-                        as usize;
+        let load_radius = self.config.load_radius;
+        let load_limit = load_radius + CHUNK_LOAD_PADDING;
+        let in_load_range =
+            |position: &IVec2| Self::chunk_distance(player_chunk, *position) <= load_limit;
+        let in_mesh_range =
+            |position: &IVec2| Self::chunk_distance(player_chunk, *position) <= load_radius;
+
+        self.world.load_queue.retain(in_load_range);
+        self.world.build_queue.retain(in_mesh_range);
+
+        Self::cancel_work_outside(
+            &mut self.world.currently_generating,
+            &mut self.cancelled_generation,
+            in_load_range,
+        );
+        Self::cancel_work_outside(
+            &mut self.world.currently_meshing,
+            &mut self.cancelled_meshing,
+            in_mesh_range,
+        );
+    }
+
+    /// Cancels all in-flight work whose chunk position is not in range, moving it into
+    /// `cancelled` until its workers finish.
+    fn cancel_work_outside(
+        work: &mut HashMap<IVec2, CancellationToken>,
+        cancelled: &mut CancelledWork,
+        in_range: impl Fn(&IVec2) -> bool,
+    ) {
+        let stale = work
+            .keys()
+            .filter(|position| !in_range(position))
+            .copied()
+            .collect::<Vec<_>>();
+
+        for position in stale {
+            if let Some(token) = work.remove(&position) {
+                cancelled.cancel(token);
+            }
+        }
+    }
+
+    /// Drops the voxel data and meshes of all chunks that are sufficiently far away from the
+    /// player.
+    fn unload_distant_chunks(&mut self) {
+        let Some(player_chunk) = self.world.current_chunk else {
+            return;
+        };
+
+        let load_radius = self.config.load_radius;
+        let chunk_limit = load_radius + CHUNK_LOAD_PADDING + CHUNK_UNLOAD_HYSTERESIS;
+        let mesh_limit = load_radius + CHUNK_UNLOAD_HYSTERESIS;
+
+        let unloaded = self
+            .world
+            .chunks
+            .keys()
+            .filter(|position| Self::chunk_distance(player_chunk, **position) > chunk_limit)
+            .copied()
+            .collect::<Vec<_>>();
+
+        for position in unloaded {
+            // modified chunks are written out before being forgotten, or their edits are lost
+            if self.world.modified_chunks.remove(&position) {
+                self.save_chunk(position);
+            }
+
+            self.world.undecorated.remove(&position);
+
+            if let Some(mut chunk) = self.world.chunks.remove(&position) {
+                Self::run_block_entity_hooks(
+                    &self.block_entity_handlers,
+                    &mut chunk,
+                    |handler, position, entity| handler.on_unload(position, entity),
+                );
+            }
+        }
+
+        self.world
+            .unuploaded_meshes
+            .retain(|position, _| Self::chunk_distance(player_chunk, *position) <= mesh_limit);
+        self.world
+            .visibility
+            .retain(|position, _| Self::chunk_distance(player_chunk, *position) <= mesh_limit);
+
+        // dropping a mesh returns its space in the mesh pool
+        self.world
+            .uploaded_meshes
+            .retain(|position, _| Self::chunk_distance(player_chunk, *position) <= mesh_limit);
+    }
+
+    /// Updates the queue depths reported in the pipeline metrics.
+    fn update_queue_metrics(&mut self) {
+        let metrics = &mut self.metrics;
+
+        metrics.generation.queued = self.world.load_queue.len();
+        metrics.generation.in_flight = self.world.currently_generating.len();
+        metrics.meshing.queued = self.world.build_queue.len();
+        metrics.meshing.in_flight = self.world.currently_meshing.len();
+        metrics.upload.queued = self.world.unuploaded_meshes.len();
+    }
+
+    /// Uploads the meshes that have been built but not uploaded into the given mesh pool, those
+    /// of the chunks nearest to the player first, until `max_upload_bytes_per_frame` bytes have
+    /// been uploaded. The rest are uploaded during later frames, so that frames stay smooth
+    /// when many meshes are built at once (such as after teleporting).
+    pub fn resolve_mesh_uploads(&mut self, device: &Device, queue: &Queue, pool: &Arc<MeshPool>) {
+        let mut positions = self
+            .world
+            .unuploaded_meshes
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+
+        if let Some(player_chunk) = self.world.current_chunk {
+            positions.sort_by_key(|position| Self::chunk_distance(player_chunk, *position));
+        }
+
+        let mut uploaded_bytes = 0;
+
+        for position in positions {
+            if uploaded_bytes > 0 && uploaded_bytes >= self.config.max_upload_bytes_per_frame {
+                break;
+            }
+
+            let Some(sections) = self.world.unuploaded_meshes.remove(&position) else {
+                continue;
+            };
+
+            uploaded_bytes += sections
+                .iter()
+                .map(|(_, _, (vertices, indices))| {
+                    size_of_val(vertices.as_slice()) + size_of_val(indices.as_slice())
+                })
+                .sum::<usize>();
+
+            let start = Instant::now();
+            let origin = ivec3(position.x, 0, position.y) * CHUNK_WIDTH as i32;
 
-                    (chunk, distance)
+            let meshes = sections
+                .into_iter()
+                .filter_map(|(section, layer, (vertices, indices))| {
+                    match Mesh::new(&vertices, &indices, origin, pool, device, queue) {
+                        Ok(mesh) => Some((section, layer, mesh)),
+                        Err(error) => {
+                            eprintln!("could not upload mesh of chunk {position}: {error:?}");
+                            None
+                        }
+                    }
                 })
-                .collect::<Vec<_>>();
+                .collect();
+
+            // the previous meshes (if any) are dropped, returning their space in the pool
+            self.world.uploaded_meshes.insert(position, meshes);
+
+            self.metrics.upload.record(start.elapsed());
+        }
+    }
+
+    /// Returns the timing and throughput statistics of each stage of the pipeline.
+    pub fn metrics(&self) -> &PipelineMetrics {
+        &self.metrics
+    }
 
-        // prioritize "padding" chunks (i.e. chunks outside the load-radius), then sort from
-        // closest to the player
-        neighbors.sort_by(|(_, dist_a), (_, dist_b)| {
-            let a_inside = *dist_a <= CHUNK_LOAD_RADIUS;
-            let b_inside = *dist_b <= CHUNK_LOAD_RADIUS;
+    /// Returns the voxel at the given world position, or None if the chunk containing it has not
+    /// been loaded (or the position is outside the world vertically).
+    pub fn get_block(&self, position: IVec3) -> Option<Voxel> {
+        let (chunk, local) = Chunk::locate(position)?;
+
+        self.world
+            .chunks
+            .get(&chunk)
+            .map(|chunk| chunk.get_voxel(local))
+    }
+
+    /// Returns the first voxel that can be targeted (anything other than air and water) along
+    /// the ray from `origin` in the given `direction`, within `max_distance`.
+    pub fn raycast(&self, origin: DVec3, direction: Vec3, max_distance: f32) -> Option<RaycastHit> {
+        raycast(origin, direction, max_distance, |position| {
+            self.get_block(position)
+                .is_some_and(|voxel| !matches!(voxel, Voxel::Air | Voxel::Water))
+        })
+    }
+
+    /// Sets the voxel at the given world position, and rebuilds the meshes of the owning chunk
+    /// (and any neighbors touching the voxel) right away, rather than on the mesh workers, so
+    /// single edits (like the player's) show up the same frame. Returns whether the voxel could
+    /// be set, which requires its chunk to be loaded.
+    pub fn set_block(&mut self, position: IVec3, voxel: Voxel) -> bool {
+        if self.set_blocks(std::iter::once((position, voxel))) != 1 {
+            return false;
+        }
+
+        if let Some((chunk, local)) = Chunk::locate(position) {
+            for affected in Self::chunks_touching(chunk, local) {
+                self.remesh_now(affected);
+            }
+        }
+
+        true
+    }
 
-            // Inside chunks first, then by distance ascending
-            b_inside.cmp(&a_inside).then_with(|| dist_a.cmp(dist_b))
+    /// Breaks the voxel at the given world position, replacing it with air, unless it is not
+    /// breakable (see `Voxel::is_breakable`). Returns the voxel that was broken.
+    pub fn break_block(&mut self, position: IVec3) -> Option<Voxel> {
+        let voxel = self.get_block(position)?;
+
+        if !voxel.is_breakable() {
+            return None;
+        }
+
+        self.set_block(position, Voxel::Air).then_some(voxel)
+    }
+
+    /// Fills the box between `min` and `max` (inclusive) with the given voxel. Returns the number
+    /// of voxels that were set.
+    pub fn fill_region(&mut self, min: IVec3, max: IVec3, voxel: Voxel) -> usize {
+        let (min, max) = (min.min(max), min.max(max));
+
+        let positions = (min.y..=max.y).flat_map(move |y| {
+            (min.z..=max.z).flat_map(move |z| (min.x..=max.x).map(move |x| ivec3(x, y, z)))
         });
 
+        self.set_blocks(positions.map(|position| (position, voxel)))
+    }
+
+    /// Fills every voxel whose center lies within `radius` of `center` with the given voxel.
+    /// Returns the number of voxels that were set.
+    pub fn fill_sphere(&mut self, center: IVec3, radius: u32, voxel: Voxel) -> usize {
+        let radius = radius as i32;
+
+        let positions = (-radius..=radius)
+            .flat_map(move |y| {
+                (-radius..=radius)
+                    .flat_map(move |z| (-radius..=radius).map(move |x| ivec3(x, y, z)))
+            })
+            .filter(move |offset| offset.length_squared() <= radius * radius)
+            .map(move |offset| center + offset);
+
+        self.set_blocks(positions.map(|position| (position, voxel)))
+    }
+
+    /// Applies a batch of voxel edits, rebuilding the mesh of each affected chunk only once no
+    /// matter how many of its voxels changed. Edits to unloaded chunks are skipped. Returns the
+    /// number of voxels that were set.
+    pub fn set_blocks(&mut self, edits: impl IntoIterator<Item = (IVec3, Voxel)>) -> usize {
+        let mut affected = HashSet::new();
+        let mut count = 0;
+
+        for (position, voxel) in edits {
+            let Some((chunk_position, local)) = Chunk::locate(position) else {
+                continue;
+            };
+
+            let Some(chunk) = self.world.chunks.get_mut(&chunk_position) else {
+                continue;
+            };
+
+            // mesh workers may still hold a reference to the old chunk, in which case it is
+            // copied (only on the first edit to it)
+            let chunk = Arc::make_mut(chunk);
+
+            // block entities belong to the voxel they were placed on
+            if chunk.get_voxel(local) != voxel {
+                chunk.block_entities.remove(&local);
+            }
+
+            chunk.set_voxel(local, voxel);
+
+            affected.extend(Self::chunks_touching(chunk_position, local));
+            self.world.modified_chunks.insert(chunk_position);
+            count += 1;
+        }
+
+        for position in affected {
+            self.queue_remesh(position);
+        }
+
+        count
+    }
+
+    /// Stamps a structure into the loaded world with its minimum corner at `origin`, as a single
+    /// batch of edits. Returns the number of voxels that were set.
+    pub fn place_structure(&mut self, structure: &Structure, origin: IVec3) -> usize {
+        self.set_blocks(structure.voxels_at(origin))
+    }
+
+    /// Returns the block entity at the given world position, or None if there is none or its chunk
+    /// has not been loaded.
+    pub fn get_block_entity(&self, position: IVec3) -> Option<&BlockEntity> {
+        let (chunk, local) = Chunk::locate(position)?;
+
+        self.world.chunks.get(&chunk)?.block_entities.get(&local)
+    }
+
+    /// Attaches a block entity to the voxel at the given world position, replacing any it already
+    /// had. Returns whether the block entity could be set, which requires its chunk to be loaded.
+    pub fn set_block_entity(&mut self, position: IVec3, entity: BlockEntity) -> bool {
+        let Some((chunk_position, local)) = Chunk::locate(position) else {
+            return false;
+        };
+
+        let Some(chunk) = self.world.chunks.get_mut(&chunk_position) else {
+            return false;
+        };
+
+        Arc::make_mut(chunk).block_entities.insert(local, entity);
+        self.world.modified_chunks.insert(chunk_position);
+
+        true
+    }
+
+    /// Removes and returns the block entity at the given world position, if there was one.
+    pub fn remove_block_entity(&mut self, position: IVec3) -> Option<BlockEntity> {
+        let (chunk_position, local) = Chunk::locate(position)?;
+        let chunk = self.world.chunks.get_mut(&chunk_position)?;
+
+        if !chunk.block_entities.contains_key(&local) {
+            return None;
+        }
+
+        self.world.modified_chunks.insert(chunk_position);
+
+        Arc::make_mut(chunk).block_entities.remove(&local)
+    }
+
+    /// Registers the handler whose hooks are run on every block entity of the given kind.
+    /// Replaces any handler previously registered for that kind.
+    pub fn register_block_entity_handler(
+        &mut self,
+        kind: impl Into<String>,
+        handler: impl BlockEntityHandler + 'static,
+    ) {
+        self.block_entity_handlers
+            .insert(kind.into(), Box::new(handler));
+    }
+
+    /// Runs a hook on every block entity in the chunk that has a handler registered for its
+    /// kind. The chunk is only copied if a mesh worker still holds it and a hook needs to run.
+    fn run_block_entity_hooks(
+        handlers: &HashMap<String, Box<dyn BlockEntityHandler>>,
+        chunk: &mut Arc<Chunk>,
+        hook: impl Fn(&dyn BlockEntityHandler, IVec3, &mut BlockEntity),
+    ) {
+        let handled = chunk
+            .block_entities
+            .values()
+            .any(|entity| handlers.contains_key(&entity.kind));
+
+        if !handled {
+            return;
+        }
+
+        let chunk = Arc::make_mut(chunk);
+        let origin = chunk.world_position([0, 0, 0]);
+
+        for ([x, y, z], entity) in chunk.block_entities.iter_mut() {
+            if let Some(handler) = handlers.get(&entity.kind) {
+                let position = origin + ivec3(*x as i32, *y as i32, *z as i32);
+
+                hook(handler.as_ref(), position, entity);
+            }
+        }
+    }
+
+    /// Returns the chunks whose meshes depend on the voxel at the given local position, that is
+    /// the chunk itself and any neighbors the voxel borders (including diagonally, for ambient
+    /// occlusion).
+    fn chunks_touching(chunk: IVec2, [x, _, z]: [usize; 3]) -> impl Iterator<Item = IVec2> {
+        let edge_offsets = |n: usize| match n {
+            0 => -1..=0,
+            n if n == CHUNK_WIDTH - 1 => 0..=1,
+            _ => 0..=0,
+        };
+
+        let zs = edge_offsets(z);
+
+        edge_offsets(x).flat_map(move |dx| zs.clone().map(move |dz| chunk + ivec2(dx, dz)))
+    }
+
+    /// Queues a chunk whose voxel data has changed to have its mesh rebuilt, ahead of any other
+    /// chunks waiting to be meshed. Chunks that were never meshed are left alone, and meshes
+    /// being built from stale data are cancelled.
+    fn queue_remesh(&mut self, position: IVec2) {
+        if self.world.build_queue.contains(&position) {
+            return;
+        }
+
+        let in_flight = match self.world.currently_meshing.remove(&position) {
+            Some(token) => {
+                self.cancelled_meshing.cancel(token);
+                true
+            }
+            None => false,
+        };
+
+        let mesh_built = self.world.unuploaded_meshes.contains_key(&position)
+            || self.world.uploaded_meshes.contains_key(&position);
+
+        if in_flight || mesh_built {
+            self.world.build_queue.push_front(position);
+        }
+    }
+
+    /// Rebuilds the mesh of a chunk queued by `ChunkManager::queue_remesh` on this thread, instead
+    /// of waiting for a mesh worker to build it. Chunks whose neighbors aren't all generated are
+    /// left in the queue.
+    fn remesh_now(&mut self, position: IVec2) {
+        let Some(index) = self
+            .world
+            .build_queue
+            .iter()
+            .position(|&queued| queued == position)
+        else {
+            return;
+        };
+
+        let Some(neighborhood) = self.neighborhood_of(position) else {
+            return;
+        };
+
+        self.world.build_queue.remove(index);
+
+        let start = Instant::now();
+        let mesh = ChunkMesher::new(&neighborhood, position, &*self.world.generator).build();
+        let visibility = ChunkVisibility::compute(&neighborhood[&position]);
+
+        self.metrics.meshing.record(start.elapsed());
+
+        // uploaded with the rest of this frame's meshes, replacing the previous one
+        self.world.unuploaded_meshes.insert(position, mesh);
+        self.world.visibility.insert(position, visibility);
+    }
+
+    /// Adds the chunks that are not currently being built or have not already been generated (mesh
+    /// or voxel data) onto the respective queues.
+    fn queue_surrounding_chunks(&mut self) {
+        let Some(player_chunk) = self.world.current_chunk else {
+            return;
+        };
+
+        let neighbors =
+            Self::get_chunks_around(player_chunk, self.config.load_radius + CHUNK_LOAD_PADDING)
+                .map(|chunk| (chunk, Self::chunk_distance(player_chunk, chunk)));
+
         for (neighbor, distance) in neighbors {
-            if !(self.chunks.contains_key(&neighbor)
-                || self.load_queue.contains(&neighbor)
-                || self.currently_generating.contains(&neighbor))
+            if !(self.world.chunks.contains_key(&neighbor)
+                || self.world.load_queue.contains(&neighbor)
+                || self.world.currently_generating.contains_key(&neighbor))
             {
-                self.load_queue.push_back(neighbor);
+                self.world.load_queue.push_back(neighbor);
             }
 
-            if distance > CHUNK_LOAD_RADIUS {
+            if distance > self.config.load_radius {
                 continue;
             }
 
-            let mesh_built = self.unuploaded_meshes.contains_key(&neighbor)
-                || self.uploaded_meshes.contains_key(&neighbor);
+            let mesh_built = self.world.unuploaded_meshes.contains_key(&neighbor)
+                || self.world.uploaded_meshes.contains_key(&neighbor);
 
             if !(mesh_built
-                || self.build_queue.contains(&neighbor)
-                || self.currently_meshing.contains(&neighbor))
+                || self.world.build_queue.contains(&neighbor)
+                || self.world.currently_meshing.contains_key(&neighbor))
             {
-                self.build_queue.push_back(neighbor);
+                self.world.build_queue.push_back(neighbor);
             }
         }
+
+        self.prioritize_queues();
+    }
+
+    /// Sorts the load and build queues so that chunks inside the load radius come first, then
+    /// by their distance to the player, weighted towards the chunks the player is looking at.
+    fn prioritize_queues(&mut self) {
+        let Some(player_chunk) = self.world.current_chunk else {
+            return;
+        };
+
+        let view_direction = self.view_direction;
+        let load_radius = self.config.load_radius;
+        let priority = |chunk: &IVec2| {
+            let distance = Self::chunk_distance(player_chunk, *chunk);
+            let facing = (*chunk - player_chunk)
+                .as_vec2()
+                .normalize_or_zero()
+                .dot(view_direction);
+
+            let weighted = distance as f32 * (1.0 + VIEW_PRIORITY_WEIGHT * (1.0 - facing) / 2.0);
+
+            (distance > load_radius, weighted)
+        };
+
+        let compare = |a: &IVec2, b: &IVec2| {
+            let (a_outside, a_weighted) = priority(a);
+            let (b_outside, b_weighted) = priority(b);
+
+            // inside chunks first, then by weighted distance ascending
+            a_outside
+                .cmp(&b_outside)
+                .then_with(|| a_weighted.total_cmp(&b_weighted))
+        };
+
+        self.world.load_queue.make_contiguous().sort_by(compare);
+        self.world.build_queue.make_contiguous().sort_by(compare);
+
+        self.prioritized_direction = view_direction;
     }
 
-    /// Loads upto `MAX_CHUNK_GENERATION_PER_FRAME` chunks that are currently in the load queue.
+    /// Loads upto `max_generation_per_frame` chunks that are currently in the load queue.
+    /// Each chunk is first requested from the disk I/O worker, and only generated on the chunk
+    /// thread pool if it has never been saved; finished chunks are collected on a later frame.
+    /// No more than `MAX_CHUNKS_IN_FLIGHT` chunks (including cancelled ones whose workers are
+    /// still running) are ever being loaded at once.
     fn load_chunks(&mut self) {
-        for chunk in self.chunk_rx.try_iter() {
-            self.currently_generating.remove(&chunk.position);
-            self.chunks.insert(chunk.position, chunk);
+        for (dimension, position, chunk) in self.io_rx.try_iter() {
+            // results of cancelled work (or of other dimensions) are no longer being waited on
+            if dimension != self.world.dimension {
+                continue;
+            }
+
+            let Some(cancelled) = self.world.currently_generating.get(&position) else {
+                continue;
+            };
+
+            match chunk {
+                Some(chunk) => {
+                    self.world.currently_generating.remove(&position);
+                    self.world.insert_chunk(chunk, &self.block_entity_handlers);
+                }
+                None => self.spawn_generation(position, Arc::clone(cancelled)),
+            }
         }
 
-        for position in self
-            .load_queue
-            .drain(..MAX_CHUNK_DATA_GENERATION_PER_FRAME.min(self.load_queue.len()))
-        {
-            let tx = self.chunk_tx.clone();
+        for (dimension, chunk, duration, _token) in self.chunk_rx.try_iter() {
+            if dimension == self.world.dimension
+                && self
+                    .world
+                    .currently_generating
+                    .remove(&chunk.position)
+                    .is_some()
+            {
+                self.metrics.generation.record(duration);
+                self.world.insert_chunk(chunk, &self.block_entity_handlers);
+            }
+        }
 
-            self.currently_generating.insert(position);
+        let in_flight =
+            self.world.currently_generating.len() + self.cancelled_generation.in_flight();
+        let available = MAX_CHUNKS_IN_FLIGHT.saturating_sub(in_flight);
+        let count = self
+            .config
+            .max_generation_per_frame
+            .min(available)
+            .min(self.world.load_queue.len());
 
-            self.chunk_thread_pool.scope(|_| {
-                let mut chunk = Chunk::new(position);
-                chunk.fill_perlin(&*self.noise);
+        for position in self.world.load_queue.drain(..count) {
+            self.world
+                .currently_generating
+                .insert(position, CancellationToken::default());
 
-                tx.send(chunk).unwrap();
-            });
+            // the worker only stops once the manager is dropped
+            let _ = self
+                .io_tx
+                .send(IoRequest::Load(self.world.dimension, position));
         }
     }
 
-    /// Builds upto `MAX_CHUNK_MESH_GENERATION_PER_FRAME` meshes that are currently in the build
-    /// queue.
-    pub fn build_meshes(&mut self) {
-        for (position, mesh) in self.mesh_rx.try_iter() {
-            self.currently_meshing.remove(&position);
+    /// Decorates every generated chunk whose neighbors have all been generated. Decorations are
+    /// allowed to spill into neighboring chunks, which is why chunks are only meshed once they
+    /// and all of their neighbors have been decorated; the `CHUNK_LOAD_PADDING` ensures the
+    /// neighbors of meshed chunks get decorated.
+    fn decorate_chunks(&mut self) {
+        let ready = self
+            .world
+            .undecorated
+            .iter()
+            .filter(|position| {
+                Self::get_chunks_around(**position, 1)
+                    .all(|neighbor| self.world.chunks.contains_key(&neighbor))
+            })
+            .copied()
+            .collect::<Vec<_>>();
+
+        for position in ready {
+            let chunks = Self::get_chunks_around(position, 1)
+                .filter_map(|neighbor| Some((neighbor, self.world.chunks.remove(&neighbor)?)))
+                .collect();
+
+            let mut region = ChunkRegion::new(position, chunks);
+            self.world.generator.decorate(&mut region);
+
+            let (mut chunks, modified) = region.into_parts();
+
+            if let Some(chunk) = chunks.get_mut(&position) {
+                Arc::make_mut(chunk).decorated = true;
+            }
 
-            assert!(
-                self.unuploaded_meshes.insert(position, mesh).is_none()
-                    && !self.uploaded_meshes.contains_key(&position),
-                "should not build duplicate meshes"
-            );
+            self.world.chunks.extend(chunks);
+            self.world.undecorated.remove(&position);
+
+            // the decorated chunk and the neighbors it spilled into are saved together, so a
+            // neighbor loaded from disk is never decorated again by a regenerated chunk (which
+            // would undo any edits made to the voxels spilled into it)
+            self.world.modified_chunks.insert(position);
+            self.world.modified_chunks.extend(modified.iter().copied());
+
+            // neighbors that were meshed before being spilled into (only possible when loaded
+            // from disk already decorated) have to be rebuilt
+            for neighbor in modified {
+                if neighbor != position {
+                    self.queue_remesh(neighbor);
+                }
+            }
         }
+    }
 
-        for position in self
-            .build_queue
-            .drain(..MAX_CHUNK_MESH_GENERATION_PER_FRAME.min(self.build_queue.len()))
-        {
-            let tx = self.mesh_tx.clone();
-            let chunks = &self.chunks;
+    /// Generates the voxel data of a chunk that was not found on disk.
+    fn spawn_generation(&self, position: IVec2, cancelled: CancellationToken) {
+        let tx = self.chunk_tx.clone();
+        let dimension = self.world.dimension;
+        let generator = Arc::clone(&self.world.generator);
+
+        self.chunk_thread_pool.spawn(move || {
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let start = Instant::now();
+
+            let mut chunk = Chunk::new(position);
+            generator.generate(&mut chunk);
+
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+
+            // never blocks, as no more jobs than the channel holds are ever in flight. The
+            // receiver only disconnects once the manager is dropped
+            let _ = tx.send((dimension, chunk, start.elapsed(), cancelled));
+        });
+    }
+
+    /// Queues the chunk at the given position in the current dimension to be written to disk by
+    /// the I/O worker. Returns whether the chunk was loaded.
+    pub fn save_chunk(&mut self, position: IVec2) -> bool {
+        let Some(chunk) = self.world.chunks.get_mut(&position) else {
+            return false;
+        };
+
+        Self::run_block_entity_hooks(
+            &self.block_entity_handlers,
+            chunk,
+            |handler, position, entity| handler.on_save(position, entity),
+        );
+
+        self.world.modified_chunks.remove(&position);
+        self.pending_saves.fetch_add(1, Ordering::Relaxed);
+
+        let _ = self
+            .io_tx
+            .send(IoRequest::Save(self.world.dimension, Arc::clone(chunk)));
+
+        true
+    }
+
+    /// Queues every chunk modified since it was last saved to be written to disk. Returns the
+    /// number of chunks queued.
+    pub fn save_modified(&mut self) -> usize {
+        let modified = self.world.modified_chunks.drain().collect::<Vec<_>>();
 
-            if !self.chunks.contains_key(&position) {
+        modified
+            .into_iter()
+            .filter(|position| self.save_chunk(*position))
+            .count()
+    }
+
+    /// Returns the number of chunks that have been modified but not yet written to disk, either
+    /// because they have not been queued yet or the I/O worker has not gotten to them.
+    pub fn pending_saves(&self) -> usize {
+        self.world.modified_chunks.len() + self.pending_saves.load(Ordering::Relaxed)
+    }
+
+    /// Builds upto `max_meshing_per_frame` meshes that are currently in the build
+    /// queue. Only chunks whose neighbors have all been generated are meshed; the rest are kept
+    /// in the queue until their neighbors arrive. Meshing stalls while `MAX_MESHES_IN_FLIGHT`
+    /// meshes are being built (including cancelled ones) or waiting to be uploaded.
+    pub fn build_meshes(&mut self) {
+        for (dimension, position, mesh, visibility, duration, _token) in self.mesh_rx.try_iter() {
+            if dimension != self.world.dimension
+                || self.world.currently_meshing.remove(&position).is_none()
+            {
                 continue;
             }
 
-            self.currently_meshing.insert(position);
+            self.metrics.meshing.record(duration);
+
+            // a remeshed chunk replaces whatever mesh it previously had once uploaded
+            self.world.unuploaded_meshes.insert(position, mesh);
+            self.world.visibility.insert(position, visibility);
+        }
+
+        let in_flight = self.world.currently_meshing.len()
+            + self.world.unuploaded_meshes.len()
+            + self.cancelled_meshing.in_flight();
+        let budget = self
+            .config
+            .max_meshing_per_frame
+            .min(MAX_MESHES_IN_FLIGHT.saturating_sub(in_flight));
+
+        let mut dispatched = 0;
+        let mut waiting = VecDeque::new();
+
+        while let Some(position) = self.world.build_queue.pop_front() {
+            if dispatched == budget {
+                self.world.build_queue.push_front(position);
+                break;
+            }
+
+            let Some(neighborhood) = self.neighborhood_of(position) else {
+                waiting.push_back(position);
+                continue;
+            };
+
+            let tx = self.mesh_tx.clone();
+            let dimension = self.world.dimension;
+            let generator = Arc::clone(&self.world.generator);
+            let cancelled = CancellationToken::default();
 
-            self.mesh_thread_pool.scope(move |_| {
-                let mesh = ChunkMesher::new(&chunks, position).build();
-                tx.send((position, mesh)).unwrap();
+            self.world
+                .currently_meshing
+                .insert(position, Arc::clone(&cancelled));
+            dispatched += 1;
+
+            self.mesh_thread_pool.spawn(move || {
+                if cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                let start = Instant::now();
+                let mesh = ChunkMesher::new(&neighborhood, position, &*generator).build();
+                let visibility = ChunkVisibility::compute(&neighborhood[&position]);
+
+                if !cancelled.load(Ordering::Relaxed) {
+                    let elapsed = start.elapsed();
+                    let _ = tx.send((dimension, position, mesh, visibility, elapsed, cancelled));
+                }
             });
         }
+
+        waiting.append(&mut self.world.build_queue);
+        self.world.build_queue = waiting;
+    }
+
+    /// Returns the chunk at the given position along with its eight neighbors, or None if any of
+    /// them have not been generated and decorated yet. The returned map is cheap to clone into a
+    /// worker.
+    fn neighborhood_of(&self, position: IVec2) -> Option<HashMap<IVec2, Arc<Chunk>>> {
+        Self::get_chunks_around(position, 1)
+            .map(|neighbor| {
+                let chunk = self.world.chunks.get(&neighbor)?;
+
+                chunk.decorated.then(|| (neighbor, Arc::clone(chunk)))
+            })
+            .collect()
+    }
+
+    /// Returns the chebyshev distance (in chunks) between two chunk positions.
+    fn chunk_distance(a: IVec2, b: IVec2) -> usize {
+        (a.x - b.x).abs().max((a.y - b.y).abs()) as usize
     }
 
     /// Gets the chunks around a chunk in the provided radius.
@@ -249,19 +1274,86 @@ impl ChunkManager {
             .flat_map(move |x| (-radius..=radius).map(move |z| position + ivec2(x, z)))
     }
 
-    /// Returns all the meshes that have been uploaded to the GPU, and
-    /// are ready for rendering.
-    pub fn loaded_meshes(&self) -> impl Iterator<Item = &Mesh> {
-        self.uploaded_meshes.values()
+    /// Returns all the meshes in the given layer of the current dimension that have been
+    /// uploaded to the GPU, and are ready for rendering, along with the position of the section
+    /// each belongs to (the chunk's position as x and z, with the section's index as y).
+    pub fn loaded_meshes(&self, layer: MeshLayer) -> impl Iterator<Item = (IVec3, &Mesh)> {
+        self.world
+            .uploaded_meshes
+            .iter()
+            .flat_map(move |(position, sections)| {
+                sections
+                    .iter()
+                    .filter(move |(_, mesh_layer, _)| *mesh_layer == layer)
+                    .map(|(section, _, mesh)| {
+                        (ivec3(position.x, *section as i32, position.y), mesh)
+                    })
+            })
+    }
+
+    /// Returns the positions of the sections that could be visible from a camera at `eye`, as
+    /// given by `visibility::visible_sections`, or None if every section should be drawn.
+    pub fn visible_sections(&self, eye: DVec3, frustum: &Frustum) -> Option<HashSet<IVec3>> {
+        visibility::visible_sections(eye, frustum, |chunk| self.world.visibility.get(&chunk))
+    }
+
+    /// Returns how many of the chunks within `radius` of the player have had their meshes
+    /// uploaded, along with the total number of chunks in that radius. The radius is clamped to
+    /// the load radius, as the meshes of chunks beyond it are never built.
+    pub fn meshes_uploaded_around_player(&self, radius: usize) -> (usize, usize) {
+        let Some(player_chunk) = self.world.current_chunk else {
+            return (0, 1);
+        };
+
+        let radius = radius.min(self.config.load_radius);
+
+        Self::get_chunks_around(player_chunk, radius).fold((0, 0), |(uploaded, total), chunk| {
+            let is_uploaded = self.world.uploaded_meshes.contains_key(&chunk) as usize;
+
+            (uploaded + is_uploaded, total + 1)
+        })
+    }
+
+    /// Returns how far along the pipeline the chunk at the given position of the current
+    /// dimension is, or None if it is neither loaded nor queued.
+    pub fn load_state(&self, position: IVec2) -> Option<ChunkLoadState> {
+        let world = &self.world;
+
+        if world.uploaded_meshes.contains_key(&position) {
+            Some(ChunkLoadState::Uploaded)
+        } else if world.unuploaded_meshes.contains_key(&position) {
+            Some(ChunkLoadState::Uploading)
+        } else if world.undecorated.contains(&position) {
+            Some(ChunkLoadState::Undecorated)
+        } else if world.chunks.contains_key(&position) {
+            Some(ChunkLoadState::Meshing)
+        } else if world.currently_generating.contains_key(&position) {
+            Some(ChunkLoadState::Generating)
+        } else if world.load_queue.contains(&position) {
+            Some(ChunkLoadState::Queued)
+        } else {
+            None
+        }
     }
 
     /// Returns the number of chunks currently loaded.
     pub fn chunks_loaded(&self) -> usize {
-        self.chunks.len()
+        self.world.chunks.len()
     }
 
     /// Returns the number of meshes currently built.
     pub fn meshes_loaded(&self) -> usize {
-        self.uploaded_meshes.len() + self.unuploaded_meshes.len()
+        self.world.uploaded_meshes.len() + self.world.unuploaded_meshes.len()
+    }
+}
+
+impl Drop for ChunkManager {
+    /// Waits for the I/O worker to finish writing any queued saves.
+    fn drop(&mut self) {
+        let _ = self.io_tx.send(IoRequest::Shutdown);
+
+        if let Some(io_thread) = self.io_thread.take() {
+            let _ = io_thread.join();
+        }
     }
 }
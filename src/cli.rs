@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::{
+    adapter::{GpuPreference, GraphicsBackend},
+    dimension::WorldType,
+    storage::Seed,
+};
+
+/// A voxel engine.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Generates and saves every chunk within the given radius (in chunks) around the origin,
+    /// then exits without opening a window.
+    #[arg(long, value_name = "RADIUS")]
+    pub pregen: Option<usize>,
+
+    /// The directory of the world to open, or create if nothing has been saved there. Defaults
+    /// to the world opened last.
+    #[arg(long, value_name = "PATH")]
+    pub world: Option<PathBuf>,
+
+    /// The seed used to generate the world, if it is being created; either a number or any
+    /// text. Existing worlds keep the seed they were created with. Overrides the seed set in the
+    /// world config.
+    #[arg(long)]
+    pub seed: Option<Seed>,
+
+    /// The type of world to create, if it is being created; one of `normal`, `superflat` or
+    /// `void`. Existing worlds keep the type they were created with. Overrides the world type set
+    /// in the world config, including its superflat layers.
+    #[arg(long)]
+    pub world_type: Option<WorldType>,
+
+    /// Creates the world from the terrain of the given grayscale heightmap image (such as a PNG
+    /// or EXR file), if it is being created, with the image's top left corner at the origin.
+    /// Other import options can be set in the world config.
+    #[arg(long, value_name = "PATH", conflicts_with = "world_type")]
+    pub heightmap: Option<PathBuf>,
+
+    /// The graphics API to draw with; one of `vulkan`, `dx12`, `metal` or `gl`. Overrides the
+    /// backend set in the graphics config.
+    #[arg(long)]
+    pub backend: Option<GraphicsBackend>,
+
+    /// Which kind of GPU to draw with, unless one is chosen with `--gpu`; either
+    /// `high-performance` or `low-power`. Overrides the power preference set in the graphics
+    /// config.
+    #[arg(long)]
+    pub power_preference: Option<GpuPreference>,
+
+    /// The index of the GPU to draw with, as listed by `--list-gpus`, such as to force the
+    /// discrete GPU of a laptop with two. Overrides the adapter set in the graphics config.
+    #[arg(long, value_name = "INDEX")]
+    pub gpu: Option<usize>,
+
+    /// Lists the GPUs available to the chosen backend along with their indices, then exits
+    /// without opening a window.
+    #[arg(long)]
+    pub list_gpus: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// A task run instead of opening a window.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Converts the chunks of a Minecraft (Java Edition 1.13 or later) world into the overworld,
+    /// replacing any chunks already saved there, then exits. Chunks that weren't imported are
+    /// generated as usual, so importing into a void world keeps only the Minecraft terrain.
+    ImportAnvil {
+        /// The `region` directory of the Minecraft world, holding its `.mca` files.
+        region_directory: PathBuf,
+
+        /// How many blocks every Minecraft block is moved up by. Blocks below the bottom or
+        /// above the top of the world are cut off, so worlds from Minecraft 1.18 or later, which
+        /// reach down to y = -64, can be moved up by 64 to keep their deepest caves.
+        #[arg(long, default_value_t = 0, allow_hyphen_values = true)]
+        y_offset: i32,
+    },
+}
@@ -0,0 +1,33 @@
+use std::{fs, io, path::PathBuf};
+
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// The directory configuration files are read from.
+pub const CONFIG_DIRECTORY: &str = "config";
+
+/// Returns the path of the config file with the given name.
+pub fn config_path(name: &str) -> PathBuf {
+    PathBuf::from(CONFIG_DIRECTORY).join(name)
+}
+
+/// Loads a TOML config file with the given name from the config directory. Missing files, and
+/// any fields missing from them, fall back to their defaults.
+pub fn load_or_default<T: DeserializeOwned + Default>(name: &str) -> anyhow::Result<T> {
+    let path = config_path(name);
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => toml::from_str(&contents).context(format!("parsing {path:?}")),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(error) => Err(error).context(format!("reading {path:?}")),
+    }
+}
+
+/// Saves a TOML config file with the given name to the config directory, replacing the file
+/// if it already exists.
+pub fn save<T: Serialize>(name: &str, value: &T) -> anyhow::Result<()> {
+    let path = config_path(name);
+
+    fs::create_dir_all(CONFIG_DIRECTORY).context(format!("creating {CONFIG_DIRECTORY:?}"))?;
+    fs::write(&path, toml::to_string(value)?).context(format!("writing {path:?}"))
+}
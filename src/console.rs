@@ -0,0 +1,119 @@
+use std::{collections::VecDeque, str::FromStr};
+
+use anyhow::bail;
+use egui::*;
+
+use crate::game_mode::GameMode;
+
+/// The most lines kept in the console's log, after which the oldest are dropped.
+const MAX_LOG_LINES: usize = 64;
+
+/// The commands listed by `help`.
+pub const HELP: &str = "commands:
+  help - lists the commands
+  gamemode <survival|creative> - switches the game mode";
+
+/// A command typed into the console.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsoleCommand {
+    /// Lists the commands.
+    Help,
+    /// Switches the game mode.
+    GameMode(GameMode),
+}
+
+/// A text box commands are typed into, above a log of the commands run and what they printed.
+#[derive(Debug, Default)]
+pub struct Console {
+    /// Whether the console is shown.
+    open: bool,
+    /// The line being typed.
+    input: String,
+    /// The lines printed so far, oldest first.
+    log: VecDeque<String>,
+    /// Whether the text box takes keyboard focus the next time the console is shown.
+    focus_input: bool,
+}
+
+impl Console {
+    /// Shows the console, with its text box ready to be typed into.
+    pub fn open(&mut self) {
+        self.open = true;
+        self.focus_input = true;
+    }
+
+    /// Adds a line to the bottom of the log.
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.log.push_back(line.into());
+
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.pop_front();
+        }
+    }
+
+    /// Shows the console while it is open, returning the line submitted (with enter) this
+    /// frame. Escape closes it.
+    pub fn show(&mut self, ui: &Context) -> Option<String> {
+        if !self.open {
+            return None;
+        }
+
+        let mut submitted = None;
+
+        Window::new("console")
+            .resizable(false)
+            .collapsible(false)
+            .anchor(Align2::LEFT_BOTTOM, vec2(8.0, -8.0))
+            .show(ui, |ui| {
+                ScrollArea::vertical()
+                    .max_height(200.0)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        for line in &self.log {
+                            ui.label(line);
+                        }
+                    });
+
+                let response = ui.add(
+                    TextEdit::singleline(&mut self.input)
+                        .hint_text("type help for a list of commands")
+                        .desired_width(400.0),
+                );
+
+                if std::mem::take(&mut self.focus_input) {
+                    response.request_focus();
+                }
+
+                // text boxes lose focus when enter is pressed
+                if response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter)) {
+                    let line = std::mem::take(&mut self.input);
+
+                    if !line.trim().is_empty() {
+                        submitted = Some(line);
+                    }
+
+                    response.request_focus();
+                }
+            });
+
+        if ui.input(|input| input.key_pressed(Key::Escape)) {
+            self.open = false;
+        }
+
+        submitted
+    }
+}
+
+impl FromStr for ConsoleCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["help"] => Ok(Self::Help),
+            ["gamemode", mode] => Ok(Self::GameMode(mode.parse()?)),
+            ["gamemode", ..] => bail!("usage: gamemode <survival|creative>"),
+            [name, ..] => bail!("unknown command, '{name}'"),
+            [] => bail!("no command given"),
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use glam::*;
+use wgpu::*;
+
+use crate::{
+    chunk::{CHUNK_HEIGHT, CHUNK_WIDTH, SECTION_HEIGHT},
+    chunk_manager::ChunkLoadState,
+    model, preprocessor,
+};
+
+/// The number of vertices the vertex buffer is created with.
+const INITIAL_VERTEX_CAPACITY: usize = 1024;
+
+/// The radius (in chunks) around the player whose borders are drawn.
+const BORDER_RADIUS: i32 = 2;
+/// The radius (in chunks) around the player whose load states are drawn.
+const LOAD_STATE_RADIUS: i32 = 8;
+
+/// The colors of the borders of the chunk the player is in, of its sections, and of the chunks
+/// around it.
+const CURRENT_CHUNK_COLOR: Vec3 = vec3(1.0, 0.2, 0.2);
+const SECTION_COLOR: Vec3 = vec3(0.2, 0.4, 1.0);
+const CHUNK_COLOR: Vec3 = vec3(1.0, 0.9, 0.2);
+
+/// A straight line drawn over the world.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugLine {
+    /// The world positions of the ends of the line, where a voxel at `(x, y, z)` spans from
+    /// `(x, y, z)` to `(x + 1, y + 1, z + 1)`, so voxel boundaries lie on whole numbers.
+    pub start: Vec3,
+    pub end: Vec3,
+    /// The color of the line (in linear space).
+    pub color: Vec3,
+}
+
+/// The end of a line as laid out in the vertex buffer.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    /// The position of the vertex relative to the camera.
+    offset: Vec3,
+    color: Vec3,
+}
+
+impl LineVertex {
+    /// The vertex attributes of how the data is structured.
+    const ATTRIBS: &'static [VertexAttribute] = &vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3
+    ];
+
+    /// Returns the wgpu vertex buffer layout of how each vertex is interpreted.
+    fn desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: Self::ATTRIBS,
+        }
+    }
+}
+
+/// Draws colored lines over the world for debugging, such as the borders of the chunks around
+/// the player. The lines are tested against the depth buffer, so terrain hides them like
+/// anything else in the world.
+pub struct DebugLines {
+    /// The pipeline drawing the lines.
+    pipeline: RenderPipeline,
+    /// The ends of every line drawn in a frame, as `LineVertex`.
+    vertex_buffer: Buffer,
+    /// The number of vertices `vertex_buffer` can hold.
+    vertex_capacity: usize,
+    /// The number of vertices drawn this frame.
+    vertex_count: u32,
+}
+
+impl DebugLines {
+    /// Creates the pipeline drawing the lines onto surfaces of the given format, with the camera
+    /// bound as group 0.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        camera_bind_group_layout: &BindGroupLayout,
+    ) -> Self {
+        let shader = preprocessor::create_shader_module(
+            device,
+            "debug_lines.wgsl",
+            include_str!("debug_lines.wgsl"),
+            &[],
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Debug Lines Pipeline Layout"),
+            push_constant_ranges: &[],
+            bind_group_layouts: &[camera_bind_group_layout],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Debug Lines Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[LineVertex::desc()],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: PipelineCompilationOptions::default(),
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        let vertex_capacity = INITIAL_VERTEX_CAPACITY;
+        let vertex_buffer = Self::create_vertex_buffer(device, vertex_capacity);
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            vertex_capacity,
+            vertex_count: 0,
+        }
+    }
+
+    fn create_vertex_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Debug Lines Vertex Buffer"),
+            size: (capacity * size_of::<LineVertex>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Uploads the lines to draw from now on, as seen from the camera at `eye`.
+    pub fn update(&mut self, device: &Device, queue: &Queue, lines: &[DebugLine], eye: DVec3) {
+        // offset in integer space first, like the meshes, to stay precise far from the origin
+        let offset = |position: Vec3| {
+            let whole = position.floor();
+            model::offset_from(whole.as_ivec3(), eye) + (position - whole) - 0.5
+        };
+
+        let vertices = lines
+            .iter()
+            .flat_map(|line| {
+                [line.start, line.end].map(|position| LineVertex {
+                    offset: offset(position),
+                    color: line.color,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = vertices.len().next_power_of_two();
+            self.vertex_buffer = Self::create_vertex_buffer(device, self.vertex_capacity);
+        }
+
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    /// Draws the lines onto the given view, tested against the depth buffer of the scene
+    /// already drawn into it.
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        depth_view: &TextureView,
+        camera_bind_group: &BindGroup,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Debug Lines Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+/// Returns the lines along the borders of the chunks around the chunk at `player_chunk`, with
+/// the boundaries of its sections ringed around it.
+pub fn chunk_borders(player_chunk: IVec2) -> Vec<DebugLine> {
+    let width = CHUNK_WIDTH as f32;
+    let height = CHUNK_HEIGHT as f32;
+    let mut lines = Vec::new();
+
+    // the corners shared by the chunks around the player, running the height of the world
+    for x in -BORDER_RADIUS..=BORDER_RADIUS + 1 {
+        for z in -BORDER_RADIUS..=BORDER_RADIUS + 1 {
+            let corner = (player_chunk + ivec2(x, z)).as_vec2() * width;
+            let color = match (0..=1).contains(&x) && (0..=1).contains(&z) {
+                true => CURRENT_CHUNK_COLOR,
+                false => CHUNK_COLOR,
+            };
+
+            lines.push(DebugLine {
+                start: vec3(corner.x, 0.0, corner.y),
+                end: vec3(corner.x, height, corner.y),
+                color,
+            });
+        }
+    }
+
+    let min = player_chunk.as_vec2() * width;
+
+    for section in 0..=CHUNK_HEIGHT / SECTION_HEIGHT {
+        let y = (section * SECTION_HEIGHT) as f32;
+        lines.extend(rectangle(min, min + width, y, SECTION_COLOR));
+    }
+
+    lines
+}
+
+/// Returns a square over each chunk around the chunk at `player_chunk`, at the height `y`,
+/// colored by how far along the pipeline it is.
+pub fn chunk_load_states(
+    player_chunk: IVec2,
+    y: f32,
+    load_state: impl Fn(IVec2) -> Option<ChunkLoadState>,
+) -> Vec<DebugLine> {
+    let width = CHUNK_WIDTH as f32;
+    let mut lines = Vec::new();
+
+    for x in -LOAD_STATE_RADIUS..=LOAD_STATE_RADIUS {
+        for z in -LOAD_STATE_RADIUS..=LOAD_STATE_RADIUS {
+            let chunk = player_chunk + ivec2(x, z);
+
+            let Some(state) = load_state(chunk) else {
+                continue;
+            };
+
+            // inset, so the squares of neighboring chunks don't overlap
+            let min = chunk.as_vec2() * width + 1.0;
+            let max = min + width - 2.0;
+            let color = load_state_color(state);
+
+            lines.extend(rectangle(min, max, y, color));
+            // crossed, to tell the squares apart from the chunk borders
+            lines.push(DebugLine {
+                start: vec3(min.x, y, min.y),
+                end: vec3(max.x, y, max.y),
+                color,
+            });
+            lines.push(DebugLine {
+                start: vec3(min.x, y, max.y),
+                end: vec3(max.x, y, min.y),
+                color,
+            });
+        }
+    }
+
+    lines
+}
+
+/// Returns the color chunks in the given state are drawn with.
+fn load_state_color(state: ChunkLoadState) -> Vec3 {
+    match state {
+        ChunkLoadState::Queued => vec3(0.4, 0.4, 0.4),
+        ChunkLoadState::Generating => vec3(1.0, 0.1, 0.1),
+        ChunkLoadState::Undecorated => vec3(1.0, 0.5, 0.0),
+        ChunkLoadState::Meshing => vec3(1.0, 1.0, 0.0),
+        ChunkLoadState::Uploading => vec3(0.0, 0.8, 1.0),
+        ChunkLoadState::Uploaded => vec3(0.1, 1.0, 0.1),
+    }
+}
+
+/// Returns the four edges of the horizontal rectangle between the corners `min` and `max` (in
+/// x and z), at the height `y`.
+fn rectangle(min: Vec2, max: Vec2, y: f32, color: Vec3) -> [DebugLine; 4] {
+    let corners = [
+        vec3(min.x, y, min.y),
+        vec3(max.x, y, min.y),
+        vec3(max.x, y, max.y),
+        vec3(min.x, y, max.y),
+    ];
+
+    std::array::from_fn(|i| DebugLine {
+        start: corners[i],
+        end: corners[(i + 1) % 4],
+        color,
+    })
+}
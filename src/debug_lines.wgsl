@@ -0,0 +1,27 @@
+// Uniforms
+#include "camera.wgsl"
+
+struct VertexInput {
+	// The position of the vertex relative to the camera.
+	@location(0) offset: vec3<f32>,
+	@location(1) color: vec3<f32>,
+}
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) color: vec3<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+	var output: VertexOutput;
+	output.clip_position = camera.view_proj * vec4<f32>(input.offset, 1.0);
+	output.color = input.color;
+
+	return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+	return vec4<f32>(input.color, 1.0);
+}
@@ -0,0 +1,159 @@
+use std::{str::FromStr, sync::Arc};
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chunk::{Voxel, SEA_LEVEL},
+    generator::{
+        CaveGenerator, CaveSettings, ChunkGenerator, FlatGenerator, HeightmapGenerator,
+        LakeSettings, RiverSettings, StructureSettings, TerrainGenerator, TerrainMode,
+        TreeSettings, VoidGenerator, WorldgenConfig,
+    },
+    heightmap_import::{HeightmapImport, ImportedHeightmap},
+    structure::{self, STRUCTURE_DIRECTORY},
+};
+
+/// The layers of superflat worlds (and the flat dimension) from the bottom up, with their
+/// thickness, unless others are configured.
+pub const DEFAULT_SUPERFLAT_LAYERS: [(Voxel, usize); 4] = [
+    (Voxel::Bedrock, 1),
+    (Voxel::Stone, 59),
+    (Voxel::Dirt, 3),
+    (Voxel::Grass, 1),
+];
+
+/// The kind of terrain a world is filled with, chosen when the world is created.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum WorldType {
+    /// Every dimension is generated with its own regular terrain.
+    #[default]
+    Normal,
+    /// Every dimension is made of the same horizontal layers of voxels, given from the bottom
+    /// up along with their thickness.
+    Superflat { layers: Vec<(Voxel, usize)> },
+    /// Every dimension is completely empty.
+    Void,
+    /// Every dimension is made of the terrain of a heightmap image, covering a region of the
+    /// world, with nothing outside of it.
+    Heightmap(HeightmapImport),
+}
+
+impl WorldType {
+    /// Creates the generator used to fill in the chunks of a dimension in a world of this type.
+    pub fn generator(
+        &self,
+        dimension: Dimension,
+        seed: u32,
+        worldgen: &WorldgenConfig,
+    ) -> Arc<dyn ChunkGenerator> {
+        match self {
+            Self::Normal => dimension.generator(seed, worldgen),
+            Self::Superflat { layers } => Arc::new(FlatGenerator::new(layers.clone())),
+            Self::Void => Arc::new(VoidGenerator),
+            Self::Heightmap(import) => match ImportedHeightmap::load(import) {
+                Ok(heightmap) => Arc::new(HeightmapGenerator::new(heightmap)),
+                Err(error) => {
+                    eprintln!("could not import heightmap: {error:?}");
+                    Arc::new(VoidGenerator)
+                }
+            },
+        }
+    }
+}
+
+impl FromStr for WorldType {
+    type Err = anyhow::Error;
+
+    /// Parses a world type from its name, with superflat worlds using the default layers.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(Self::Normal),
+            "superflat" => Ok(Self::Superflat {
+                layers: DEFAULT_SUPERFLAT_LAYERS.to_vec(),
+            }),
+            "void" => Ok(Self::Void),
+            _ => bail!("unknown world type, '{s}'"),
+        }
+    }
+}
+
+/// An independent world with its own terrain, chunks and meshes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Dimension {
+    /// The regular world players spawn into.
+    #[default]
+    Overworld,
+    /// An underground world of caverns.
+    Caves,
+    /// A flat world, useful for testing.
+    Flat,
+    /// A world of towering cliffs, overhangs and floating islands.
+    Amplified,
+}
+
+impl Dimension {
+    /// Every dimension that exists.
+    pub const ALL: [Dimension; 4] = [Self::Overworld, Self::Caves, Self::Flat, Self::Amplified];
+
+    /// Returns the name of the dimension, which is also the name of the directory its chunks are
+    /// saved in.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Overworld => "overworld",
+            Self::Caves => "caves",
+            Self::Flat => "flat",
+            Self::Amplified => "amplified",
+        }
+    }
+
+    /// Returns the dimension after this one in `Dimension::ALL`, wrapping around to the first.
+    pub fn next(&self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|dimension| dimension == self)
+            .unwrap_or(0);
+
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Creates the generator used to fill in this dimension's chunks, shaped by the given
+    /// worldgen config.
+    pub fn generator(&self, seed: u32, worldgen: &WorldgenConfig) -> Arc<dyn ChunkGenerator> {
+        match self {
+            Self::Overworld => {
+                let mut generator = TerrainGenerator::new(seed, TerrainMode::Heightmap, worldgen)
+                    .with_sea_level(SEA_LEVEL)
+                    .with_caves(CaveSettings::default())
+                    .with_rivers(RiverSettings::default())
+                    .with_lakes(LakeSettings::default())
+                    .with_trees(TreeSettings::default());
+
+                match structure::load_structures(STRUCTURE_DIRECTORY.as_ref()) {
+                    Ok(templates) => {
+                        generator = generator.with_structures(StructureSettings::new(templates))
+                    }
+                    Err(error) => eprintln!("could not load structures: {error:?}"),
+                }
+
+                Arc::new(generator)
+            }
+            Self::Caves => Arc::new(CaveGenerator::new(seed)),
+            Self::Flat => Arc::new(FlatGenerator::new(DEFAULT_SUPERFLAT_LAYERS.to_vec())),
+            Self::Amplified => Arc::new(
+                TerrainGenerator::new(
+                    seed,
+                    TerrainMode::Density {
+                        base_height: 96.0,
+                        squash: 48.0,
+                    },
+                    worldgen,
+                )
+                .with_sea_level(SEA_LEVEL)
+                .with_caves(CaveSettings::default())
+                .with_trees(TreeSettings::default()),
+            ),
+        }
+    }
+}
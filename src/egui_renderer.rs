@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use egui::Context;
+use egui::{epaint::ClippedPrimitive, Context, TextureId};
 use egui_wgpu::{Renderer, ScreenDescriptor};
 use egui_winit::State;
 
@@ -19,6 +19,17 @@ pub struct EguiRenderer {
     window: Arc<winit::window::Window>,
     /// egui's internal state of the window.
     state: egui_winit::State,
+
+    /// The ui of the current frame, prepared to be drawn.
+    frame: Option<EguiFrame>,
+    /// The textures to free once the current frame has been drawn.
+    textures_to_free: Vec<TextureId>,
+}
+
+/// The tessellated ui of a frame.
+struct EguiFrame {
+    tris: Vec<ClippedPrimitive>,
+    screen_descriptor: ScreenDescriptor,
 }
 
 impl EguiRenderer {
@@ -42,23 +53,30 @@ impl EguiRenderer {
             renderer,
             window,
             state,
+            frame: None,
+            textures_to_free: Vec::new(),
         }
     }
 
-    /// Updates egui with the latest events.
-    pub fn handle_input(&mut self, event: &WindowEvent) {
-        let _ = self.state.on_window_event(&self.window, event);
+    /// Updates egui with the latest events. Returns whether egui used the event (such as a key
+    /// typed into a text box), in which case the rest of the app should ignore it.
+    pub fn handle_input(&mut self, event: &WindowEvent) -> bool {
+        self.state.on_window_event(&self.window, event).consumed
     }
 
-    /// Renders all egui content on to the surface.
-    pub fn render(
+    /// Runs the ui, and uploads its meshes and textures so it can be drawn with `draw`.
+    pub fn prepare(
         &mut self,
         device: &Device,
         queue: &Queue,
         encoder: &mut CommandEncoder,
-        view: &TextureView,
         ui: impl FnOnce(&Context),
     ) {
+        // the last frame has been drawn by now
+        for id in self.textures_to_free.drain(..) {
+            self.renderer.free_texture(&id);
+        }
+
         let input = self.state.take_egui_input(&self.window);
         let full_output = self.context.run(input, ui);
 
@@ -84,28 +102,42 @@ impl EguiRenderer {
         self.renderer
             .update_buffers(device, queue, encoder, &tris, &screen_descriptor);
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                label: Some("Egui Render Pass"),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
+        self.textures_to_free = full_output.textures_delta.free;
+        self.frame = Some(EguiFrame {
+            tris,
+            screen_descriptor,
+        });
+    }
 
-            self.renderer
-                .render(&mut render_pass, &tris, &screen_descriptor);
-        }
+    /// Draws the ui prepared by `prepare` on to the given view, which is the given number of
+    /// times larger than the window (scaling the ui up with it).
+    pub fn draw(&self, encoder: &mut CommandEncoder, view: &TextureView, scale: u32) {
+        let Some(frame) = &self.frame else {
+            return;
+        };
 
-        for x in &full_output.textures_delta.free {
-            self.renderer.free_texture(x)
-        }
+        let [width, height] = frame.screen_descriptor.size_in_pixels;
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [width * scale, height * scale],
+            pixels_per_point: frame.screen_descriptor.pixels_per_point * scale as f32,
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            label: Some("Egui Render Pass"),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        self.renderer
+            .render(&mut render_pass, &frame.tris, &screen_descriptor);
     }
 }
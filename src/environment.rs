@@ -0,0 +1,207 @@
+use std::f32::consts::TAU;
+
+use glam::*;
+use wgpu::{util::*, *};
+
+use crate::chunk::SEA_LEVEL;
+
+/// How much of the sky color is let through at the distance fog is made to hide. Fog never
+/// fully covers anything, as it thickens exponentially.
+const FOG_VISIBILITY_AT_DISTANCE: f32 = 0.02;
+
+/// How long a full day and night lasts, in seconds.
+const DAY_LENGTH: f32 = 20.0 * 60.0;
+/// How far the sun's path is tilted away from passing straight overhead, so no two axes are lit
+/// the same.
+const SUN_TILT: f32 = 0.35;
+
+/// How often (in seconds) the time animations are driven by wraps around, so it never grows
+/// large enough to lose precision (at the cost of the animations skipping once each time).
+const ANIMATION_PERIOD: f32 = 60.0 * 60.0;
+
+/// The color of the sky during the day and at night (in linear space).
+const DAY_SKY_COLOR: Vec3 = vec3(0.45, 0.65, 0.95);
+const NIGHT_SKY_COLOR: Vec3 = vec3(0.01, 0.015, 0.04);
+/// The ambient light during the day and at night.
+const DAY_AMBIENT_LIGHT: f32 = 0.4;
+const NIGHT_AMBIENT_LIGHT: f32 = 0.15;
+
+/// The color of the fog while the camera is underwater (in linear space), dimming with the
+/// daylight like the sky, and how far it lets the camera see.
+const UNDERWATER_FOG_COLOR: Vec3 = vec3(0.05, 0.25, 0.3);
+const UNDERWATER_VISIBILITY: f32 = 24.0;
+
+/// The lighting and fog of the world the terrain is shaded with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Environment {
+    /// How far through the day it is, from 0 to 1, where 0 is midnight and 0.5 is noon.
+    pub time_of_day: f32,
+    /// The seconds passed, wrapped around every `ANIMATION_PERIOD`, which animates water.
+    pub time: f32,
+    /// The direction pointing towards the sun.
+    pub sun_direction: Vec3,
+    /// The amount of light faces facing away from the sun still receive, from 0 to 1.
+    pub ambient_light: f32,
+
+    /// The color of the sky, which distant terrain fades into (in linear space).
+    pub sky_color: Vec3,
+    /// How thick the fog is at `fog_base_height`, as the fraction of light lost per voxel.
+    pub fog_density: f32,
+    /// How quickly the fog thins out above `fog_base_height` (and thickens below it), per voxel.
+    pub fog_height_falloff: f32,
+    /// The height the fog has a density of `fog_density` at.
+    pub fog_base_height: f32,
+
+    /// Whether the camera is inside water, which replaces the fog with a thick blue-green one
+    /// and hides the night sky.
+    pub underwater: bool,
+}
+
+/// The environment as laid out in the shader's uniform buffer.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EnvironmentUniform {
+    sun_direction: Vec3,
+    ambient_light: f32,
+    sky_color: Vec3,
+    fog_density: f32,
+    fog_height_falloff: f32,
+    fog_base_height: f32,
+    daylight: f32,
+    time: f32,
+    underwater: f32,
+    _padding: [f32; 3],
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        let mut environment = Self {
+            time_of_day: 0.0,
+            time: 0.0,
+            sun_direction: Vec3::Y,
+            ambient_light: DAY_AMBIENT_LIGHT,
+            sky_color: DAY_SKY_COLOR,
+            fog_density: Self::fog_density_for(256.0),
+            fog_height_falloff: 0.01,
+            fog_base_height: SEA_LEVEL as f32,
+            underwater: false,
+        };
+
+        // mid morning
+        environment.set_time_of_day(0.35);
+        environment
+    }
+}
+
+impl Environment {
+    /// Returns the uniform data of the environment, to be sent to the GPU.
+    pub fn uniform(&self) -> EnvironmentUniform {
+        // underwater, the fog is the same thickness at every height
+        let (fog_density, fog_height_falloff) = match self.underwater {
+            true => (Self::fog_density_for(UNDERWATER_VISIBILITY), 0.0),
+            false => (self.fog_density.max(0.0), self.fog_height_falloff),
+        };
+
+        EnvironmentUniform {
+            sun_direction: self.sun_direction.normalize_or(Vec3::Y),
+            ambient_light: self.ambient_light.clamp(0.0, 1.0),
+            sky_color: self.fog_color(),
+            fog_density,
+            fog_height_falloff,
+            fog_base_height: self.fog_base_height,
+            daylight: self.daylight(),
+            time: self.time,
+            underwater: self.underwater as u32 as f32,
+            _padding: [0.0; 3],
+        }
+    }
+
+    /// Moves the sun to where it is at the given time of day (wrapped to 0 to 1), and fades the
+    /// sky and ambient light between day and night.
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.time_of_day = time_of_day.rem_euclid(1.0);
+
+        // the sun rises along +x at 0.25, and sets along -x at 0.75
+        let angle = (self.time_of_day - 0.25) * TAU;
+        self.sun_direction = vec3(angle.cos(), angle.sin(), SUN_TILT).normalize();
+
+        let daylight = self.daylight();
+        self.sky_color = NIGHT_SKY_COLOR.lerp(DAY_SKY_COLOR, daylight);
+        self.ambient_light =
+            NIGHT_AMBIENT_LIGHT + (DAY_AMBIENT_LIGHT - NIGHT_AMBIENT_LIGHT) * daylight;
+    }
+
+    /// Moves the day along by the given number of seconds.
+    pub fn advance_time(&mut self, dt: f32) {
+        self.time = (self.time + dt) % ANIMATION_PERIOD;
+        self.set_time_of_day(self.time_of_day + dt / DAY_LENGTH);
+    }
+
+    /// Returns how bright the day is from the sun's height, from 0 at night to 1 once the sun
+    /// is clear of the horizon.
+    pub fn daylight(&self) -> f32 {
+        let t = ((self.sun_direction.y + 0.1) / 0.3).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    /// Returns the fog density that almost entirely hides terrain at the given distance, when
+    /// looking across it at `fog_base_height`.
+    pub fn fog_density_for(distance: f32) -> f32 {
+        -FOG_VISIBILITY_AT_DISTANCE.ln() / distance.max(1.0)
+    }
+
+    /// Returns the color distant terrain fades into, being the color of the sky, or of the water
+    /// the camera is in.
+    pub fn fog_color(&self) -> Vec3 {
+        match self.underwater {
+            true => UNDERWATER_FOG_COLOR * (0.2 + 0.8 * self.daylight()),
+            false => self.sky_color,
+        }
+    }
+
+    /// Returns the color the screen is cleared to, being the color of the fog.
+    pub fn clear_color(&self) -> Color {
+        let color = self.fog_color();
+
+        Color {
+            r: color.x as f64,
+            g: color.y as f64,
+            b: color.z as f64,
+            a: 1.0,
+        }
+    }
+
+    /// Creates an environment uniform buffer, and binding group (layout).
+    pub fn create_buffers(&self, device: &Device) -> (Buffer, BindGroupLayout, BindGroup) {
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Environment Uniform Buffer"),
+            contents: bytemuck::bytes_of(&self.uniform()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Environment Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Environment Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        (uniform_buffer, bind_group_layout, bind_group)
+    }
+}
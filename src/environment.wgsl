@@ -0,0 +1,20 @@
+// The environment, as laid out in `EnvironmentUniform`. Shaders bind it as `environment`
+// themselves, as they use different groups.
+struct EnvironmentUniform {
+	// The direction pointing towards the sun.
+	sun_direction: vec3<f32>,
+	// The light faces facing away from the sun still receive.
+	ambient_light: f32,
+	// The color of the sky, which distant terrain fades into.
+	sky_color: vec3<f32>,
+	// How thick the fog is at `fog_base_height`, and how quickly it thins out above it.
+	fog_density: f32,
+	fog_height_falloff: f32,
+	fog_base_height: f32,
+	// How bright the day is, from 0 at night to 1 once the sun is clear of the horizon.
+	daylight: f32,
+	// The seconds passed, animating water.
+	time: f32,
+	// 1 while the camera is inside water, and 0 otherwise.
+	underwater: f32,
+}
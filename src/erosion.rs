@@ -0,0 +1,177 @@
+use glam::{dvec2, DVec2};
+use serde::{Deserialize, Serialize};
+
+/// Options for the hydraulic erosion simulated over the heightmap terrain, part of the worldgen
+/// config. Erosion carves valleys and leaves sediment fans behind, but is expensive, so it is
+/// off by default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ErosionConfig {
+    /// Whether erosion is simulated at all.
+    pub enabled: bool,
+    /// How many droplets are simulated per column of each region.
+    pub droplets_per_column: f64,
+    /// How many steps a droplet takes before it stops.
+    pub droplet_lifetime: usize,
+    /// How much a droplet keeps going in the direction it was going, rather than flowing
+    /// downhill, between 0 and 1.
+    pub inertia: f64,
+    /// How much sediment a droplet can carry, relative to its speed, water and slope.
+    pub sediment_capacity: f64,
+    /// The least amount of sediment a droplet can carry, so droplets on flat ground still erode.
+    pub min_sediment_capacity: f64,
+    /// The fraction of its free capacity a droplet erodes each step.
+    pub erode_speed: f64,
+    /// The fraction of its excess sediment a droplet deposits each step.
+    pub deposit_speed: f64,
+    /// The fraction of its water a droplet loses each step.
+    pub evaporate_speed: f64,
+    /// How quickly droplets speed up when flowing downhill.
+    pub gravity: f64,
+}
+
+impl Default for ErosionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            droplets_per_column: 1.0,
+            droplet_lifetime: 30,
+            inertia: 0.05,
+            sediment_capacity: 4.0,
+            min_sediment_capacity: 0.01,
+            erode_speed: 0.3,
+            deposit_speed: 0.3,
+            evaporate_speed: 0.01,
+            gravity: 4.0,
+        }
+    }
+}
+
+/// A square heightmap being eroded, indexed by `z * size + x`.
+struct Heightmap<'a> {
+    heights: &'a mut [f64],
+    size: usize,
+}
+
+impl Heightmap<'_> {
+    /// Returns the height and downhill gradient at the given position, interpolated between the
+    /// four surrounding columns.
+    fn sample(&self, position: DVec2) -> (f64, DVec2) {
+        let cell = position.floor();
+        let offset = position - cell;
+        let index = cell.y as usize * self.size + cell.x as usize;
+
+        let north_west = self.heights[index];
+        let north_east = self.heights[index + 1];
+        let south_west = self.heights[index + self.size];
+        let south_east = self.heights[index + self.size + 1];
+
+        let gradient = dvec2(
+            (north_east - north_west) * (1.0 - offset.y) + (south_east - south_west) * offset.y,
+            (south_west - north_west) * (1.0 - offset.x) + (south_east - north_east) * offset.x,
+        );
+
+        let height = north_west * (1.0 - offset.x) * (1.0 - offset.y)
+            + north_east * offset.x * (1.0 - offset.y)
+            + south_west * (1.0 - offset.x) * offset.y
+            + south_east * offset.x * offset.y;
+
+        (height, gradient)
+    }
+
+    /// Raises the four columns surrounding the given position by `amount` in total, weighted by
+    /// how close they are to it. Negative amounts lower them.
+    fn deposit(&mut self, position: DVec2, amount: f64) {
+        let cell = position.floor();
+        let offset = position - cell;
+        let index = cell.y as usize * self.size + cell.x as usize;
+
+        self.heights[index] += amount * (1.0 - offset.x) * (1.0 - offset.y);
+        self.heights[index + 1] += amount * offset.x * (1.0 - offset.y);
+        self.heights[index + self.size] += amount * (1.0 - offset.x) * offset.y;
+        self.heights[index + self.size + 1] += amount * offset.x * offset.y;
+    }
+
+    /// Returns whether the given position lies far enough inside the heightmap to be sampled.
+    fn contains(&self, position: DVec2) -> bool {
+        let max = (self.size - 1) as f64;
+        position.x >= 0.0 && position.y >= 0.0 && position.x < max && position.y < max
+    }
+}
+
+/// Simulates hydraulic erosion over a square heightmap `size` columns wide, indexed by
+/// `z * size + x`. Droplets start at random columns, picked deterministically from `seed`, and
+/// flow downhill, picking up sediment on steep slopes and dropping it where they slow down.
+pub fn erode(heights: &mut [f64], size: usize, config: &ErosionConfig, seed: u64) {
+    let mut heightmap = Heightmap { heights, size };
+    let mut random = seed;
+
+    let droplets = (size * size) as f64 * config.droplets_per_column;
+
+    for _ in 0..droplets as usize {
+        random = splitmix(random);
+        let mut position = dvec2(
+            (random % (size - 1) as u64) as f64,
+            ((random >> 32) % (size - 1) as u64) as f64,
+        );
+
+        let mut direction = DVec2::ZERO;
+        let mut speed = 1.0;
+        let mut water = 1.0;
+        let mut sediment = 0.0;
+
+        for _ in 0..config.droplet_lifetime {
+            let (height, gradient) = heightmap.sample(position);
+
+            direction = (direction * config.inertia - gradient * (1.0 - config.inertia))
+                .try_normalize()
+                .unwrap_or(DVec2::ZERO);
+
+            if direction == DVec2::ZERO {
+                break;
+            }
+
+            let previous = position;
+            position += direction;
+
+            if !heightmap.contains(position) {
+                break;
+            }
+
+            let height_change = heightmap.sample(position).0 - height;
+            let capacity = (-height_change * speed * water * config.sediment_capacity)
+                .max(config.min_sediment_capacity);
+
+            if height_change > 0.0 || sediment > capacity {
+                // fill in the pit the droplet is climbing out of, or drop what it can't carry
+                let amount = if height_change > 0.0 {
+                    height_change.min(sediment)
+                } else {
+                    (sediment - capacity) * config.deposit_speed
+                };
+
+                sediment -= amount;
+                heightmap.deposit(previous, amount);
+            } else {
+                // never dig deeper than the slope, so droplets don't carve holes
+                let amount = ((capacity - sediment) * config.erode_speed).min(-height_change);
+
+                sediment += amount;
+                heightmap.deposit(previous, -amount);
+            }
+
+            speed = (speed * speed - height_change * config.gravity)
+                .max(0.0)
+                .sqrt();
+            water *= 1.0 - config.evaporate_speed;
+        }
+    }
+}
+
+/// Advances a splitmix64 random number generator.
+fn splitmix(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,29 @@
+// Height fog, fading distant fragments into the sky. Expects the including shader to bind the
+// `environment`.
+#include "camera.wgsl"
+#include "environment.wgsl"
+
+// Returns how much of a fragment at the given offset from the camera is hidden by fog, from 0 to
+// 1. The fog's density falls off exponentially with height, so the density is integrated along
+// the ray from the camera to the fragment.
+fn fog_amount(offset: vec3<f32>) -> f32 {
+	let distance = length(offset);
+	let falloff = environment.fog_height_falloff;
+	let density = environment.fog_density
+		* exp(-falloff * (camera.eye.y - environment.fog_base_height));
+
+	// the fraction of the density at the camera's height the ray passes through on average
+	var average = 1.0;
+	let rise = falloff * offset.y;
+
+	if abs(rise) > 0.0001 {
+		average = (1.0 - exp(-rise)) / rise;
+	}
+
+	return 1.0 - exp(-density * distance * average);
+}
+
+// Blends the given color into the sky color by the fog between the camera and the fragment.
+fn apply_fog(color: vec3<f32>, offset: vec3<f32>) -> vec3<f32> {
+	return mix(color, environment.sky_color, fog_amount(offset));
+}
@@ -0,0 +1,147 @@
+// The rendered scene being anti-aliased.
+@group(0) @binding(0) var scene: texture_2d<f32>;
+@group(0) @binding(1) var scene_sampler: sampler;
+
+// The smallest local contrast an edge must have to be smoothed, relative to the brightest
+// neighbour, and in absolute terms (so dark areas aren't smoothed for noise).
+const EDGE_THRESHOLD: f32 = 0.125;
+const EDGE_THRESHOLD_MIN: f32 = 0.0312;
+// How far along an edge is searched for its ends, in steps of one pixel.
+const SEARCH_STEPS: i32 = 12;
+// How much of the blur across the edge is kept from the neighbourhood average.
+const SUBPIXEL_QUALITY: f32 = 0.75;
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	// The position of the pixel on the screen, from (0, 0) at the top left to (1, 1).
+	@location(0) uv: vec2<f32>,
+}
+
+// Covers the screen with a single triangle.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_id: u32) -> VertexOutput {
+	var out: VertexOutput;
+
+	let uv = vec2<f32>(f32((vertex_id << 1u) & 2u), f32(vertex_id & 2u));
+	out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+	out.uv = uv;
+
+	return out;
+}
+
+// Returns the perceived brightness of the scene at the given position. The scene is sampled in
+// linear space, so it is brought roughly back to gamma space first.
+fn luma_at(uv: vec2<f32>) -> f32 {
+	let color = textureSampleLevel(scene, scene_sampler, uv, 0.0).rgb;
+	return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+	let texel = 1.0 / vec2<f32>(textureDimensions(scene));
+	let uv = input.uv;
+	let center = textureSampleLevel(scene, scene_sampler, uv, 0.0);
+
+	let luma = luma_at(uv);
+	let up = luma_at(uv + vec2<f32>(0.0, -texel.y));
+	let down = luma_at(uv + vec2<f32>(0.0, texel.y));
+	let left = luma_at(uv + vec2<f32>(-texel.x, 0.0));
+	let right = luma_at(uv + vec2<f32>(texel.x, 0.0));
+
+	let luma_min = min(luma, min(min(up, down), min(left, right)));
+	let luma_max = max(luma, max(max(up, down), max(left, right)));
+	let range = luma_max - luma_min;
+
+	// not on an edge
+	if range < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD) {
+		return center;
+	}
+
+	let up_left = luma_at(uv - texel);
+	let down_right = luma_at(uv + texel);
+	let up_right = luma_at(uv + vec2<f32>(texel.x, -texel.y));
+	let down_left = luma_at(uv + vec2<f32>(-texel.x, texel.y));
+
+	// whether the edge runs horizontally or vertically through the pixel
+	let horizontal_change = abs(up_left + down_left - 2.0 * left) + 2.0 * abs(up + down - 2.0 * luma)
+		+ abs(up_right + down_right - 2.0 * right);
+	let vertical_change = abs(up_left + up_right - 2.0 * up) + 2.0 * abs(left + right - 2.0 * luma)
+		+ abs(down_left + down_right - 2.0 * down);
+	let is_horizontal = horizontal_change >= vertical_change;
+
+	// which side of the pixel the edge lies on
+	let luma_negative = select(left, up, is_horizontal);
+	let luma_positive = select(right, down, is_horizontal);
+	let gradient_negative = abs(luma_negative - luma);
+	let gradient_positive = abs(luma_positive - luma);
+
+	var step_length = select(texel.x, texel.y, is_horizontal);
+	var luma_local = 0.5 * (luma_positive + luma);
+	if gradient_negative >= gradient_positive {
+		step_length = -step_length;
+		luma_local = 0.5 * (luma_negative + luma);
+	}
+	let gradient_scaled = 0.25 * max(gradient_negative, gradient_positive);
+
+	// move onto the edge, then search along it in both directions for where it ends
+	var edge_uv = uv;
+	var along = vec2<f32>(texel.x, 0.0);
+	if is_horizontal {
+		edge_uv.y += step_length * 0.5;
+	} else {
+		edge_uv.x += step_length * 0.5;
+		along = vec2<f32>(0.0, texel.y);
+	}
+
+	var uv_negative = edge_uv - along;
+	var uv_positive = edge_uv + along;
+	var end_negative = luma_at(uv_negative) - luma_local;
+	var end_positive = luma_at(uv_positive) - luma_local;
+
+	for (var i = 0; i < SEARCH_STEPS; i++) {
+		let done_negative = abs(end_negative) >= gradient_scaled;
+		let done_positive = abs(end_positive) >= gradient_scaled;
+
+		if done_negative && done_positive {
+			break;
+		}
+
+		if !done_negative {
+			uv_negative -= along;
+			end_negative = luma_at(uv_negative) - luma_local;
+		}
+
+		if !done_positive {
+			uv_positive += along;
+			end_positive = luma_at(uv_positive) - luma_local;
+		}
+	}
+
+	let distance_negative = select(uv.y - uv_negative.y, uv.x - uv_negative.x, is_horizontal);
+	let distance_positive = select(uv_positive.y - uv.y, uv_positive.x - uv.x, is_horizontal);
+	let is_negative_closer = distance_negative < distance_positive;
+	let distance = min(distance_negative, distance_positive);
+	let edge_length = distance_negative + distance_positive;
+
+	// only blend when the pixel lies on the darker or lighter side the edge's end agrees with
+	let is_luma_smaller = luma < luma_local;
+	let end_luma = select(end_positive, end_negative, is_negative_closer);
+	let correct_variation = (end_luma < 0.0) != is_luma_smaller;
+	var offset = select(0.0, 0.5 - distance / edge_length, correct_variation);
+
+	// blur pixels standing out from their whole neighbourhood (sub-pixel aliasing)
+	let average = (2.0 * (up + down + left + right) + up_left + up_right + down_left + down_right)
+		/ 12.0;
+	let subpixel = clamp(abs(average - luma) / range, 0.0, 1.0);
+	let subpixel_smooth = smoothstep(0.0, 1.0, subpixel);
+	offset = max(offset, subpixel_smooth * subpixel_smooth * SUBPIXEL_QUALITY);
+
+	var final_uv = uv;
+	if is_horizontal {
+		final_uv.y += offset * step_length;
+	} else {
+		final_uv.x += offset * step_length;
+	}
+
+	return textureSampleLevel(scene, scene_sampler, final_uv, 0.0);
+}
@@ -0,0 +1,46 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+
+/// The rules the player plays by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GameMode {
+    /// Walking under physics, breaking voxels takes time depending on their hardness.
+    Survival,
+    /// Flying is allowed, and voxels break instantly. Voxels are never used up by placing them.
+    #[default]
+    Creative,
+}
+
+impl GameMode {
+    /// Returns the name of the game mode, which it is switched to with in the console.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Survival => "survival",
+            Self::Creative => "creative",
+        }
+    }
+
+    /// Returns whether the player can fly.
+    pub fn allows_flight(&self) -> bool {
+        *self == Self::Creative
+    }
+
+    /// Returns whether voxels break as soon as they are clicked, rather than after being held
+    /// for `Voxel::hardness` seconds.
+    pub fn breaks_instantly(&self) -> bool {
+        *self == Self::Creative
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "survival" => Ok(Self::Survival),
+            "creative" => Ok(Self::Creative),
+            _ => bail!("unknown game mode, '{s}'"),
+        }
+    }
+}
@@ -0,0 +1,786 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use glam::{dvec2, ivec2, ivec3, IVec2, IVec3, Vec3};
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    biome::{self, BiomeConfig, BiomeMap},
+    chunk::*,
+    config,
+    erosion::{self, ErosionConfig},
+    heightmap_import::ImportedHeightmap,
+    preview::PreviewConfig,
+    row_noise::{RowFbm, RowMin, RowRidgedMulti},
+    structure::Structure,
+};
+
+/// The name of the config file the worldgen config is loaded from.
+pub const WORLDGEN_CONFIG_FILE: &str = "worldgen.toml";
+
+/// Options shaping the terrain of generated worlds, loaded from `WORLDGEN_CONFIG_FILE`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldgenConfig {
+    /// The shape of the heightmap terrain.
+    pub terrain: TerrainNoiseConfig,
+    /// The erosion simulated over the heightmap terrain.
+    pub erosion: ErosionConfig,
+    /// How biomes are laid out.
+    pub biomes: BiomeConfig,
+    /// The area shown in the worldgen preview.
+    pub preview: PreviewConfig,
+}
+
+impl WorldgenConfig {
+    /// Loads the worldgen config, checking that it describes valid terrain.
+    pub fn load() -> anyhow::Result<Self> {
+        let config: Self = config::load_or_default(WORLDGEN_CONFIG_FILE)?;
+
+        if config.terrain.curve.len() < 4 {
+            bail!("the terrain curve needs at least 4 control points");
+        }
+
+        Ok(config)
+    }
+}
+
+/// Fills in the voxels of newly created chunks.
+pub trait ChunkGenerator: Send + Sync {
+    /// Generates the voxels of the given (empty) chunk.
+    fn generate(&self, chunk: &mut Chunk);
+
+    /// Places the decorations (trees, structures, etc.) of the chunk at the center of the region,
+    /// which may spill into its neighbors. Called once the chunk and all of its neighbors have
+    /// been generated.
+    fn decorate(&self, _region: &mut ChunkRegion) {}
+
+    /// Returns the color grass and leaves are tinted with in the column at the given world
+    /// position, whose terrain rises to the given height. Worlds without biomes have a mild
+    /// climate everywhere.
+    fn foliage_tint(&self, _x: i32, _z: i32, _height: usize) -> Vec3 {
+        biome::foliage_tint(0.0, 0.0)
+    }
+}
+
+/// A chunk being decorated, along with its eight neighbors.
+pub struct ChunkRegion {
+    /// The position of the chunk being decorated.
+    pub center: IVec2,
+    /// The chunks making up the region.
+    chunks: HashMap<IVec2, Arc<Chunk>>,
+    /// The chunks that have had any of their voxels set.
+    modified: HashSet<IVec2>,
+}
+
+impl ChunkRegion {
+    /// Creates a new region around `center` from the given chunks.
+    pub fn new(center: IVec2, chunks: HashMap<IVec2, Arc<Chunk>>) -> Self {
+        Self {
+            center,
+            chunks,
+            modified: HashSet::new(),
+        }
+    }
+
+    /// Returns the chunks making up the region, along with the positions of the ones that were
+    /// modified.
+    pub fn into_parts(self) -> (HashMap<IVec2, Arc<Chunk>>, HashSet<IVec2>) {
+        (self.chunks, self.modified)
+    }
+
+    /// Returns the voxel at the given world position, or None if it is outside the region.
+    pub fn get_block(&self, position: IVec3) -> Option<Voxel> {
+        let (chunk, local) = Chunk::locate(position)?;
+
+        self.chunks.get(&chunk).map(|chunk| chunk.get_voxel(local))
+    }
+
+    /// Sets the voxel at the given world position. Returns whether the position was inside the
+    /// region.
+    pub fn set_block(&mut self, position: IVec3, voxel: Voxel) -> bool {
+        let Some((chunk_position, local)) = Chunk::locate(position) else {
+            return false;
+        };
+
+        let Some(chunk) = self.chunks.get_mut(&chunk_position) else {
+            return false;
+        };
+
+        Arc::make_mut(chunk).set_voxel(local, voxel);
+        self.modified.insert(chunk_position);
+
+        true
+    }
+
+    /// Returns the height of the highest non-air voxel in the given column, or None if the
+    /// column is empty or outside the region.
+    pub fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        (0..CHUNK_HEIGHT as i32).rev().find(|y| {
+            self.get_block(ivec3(x, *y, z))
+                .is_some_and(|voxel| voxel != Voxel::Air)
+        })
+    }
+}
+
+/// Returns a pseudo-random number that is always the same for a given seed, chunk position and
+/// index, so decorations are placed identically every time a chunk is generated.
+fn chunk_random(seed: u32, position: IVec2, index: u32) -> u64 {
+    // splitmix64
+    let mut z = (seed as u64)
+        ^ (position.x as u32 as u64) << 16
+        ^ (position.y as u32 as u64) << 40
+        ^ (index as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// How a terrain generator decides which voxels are solid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TerrainMode {
+    /// Every column is filled up to a height sampled from 2d noise, producing rolling hills.
+    Heightmap,
+    /// Voxels are solid where 3d density noise outweighs a bias growing with height, producing
+    /// overhangs, cliffs and floating islands.
+    Density {
+        /// The height around which the terrain surface lies.
+        base_height: f64,
+        /// How many voxels it takes for the height bias to grow by one. Higher values give
+        /// taller, more chaotic terrain.
+        squash: f64,
+    },
+}
+
+/// Tuning options for the cave carving pass run after terrain has been filled in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaveSettings {
+    /// The frequency the cave noise is sampled at. Higher values give smaller, more frequent
+    /// caves.
+    pub frequency: f64,
+    /// How high the cave noise must be for a voxel to be carved out, between 0 and 1. Lower
+    /// values give wider tunnels.
+    pub threshold: f64,
+    /// The minimum depth below the surface caves are carved at, so they do not break through
+    /// the ground everywhere.
+    pub min_depth: usize,
+}
+
+impl Default for CaveSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 1.0 / 64.0,
+            threshold: 0.75,
+            min_depth: 8,
+        }
+    }
+}
+
+/// Tuning options for the rivers carved into the terrain after it has been filled in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RiverSettings {
+    /// The frequency the river noise is sampled at. Higher values give more, shorter rivers.
+    pub frequency: f64,
+    /// How close to zero the river noise must be for a column to be part of a river. Higher
+    /// values give wider rivers.
+    pub width: f64,
+    /// How many voxels below the water level the center of a river is carved.
+    pub depth: usize,
+    /// The height rivers are filled with water up to.
+    pub water_level: usize,
+}
+
+impl Default for RiverSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 1.0 / 700.0,
+            width: 0.03,
+            depth: 5,
+            water_level: SEA_LEVEL,
+        }
+    }
+}
+
+/// Tuning options for the lakes placed in the local height minima of heightmap terrain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LakeSettings {
+    /// The width of the grid cells that can each hold one lake.
+    pub spacing: usize,
+    /// How far below its surroundings the center of a cell must be to hold a lake.
+    pub min_depth: usize,
+}
+
+impl Default for LakeSettings {
+    fn default() -> Self {
+        Self {
+            spacing: 64,
+            min_depth: 4,
+        }
+    }
+}
+
+/// Tuning options for the trees placed on grass during decoration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TreeSettings {
+    /// How many times per chunk a tree is attempted to be placed. Attempts that do not land on
+    /// grass are skipped.
+    pub attempts: u32,
+    /// The shortest a tree trunk can be.
+    pub min_trunk_height: u32,
+    /// The tallest a tree trunk can be.
+    pub max_trunk_height: u32,
+}
+
+impl Default for TreeSettings {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            min_trunk_height: 4,
+            max_trunk_height: 6,
+        }
+    }
+}
+
+/// Places a tree with its trunk starting at `base`. The canopy spreads up to two voxels out
+/// from the trunk, and only replaces air.
+fn place_tree(region: &mut ChunkRegion, base: IVec3, trunk_height: i32) {
+    let top = base.y + trunk_height - 1;
+
+    for y in top - 2..=top + 1 {
+        let radius: i32 = if y < top { 2 } else { 1 };
+
+        for z in -radius..=radius {
+            for x in -radius..=radius {
+                let is_corner = x.abs() == radius && z.abs() == radius;
+                let position = ivec3(base.x + x, y, base.z + z);
+
+                if !(is_corner && y > top - 2) && region.get_block(position) == Some(Voxel::Air) {
+                    region.set_block(position, Voxel::Leaves);
+                }
+            }
+        }
+    }
+
+    for y in base.y..=top {
+        region.set_block(ivec3(base.x, y, base.z), Voxel::Log);
+    }
+
+    region.set_block(base - IVec3::Y, Voxel::Dirt);
+}
+
+/// The first `chunk_random` index used when placing villages, kept well clear of the indices
+/// used for trees.
+const VILLAGE_RANDOM_INDEX: u32 = 1 << 16;
+/// The first `chunk_random` index used when placing dungeons.
+const DUNGEON_RANDOM_INDEX: u32 = 1 << 17;
+/// The `chunk_random` index used to seed the erosion of a region.
+const EROSION_RANDOM_INDEX: u32 = 1 << 18;
+
+/// The width (in voxels) of the square regions erosion is simulated over. Always a multiple of
+/// `CHUNK_WIDTH`, so every chunk lies within a single region.
+const EROSION_REGION_SIZE: i32 = 64;
+/// How far (in voxels) past the edges of a region its erosion is simulated, so droplets can
+/// flow in from the surrounding terrain.
+const EROSION_MARGIN: i32 = 16;
+/// How far (in voxels) from the edges of a region its erosion fades out, so neighboring regions
+/// (which are eroded separately) meet without forming steps.
+const EROSION_FADE: f64 = 12.0;
+/// The most eroded regions kept around before they are all dropped.
+const MAX_CACHED_EROSION_REGIONS: usize = 64;
+
+/// How far (in voxels) the buildings of a village are placed from the one at its center.
+const VILLAGE_SPREAD: f64 = 10.0;
+
+/// The least amount of voxels left between the top of a dungeon and the surface above it.
+const MIN_DUNGEON_COVER: i32 = 8;
+
+/// Tuning options for the structures placed during decoration, along with the templates they
+/// are built from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureSettings {
+    /// The chance of any chunk anchoring a village.
+    pub village_chance: f64,
+    /// The number of buildings making up a village.
+    pub village_buildings: usize,
+    /// The chance of any chunk anchoring a dungeon.
+    pub dungeon_chance: f64,
+    /// The templates village buildings are picked from.
+    pub villages: Vec<Structure>,
+    /// The templates dungeons are picked from.
+    pub dungeons: Vec<Structure>,
+}
+
+impl StructureSettings {
+    /// Creates structure settings from the given templates, building villages from the ones
+    /// whose name starts with `village_` and dungeons from the ones whose name starts with
+    /// `dungeon_`.
+    pub fn new(templates: HashMap<String, Structure>) -> Self {
+        // sorted so the same template is picked for the same random number on every run
+        let mut templates = templates.into_iter().collect::<Vec<_>>();
+        templates.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let with_prefix = |prefix: &str| {
+            templates
+                .iter()
+                .filter(|(name, _)| name.starts_with(prefix))
+                .map(|(_, structure)| structure.clone())
+                .collect()
+        };
+
+        Self {
+            village_chance: 1.0 / 96.0,
+            village_buildings: 4,
+            dungeon_chance: 1.0 / 24.0,
+            villages: with_prefix("village_"),
+            dungeons: with_prefix("dungeon_"),
+        }
+    }
+}
+
+/// Returns whether a random number from `chunk_random` falls within the given chance.
+fn roll(random: u64, chance: f64) -> bool {
+    (random as f64 / u64::MAX as f64) < chance
+}
+
+/// Places a village anchored at the center of the region, if the seed decides the chunk
+/// anchors one. The first building stands in the middle of the village, with the rest spread
+/// around it. Buildings are only placed on grass, with their bottom layer replacing it.
+fn place_village(region: &mut ChunkRegion, seed: u32, structures: &StructureSettings) {
+    let random = chunk_random(seed, region.center, VILLAGE_RANDOM_INDEX);
+
+    if structures.villages.is_empty() || !roll(random, structures.village_chance) {
+        return;
+    }
+
+    let center = (region.center * CHUNK_WIDTH as i32 + CHUNK_WIDTH as i32 / 2).as_dvec2();
+    let ring = structures.village_buildings.saturating_sub(1).max(1) as f64;
+
+    for building in 0..structures.village_buildings {
+        let random = chunk_random(
+            seed,
+            region.center,
+            VILLAGE_RANDOM_INDEX + 1 + building as u32,
+        );
+        let template = &structures.villages[(random % structures.villages.len() as u64) as usize];
+
+        let position = if building == 0 {
+            center
+        } else {
+            let jitter = ((random >> 16) % 256) as f64 / 256.0 - 0.5;
+            let angle = (building as f64 - 1.0 + jitter * 0.5) / ring * std::f64::consts::TAU;
+
+            center + dvec2(angle.cos(), angle.sin()) * VILLAGE_SPREAD
+        };
+
+        let position = position.as_ivec2();
+        let size = template.size().as_ivec3();
+
+        let Some(surface) = region.surface_height(position.x, position.y) else {
+            continue;
+        };
+
+        if surface + size.y >= CHUNK_HEIGHT as i32
+            || region.get_block(ivec3(position.x, surface, position.y)) != Some(Voxel::Grass)
+        {
+            continue;
+        }
+
+        let origin = ivec3(position.x - size.x / 2, surface, position.y - size.z / 2);
+        template.place(region, origin);
+    }
+}
+
+/// Places a dungeon buried below the center chunk of the region, if the seed decides the chunk
+/// anchors one.
+fn place_dungeon(region: &mut ChunkRegion, seed: u32, structures: &StructureSettings) {
+    let random = chunk_random(seed, region.center, DUNGEON_RANDOM_INDEX);
+
+    if structures.dungeons.is_empty() || !roll(random, structures.dungeon_chance) {
+        return;
+    }
+
+    let random = chunk_random(seed, region.center, DUNGEON_RANDOM_INDEX + 1);
+    let template = &structures.dungeons[(random % structures.dungeons.len() as u64) as usize];
+    let size = template.size().as_ivec3();
+
+    let origin = region.center * CHUNK_WIDTH as i32;
+    let x = origin.x + ((random >> 8) % CHUNK_WIDTH as u64) as i32;
+    let z = origin.y + ((random >> 16) % CHUNK_WIDTH as u64) as i32;
+
+    let Some(surface) = region.surface_height(x, z) else {
+        return;
+    };
+
+    let lowest = MAX_BEDROCK_THICKNESS as i32 + 1;
+    let highest = surface - MIN_DUNGEON_COVER - size.y;
+
+    if highest < lowest {
+        return;
+    }
+
+    let y = lowest + ((random >> 24) % (highest - lowest + 1) as u64) as i32;
+    template.place(region, ivec3(x - size.x / 2, y, z - size.z / 2));
+}
+
+/// Generates natural terrain from noise, in either of the `TerrainMode`s.
+pub struct TerrainGenerator {
+    /// The seed the terrain is generated with.
+    seed: u32,
+    /// How the terrain is shaped.
+    mode: TerrainMode,
+    /// The noise function the terrain height is sampled from in `TerrainMode::Heightmap`.
+    height_noise: Box<dyn NoiseFn<f64, 2> + Send + Sync>,
+    /// The biomes deciding the surface of the terrain in `TerrainMode::Heightmap`.
+    biomes: BiomeMap,
+    /// The noise function the terrain density is sampled from in `TerrainMode::Density`.
+    density_noise: RowFbm,
+    /// The settings of the cave carving pass, if caves are carved at all.
+    caves: Option<CaveSettings>,
+    /// The noise function caves are carved with. Two ridged noise fields only both peak along
+    /// the lines where their ridges cross, giving long winding tunnels.
+    cave_noise: RowMin<RowRidgedMulti, RowRidgedMulti>,
+    /// The noise function varying the thickness of the bedrock layer.
+    bedrock_noise: Perlin,
+    /// The settings of the trees placed during decoration, if trees are placed at all.
+    trees: Option<TreeSettings>,
+    /// The settings of the villages and dungeons placed during decoration, if any are.
+    structures: Option<StructureSettings>,
+    /// The settings of the rivers carved into the terrain, if any are.
+    rivers: Option<RiverSettings>,
+    /// The noise function whose zero crossings rivers follow.
+    river_noise: Fbm<Perlin>,
+    /// The settings of the lakes filled into the terrain, if any are. Only used in
+    /// `TerrainMode::Heightmap`.
+    lakes: Option<LakeSettings>,
+    /// The height oceans are filled up to, if the terrain has oceans.
+    sea_level: Option<usize>,
+    /// The settings of the erosion simulated over the terrain in `TerrainMode::Heightmap`, if
+    /// it is enabled.
+    erosion: Option<ErosionConfig>,
+    /// The eroded heightmaps of the regions generated recently, keyed by region position. Every
+    /// chunk of a region uses the same heightmap, so it is only eroded once.
+    eroded_regions: Mutex<HashMap<IVec2, Arc<Vec<f64>>>>,
+}
+
+impl TerrainGenerator {
+    /// Creates a new terrain generator from the given seed, shaping terrain with the given mode
+    /// and worldgen config.
+    pub fn new(seed: u32, mode: TerrainMode, worldgen: &WorldgenConfig) -> Self {
+        Self {
+            seed,
+            mode,
+            height_noise: Box::new(create_noise_generator(seed, &worldgen.terrain)),
+            biomes: BiomeMap::new(seed, &worldgen.biomes),
+            density_noise: RowFbm::new(seed, 4),
+            caves: None,
+            cave_noise: RowMin::new(
+                RowRidgedMulti::new(seed.wrapping_add(1), 2),
+                RowRidgedMulti::new(seed.wrapping_add(2), 2),
+            ),
+            bedrock_noise: Perlin::new(seed.wrapping_add(4)),
+            trees: None,
+            structures: None,
+            rivers: None,
+            river_noise: Fbm::<Perlin>::new(seed.wrapping_add(3)).set_octaves(4),
+            lakes: None,
+            sea_level: None,
+            erosion: worldgen.erosion.enabled.then(|| worldgen.erosion.clone()),
+            eroded_regions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Enables the cave carving pass with the given settings.
+    pub fn with_caves(mut self, caves: CaveSettings) -> Self {
+        self.caves = Some(caves);
+        self
+    }
+
+    /// Enables carving rivers with the given settings.
+    pub fn with_rivers(mut self, rivers: RiverSettings) -> Self {
+        self.rivers = Some(rivers);
+        self
+    }
+
+    /// Enables filling lakes with the given settings.
+    pub fn with_lakes(mut self, lakes: LakeSettings) -> Self {
+        self.lakes = Some(lakes);
+        self
+    }
+
+    /// Enables filling every low area of the terrain with water up to the given sea level.
+    pub fn with_sea_level(mut self, sea_level: usize) -> Self {
+        self.sea_level = Some(sea_level);
+        self
+    }
+
+    /// Enables placing trees during decoration with the given settings.
+    pub fn with_trees(mut self, trees: TreeSettings) -> Self {
+        self.trees = Some(trees);
+        self
+    }
+
+    /// Returns the height of the topmost voxel of the heightmap terrain at the given world
+    /// column, after erosion if it is enabled.
+    fn column_height(&self, x: i32, z: i32) -> usize {
+        if self.erosion.is_none() {
+            return terrain_height(&*self.height_noise, &self.biomes, x, z);
+        }
+
+        let position = ivec2(x, z);
+        let region = position.div_euclid(IVec2::splat(EROSION_REGION_SIZE));
+        let local = position - region * EROSION_REGION_SIZE;
+
+        column_height(
+            self.eroded_region(region)[(local.y * EROSION_REGION_SIZE + local.x) as usize],
+        )
+    }
+
+    /// Returns the eroded heightmap of the chunk at the given position, indexed by
+    /// `z * CHUNK_WIDTH + x`.
+    fn eroded_heights(&self, chunk: IVec2) -> Vec<f64> {
+        let chunks_per_region = EROSION_REGION_SIZE / CHUNK_WIDTH as i32;
+        let region = chunk.div_euclid(IVec2::splat(chunks_per_region));
+        let local = (chunk - region * chunks_per_region) * CHUNK_WIDTH as i32;
+        let heights = self.eroded_region(region);
+
+        (0..CHUNK_WIDTH as i32)
+            .flat_map(|z| (0..CHUNK_WIDTH as i32).map(move |x| local + ivec2(x, z)))
+            .map(|position| heights[(position.y * EROSION_REGION_SIZE + position.x) as usize])
+            .collect()
+    }
+
+    /// Returns the eroded heightmap of the region at the given position, indexed by
+    /// `z * EROSION_REGION_SIZE + x`, eroding it if it hasn't been recently.
+    fn eroded_region(&self, region: IVec2) -> Arc<Vec<f64>> {
+        if let Some(heights) = self.eroded_regions.lock().unwrap().get(&region) {
+            return Arc::clone(heights);
+        }
+
+        let config = self.erosion.as_ref().expect("erosion is enabled");
+        let size = EROSION_REGION_SIZE + EROSION_MARGIN * 2;
+        let origin = region * EROSION_REGION_SIZE - EROSION_MARGIN;
+
+        let raw = terrain_heights(
+            &*self.height_noise,
+            &self.biomes,
+            origin,
+            IVec2::splat(size),
+        );
+        let mut eroded = raw.clone();
+        let seed = chunk_random(self.seed, region, EROSION_RANDOM_INDEX);
+        erosion::erode(&mut eroded, size as usize, config, seed);
+
+        let heights = (0..EROSION_REGION_SIZE)
+            .flat_map(|z| (0..EROSION_REGION_SIZE).map(move |x| ivec2(x, z)))
+            .map(|local| {
+                let edge = local
+                    .min(IVec2::splat(EROSION_REGION_SIZE - 1) - local)
+                    .min_element();
+                let fade = (edge as f64 / EROSION_FADE).min(1.0);
+                let index = ((local.y + EROSION_MARGIN) * size + local.x + EROSION_MARGIN) as usize;
+
+                raw[index] + (eroded[index] - raw[index]) * fade
+            })
+            .collect::<Vec<_>>();
+
+        let heights = Arc::new(heights);
+        let mut regions = self.eroded_regions.lock().unwrap();
+
+        if regions.len() >= MAX_CACHED_EROSION_REGIONS {
+            regions.clear();
+        }
+
+        regions.insert(region, Arc::clone(&heights));
+        heights
+    }
+
+    /// Enables placing villages and dungeons during decoration with the given settings.
+    pub fn with_structures(mut self, structures: StructureSettings) -> Self {
+        self.structures = Some(structures);
+        self
+    }
+}
+
+impl ChunkGenerator for TerrainGenerator {
+    fn generate(&self, chunk: &mut Chunk) {
+        match self.mode {
+            TerrainMode::Heightmap if self.erosion.is_some() => {
+                let heights = self.eroded_heights(chunk.position);
+                chunk.fill_heightmap(&heights, &self.biomes)
+            }
+            TerrainMode::Heightmap => chunk.fill_perlin(&*self.height_noise, &self.biomes),
+            TerrainMode::Density {
+                base_height,
+                squash,
+            } => chunk.fill_density(&self.density_noise, base_height, squash),
+        }
+
+        if let Some(rivers) = self.rivers {
+            chunk.carve_rivers(
+                &self.river_noise,
+                rivers.frequency,
+                rivers.width,
+                rivers.depth,
+                rivers.water_level,
+            );
+        }
+
+        if let (Some(lakes), TerrainMode::Heightmap) = (self.lakes, self.mode) {
+            chunk.fill_lakes(
+                |x, z| self.column_height(x, z),
+                lakes.spacing,
+                lakes.min_depth,
+            );
+        }
+
+        if let Some(sea_level) = self.sea_level {
+            chunk.fill_ocean(sea_level);
+            chunk.place_beaches(sea_level);
+        }
+
+        if let Some(caves) = self.caves {
+            chunk.carve_caves(
+                &self.cave_noise,
+                caves.frequency,
+                caves.threshold,
+                caves.min_depth,
+            );
+        }
+
+        chunk.fill_bedrock(self.bedrock_noise);
+    }
+
+    fn decorate(&self, region: &mut ChunkRegion) {
+        if let Some(structures) = &self.structures {
+            place_village(region, self.seed, structures);
+            place_dungeon(region, self.seed, structures);
+        }
+
+        let Some(trees) = self.trees else {
+            return;
+        };
+
+        let origin = region.center * CHUNK_WIDTH as i32;
+        let heights = (trees.max_trunk_height - trees.min_trunk_height + 1) as u64;
+
+        for attempt in 0..trees.attempts {
+            let random = chunk_random(self.seed, region.center, attempt);
+
+            let x = origin.x + (random % CHUNK_WIDTH as u64) as i32;
+            let z = origin.y + ((random >> 8) % CHUNK_WIDTH as u64) as i32;
+            let trunk_height = trees.min_trunk_height as i32 + ((random >> 16) % heights) as i32;
+
+            let Some(surface) = region.surface_height(x, z) else {
+                continue;
+            };
+
+            let top = surface + trunk_height + 1;
+
+            if top >= CHUNK_HEIGHT as i32
+                || region.get_block(ivec3(x, surface, z)) != Some(Voxel::Grass)
+            {
+                continue;
+            }
+
+            place_tree(region, ivec3(x, surface + 1, z), trunk_height);
+        }
+    }
+
+    fn foliage_tint(&self, x: i32, z: i32, height: usize) -> Vec3 {
+        self.biomes.foliage_tint(x, z, height)
+    }
+}
+
+/// Generates an enclosed network of caverns carved out of solid stone by 3d noise.
+pub struct CaveGenerator {
+    /// The noise function deciding which voxels are carved out.
+    noise: RowFbm,
+    /// The noise function varying the thickness of the bedrock floor.
+    bedrock_noise: Perlin,
+}
+
+impl CaveGenerator {
+    /// Creates a new cave generator from the given seed.
+    pub fn new(seed: u32) -> Self {
+        Self {
+            noise: RowFbm::new(seed, 3),
+            bedrock_noise: Perlin::new(seed.wrapping_add(4)),
+        }
+    }
+}
+
+impl ChunkGenerator for CaveGenerator {
+    fn generate(&self, chunk: &mut Chunk) {
+        chunk.fill_caves(&self.noise);
+        chunk.fill_bedrock(self.bedrock_noise);
+    }
+}
+
+/// Generates perfectly flat terrain made of horizontal layers.
+pub struct FlatGenerator {
+    /// The layers of the world from the bottom up, with their thickness.
+    layers: Vec<(Voxel, usize)>,
+}
+
+impl FlatGenerator {
+    /// Creates a new flat generator with the given layers, from the bottom up.
+    pub fn new(layers: Vec<(Voxel, usize)>) -> Self {
+        Self { layers }
+    }
+}
+
+impl ChunkGenerator for FlatGenerator {
+    fn generate(&self, chunk: &mut Chunk) {
+        chunk.fill_flat(&self.layers);
+    }
+}
+
+/// Generates the terrain of an imported heightmap within the region it covers, leaving the rest
+/// of the world empty.
+pub struct HeightmapGenerator {
+    /// The heightmap the terrain is shaped by.
+    heightmap: ImportedHeightmap,
+}
+
+impl HeightmapGenerator {
+    /// Creates a new generator from the given imported heightmap.
+    pub fn new(heightmap: ImportedHeightmap) -> Self {
+        Self { heightmap }
+    }
+}
+
+impl ChunkGenerator for HeightmapGenerator {
+    fn generate(&self, chunk: &mut Chunk) {
+        let global_position = chunk.position * CHUNK_WIDTH as i32;
+
+        let heights = (0..CHUNK_WIDTH * CHUNK_WIDTH)
+            .map(|index| {
+                let x = global_position.x + (index % CHUNK_WIDTH) as i32;
+                let z = global_position.y + (index / CHUNK_WIDTH) as i32;
+
+                self.heightmap.height_at(x, z)
+            })
+            .collect::<Vec<_>>();
+
+        chunk.fill_columns(&heights, self.heightmap.water_level());
+
+        if let Some(water_level) = self.heightmap.water_level() {
+            chunk.place_beaches(water_level);
+        }
+    }
+}
+
+/// Generates nothing at all, leaving every chunk empty.
+pub struct VoidGenerator;
+
+impl ChunkGenerator for VoidGenerator {
+    fn generate(&self, chunk: &mut Chunk) {
+        chunk.fill_void();
+    }
+}
@@ -0,0 +1,156 @@
+use wgpu::*;
+
+use crate::preprocessor;
+
+/// Draws volumetric light shafts (crepuscular rays) over the scene. For each pixel, the depth
+/// buffer is sampled along the line towards the sun on the screen, and the pixel is brightened
+/// by how much open sky that line passes over, so rays stream out from behind terrain.
+pub struct GodRays {
+    /// The pipeline adding the rays onto the scene.
+    pipeline: RenderPipeline,
+    /// The layout of the bind group the depth buffer is read through.
+    depth_bind_group_layout: BindGroupLayout,
+    /// The bind group of the current depth buffer, replaced whenever the surface is resized.
+    depth_bind_group: BindGroup,
+}
+
+impl GodRays {
+    /// Creates the pipeline drawing the rays onto surfaces of the given format, with the camera
+    /// and environment bound as groups 0 and 1, reading from the given depth buffer.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        camera_bind_group_layout: &BindGroupLayout,
+        environment_bind_group_layout: &BindGroupLayout,
+        depth_view: &TextureView,
+    ) -> Self {
+        let shader = preprocessor::create_shader_module(
+            device,
+            "god_rays.wgsl",
+            include_str!("god_rays.wgsl"),
+            &[],
+        );
+
+        let depth_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("God Rays Depth Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("God Rays Pipeline Layout"),
+            push_constant_ranges: &[],
+            bind_group_layouts: &[
+                camera_bind_group_layout,
+                environment_bind_group_layout,
+                &depth_bind_group_layout,
+            ],
+        });
+
+        // the rays are added onto what has been drawn, leaving its alpha alone
+        let blend = BlendState {
+            color: BlendComponent {
+                src_factor: BlendFactor::One,
+                dst_factor: BlendFactor::One,
+                operation: BlendOperation::Add,
+            },
+            alpha: BlendComponent {
+                src_factor: BlendFactor::Zero,
+                dst_factor: BlendFactor::One,
+                operation: BlendOperation::Add,
+            },
+        };
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("God Rays Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(blend),
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: PipelineCompilationOptions::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        let depth_bind_group =
+            Self::create_depth_bind_group(device, &depth_bind_group_layout, depth_view);
+
+        Self {
+            pipeline,
+            depth_bind_group_layout,
+            depth_bind_group,
+        }
+    }
+
+    fn create_depth_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        depth_view: &TextureView,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("God Rays Depth Bind Group"),
+            layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(depth_view),
+            }],
+        })
+    }
+
+    /// Reads from the given depth buffer from now on, after it has been recreated.
+    pub fn resize(&mut self, device: &Device, depth_view: &TextureView) {
+        self.depth_bind_group =
+            Self::create_depth_bind_group(device, &self.depth_bind_group_layout, depth_view);
+    }
+
+    /// Adds the rays onto the given view, which must already hold the scene whose depth buffer
+    /// is being read.
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        camera_bind_group: &BindGroup,
+        environment_bind_group: &BindGroup,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("God Rays Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, environment_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.depth_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
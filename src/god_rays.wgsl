@@ -0,0 +1,87 @@
+// Uniforms
+#include "camera.wgsl"
+#include "environment.wgsl"
+
+@group(1) @binding(0) var<uniform> environment: EnvironmentUniform;
+
+// The depth buffer of the scene, where the sky is left at the far plane (1).
+@group(2) @binding(0) var depth: texture_depth_2d;
+
+// The number of depth samples taken along the ray from each pixel towards the sun.
+const SAMPLES: i32 = 48;
+// How far towards the sun the rays reach, as a fraction of the distance from the pixel to it.
+const DENSITY: f32 = 0.9;
+// How quickly each sample contributes less than the one before it.
+const DECAY: f32 = 0.96;
+// The brightness of the rays.
+const EXPOSURE: f32 = 0.35;
+// The color of the light the rays are made of.
+const SUN_COLOR: vec3<f32> = vec3<f32>(1.0, 0.85, 0.6);
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	// The position of the pixel on the screen, from (0, 0) at the top left to (1, 1).
+	@location(0) uv: vec2<f32>,
+}
+
+// Covers the screen with a single triangle.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_id: u32) -> VertexOutput {
+	var out: VertexOutput;
+
+	let uv = vec2<f32>(f32((vertex_id << 1u) & 2u), f32(vertex_id & 2u));
+	out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+	out.uv = uv;
+
+	return out;
+}
+
+// Returns whether the sky can be seen at the given position on the screen.
+fn is_sky(uv: vec2<f32>) -> f32 {
+	let size = vec2<f32>(textureDimensions(depth));
+	let pixel = vec2<i32>(clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0)) * (size - 1.0));
+
+	return select(0.0, 1.0, textureLoad(depth, pixel, 0) >= 1.0);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+	let sun_direction = environment.sun_direction;
+
+	// the sun is infinitely far away, so only its direction is projected
+	let sun_clip = camera.view_proj * vec4<f32>(sun_direction, 0.0);
+
+	if sun_clip.w <= 0.0 {
+		return vec4<f32>(0.0);
+	}
+
+	let sun_uv = (sun_clip.xy / sun_clip.w) * vec2<f32>(0.5, -0.5) + 0.5;
+
+	// rays are strongest when the sun is low (at sunrise and sunset), and fade out as it sets
+	let height = sun_direction.y;
+	let strength = (1.0 - 0.8 * clamp(height, 0.0, 1.0)) * smoothstep(-0.1, 0.05, height)
+		* (1.0 - environment.underwater);
+
+	// and when looking towards the sun
+	let facing = 1.0 - smoothstep(0.0, 1.5, length(sun_uv - input.uv));
+
+	if strength * facing <= 0.0 {
+		return vec4<f32>(0.0);
+	}
+
+	let step = (sun_uv - input.uv) * DENSITY / f32(SAMPLES);
+
+	var uv = input.uv;
+	var weight = 1.0;
+	var light = 0.0;
+
+	for (var i = 0; i < SAMPLES; i++) {
+		uv += step;
+		light += is_sky(uv) * weight;
+		weight *= DECAY;
+	}
+
+	let rays = light / f32(SAMPLES) * EXPOSURE * strength * facing;
+
+	return vec4<f32>(SUN_COLOR * rays, 0.0);
+}
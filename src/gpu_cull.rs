@@ -0,0 +1,266 @@
+use glam::Vec4;
+use wgpu::{util::DrawIndexedIndirectArgs, *};
+
+use crate::camera::Frustum;
+
+/// The number of draws each workgroup of the culling pass tests, matching `gpu_cull.wgsl`.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// The number of draws the draw buffers are created with.
+const INITIAL_DRAW_CAPACITY: usize = 1024;
+
+/// The parameters of the culling pass, laid out as `CullUniform` in `gpu_cull.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullUniform {
+    /// The inward facing clip planes of the camera's frustum.
+    planes: [Vec4; 6],
+    /// The number of draws being culled.
+    draw_count: u32,
+    /// Whether the visible draws are packed together and counted (non-zero), or left in place.
+    compact: u32,
+    _padding: [u32; 2],
+}
+
+/// Culls the draws of chunk meshes against the camera's frustum on the GPU, in a compute pass
+/// writing the arguments of the visible draws into an indirect buffer, so the meshes can be
+/// drawn without the CPU testing each chunk. When the adapter supports drawing a count read
+/// from a buffer, the visible draws are packed together; otherwise culled draws are kept in
+/// place, drawing no instances.
+pub struct GpuCuller {
+    /// The pipeline testing each draw.
+    pipeline: ComputePipeline,
+    /// The layout of the bind group the pass reads and writes the draws through.
+    bind_group_layout: BindGroupLayout,
+    /// The uniform buffer holding the `CullUniform` of the current frame.
+    uniform_buffer: Buffer,
+
+    /// The arguments of every draw being culled.
+    draw_buffer: Buffer,
+    /// The arguments of the draws that passed, read by the indirect draw.
+    visible_buffer: Buffer,
+    /// The number of draws that passed, when they are packed together.
+    count_buffer: Buffer,
+    /// The number of draws `draw_buffer` and `visible_buffer` can hold.
+    capacity: usize,
+
+    /// Whether the visible draws are packed together and drawn with a count read from
+    /// `count_buffer`.
+    compact: bool,
+    /// The number of draws culled this frame.
+    draw_count: u32,
+    /// The bind group of this frame's culling pass, if there are any draws to cull.
+    bind_group: Option<BindGroup>,
+}
+
+impl GpuCuller {
+    /// Creates the culling pipeline and buffers. `compact` must only be set when the device
+    /// supports `Features::MULTI_DRAW_INDIRECT_COUNT`.
+    pub fn new(device: &Device, compact: bool) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("gpu_cull.wgsl"));
+
+        let storage = |binding, read_only| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Cull Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, true),
+                storage(3, false),
+                storage(4, false),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Cull Pipeline Layout"),
+            push_constant_ranges: &[],
+            bind_group_layouts: &[&bind_group_layout],
+        });
+
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Cull Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+            compilation_options: PipelineCompilationOptions::default(),
+        });
+
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Cull Uniform Buffer"),
+            size: size_of::<CullUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let count_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Cull Count Buffer"),
+            size: size_of::<u32>() as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let capacity = INITIAL_DRAW_CAPACITY;
+        let (draw_buffer, visible_buffer) = Self::create_draw_buffers(device, capacity);
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            uniform_buffer,
+            draw_buffer,
+            visible_buffer,
+            count_buffer,
+            capacity,
+            compact,
+            draw_count: 0,
+            bind_group: None,
+        }
+    }
+
+    /// Creates the buffers holding the arguments of the draws being culled, and of the ones
+    /// that passed, each able to hold `capacity` draws.
+    fn create_draw_buffers(device: &Device, capacity: usize) -> (Buffer, Buffer) {
+        let size = (capacity * size_of::<DrawIndexedIndirectArgs>()) as BufferAddress;
+
+        let draw_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Cull Draw Buffer"),
+            size,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let visible_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Cull Visible Buffer"),
+            size,
+            usage: BufferUsages::STORAGE | BufferUsages::INDIRECT,
+            mapped_at_creation: false,
+        });
+
+        (draw_buffer, visible_buffer)
+    }
+
+    /// Uploads the given draws to be culled against the `frustum` by the next `dispatch`. Each
+    /// draw's chunk origin is read from its first instance in `instance_buffer`, which must be
+    /// usable as storage.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        frustum: &Frustum,
+        draws: &[DrawIndexedIndirectArgs],
+        instance_buffer: &Buffer,
+    ) {
+        self.draw_count = draws.len() as u32;
+        self.bind_group = None;
+
+        if draws.is_empty() {
+            return;
+        }
+
+        if draws.len() > self.capacity {
+            self.capacity = draws.len().next_power_of_two();
+            (self.draw_buffer, self.visible_buffer) =
+                Self::create_draw_buffers(device, self.capacity);
+        }
+
+        let uniform = CullUniform {
+            planes: frustum.planes,
+            draw_count: self.draw_count,
+            compact: self.compact as u32,
+            _padding: [0; 2],
+        };
+
+        let draws = draws
+            .iter()
+            .flat_map(|draw| draw.as_bytes())
+            .copied()
+            .collect::<Vec<_>>();
+
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+        queue.write_buffer(&self.draw_buffer, 0, &draws);
+
+        // the instance buffer may have been replaced since the last frame
+        self.bind_group = Some(device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Cull Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: self.draw_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: instance_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: self.visible_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: self.count_buffer.as_entire_binding(),
+                },
+            ],
+        }));
+    }
+
+    /// Records the pass culling the draws uploaded by `prepare`.
+    pub fn dispatch(&self, encoder: &mut CommandEncoder) {
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+
+        encoder.clear_buffer(&self.count_buffer, 0, None);
+
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("Cull Pass"),
+            timestamp_writes: None,
+        });
+
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, bind_group, &[]);
+        compute_pass.dispatch_workgroups(self.draw_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Draws the meshes that passed this frame's culling pass. The buffers of the `MeshPool`
+    /// and the instance buffer must already be bound.
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        if self.draw_count == 0 {
+            return;
+        }
+
+        if self.compact {
+            render_pass.multi_draw_indexed_indirect_count(
+                &self.visible_buffer,
+                0,
+                &self.count_buffer,
+                0,
+                self.draw_count,
+            );
+        } else {
+            render_pass.multi_draw_indexed_indirect(&self.visible_buffer, 0, self.draw_count);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+// The arguments of an indexed indirect draw.
+struct DrawArgs {
+	index_count: u32,
+	instance_count: u32,
+	first_index: u32,
+	base_vertex: i32,
+	first_instance: u32,
+}
+
+struct CullUniform {
+	// The inward facing clip planes of the camera's frustum, as (normal, d).
+	planes: array<vec4<f32>, 6>,
+	// The number of draws being culled.
+	draw_count: u32,
+	// Whether the visible draws are packed at the start of `visible_draws` (and counted in
+	// `visible_count`), rather than left in place with culled draws drawing no instances.
+	compact: u32,
+}
+
+@group(0) @binding(0) var<uniform> cull: CullUniform;
+@group(0) @binding(1) var<storage, read> draws: array<DrawArgs>;
+// The instance of every draw, holding the offset of its chunk's origin from the camera, packed
+// as 3 floats each.
+@group(0) @binding(2) var<storage, read> instances: array<f32>;
+@group(0) @binding(3) var<storage, read_write> visible_draws: array<DrawArgs>;
+@group(0) @binding(4) var<storage, read_write> visible_count: atomic<u32>;
+
+// The size of the bounding box of a chunk.
+const CHUNK_SIZE = vec3<f32>(16.0, 256.0, 16.0);
+
+// Returns whether any part of the box between `min` and `max` could be inside the frustum.
+fn intersects_frustum(min: vec3<f32>, max: vec3<f32>) -> bool {
+	for (var i = 0u; i < 6u; i++) {
+		let plane = cull.planes[i];
+		// the corner of the box furthest along the plane's normal
+		let corner = select(min, max, plane.xyz >= vec3<f32>(0.0));
+
+		if dot(plane.xyz, corner) + plane.w < 0.0 {
+			return false;
+		}
+	}
+
+	return true;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+	let index = id.x;
+
+	if index >= cull.draw_count {
+		return;
+	}
+
+	var draw = draws[index];
+	let instance = draw.first_instance * 3u;
+	let min = vec3<f32>(instances[instance], instances[instance + 1u], instances[instance + 2u]);
+	let visible = intersects_frustum(min, min + CHUNK_SIZE);
+
+	if cull.compact != 0u {
+		if visible {
+			visible_draws[atomicAdd(&visible_count, 1u)] = draw;
+		}
+
+		return;
+	}
+
+	if !visible {
+		draw.instance_count = 0u;
+	}
+
+	visible_draws[index] = draw;
+}
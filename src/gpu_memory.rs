@@ -0,0 +1,125 @@
+use std::{
+    ops::Deref,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use wgpu::*;
+
+/// What a tracked GPU resource is used for, which its memory is counted towards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryCategory {
+    /// The vertices of chunk meshes.
+    Vertices,
+    /// The indices of chunk meshes.
+    Indices,
+    /// The voxel textures, along with their mipmaps.
+    Textures,
+    /// The depth buffer.
+    Depth,
+    /// The textures the scene is drawn into before being presented.
+    RenderTargets,
+}
+
+impl MemoryCategory {
+    /// Every category, in the order they are listed.
+    pub const ALL: [Self; 5] = [
+        Self::Vertices,
+        Self::Indices,
+        Self::Textures,
+        Self::Depth,
+        Self::RenderTargets,
+    ];
+
+    /// Returns the name the category is listed as.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Vertices => "chunk vertices",
+            Self::Indices => "chunk indices",
+            Self::Textures => "textures",
+            Self::Depth => "depth",
+            Self::RenderTargets => "render targets",
+        }
+    }
+
+    /// Returns the number of bytes allocated for the category's resources that currently exist.
+    pub fn allocated(self) -> u64 {
+        ALLOCATED[self as usize].load(Ordering::Relaxed)
+    }
+}
+
+/// The number of bytes allocated for each category, indexed by `MemoryCategory`.
+static ALLOCATED: [AtomicU64; MemoryCategory::ALL.len()] =
+    [const { AtomicU64::new(0) }; MemoryCategory::ALL.len()];
+
+/// A GPU resource whose size counts towards the memory allocated for its category for as long
+/// as it exists.
+#[derive(Debug)]
+pub struct Tracked<T> {
+    /// The resource being tracked.
+    resource: T,
+    /// The category the resource's memory is counted towards.
+    category: MemoryCategory,
+    /// The size of the resource, in bytes.
+    size: u64,
+}
+
+impl<T> Tracked<T> {
+    /// Starts counting the given size of the resource towards the category.
+    fn new(resource: T, category: MemoryCategory, size: u64) -> Self {
+        ALLOCATED[category as usize].fetch_add(size, Ordering::Relaxed);
+
+        Self {
+            resource,
+            category,
+            size,
+        }
+    }
+}
+
+impl<T> Deref for Tracked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.resource
+    }
+}
+
+impl<T> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        ALLOCATED[self.category as usize].fetch_sub(self.size, Ordering::Relaxed);
+    }
+}
+
+/// Creates a buffer, counting its size towards the given category.
+pub fn create_buffer(
+    device: &Device,
+    category: MemoryCategory,
+    descriptor: &BufferDescriptor,
+) -> Tracked<Buffer> {
+    Tracked::new(device.create_buffer(descriptor), category, descriptor.size)
+}
+
+/// Creates a texture, counting the size of every mip level of it towards the given category.
+pub fn create_texture(
+    device: &Device,
+    category: MemoryCategory,
+    descriptor: &TextureDescriptor,
+) -> Tracked<Texture> {
+    let (block_width, block_height) = descriptor.format.block_dimensions();
+    // formats without a fixed size (like packed depth and stencil) are counted as 4 bytes
+    let block_size = descriptor.format.block_copy_size(None).unwrap_or(4) as u64;
+
+    let size = (0..descriptor.mip_level_count)
+        .map(|level| {
+            let size = descriptor.size.mip_level_size(level, descriptor.dimension);
+
+            size.width.div_ceil(block_width) as u64
+                * size.height.div_ceil(block_height) as u64
+                * size.depth_or_array_layers as u64
+                * block_size
+        })
+        .sum::<u64>()
+        * descriptor.sample_count as u64;
+
+    Tracked::new(device.create_texture(descriptor), category, size)
+}
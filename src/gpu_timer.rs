@@ -0,0 +1,181 @@
+use std::{
+    mem::size_of,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use wgpu::*;
+
+/// The features the device needs for passes to be timed.
+pub const TIMER_FEATURES: Features =
+    Features::TIMESTAMP_QUERY.union(Features::TIMESTAMP_QUERY_INSIDE_ENCODERS);
+
+/// The most passes that can be timed in a frame. Passes after these go untimed.
+const MAX_TIMED_PASSES: u32 = 64;
+
+/// Times how long the GPU takes to draw each pass of a frame, with timestamps written into the
+/// frame's encoder before and after every pass. Timestamps are read back a few frames later,
+/// during which no other frames are timed.
+pub struct GpuTimer {
+    /// The timestamps written this frame, two for each pass.
+    query_set: QuerySet,
+    /// The buffer the timestamps are resolved into.
+    resolve_buffer: Buffer,
+    /// The buffer the resolved timestamps are copied into, to be read back.
+    readback_buffer: Buffer,
+    /// The number of nanoseconds per timestamp tick.
+    period: f32,
+
+    /// Whether the passes of the frame being drawn are timed, which they aren't while the
+    /// timestamps of an earlier frame are being read back.
+    timing: bool,
+    /// Whether the pass being drawn has its start written, and needs its end written too.
+    pass_open: bool,
+    /// The names of the passes timed in the frame being drawn, in order.
+    passes: Vec<&'static str>,
+    /// The names of the passes whose timestamps are being read back.
+    in_flight: Option<Vec<&'static str>>,
+    /// The result of mapping `readback_buffer`, once it is mapped.
+    read_result: Arc<Mutex<Option<Result<(), BufferAsyncError>>>>,
+    /// The time the GPU spent drawing each pass, as of the latest timestamps read back.
+    timings: Vec<(&'static str, Duration)>,
+}
+
+impl GpuTimer {
+    /// Creates the timestamp queries and buffers. The device must have been created with the
+    /// `TIMER_FEATURES`.
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let count = MAX_TIMED_PASSES * 2;
+        let size = count as BufferAddress * size_of::<u64>() as BufferAddress;
+
+        let query_set = device.create_query_set(&QuerySetDescriptor {
+            label: Some("Pass Timestamps"),
+            ty: QueryType::Timestamp,
+            count,
+        });
+
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Pass Timestamp Resolve Buffer"),
+            size,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Pass Timestamp Readback Buffer"),
+            size,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            timing: false,
+            pass_open: false,
+            passes: Vec::new(),
+            in_flight: None,
+            read_result: Arc::default(),
+            timings: Vec::new(),
+        }
+    }
+
+    /// Collects the timestamps read back since the last frame, and starts timing the frame
+    /// about to be drawn unless timestamps are still being read back.
+    pub fn begin_frame(&mut self, device: &Device) {
+        device.poll(Maintain::Poll);
+
+        if let Some(read_result) = self.read_result.lock().unwrap().take() {
+            let passes = self.in_flight.take().unwrap_or_default();
+
+            match read_result {
+                Ok(()) => {
+                    let size = passes.len() * 2 * size_of::<u64>();
+
+                    {
+                        let data = self.readback_buffer.slice(..size as u64).get_mapped_range();
+                        let timestamps: &[u64] = bytemuck::cast_slice(&data);
+
+                        self.timings = passes
+                            .into_iter()
+                            .zip(timestamps.chunks_exact(2))
+                            .map(|(name, pair)| {
+                                let ticks = pair[1].saturating_sub(pair[0]);
+                                (
+                                    name,
+                                    Duration::from_nanos((ticks as f32 * self.period) as u64),
+                                )
+                            })
+                            .collect();
+                    }
+
+                    self.readback_buffer.unmap();
+                }
+                Err(error) => eprintln!("could not read pass timestamps: {error:?}"),
+            }
+        }
+
+        self.timing = self.in_flight.is_none();
+        self.passes.clear();
+    }
+
+    /// Writes the timestamp starting the pass with the given name, if this frame is timed.
+    pub fn begin_pass(&mut self, encoder: &mut CommandEncoder, name: &'static str) {
+        if !self.timing || self.passes.len() as u32 == MAX_TIMED_PASSES {
+            return;
+        }
+
+        encoder.write_timestamp(&self.query_set, self.passes.len() as u32 * 2);
+        self.passes.push(name);
+        self.pass_open = true;
+    }
+
+    /// Writes the timestamp ending the pass started by `GpuTimer::begin_pass`.
+    pub fn end_pass(&mut self, encoder: &mut CommandEncoder) {
+        if !std::mem::take(&mut self.pass_open) {
+            return;
+        }
+
+        encoder.write_timestamp(&self.query_set, self.passes.len() as u32 * 2 - 1);
+    }
+
+    /// Records resolving the timestamps of this frame's passes, to be read back once the frame
+    /// has been submitted.
+    pub fn resolve(&self, encoder: &mut CommandEncoder) {
+        if !self.timing || self.passes.is_empty() {
+            return;
+        }
+
+        let count = self.passes.len() as u32 * 2;
+        let size = count as BufferAddress * size_of::<u64>() as BufferAddress;
+
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, size);
+    }
+
+    /// Starts reading back the timestamps of this frame, once it has been submitted.
+    pub fn read_back(&mut self) {
+        if !self.timing || self.passes.is_empty() {
+            return;
+        }
+
+        let size = (self.passes.len() * 2 * size_of::<u64>()) as BufferAddress;
+        let read_result = Arc::clone(&self.read_result);
+
+        self.readback_buffer
+            .slice(..size)
+            .map_async(MapMode::Read, move |result| {
+                *read_result.lock().unwrap() = Some(result);
+            });
+
+        self.in_flight = Some(std::mem::take(&mut self.passes));
+        self.timing = false;
+    }
+
+    /// Returns the time the GPU spent drawing each pass of the latest frame timed, in order.
+    pub fn timings(&self) -> &[(&'static str, Duration)] {
+        &self.timings
+    }
+}
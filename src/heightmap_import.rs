@@ -0,0 +1,168 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context};
+use glam::{dvec2, ivec2, IVec2};
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::CHUNK_HEIGHT;
+
+/// Options for importing a heightmap image as the terrain of a region of the world.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeightmapImport {
+    /// The grayscale image the terrain heights are read from, such as a PNG (8 or 16 bit) or an
+    /// EXR file. Brighter pixels are higher.
+    pub path: PathBuf,
+    /// The world position (x, z) of the corner of the region the top left pixel of the image is
+    /// placed at. The image's rows run along the z axis.
+    #[serde(default)]
+    pub origin: IVec2,
+    /// How many voxels wide the area each pixel of the image covers is, with the terrain
+    /// smoothly interpolated between pixels.
+    #[serde(default = "HeightmapImport::default_voxels_per_pixel")]
+    pub voxels_per_pixel: usize,
+    /// The height of the terrain where the image is black.
+    #[serde(default = "HeightmapImport::default_min_height")]
+    pub min_height: usize,
+    /// The height of the terrain where the image is white.
+    #[serde(default = "HeightmapImport::default_max_height")]
+    pub max_height: usize,
+    /// Whether the darkest and brightest pixels of the image are stretched to `min_height` and
+    /// `max_height`, rather than black and white. Useful for elevation data stored as floats,
+    /// whose values aren't between 0 and 1.
+    #[serde(default)]
+    pub normalize: bool,
+    /// The height up to which the terrain is flooded with water, if any.
+    #[serde(default)]
+    pub water_level: Option<usize>,
+}
+
+impl HeightmapImport {
+    /// Creates the options for importing the heightmap at the given path, with its top left
+    /// corner at the origin and every other option left at its default.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            origin: IVec2::ZERO,
+            voxels_per_pixel: Self::default_voxels_per_pixel(),
+            min_height: Self::default_min_height(),
+            max_height: Self::default_max_height(),
+            normalize: false,
+            water_level: None,
+        }
+    }
+
+    fn default_voxels_per_pixel() -> usize {
+        1
+    }
+
+    fn default_min_height() -> usize {
+        1
+    }
+
+    fn default_max_height() -> usize {
+        160
+    }
+}
+
+/// The terrain heights read from a heightmap image, placed over a region of the world.
+pub struct ImportedHeightmap {
+    /// The width and depth of the image, in pixels.
+    size: IVec2,
+    /// The height of the terrain at each pixel, indexed by `z * size.x + x`.
+    heights: Vec<f64>,
+    /// The world position of the corner of the region the heightmap covers.
+    origin: IVec2,
+    /// How many voxels wide the area each pixel covers is.
+    voxels_per_pixel: usize,
+    /// The height up to which the terrain is flooded with water, if any.
+    water_level: Option<usize>,
+}
+
+impl ImportedHeightmap {
+    /// Reads the heightmap image described by the given import options.
+    pub fn load(import: &HeightmapImport) -> anyhow::Result<Self> {
+        if import.voxels_per_pixel == 0 {
+            bail!("voxels per pixel must be at least 1");
+        }
+
+        if import.min_height > import.max_height || import.max_height >= CHUNK_HEIGHT {
+            bail!(
+                "heights must satisfy min_height <= max_height < {CHUNK_HEIGHT}, got {} and {}",
+                import.min_height,
+                import.max_height
+            );
+        }
+
+        let image = image::open(&import.path)
+            .context(format!("reading {:?}", import.path))?
+            .to_luma32f();
+
+        let mut values = image
+            .pixels()
+            .map(|pixel| pixel.0[0] as f64)
+            .collect::<Vec<_>>();
+
+        if values.is_empty() {
+            bail!("{:?} is empty", import.path);
+        }
+
+        if import.normalize {
+            let lowest = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let highest = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let range = (highest - lowest).max(f64::EPSILON);
+
+            for value in &mut values {
+                *value = (*value - lowest) / range;
+            }
+        }
+
+        let (min_height, max_height) = (import.min_height as f64, import.max_height as f64);
+        let heights = values
+            .into_iter()
+            .map(|value| min_height + value.clamp(0.0, 1.0) * (max_height - min_height))
+            .collect();
+
+        Ok(Self {
+            size: ivec2(image.width() as i32, image.height() as i32),
+            heights,
+            origin: import.origin,
+            voxels_per_pixel: import.voxels_per_pixel,
+            water_level: import.water_level,
+        })
+    }
+
+    /// Returns the height up to which the terrain is flooded with water, if any.
+    pub fn water_level(&self) -> Option<usize> {
+        self.water_level
+    }
+
+    /// Returns the height of the terrain of the column at the given world position, or None if
+    /// the column lies outside of the region the heightmap covers.
+    pub fn height_at(&self, x: i32, z: i32) -> Option<usize> {
+        let scale = self.voxels_per_pixel as i32;
+        let local = ivec2(x, z) - self.origin;
+
+        if local.cmplt(IVec2::ZERO).any() || local.cmpge(self.size * scale).any() {
+            return None;
+        }
+
+        // interpolate between the centers of the surrounding pixels
+        let position = ((local.as_dvec2() + 0.5) / scale as f64 - 0.5)
+            .clamp(dvec2(0.0, 0.0), (self.size - IVec2::ONE).as_dvec2());
+        let cell = position.floor();
+        let offset = position - cell;
+
+        let pixel = |x: f64, z: f64| {
+            let x = (x as i32).min(self.size.x - 1);
+            let z = (z as i32).min(self.size.y - 1);
+            self.heights[(z * self.size.x + x) as usize]
+        };
+
+        let north =
+            pixel(cell.x, cell.y) * (1.0 - offset.x) + pixel(cell.x + 1.0, cell.y) * offset.x;
+        let south = pixel(cell.x, cell.y + 1.0) * (1.0 - offset.x)
+            + pixel(cell.x + 1.0, cell.y + 1.0) * offset.x;
+
+        Some((north * (1.0 - offset.y) + south * offset.y).round() as usize)
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+
+use crate::config;
+
+/// The name of the config file the keybindings are loaded from.
+pub const KEYBINDINGS_FILE: &str = "keybindings.toml";
+
+/// Something the player does by pressing or holding a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    /// Jumps while walking, flies upwards while flying, and swims upwards while in water.
+    Jump,
+    Sprint,
+    Sneak,
+    /// Narrows the field of view while held.
+    Zoom,
+    FlyDown,
+    SwimDown,
+    FlyFast,
+    FlySlow,
+    ToggleFlight,
+    /// Releases the cursor, or captures it again.
+    ToggleFocus,
+    SwitchDimension,
+    TogglePreview,
+    Screenshot,
+    /// Held while taking a screenshot to take a high resolution one instead.
+    HighResolution,
+    ToggleWireframe,
+    ToggleChunkBorders,
+    ToggleRecording,
+    ToggleFullscreen,
+    /// Opens the console, releasing the cursor to type into it.
+    OpenConsole,
+}
+
+/// The keys bound to each action, loaded from `KEYBINDINGS_FILE`. Actions left out of the file
+/// keep their default keys.
+#[derive(Debug, Clone)]
+pub struct Keybindings {
+    /// The keys that perform each action. Any of them does.
+    bindings: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        use Action::*;
+        use KeyCode::*;
+
+        let bindings = [
+            (MoveForward, vec![KeyW]),
+            (MoveBackward, vec![KeyS]),
+            (MoveLeft, vec![KeyA]),
+            (MoveRight, vec![KeyD]),
+            (Jump, vec![Space]),
+            (Sprint, vec![ShiftLeft]),
+            (Sneak, vec![ControlLeft, KeyC]),
+            (Zoom, vec![KeyZ]),
+            (FlyDown, vec![ShiftLeft]),
+            (SwimDown, vec![ShiftLeft]),
+            (FlyFast, vec![ControlLeft]),
+            (FlySlow, vec![AltLeft]),
+            (ToggleFlight, vec![KeyF]),
+            (ToggleFocus, vec![Escape]),
+            (SwitchDimension, vec![Tab]),
+            (TogglePreview, vec![F6]),
+            (Screenshot, vec![F2]),
+            (HighResolution, vec![ShiftLeft, ShiftRight]),
+            (ToggleWireframe, vec![F3]),
+            (ToggleChunkBorders, vec![F4]),
+            (ToggleRecording, vec![F9]),
+            (ToggleFullscreen, vec![F11]),
+            (OpenConsole, vec![Slash]),
+        ];
+
+        Self {
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+}
+
+impl Keybindings {
+    /// Loads the keybindings from the config directory, replacing the default keys of the
+    /// actions listed in it.
+    pub fn load() -> anyhow::Result<Self> {
+        let mut keybindings = Self::default();
+        let bindings: HashMap<Action, Vec<KeyCode>> = config::load_or_default(KEYBINDINGS_FILE)?;
+
+        keybindings.bindings.extend(bindings);
+        Ok(keybindings)
+    }
+
+    /// Returns whether any key bound to the action is held.
+    pub fn is_held(&self, action: Action, keys_held: &HashSet<KeyCode>) -> bool {
+        self.bindings
+            .get(&action)
+            .is_some_and(|keys| keys.iter().any(|key| keys_held.contains(key)))
+    }
+
+    /// Returns the actions the key is bound to.
+    pub fn actions(&self, key: KeyCode) -> impl Iterator<Item = Action> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+}
@@ -0,0 +1,291 @@
+use glam::*;
+use wgpu::*;
+
+/// The width of each cluster of the light grid, in voxels.
+const CLUSTER_SIZE: i32 = 16;
+/// The number of clusters along each axis of the light grid, which is centered on the camera.
+/// Matches `LIGHT_GRID_SIZE` in `shader.wgsl`.
+const GRID_SIZE: i32 = 16;
+/// The number of clusters in the light grid.
+const CLUSTER_COUNT: usize = (GRID_SIZE * GRID_SIZE * GRID_SIZE) as usize;
+
+/// The number of lights, and of light indices, the buffers are created with.
+const INITIAL_LIGHT_CAPACITY: usize = 64;
+const INITIAL_INDEX_CAPACITY: usize = 1024;
+
+/// A light shining equally in every direction from a point, fading out to nothing at its
+/// radius.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    /// The position of the light in the world.
+    pub position: DVec3,
+    /// The color of the light (in linear space), scaled by its brightness.
+    pub color: Vec3,
+    /// The distance the light reaches, in voxels.
+    pub radius: f32,
+}
+
+/// A point light as laid out in the shader's light buffer.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightData {
+    /// The position of the light relative to the camera.
+    offset: Vec3,
+    radius: f32,
+    color: Vec3,
+    _padding: f32,
+}
+
+/// The position of the light grid, as laid out in the shader's uniform buffer.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightGridUniform {
+    /// The cluster (in world cluster coordinates) at the corner of the grid.
+    origin: IVec3,
+    /// The number of lights in the light buffer.
+    light_count: u32,
+}
+
+/// Sorts point lights into a grid of clusters around the camera for clustered forward shading,
+/// so each fragment only shades the lights that can reach its cluster, instead of every light
+/// in the world. The grid is rebuilt on the CPU every frame, and read by the terrain shader
+/// from storage buffers, so any number of lights can be drawn.
+pub struct LightGrid {
+    /// The layout of the bind group the grid is read through.
+    pub bind_group_layout: BindGroupLayout,
+    /// The bind group of the grid's buffers, replaced whenever one of them grows.
+    bind_group: BindGroup,
+
+    /// The uniform buffer holding the `LightGridUniform` of the current frame.
+    uniform_buffer: Buffer,
+    /// Every light, as `LightData`.
+    light_buffer: Buffer,
+    /// The range of `index_buffer` listing the lights of each cluster, as an offset and count.
+    cluster_buffer: Buffer,
+    /// The indices into `light_buffer` of the lights of every cluster, one after another.
+    index_buffer: Buffer,
+
+    /// The number of lights `light_buffer` can hold.
+    light_capacity: usize,
+    /// The number of indices `index_buffer` can hold.
+    index_capacity: usize,
+}
+
+impl LightGrid {
+    /// Creates the (empty) grid's buffers.
+    pub fn new(device: &Device) -> Self {
+        let storage = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Light Grid Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1),
+                storage(2),
+                storage(3),
+            ],
+        });
+
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Light Grid Uniform Buffer"),
+            size: size_of::<LightGridUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let cluster_buffer = Self::create_storage_buffer(
+            device,
+            "Light Grid Cluster Buffer",
+            CLUSTER_COUNT * size_of::<UVec2>(),
+        );
+
+        let light_capacity = INITIAL_LIGHT_CAPACITY;
+        let light_buffer = Self::create_storage_buffer(
+            device,
+            "Light Grid Light Buffer",
+            light_capacity * size_of::<LightData>(),
+        );
+
+        let index_capacity = INITIAL_INDEX_CAPACITY;
+        let index_buffer = Self::create_storage_buffer(
+            device,
+            "Light Grid Index Buffer",
+            index_capacity * size_of::<u32>(),
+        );
+
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            [
+                &uniform_buffer,
+                &light_buffer,
+                &cluster_buffer,
+                &index_buffer,
+            ],
+        );
+
+        Self {
+            bind_group_layout,
+            bind_group,
+            uniform_buffer,
+            light_buffer,
+            cluster_buffer,
+            index_buffer,
+            light_capacity,
+            index_capacity,
+        }
+    }
+
+    fn create_storage_buffer(device: &Device, label: &str, size: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size: size as BufferAddress,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        buffers: [&Buffer; 4],
+    ) -> BindGroup {
+        let entries = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Light Grid Bind Group"),
+            layout,
+            entries: &entries,
+        })
+    }
+
+    /// Sorts the given lights into the clusters around the camera at `eye`, and uploads the
+    /// grid. Lights outside the grid are left out.
+    pub fn update(&mut self, device: &Device, queue: &Queue, lights: &[PointLight], eye: DVec3) {
+        let origin = (eye / CLUSTER_SIZE as f64).floor().as_ivec3() - IVec3::splat(GRID_SIZE / 2);
+        let grid_min = (origin * CLUSTER_SIZE).as_dvec3();
+
+        let mut cluster_lights = vec![Vec::new(); CLUSTER_COUNT];
+        let mut light_data = Vec::new();
+
+        for light in lights {
+            // the clusters overlapped by the light's bounding box
+            let radius = light.radius as f64;
+            let min = ((light.position - radius - grid_min) / CLUSTER_SIZE as f64)
+                .floor()
+                .as_ivec3()
+                .max(IVec3::ZERO);
+            let max = ((light.position + radius - grid_min) / CLUSTER_SIZE as f64)
+                .floor()
+                .as_ivec3()
+                .min(IVec3::splat(GRID_SIZE - 1));
+
+            if min.cmpgt(max).any() {
+                continue;
+            }
+
+            let index = light_data.len() as u32;
+            light_data.push(LightData {
+                offset: (light.position - eye).as_vec3(),
+                radius: light.radius,
+                color: light.color,
+                _padding: 0.0,
+            });
+
+            for z in min.z..=max.z {
+                for y in min.y..=max.y {
+                    for x in min.x..=max.x {
+                        let cluster = (x + (y + z * GRID_SIZE) * GRID_SIZE) as usize;
+                        cluster_lights[cluster].push(index);
+                    }
+                }
+            }
+        }
+
+        let mut clusters = Vec::with_capacity(CLUSTER_COUNT);
+        let mut indices = Vec::new();
+
+        for lights in &cluster_lights {
+            clusters.push(uvec2(indices.len() as u32, lights.len() as u32));
+            indices.extend_from_slice(lights);
+        }
+
+        self.reserve(device, light_data.len(), indices.len());
+
+        let uniform = LightGridUniform {
+            origin,
+            light_count: light_data.len() as u32,
+        };
+
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+        queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&light_data));
+        queue.write_buffer(&self.cluster_buffer, 0, bytemuck::cast_slice(&clusters));
+        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&indices));
+    }
+
+    /// Grows the light and index buffers to hold at least the given number of each.
+    fn reserve(&mut self, device: &Device, lights: usize, indices: usize) {
+        if lights <= self.light_capacity && indices <= self.index_capacity {
+            return;
+        }
+
+        if lights > self.light_capacity {
+            self.light_capacity = lights.next_power_of_two();
+            self.light_buffer = Self::create_storage_buffer(
+                device,
+                "Light Grid Light Buffer",
+                self.light_capacity * size_of::<LightData>(),
+            );
+        }
+
+        if indices > self.index_capacity {
+            self.index_capacity = indices.next_power_of_two();
+            self.index_buffer = Self::create_storage_buffer(
+                device,
+                "Light Grid Index Buffer",
+                self.index_capacity * size_of::<u32>(),
+            );
+        }
+
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            [
+                &self.uniform_buffer,
+                &self.light_buffer,
+                &self.cluster_buffer,
+                &self.index_buffer,
+            ],
+        );
+    }
+
+    /// Returns the bind group the grid is read through.
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+}
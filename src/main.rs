@@ -2,29 +2,127 @@
 
 use std::sync::Arc;
 
+use adapter::{GraphicsConfig, GRAPHICS_CONFIG_FILE};
 use app::App;
-use winit::{dpi::LogicalSize, event_loop::EventLoop, window::WindowBuilder};
+use clap::Parser;
+use cli::{Args, Command};
+use dimension::{Dimension, WorldType};
+use heightmap_import::HeightmapImport;
+use storage::{Compression, WorldConfig, WorldInfo, WORLD_CONFIG_FILE};
+use window_state::{WindowState, WINDOW_STATE_FILE};
+use winit::event_loop::EventLoop;
 
+mod adapter;
+mod anvil;
 mod app;
 mod asset_loader;
+mod biome;
+mod block_entity;
 mod camera;
 mod chunk;
 mod chunk_manager;
+mod cli;
+mod config;
+mod console;
+mod debug_lines;
+mod dimension;
 mod egui_renderer;
+mod environment;
+mod erosion;
+mod game_mode;
+mod generator;
+mod god_rays;
+mod gpu_cull;
+mod gpu_memory;
+mod gpu_timer;
+mod heightmap_import;
+mod keybindings;
+mod lighting;
+mod mesh_pool;
 mod mesher;
+mod metrics;
+mod mipmap;
 mod model;
+mod occlusion;
+mod outline;
+mod physics;
+mod pipeline_cache;
+mod post;
+mod pregen;
+mod preprocessor;
+mod preview;
+mod raycast;
+mod recording;
+mod render_graph;
 mod renderer;
+mod row_noise;
+mod screenshot;
+mod sky;
+mod ssao;
+mod storage;
+mod structure;
 mod texture;
+mod visibility;
+mod window_state;
 
 #[pollster::main]
 async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let mut graphics: GraphicsConfig = config::load_or_default(GRAPHICS_CONFIG_FILE)?;
+    graphics.backend = args.backend.or(graphics.backend);
+    graphics.power_preference = args.power_preference.unwrap_or(graphics.power_preference);
+    graphics.adapter = args.gpu.or(graphics.adapter);
+
+    if args.list_gpus {
+        adapter::list_adapters(&graphics);
+        return Ok(());
+    }
+
+    let mut window_state: WindowState = config::load_or_default(WINDOW_STATE_FILE)?;
+    window_state.world = args.world.unwrap_or(window_state.world);
+
+    let world_config: WorldConfig = config::load_or_default(WORLD_CONFIG_FILE)?;
+    let world = WorldInfo::open_or_create(
+        &window_state.world,
+        args.seed.or(world_config.seed).map(|seed| seed.value()),
+        args.heightmap
+            .map(|path| WorldType::Heightmap(HeightmapImport::new(path)))
+            .or(args.world_type)
+            .or(world_config.world_type),
+    )?;
+
+    if let Some(Command::ImportAnvil {
+        region_directory,
+        y_offset,
+    }) = &args.command
+    {
+        return anvil::import(
+            region_directory,
+            &window_state.world,
+            Dimension::Overworld,
+            Compression::default(),
+            *y_offset,
+        );
+    }
+
+    if let Some(radius) = args.pregen {
+        return pregen::pregenerate(
+            &window_state.world,
+            Dimension::Overworld,
+            &world,
+            Compression::default(),
+            radius,
+        );
+    }
+
     let event_loop = EventLoop::new()?;
-    let window = WindowBuilder::new()
+    let window = window_state
+        .window_builder()
         .with_title("Pig Engine")
-        .with_inner_size(LogicalSize::new(1920, 1080))
         .build(&event_loop)?;
 
-    let mut app = App::new(Arc::new(window)).await?;
+    let mut app = App::new(Arc::new(window), graphics, world, window_state).await?;
 
     event_loop.run(|event, elwt| app.update(event, elwt).unwrap())?;
 
@@ -0,0 +1,252 @@
+use std::{
+    ops::Range,
+    sync::{Mutex, MutexGuard},
+};
+
+use anyhow::bail;
+use wgpu::*;
+
+use crate::{
+    gpu_memory::{self, MemoryCategory, Tracked},
+    model::MeshVertex,
+};
+
+/// The number of vertices the shared vertex buffer is created with.
+const INITIAL_VERTEX_CAPACITY: u32 = 1 << 20;
+/// The number of indices the shared index buffer is created with. Every face has 4 vertices and
+/// 6 indices.
+const INITIAL_INDEX_CAPACITY: u32 = INITIAL_VERTEX_CAPACITY / 4 * 6;
+
+/// Large vertex and index buffers shared by every chunk mesh, so that all of them can be drawn
+/// without switching buffers in between. Each mesh owns a range of each buffer until it is
+/// freed, and the buffers grow when a mesh doesn't fit.
+#[derive(Debug)]
+pub struct MeshPool {
+    /// The buffer holding the vertices of every mesh.
+    vertices: Mutex<PoolBuffer>,
+    /// The buffer holding the indices of every mesh, relative to the start of the mesh's
+    /// vertices.
+    indices: Mutex<PoolBuffer>,
+}
+
+/// A buffer split into ranges of elements owned by meshes.
+#[derive(Debug)]
+pub struct PoolBuffer {
+    /// The buffer the elements are stored in.
+    pub buffer: Tracked<Buffer>,
+    /// The size of each element, in bytes.
+    element_size: u32,
+    /// The number of elements `buffer` can hold.
+    capacity: u32,
+    /// The ranges of elements not owned by any mesh, sorted and never adjacent to each other.
+    free: Vec<Range<u32>>,
+    /// The name and usage the buffer is created with.
+    label: &'static str,
+    usage: BufferUsages,
+    /// The category the buffer's memory is counted towards.
+    category: MemoryCategory,
+}
+
+impl MeshPool {
+    /// Creates the shared vertex and index buffers.
+    pub fn new(device: &Device) -> Self {
+        Self {
+            vertices: Mutex::new(PoolBuffer::new(
+                device,
+                "Mesh Pool Vertex Buffer",
+                BufferUsages::VERTEX,
+                MemoryCategory::Vertices,
+                size_of::<MeshVertex>() as u32,
+                INITIAL_VERTEX_CAPACITY,
+            )),
+            indices: Mutex::new(PoolBuffer::new(
+                device,
+                "Mesh Pool Index Buffer",
+                BufferUsages::INDEX,
+                MemoryCategory::Indices,
+                size_of::<u32>() as u32,
+                INITIAL_INDEX_CAPACITY,
+            )),
+        }
+    }
+
+    /// Uploads the given vertices and indices into the pool, returning the ranges of the vertex
+    /// and index buffers they were written to.
+    pub fn allocate(
+        &self,
+        vertices: &[MeshVertex],
+        indices: &[u32],
+        device: &Device,
+        queue: &Queue,
+    ) -> anyhow::Result<(Range<u32>, Range<u32>)> {
+        let vertex_range =
+            self.vertices
+                .lock()
+                .unwrap()
+                .write(bytemuck::cast_slice(vertices), device, queue)?;
+
+        let index_range =
+            self.indices
+                .lock()
+                .unwrap()
+                .write(bytemuck::cast_slice(indices), device, queue);
+
+        match index_range {
+            Ok(index_range) => Ok((vertex_range, index_range)),
+            Err(error) => {
+                self.vertices.lock().unwrap().free(vertex_range);
+                Err(error)
+            }
+        }
+    }
+
+    /// Returns the given ranges of the vertex and index buffers to the pool.
+    pub fn free(&self, vertices: Range<u32>, indices: Range<u32>) {
+        self.vertices.lock().unwrap().free(vertices);
+        self.indices.lock().unwrap().free(indices);
+    }
+
+    /// Locks the shared vertex and index buffers, so they can be bound while drawing.
+    pub fn buffers(&self) -> (MutexGuard<'_, PoolBuffer>, MutexGuard<'_, PoolBuffer>) {
+        (self.vertices.lock().unwrap(), self.indices.lock().unwrap())
+    }
+}
+
+impl PoolBuffer {
+    /// Creates an empty buffer able to hold `capacity` elements of the given size.
+    fn new(
+        device: &Device,
+        label: &'static str,
+        usage: BufferUsages,
+        category: MemoryCategory,
+        element_size: u32,
+        capacity: u32,
+    ) -> Self {
+        let usage = usage | BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
+
+        Self {
+            buffer: Self::create_buffer(device, label, usage, category, element_size, capacity),
+            element_size,
+            capacity,
+            free: std::iter::once(0..capacity).collect(),
+            label,
+            usage,
+            category,
+        }
+    }
+
+    fn create_buffer(
+        device: &Device,
+        label: &'static str,
+        usage: BufferUsages,
+        category: MemoryCategory,
+        element_size: u32,
+        capacity: u32,
+    ) -> Tracked<Buffer> {
+        gpu_memory::create_buffer(
+            device,
+            category,
+            &BufferDescriptor {
+                label: Some(label),
+                size: capacity as BufferAddress * element_size as BufferAddress,
+                usage,
+                mapped_at_creation: false,
+            },
+        )
+    }
+
+    /// Writes the given elements into the first free range large enough to hold them, growing
+    /// the buffer if there is none, and returns the range of elements written to.
+    fn write(&mut self, data: &[u8], device: &Device, queue: &Queue) -> anyhow::Result<Range<u32>> {
+        let count = (data.len() / self.element_size as usize) as u32;
+
+        let range = match self.allocate(count) {
+            Some(range) => range,
+            None => {
+                self.grow(count, device, queue)?;
+                self.allocate(count)
+                    .expect("grown buffer should have room for the elements")
+            }
+        };
+
+        let offset = range.start as BufferAddress * self.element_size as BufferAddress;
+        queue.write_buffer(&self.buffer, offset, data);
+
+        Ok(range)
+    }
+
+    /// Takes `count` elements from the first free range large enough to hold them.
+    fn allocate(&mut self, count: u32) -> Option<Range<u32>> {
+        let index = self
+            .free
+            .iter()
+            .position(|range| range.len() as u32 >= count)?;
+
+        let free = &mut self.free[index];
+        let range = free.start..free.start + count;
+        free.start += count;
+
+        if free.start == free.end {
+            self.free.remove(index);
+        }
+
+        Some(range)
+    }
+
+    /// Returns the given range of elements, merging it with the free ranges around it.
+    fn free(&mut self, range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+
+        let index = self.free.partition_point(|free| free.start < range.start);
+        self.free.insert(index, range);
+
+        // merge with the next range, then the previous one
+        if index + 1 < self.free.len() && self.free[index].end == self.free[index + 1].start {
+            self.free[index].end = self.free.remove(index + 1).end;
+        }
+
+        if index > 0 && self.free[index - 1].end == self.free[index].start {
+            self.free[index - 1].end = self.free.remove(index).end;
+        }
+    }
+
+    /// Grows the buffer (at least doubling it) until `count` more elements fit at its end,
+    /// copying over the existing elements.
+    fn grow(&mut self, count: u32, device: &Device, queue: &Queue) -> anyhow::Result<()> {
+        let capacity = (self.capacity as u64 * 2).max(self.capacity as u64 + count as u64);
+        let max_capacity = device.limits().max_buffer_size / self.element_size as u64;
+
+        if self.capacity as u64 + count as u64 > max_capacity {
+            bail!(
+                "{} is full, and cannot grow past {max_capacity} elements",
+                self.label
+            );
+        }
+
+        let capacity = capacity.min(max_capacity).min(u32::MAX as u64) as u32;
+        let buffer = Self::create_buffer(
+            device,
+            self.label,
+            self.usage,
+            self.category,
+            self.element_size,
+            capacity,
+        );
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Mesh Pool Grow Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, self.buffer.size());
+        queue.submit(std::iter::once(encoder.finish()));
+
+        self.buffer.destroy();
+        self.buffer = buffer;
+
+        self.free(self.capacity..capacity);
+        self.capacity = capacity;
+
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
     asset_loader::{get_texture_index, Face},
     chunk::*,
+    generator::ChunkGenerator,
     model::MeshVertex,
 };
 
@@ -17,6 +18,32 @@ const FACE_NORMALS: [(Face, [isize; 3]); 6] = [
     (Face::Side, [0, 0, -1]), // back
 ];
 
+/// The vertices and indices of a mesh that has been built on the CPU.
+pub type MeshData = (Vec<MeshVertex>, Vec<u32>);
+
+/// The render pass a mesh is drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeshLayer {
+    /// Fully opaque voxels, drawn first.
+    Opaque,
+    /// Voxels that can be seen through (water), drawn after every opaque mesh with blending.
+    Transparent,
+}
+
+impl MeshLayer {
+    /// Every layer, in the order they are drawn.
+    pub const ALL: [MeshLayer; 2] = [Self::Opaque, Self::Transparent];
+
+    /// Returns the layer the faces of the given voxel are drawn in.
+    pub fn of(voxel: Voxel) -> Self {
+        if voxel.is_opaque() {
+            Self::Opaque
+        } else {
+            Self::Transparent
+        }
+    }
+}
+
 const FACE_INDICES: [u32; 6] = [0, 1, 2, 2, 3, 0];
 
 const FACE_VERTICES: [[glam::Vec3; 4]; 6] = [
@@ -140,56 +167,101 @@ pub struct ChunkMesher<'a> {
     /// The chunk whose mesh is being built.
     chunk: &'a crate::chunk::Chunk,
     /// A list of the chunks surrounding the chunk.
-    chunks: &'a HashMap<glam::IVec2, crate::chunk::Chunk>,
+    chunks: &'a HashMap<glam::IVec2, Arc<crate::chunk::Chunk>>,
+    /// The packed color grass and leaves are tinted with in each column of the chunk, indexed by
+    /// `z * CHUNK_WIDTH + x`.
+    foliage_tints: Vec<u32>,
 
-    /// The vertices generated so far.
-    vertices: Vec<MeshVertex>,
-    /// The indices generated so far.
-    indices: Vec<u32>,
+    /// The vertices and indices generated so far, for each `MeshLayer`.
+    layers: [MeshData; MeshLayer::ALL.len()],
 }
 
 impl<'c> ChunkMesher<'c> {
-    /// Creates a new chunk mesh builder given a chunk.
-    pub fn new(chunks: &'c HashMap<IVec2, Chunk>, chunk: IVec2) -> Self {
+    /// Creates a new chunk mesh builder given a chunk, tinting its foliage by the climate the
+    /// generator gives each of its columns.
+    pub fn new(
+        chunks: &'c HashMap<IVec2, Arc<Chunk>>,
+        chunk: IVec2,
+        generator: &dyn ChunkGenerator,
+    ) -> Self {
+        let chunk = chunks
+            .get(&chunk)
+            .expect("cannot build mesh for unloaded chunk");
+
+        let foliage_tints = (0..CHUNK_WIDTH)
+            .flat_map(|z| (0..CHUNK_WIDTH).map(move |x| (x, z)))
+            .map(|(x, z)| {
+                let position = chunk.world_position([x, 0, z]);
+                let height = chunk.surface_height(x, z).unwrap_or(SEA_LEVEL);
+                let tint = generator.foliage_tint(position.x, position.z, height);
+
+                let [r, g, b] = (tint.clamp(Vec3::ZERO, Vec3::ONE) * 255.0)
+                    .round()
+                    .to_array()
+                    .map(|channel| channel as u8);
+
+                u32::from_le_bytes([r, g, b, u8::MAX])
+            })
+            .collect();
+
         Self {
-            chunk: chunks
-                .get(&chunk)
-                .expect("cannot build mesh for unloaded chunk"),
+            chunk,
             chunks,
-            vertices: Vec::new(),
-            indices: Vec::new(),
+            foliage_tints,
+            layers: Default::default(),
         }
     }
 
-    /// Builds the vertices and indices for the chunk.
-    pub fn build(mut self) -> (Vec<MeshVertex>, Vec<u32>) {
-        for y in 0..CHUNK_HEIGHT {
-            for z in 0..CHUNK_WIDTH {
-                for x in 0..CHUNK_WIDTH {
-                    self.add_block([x, y, z]);
+    /// Builds the vertices and indices for each section and layer of the chunk independently.
+    /// Sections that are empty, or layers that produce no visible faces, are skipped.
+    pub fn build(mut self) -> Vec<(usize, MeshLayer, MeshData)> {
+        let mut sections = Vec::new();
+
+        for section in 0..SECTIONS_PER_CHUNK {
+            if self.chunk.is_section_empty(section) {
+                continue;
+            }
+
+            let base_y = section * SECTION_HEIGHT;
+
+            for y in base_y..base_y + SECTION_HEIGHT {
+                for z in 0..CHUNK_WIDTH {
+                    for x in 0..CHUNK_WIDTH {
+                        self.add_block([x, y, z]);
+                    }
+                }
+            }
+
+            for layer in MeshLayer::ALL {
+                let mesh = std::mem::take(&mut self.layers[layer as usize]);
+
+                if !mesh.1.is_empty() {
+                    sections.push((section, layer, mesh));
                 }
             }
         }
 
-        (self.vertices, self.indices)
+        sections
     }
 
-    /// Returns whether the given voxel position (in world space) is solid.
-    fn is_solid(&self, [x, y, z]: [isize; 3]) -> bool {
+    /// Returns the voxel at the given position (in world space), or None if it is below the
+    /// world or in a chunk that hasn't been loaded.
+    fn voxel_at(&self, [x, y, z]: [isize; 3]) -> Option<Voxel> {
         let position = ivec2(
             x.div_euclid(CHUNK_WIDTH as isize) as i32,
             z.div_euclid(CHUNK_WIDTH as isize) as i32,
         );
 
-        let Some(chunk) = self.chunks.get(&position) else {
-            // the neighbor chunk hasn't been loaded yet.
-            return false;
-        };
+        // the neighbor chunk may not have been loaded yet.
+        let chunk = self.chunks.get(&position)?;
 
-        chunk.is_block_full(match chunk.get_local_position([x, y, z]) {
-            Some(pos) => pos,
-            None => return false,
-        })
+        Some(chunk.get_voxel(chunk.get_local_position([x, y, z])?))
+    }
+
+    /// Returns whether the given voxel position (in world space) is solid.
+    fn is_solid(&self, position: [isize; 3]) -> bool {
+        self.voxel_at(position)
+            .is_some_and(|voxel| voxel.is_opaque())
     }
 
     /// Gets the ambient occlusion values for the given normal direction and position. The order of
@@ -224,13 +296,17 @@ impl<'c> ChunkMesher<'c> {
         }
 
         let [x, y, z] = position;
-        let voxel = self.chunk.voxels[y][z][x];
+        let voxel = self.chunk.get_voxel(position);
+        let layer = MeshLayer::of(voxel);
 
         let local_position = vec3(x as f32, y as f32, z as f32);
-        let chunk_offset = self.chunk.position.extend(0).xzy().as_vec3() * CHUNK_WIDTH as f32;
 
         for (normal_index, (face, normal)) in FACE_NORMALS.iter().enumerate() {
-            if self.is_solid(self.chunk.offset_local_in_direction(position, *normal)) {
+            let neighbor = self.voxel_at(self.chunk.offset_local_in_direction(position, *normal));
+
+            // faces are hidden behind opaque voxels, and between voxels of the same kind (so
+            // bodies of water only have a surface)
+            if neighbor.is_some_and(|neighbor| neighbor.is_opaque() || neighbor == voxel) {
                 continue;
             }
 
@@ -240,28 +316,56 @@ impl<'c> ChunkMesher<'c> {
             });
 
             let ao_values = self.calculate_ambient_occlusion(position, normal_index);
+            let (vertices, indices) = &mut self.layers[layer as usize];
+            let offset = vertices.len() as u32;
 
             for (voxel_center_offset, ambient_occlusion) in
                 FACE_VERTICES[normal_index].iter().zip(ao_values)
             {
-                let world_position = voxel_center_offset + local_position + chunk_offset;
+                let position = voxel_center_offset + local_position;
                 let texture_ambient = ((texture_index as u32) << 16) | ambient_occlusion;
 
-                self.vertices.push(MeshVertex {
-                    pos: world_position,
+                vertices.push(MeshVertex {
+                    pos: position,
                     normal,
                     texture_ambient,
+                    tint: self.foliage_tints[z * CHUNK_WIDTH + x],
                 });
             }
 
-            let offset = self
-                .indices
-                .get(self.indices.len().saturating_sub(2))
-                .copied()
-                .map(|i| i + 1)
-                .unwrap_or(0);
+            indices.extend(FACE_INDICES.map(|i| i + offset));
+        }
+    }
+}
+
+/// Builds the mesh of a single voxel on its own, centered on its origin and scaled by `scale`,
+/// with every face showing and none of them occluded. `tint` is packed like
+/// `MeshVertex::tint`.
+pub fn single_voxel_mesh(voxel: Voxel, scale: f32, tint: u32) -> MeshData {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for (normal_index, (face, normal)) in FACE_NORMALS.iter().enumerate() {
+        let Some(texture_index) = get_texture_index(&voxel, face) else {
+            continue;
+        };
 
-            self.indices.extend(FACE_INDICES.map(|i| i + offset));
+        let normal = Vec3::from_array(normal.map(|n| n as f32));
+        // an ambient occlusion value of 3 is fully lit
+        let texture_ambient = ((texture_index as u32) << 16) | 3;
+        let offset = vertices.len() as u32;
+
+        for corner in FACE_VERTICES[normal_index] {
+            vertices.push(MeshVertex {
+                pos: corner * scale,
+                normal,
+                texture_ambient,
+                tint,
+            });
         }
+
+        indices.extend(FACE_INDICES.map(|i| i + offset));
     }
+
+    (vertices, indices)
 }
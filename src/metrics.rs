@@ -0,0 +1,77 @@
+use std::{collections::VecDeque, time::Duration};
+
+/// The number of recent durations kept per stage to compute statistics from.
+const SAMPLE_WINDOW: usize = 256;
+
+/// Timing and throughput statistics of a single stage of the chunk pipeline.
+#[derive(Debug, Default, Clone)]
+pub struct StageMetrics {
+    /// The durations of the most recently completed items, oldest first.
+    samples: VecDeque<Duration>,
+    /// The number of items completed so far this frame.
+    completed_this_frame: usize,
+    /// The number of items completed during the previous frame.
+    pub completed_last_frame: usize,
+    /// The number of items waiting to be started.
+    pub queued: usize,
+    /// The number of items currently being worked on.
+    pub in_flight: usize,
+}
+
+impl StageMetrics {
+    /// Records that an item finished after taking the given duration.
+    pub fn record(&mut self, duration: Duration) {
+        if self.samples.len() == SAMPLE_WINDOW {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(duration);
+        self.completed_this_frame += 1;
+    }
+
+    /// Marks the end of a frame, making this frame's item count available.
+    pub fn end_frame(&mut self) {
+        self.completed_last_frame = std::mem::take(&mut self.completed_this_frame);
+    }
+
+    /// Returns the average duration of the recent items.
+    pub fn average(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    /// Returns the duration that the given fraction (in `[0, 1]`) of recent items completed
+    /// within.
+    pub fn percentile(&self, fraction: f32) -> Duration {
+        let mut sorted = self.samples.iter().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+
+        let index = ((sorted.len() as f32 * fraction.clamp(0.0, 1.0)) as usize)
+            .min(sorted.len().saturating_sub(1));
+
+        sorted.get(index).copied().unwrap_or_default()
+    }
+}
+
+/// Statistics of every stage of the chunk pipeline.
+#[derive(Debug, Default, Clone)]
+pub struct PipelineMetrics {
+    /// Generating (or loading) the voxel data of chunks.
+    pub generation: StageMetrics,
+    /// Building the meshes of chunks.
+    pub meshing: StageMetrics,
+    /// Uploading built meshes to the GPU.
+    pub upload: StageMetrics,
+}
+
+impl PipelineMetrics {
+    /// Marks the end of a frame for every stage.
+    pub fn end_frame(&mut self) {
+        self.generation.end_frame();
+        self.meshing.end_frame();
+        self.upload.end_frame();
+    }
+}
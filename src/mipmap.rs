@@ -0,0 +1,109 @@
+use wgpu::*;
+
+/// Returns the number of levels in a full mip chain of a texture of the given size, down to a
+/// single texel.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    width.max(height).max(1).ilog2() + 1
+}
+
+/// Fills every level of the texture's mip chain past the first (in each of its array layers)
+/// by repeatedly halving the level above it. Each level is drawn with a render pass rather than
+/// a compute shader, as the sRGB formats textures are usually stored in can't be written to as
+/// storage textures. The texture must be usable as a render attachment, of the given format.
+pub fn generate_mipmaps(
+    device: &Device,
+    queue: &Queue,
+    texture: &wgpu::Texture,
+    format: TextureFormat,
+) {
+    let shader = device.create_shader_module(include_wgsl!("mipmap.wgsl"));
+
+    let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Mipmap Pipeline"),
+        layout: None,
+        vertex: VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+            compilation_options: PipelineCompilationOptions::default(),
+        },
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(format.into())],
+            compilation_options: PipelineCompilationOptions::default(),
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+    });
+
+    let sampler = device.create_sampler(&SamplerDescriptor {
+        label: Some("Mipmap Sampler"),
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..Default::default()
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("Mipmap Encoder"),
+    });
+
+    let view = |level, layer| {
+        texture.create_view(&TextureViewDescriptor {
+            label: Some("Mipmap View"),
+            dimension: Some(TextureViewDimension::D2),
+            base_mip_level: level,
+            mip_level_count: Some(1),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })
+    };
+
+    for layer in 0..texture.depth_or_array_layers() {
+        encoder.push_debug_group(&format!("layer {layer}"));
+
+        for level in 1..texture.mip_level_count() {
+            let source = view(level - 1, layer);
+            let target = view(level, layer);
+
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Mipmap Bind Group"),
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&source),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&sampler),
+                    },
+                ],
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Mipmap Pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+
+            render_pass.set_pipeline(&pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        encoder.pop_debug_group();
+    }
+
+    queue.submit(std::iter::once(encoder.finish()));
+}
@@ -0,0 +1,26 @@
+// The level of the texture above the one being drawn.
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) uv: vec2<f32>,
+}
+
+// Covers the level with a single triangle.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_id: u32) -> VertexOutput {
+	var out: VertexOutput;
+
+	let uv = vec2<f32>(f32((vertex_id << 1u) & 2u), f32(vertex_id & 2u));
+	out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+	out.uv = uv;
+
+	return out;
+}
+
+// Averages the texels of the level above covering the pixel, by sampling between them.
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+	return textureSampleLevel(source, source_sampler, input.uv, 0.0);
+}
@@ -1,55 +1,234 @@
-use crate::renderer::Render;
-use wgpu::{util::*, *};
+use std::{ops::Range, sync::Arc};
+
+use crate::{mesh_pool::MeshPool, renderer::Render};
+use wgpu::*;
 
 /// A vertex in a mesh sent to the GPU.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct MeshVertex {
-    /// The 3d position of the vertex.
+    /// The 3d position of the vertex, relative to the mesh's origin.
     pub pos: glam::Vec3,
     /// The normal vector of the vertex.
     pub normal: glam::Vec3,
     /// The first sixteen bits are an index into which texture layer to use, then the latter 16
     /// bits represent the ambient occlusion value for this vertex.
     pub texture_ambient: u32,
+    /// The color the texture is tinted with (when its material is tinted), as packed RGBA8.
+    pub tint: u32,
 }
 
 /// A mesh consists of a set of vertices connected by edges in triangles
-/// (the indices).
+/// (the indices). The vertices and indices are stored in a `MeshPool`, and are returned to it
+/// once the mesh is dropped.
 #[derive(Debug)]
 pub struct Mesh {
-    /// The vertices uploaded to the gpu.
-    pub vertex_buffer: wgpu::Buffer,
-    /// The indices uploaded to the gpu. Stored as a list of `u32`s.
-    pub index_buffer: wgpu::Buffer,
+    /// The pool the mesh's vertices and indices are stored in.
+    pool: Arc<MeshPool>,
+    /// The range of the pool's vertex buffer holding the mesh's vertices.
+    pub vertices: Range<u32>,
+    /// The range of the pool's index buffer holding the mesh's indices, which are relative to
+    /// the start of `vertices`.
+    pub indices: Range<u32>,
+
+    /// The world position the mesh's vertices are relative to.
+    pub origin: glam::IVec3,
+}
 
-    /// The number of vertices present in the buffer.
-    pub count: u32,
+/// Per-instance data of a mesh being drawn, sent to the GPU alongside its vertices.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshInstance {
+    /// The position of the mesh's origin relative to the camera. Keeping this small (rather than
+    /// a world position) avoids the precision loss of large `f32` coordinates.
+    pub offset: glam::Vec3,
+}
+
+/// The transform of one instance of a model, sent to the GPU alongside the vertices of the mesh
+/// it is drawn with.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ModelInstance {
+    /// Transforms the mesh's vertices into positions relative to the camera, for the same reason
+    /// as `MeshInstance::offset`.
+    pub model: glam::Mat4,
+}
+
+/// A buffer of model instances, growing when more are written than it can hold.
+#[derive(Debug)]
+pub struct InstanceBuffer {
+    /// The buffer the instances are stored in.
+    buffer: Buffer,
+    /// The number of instances `buffer` can hold.
+    capacity: usize,
+    /// The number of instances last written.
+    count: u32,
+}
+
+/// A mesh drawn many times over in a single call, once for every instance in its buffer. Used
+/// for models repeated around the world, rather than chunk terrain.
+#[derive(Debug)]
+pub struct InstancedMesh {
+    /// The mesh drawn for every instance, whose vertices are relative to the model's origin.
+    pub mesh: Mesh,
+    /// Where each copy of the mesh is drawn.
+    pub instances: InstanceBuffer,
 }
 
 impl Mesh {
-    // Creates a new mesh and uploads the given vertex and index data to the GPU.
-    pub fn new(vertices: &[MeshVertex], indices: &[u32], device: &Device) -> Self {
-        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(vertices),
-            usage: BufferUsages::VERTEX,
-        });
-
-        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(indices),
-            usage: BufferUsages::INDEX,
-        });
-
-        let count = indices.len() as u32;
+    // Creates a new mesh and uploads the given vertex and index data into the pool. The
+    // vertices are relative to `origin`.
+    pub fn new(
+        vertices: &[MeshVertex],
+        indices: &[u32],
+        origin: glam::IVec3,
+        pool: &Arc<MeshPool>,
+        device: &Device,
+        queue: &Queue,
+    ) -> anyhow::Result<Self> {
+        let (vertices, indices) = pool.allocate(vertices, indices, device, queue)?;
+
+        Ok(Self {
+            pool: Arc::clone(pool),
+            vertices,
+            indices,
+            origin,
+        })
+    }
+
+    /// Returns the arguments to draw the mesh's instances in the given range with, from the
+    /// pool's buffers.
+    pub fn draw_args(&self, instances: Range<u32>) -> util::DrawIndexedIndirectArgs {
+        util::DrawIndexedIndirectArgs {
+            index_count: self.indices.len() as u32,
+            instance_count: instances.len() as u32,
+            first_index: self.indices.start,
+            base_vertex: self.vertices.start as i32,
+            first_instance: instances.start,
+        }
+    }
+
+    /// Returns the offset of this mesh's origin from the given eye position, computed in integer
+    /// space so it stays precise arbitrarily far from the world origin.
+    pub fn offset_from(&self, eye: glam::DVec3) -> glam::Vec3 {
+        offset_from(self.origin, eye)
+    }
+}
+
+impl Drop for Mesh {
+    /// Returns the mesh's vertices and indices to the pool.
+    fn drop(&mut self) {
+        self.pool.free(self.vertices.clone(), self.indices.clone());
+    }
+}
+
+impl ModelInstance {
+    /// Creates an instance with its origin at the given world position, uniformly scaled by
+    /// `scale`, as seen from a camera at `eye`.
+    pub fn new(position: glam::IVec3, scale: f32, eye: glam::DVec3) -> Self {
+        Self {
+            model: glam::Mat4::from_scale_rotation_translation(
+                glam::Vec3::splat(scale),
+                glam::Quat::IDENTITY,
+                offset_from(position, eye),
+            ),
+        }
+    }
+
+    /// The vertex attributes of how the data is structured, one for each column of the matrix.
+    const ATTRIBS: &'static [VertexAttribute] = &vertex_attr_array![
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32x4
+    ];
+
+    /// Returns the wgpu vertex buffer layout of how each instance is interpreted.
+    pub fn desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: Self::ATTRIBS,
+        }
+    }
+}
 
+impl InstanceBuffer {
+    /// The number of instances a buffer is created with.
+    const INITIAL_CAPACITY: usize = 16;
+
+    /// Creates an empty buffer of instances.
+    pub fn new(device: &Device) -> Self {
         Self {
-            vertex_buffer,
-            index_buffer,
-            count,
+            buffer: Self::create_buffer(device, Self::INITIAL_CAPACITY),
+            capacity: Self::INITIAL_CAPACITY,
+            count: 0,
         }
     }
+
+    /// Creates a buffer holding the given number of instances.
+    fn create_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Model Instance Buffer"),
+            size: (capacity * size_of::<ModelInstance>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Replaces the instances in the buffer with the given ones, growing it if they do not fit.
+    pub fn write(&mut self, device: &Device, queue: &Queue, instances: &[ModelInstance]) {
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(instances));
+        self.count = instances.len() as u32;
+    }
+
+    /// Returns the number of instances last written.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+impl InstancedMesh {
+    /// Uploads the given vertex and index data into the pool as a mesh with no instances yet.
+    pub fn new(
+        vertices: &[MeshVertex],
+        indices: &[u32],
+        pool: &Arc<MeshPool>,
+        device: &Device,
+        queue: &Queue,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            mesh: Mesh::new(vertices, indices, glam::IVec3::ZERO, pool, device, queue)?,
+            instances: InstanceBuffer::new(device),
+        })
+    }
+}
+
+impl<'a, 'rp> Render<'a, Mesh> for RenderPass<'rp>
+where
+    'a: 'rp,
+{
+    /// Draws the mesh from the pool's buffers, which must already be bound.
+    fn draw_object_instanced(&mut self, mesh: &'a Mesh, instances: Range<u32>) {
+        self.draw_indexed(mesh.indices.clone(), mesh.vertices.start as i32, instances);
+    }
+}
+
+impl<'a, 'rp> Render<'a, InstancedMesh> for RenderPass<'rp>
+where
+    'a: 'rp,
+{
+    /// Draws the given range of the model's instances, binding its instance buffer. The pool's
+    /// buffers must already be bound.
+    fn draw_object_instanced(&mut self, model: &'a InstancedMesh, instances: Range<u32>) {
+        self.set_vertex_buffer(1, model.instances.buffer.slice(..));
+        self.draw_object_instanced(&model.mesh, instances);
+    }
 }
 
 impl MeshVertex {
@@ -57,7 +236,8 @@ impl MeshVertex {
     const ATTRIBS: &'static [VertexAttribute] = &vertex_attr_array![
         0 => Float32x3,
         1 => Float32x3,
-        2 => Uint32
+        2 => Uint32,
+        4 => Unorm8x4
     ];
 
     /// Returns the wgpu vertex buffer layout of how each vertex is interpreted.
@@ -70,20 +250,26 @@ impl MeshVertex {
     }
 }
 
-impl<'a, 'rp> Render<'a, Mesh> for RenderPass<'rp>
-where
-    'a: 'rp,
-{
-    fn draw_object_instanced(&mut self, mesh: &'a Mesh, instances: std::ops::Range<u32>) {
-        let Mesh {
-            vertex_buffer,
-            index_buffer,
-            count,
-        } = mesh;
-
-        self.set_vertex_buffer(0, vertex_buffer.slice(..));
-        self.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint32);
+impl MeshInstance {
+    /// The vertex attributes of how the data is structured.
+    const ATTRIBS: &'static [VertexAttribute] = &vertex_attr_array![
+        3 => Float32x3
+    ];
 
-        self.draw_indexed(0..*count, 0, instances);
+    /// Returns the wgpu vertex buffer layout of how each instance is interpreted.
+    pub fn desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: size_of::<Self>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: Self::ATTRIBS,
+        }
     }
 }
+
+/// Returns the offset of the given world position from the given eye position, computed in
+/// integer space so it stays precise arbitrarily far from the world origin.
+pub fn offset_from(position: glam::IVec3, eye: glam::DVec3) -> glam::Vec3 {
+    let eye_floor = eye.floor();
+
+    (position - eye_floor.as_ivec3()).as_vec3() - (eye - eye_floor).as_vec3()
+}
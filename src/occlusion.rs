@@ -0,0 +1,282 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use glam::{DVec3, IVec3, Vec3};
+use wgpu::*;
+
+use crate::{
+    camera::Frustum,
+    chunk::{CHUNK_HEIGHT, CHUNK_WIDTH},
+    model::{self, MeshInstance},
+    preprocessor,
+};
+
+/// The most chunks whose bounding boxes are tested for occlusion in a single frame. Chunks past
+/// this are always drawn.
+const MAX_OCCLUSION_QUERIES: usize = 4096;
+
+/// The number of vertices making up the triangles of a bounding box.
+const BOX_VERTICES: u32 = 36;
+
+/// Culls chunks hidden behind other terrain using occlusion queries. After the opaque meshes of
+/// a frame are drawn, the bounding boxes of the chunks in view are drawn against the depth
+/// buffer (without being written anywhere), counting how many of their fragments are visible.
+/// Once the counts are read back, chunks whose boxes were entirely hidden are skipped, until a
+/// later frame finds their boxes visible again.
+pub struct OcclusionCuller {
+    /// The pipeline the bounding boxes are drawn with.
+    pipeline: RenderPipeline,
+    /// The queries counting the visible fragments of each box drawn in a frame.
+    query_set: QuerySet,
+    /// The buffer the query results are resolved into.
+    resolve_buffer: Buffer,
+    /// The buffer the query results are copied into to be read back on the CPU.
+    readback_buffer: Buffer,
+
+    /// The origins of the chunks whose boxes are being drawn this frame, in query order.
+    queued: Vec<IVec3>,
+    /// The origins of the chunks whose results are being read back, in query order, if any
+    /// are. No new queries are made until they have been read.
+    in_flight: Option<Vec<IVec3>>,
+    /// Whether reading back the results in flight succeeded, once it has finished.
+    read_result: Arc<Mutex<Option<Result<(), BufferAsyncError>>>>,
+    /// The origins of the chunks that were hidden, as of the latest results.
+    occluded: HashSet<IVec3>,
+}
+
+impl OcclusionCuller {
+    /// Creates the pipeline and buffers used to cull chunks, drawing onto surfaces of the given
+    /// format with the camera bound as group 0.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        camera_bind_group_layout: &BindGroupLayout,
+    ) -> Self {
+        let shader = preprocessor::create_shader_module(
+            device,
+            "occlusion.wgsl",
+            include_str!("occlusion.wgsl"),
+            &[],
+        );
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Occlusion Pipeline Layout"),
+            push_constant_ranges: &[],
+            bind_group_layouts: &[camera_bind_group_layout],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Occlusion Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[MeshInstance::desc()],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::empty(),
+                })],
+                compilation_options: PipelineCompilationOptions::default(),
+            }),
+            // both sides are drawn, so boxes are still counted when the camera is right next to
+            // (or inside) them
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        let query_set = device.create_query_set(&QuerySetDescriptor {
+            label: Some("Occlusion Query Set"),
+            ty: QueryType::Occlusion,
+            count: MAX_OCCLUSION_QUERIES as u32,
+        });
+
+        let results_size = (MAX_OCCLUSION_QUERIES * size_of::<u64>()) as BufferAddress;
+
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Occlusion Resolve Buffer"),
+            size: results_size,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Occlusion Readback Buffer"),
+            size: results_size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            queued: Vec::new(),
+            in_flight: None,
+            read_result: Arc::new(Mutex::new(None)),
+            occluded: HashSet::new(),
+        }
+    }
+
+    /// Reads the results of the previously drawn boxes, if they have arrived.
+    pub fn update(&mut self, device: &Device) {
+        device.poll(Maintain::Poll);
+
+        let Some(read_result) = self.read_result.lock().unwrap().take() else {
+            return;
+        };
+
+        let Some(origins) = self.in_flight.take() else {
+            return;
+        };
+
+        if let Err(error) = read_result {
+            // without results, every chunk is drawn until the next ones arrive
+            eprintln!("could not read occlusion results: {error:?}");
+            self.occluded.clear();
+            return;
+        }
+
+        let size = (origins.len() * size_of::<u64>()) as BufferAddress;
+
+        {
+            let results = self.readback_buffer.slice(..size).get_mapped_range();
+
+            self.occluded = origins
+                .into_iter()
+                .zip(results.chunks_exact(size_of::<u64>()))
+                .filter(|(_, samples)| samples.iter().all(|byte| *byte == 0))
+                .map(|(origin, _)| origin)
+                .collect();
+        }
+
+        self.readback_buffer.unmap();
+    }
+
+    /// Returns whether the chunk with the given origin was hidden, as of the latest results.
+    pub fn is_occluded(&self, origin: IVec3) -> bool {
+        self.occluded.contains(&origin)
+    }
+
+    /// Queues the bounding boxes of the chunks with the given origins to be drawn this frame,
+    /// returning the origins of the queued boxes. Nothing is queued while the previous results
+    /// are still being read back. The chunk the camera at `eye` is in is never queued, as its
+    /// box is always visible, and neither are chunks outside of the camera's `frustum`, whose
+    /// boxes would always be counted as hidden.
+    pub fn queue(
+        &mut self,
+        origins: impl Iterator<Item = IVec3>,
+        eye: DVec3,
+        frustum: &Frustum,
+    ) -> &[IVec3] {
+        self.queued.clear();
+
+        if self.in_flight.is_some() {
+            return &self.queued;
+        }
+
+        let size = Vec3::new(CHUNK_WIDTH as f32, CHUNK_HEIGHT as f32, CHUNK_WIDTH as f32);
+        let mut seen = HashSet::new();
+
+        self.queued.extend(
+            origins
+                .filter(|origin| {
+                    let min = origin.as_dvec3();
+                    eye.cmplt(min).any() || eye.cmpgt(min + size.as_dvec3()).any()
+                })
+                .filter(|origin| {
+                    let min = model::offset_from(*origin, eye);
+                    frustum.intersects_aabb(min, min + size)
+                })
+                .filter(|origin| seen.insert(*origin))
+                .take(MAX_OCCLUSION_QUERIES),
+        );
+
+        &self.queued
+    }
+
+    /// Returns the query set the render pass should count the fragments of the boxes with, or
+    /// None if no boxes are drawn this frame.
+    pub fn query_set(&self) -> Option<&QuerySet> {
+        (!self.queued.is_empty()).then_some(&self.query_set)
+    }
+
+    /// Draws the queued boxes, whose instances (offsets from the camera) start at
+    /// `first_instance` in the given instance buffer. Must be called after the opaque meshes
+    /// have been drawn, in a render pass using `OcclusionCuller::query_set`.
+    pub fn draw_boxes<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        camera_bind_group: &'a BindGroup,
+        instance_buffer: &'a Buffer,
+        first_instance: u32,
+    ) {
+        if self.queued.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+
+        for query in 0..self.queued.len() as u32 {
+            let instance = first_instance + query;
+
+            render_pass.begin_occlusion_query(query);
+            render_pass.draw(0..BOX_VERTICES, instance..instance + 1);
+            render_pass.end_occlusion_query();
+        }
+    }
+
+    /// Copies the results of the boxes drawn this frame into the readback buffer.
+    pub fn resolve(&self, encoder: &mut CommandEncoder) {
+        if self.queued.is_empty() {
+            return;
+        }
+
+        let count = self.queued.len();
+        let size = (count * size_of::<u64>()) as BufferAddress;
+
+        encoder.resolve_query_set(&self.query_set, 0..count as u32, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, size);
+    }
+
+    /// Starts reading back the results of the boxes drawn this frame. Must be called after the
+    /// frame's commands have been submitted.
+    pub fn read_back(&mut self) {
+        if self.queued.is_empty() {
+            return;
+        }
+
+        let size = (self.queued.len() * size_of::<u64>()) as BufferAddress;
+        let read_result = Arc::clone(&self.read_result);
+
+        self.readback_buffer
+            .slice(..size)
+            .map_async(MapMode::Read, move |result| {
+                *read_result.lock().unwrap() = Some(result);
+            });
+
+        self.in_flight = Some(std::mem::take(&mut self.queued));
+    }
+}
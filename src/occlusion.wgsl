@@ -0,0 +1,39 @@
+// Uniforms
+#include "camera.wgsl"
+
+struct InstanceInput {
+	// The offset of the chunk's origin relative to the camera.
+	@location(3) offset: vec3<f32>,
+}
+
+// The size of the bounding box of a chunk.
+const CHUNK_SIZE = vec3<f32>(16.0, 256.0, 16.0);
+
+@vertex
+fn vs_main(
+	instance: InstanceInput,
+	@builtin(vertex_index) vertex_id: u32
+) -> @builtin(position) vec4<f32> {
+	// the corners of each of the 12 triangles making up the box, as indices into its 8 corners,
+	// whose bits give the (x, y, z) of the corner
+	var corners = array<u32, 36>(
+		0u, 2u, 1u, 1u, 2u, 3u, // -z
+		4u, 5u, 6u, 5u, 7u, 6u, // +z
+		0u, 4u, 2u, 2u, 4u, 6u, // -x
+		1u, 3u, 5u, 3u, 7u, 5u, // +x
+		0u, 1u, 4u, 1u, 5u, 4u, // -y
+		2u, 6u, 3u, 3u, 6u, 7u, // +y
+	);
+
+	let corner = corners[vertex_id];
+	let position = vec3<f32>(f32(corner & 1u), f32((corner >> 1u) & 1u), f32(corner >> 2u));
+
+	return camera.view_proj * vec4<f32>(position * CHUNK_SIZE + instance.offset, 1.0);
+}
+
+// The boxes are only drawn to count how many of their fragments pass the depth test, so nothing
+// is written.
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+	return vec4<f32>(0.0);
+}
@@ -0,0 +1,176 @@
+use glam::*;
+use wgpu::*;
+
+use crate::{model, preprocessor};
+
+/// The position of the outlined voxel, as laid out in the shader's uniform buffer.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineUniform {
+    /// The position of the voxel's center relative to the camera.
+    offset: Vec3,
+    _padding: f32,
+}
+
+/// Draws the edges of the voxel the camera is looking at, as a cube slightly larger than the
+/// voxel. The edges are drawn as a line list rather than with `PolygonMode::Line`, which would
+/// also draw the diagonals splitting each face into triangles.
+pub struct Outline {
+    /// The pipeline drawing the cube's edges.
+    pipeline: RenderPipeline,
+    /// The uniform buffer holding the `OutlineUniform` of the current frame.
+    uniform_buffer: Buffer,
+    /// The bind group of `uniform_buffer`.
+    bind_group: BindGroup,
+    /// Whether there is a voxel to outline this frame.
+    visible: bool,
+}
+
+impl Outline {
+    /// Creates the pipeline drawing the outline onto surfaces of the given format, with the
+    /// camera bound as group 0.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        camera_bind_group_layout: &BindGroupLayout,
+    ) -> Self {
+        let shader = preprocessor::create_shader_module(
+            device,
+            "outline.wgsl",
+            include_str!("outline.wgsl"),
+            &[],
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Outline Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Outline Uniform Buffer"),
+            size: size_of::<OutlineUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Outline Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Outline Pipeline Layout"),
+            push_constant_ranges: &[],
+            bind_group_layouts: &[camera_bind_group_layout, &bind_group_layout],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Outline Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: PipelineCompilationOptions::default(),
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            // hidden behind terrain in front of the voxel, without hiding anything itself
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            uniform_buffer,
+            bind_group,
+            visible: false,
+        }
+    }
+
+    /// Outlines the voxel at the given position (if any), as seen from the camera at `eye`.
+    pub fn update(&mut self, queue: &Queue, target: Option<IVec3>, eye: DVec3) {
+        self.visible = target.is_some();
+
+        if let Some(target) = target {
+            let uniform = OutlineUniform {
+                offset: model::offset_from(target, eye),
+                _padding: 0.0,
+            };
+
+            queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+        }
+    }
+
+    /// Draws the outline onto the given view, tested against the depth buffer of the scene
+    /// already drawn into it. Nothing is drawn without a voxel to outline.
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        depth_view: &TextureView,
+        camera_bind_group: &BindGroup,
+    ) {
+        if !self.visible {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Outline Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..24, 0..1);
+    }
+}
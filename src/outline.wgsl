@@ -0,0 +1,48 @@
+// Uniforms
+#include "camera.wgsl"
+
+struct OutlineUniform {
+	// The position of the outlined voxel's center, relative to the camera.
+	offset: vec3<f32>,
+}
+
+@group(1) @binding(0) var<uniform> outline: OutlineUniform;
+
+// Half the width of the outline's cube, slightly larger than a voxel so its edges aren't hidden
+// by the voxel's own faces.
+const HALF_SIZE: f32 = 0.502;
+
+// The corners at either end of each of the cube's 12 edges.
+const EDGES: array<vec3<f32>, 24> = array<vec3<f32>, 24>(
+	// bottom
+	vec3<f32>(-1.0, -1.0, -1.0), vec3<f32>(1.0, -1.0, -1.0),
+	vec3<f32>(1.0, -1.0, -1.0), vec3<f32>(1.0, -1.0, 1.0),
+	vec3<f32>(1.0, -1.0, 1.0), vec3<f32>(-1.0, -1.0, 1.0),
+	vec3<f32>(-1.0, -1.0, 1.0), vec3<f32>(-1.0, -1.0, -1.0),
+	// top
+	vec3<f32>(-1.0, 1.0, -1.0), vec3<f32>(1.0, 1.0, -1.0),
+	vec3<f32>(1.0, 1.0, -1.0), vec3<f32>(1.0, 1.0, 1.0),
+	vec3<f32>(1.0, 1.0, 1.0), vec3<f32>(-1.0, 1.0, 1.0),
+	vec3<f32>(-1.0, 1.0, 1.0), vec3<f32>(-1.0, 1.0, -1.0),
+	// sides
+	vec3<f32>(-1.0, -1.0, -1.0), vec3<f32>(-1.0, 1.0, -1.0),
+	vec3<f32>(1.0, -1.0, -1.0), vec3<f32>(1.0, 1.0, -1.0),
+	vec3<f32>(1.0, -1.0, 1.0), vec3<f32>(1.0, 1.0, 1.0),
+	vec3<f32>(-1.0, -1.0, 1.0), vec3<f32>(-1.0, 1.0, 1.0),
+);
+
+// The color of the outline.
+const COLOR: vec4<f32> = vec4<f32>(0.05, 0.05, 0.05, 1.0);
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+	var edges = EDGES;
+	let position = outline.offset + edges[index] * HALF_SIZE;
+
+	return camera.view_proj * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+	return COLOR;
+}
@@ -0,0 +1,387 @@
+use std::ops::RangeInclusive;
+
+use glam::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{camera::Animated, chunk::CHUNK_HEIGHT};
+
+/// The name of the config file the player options are loaded from.
+pub const PLAYER_CONFIG_FILE: &str = "player.toml";
+
+/// The width (along x and z) of the player's collider, in voxels.
+pub const PLAYER_WIDTH: f64 = 0.6;
+/// The height of the player's collider, in voxels.
+pub const PLAYER_HEIGHT: f64 = 1.8;
+/// The height of the player's eyes (where the camera sits) above their feet.
+pub const EYE_HEIGHT: f64 = 1.62;
+/// The height of the player's eyes while they are sneaking.
+pub const SNEAK_EYE_HEIGHT: f64 = 1.27;
+
+/// The speed the player walks at, in voxels per second.
+pub const WALK_SPEED: f64 = 4.3;
+/// The speed the player sprints at.
+pub const SPRINT_SPEED: f64 = 5.6;
+/// The speed the player sneaks at.
+pub const SNEAK_SPEED: f64 = 1.3;
+/// The downwards acceleration of the player while they are in the air.
+pub const GRAVITY: f64 = 32.0;
+/// The upwards speed of a jump, which is enough to climb onto a block one voxel high.
+pub const JUMP_SPEED: f64 = 8.9;
+/// The fastest the player can fall.
+pub const TERMINAL_VELOCITY: f64 = 78.0;
+
+/// The speed the player swims at horizontally, in water.
+pub const SWIM_SPEED: f64 = 2.2;
+/// The speed the player swims up or down at, while holding the keys to.
+const SWIM_VERTICAL_SPEED: f64 = 3.0;
+/// The downwards acceleration of the player in water, where they sink slowly.
+const SWIM_GRAVITY: f64 = 6.0;
+/// The fastest the player sinks in water.
+const SWIM_SINK_SPEED: f64 = 2.0;
+/// The speed the player rises at while the water covers their chest, so they float with their
+/// head above the surface.
+const FLOAT_SPEED: f64 = 1.5;
+/// The height of the player's chest above their feet. While it is under water, the player
+/// floats upwards.
+const FLOAT_HEIGHT: f64 = 1.2;
+/// How quickly the vertical velocity of the player settles on the speed they swim at, in
+/// water. Larger is faster.
+const WATER_DRAG: f64 = 8.0;
+
+/// The longest step of time simulated at once, so a slow frame doesn't launch the player.
+const MAX_TIME_STEP: f64 = 0.1;
+/// How close the collider can be to a voxel face while still not counted as touching it, to
+/// absorb the rounding of positions resting exactly against faces.
+const EPSILON: f64 = 1e-6;
+
+/// Options for how the player moves, loaded from `PLAYER_CONFIG_FILE`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerConfig {
+    /// The highest ledge (in voxels) the player walks up onto without jumping. 0 turns stepping
+    /// up off.
+    pub step_height: f64,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self { step_height: 1.0 }
+    }
+}
+
+/// The body of the player: an axis aligned box that falls under gravity and collides with solid
+/// voxels, moved by walking and jumping.
+#[derive(Debug, Clone)]
+pub struct Player {
+    /// The position of the center of the bottom face of the collider (the player's feet).
+    pub position: DVec3,
+    /// The velocity of the player, in voxels per second.
+    pub velocity: DVec3,
+    /// Whether the player is standing on a solid voxel, and able to jump.
+    pub on_ground: bool,
+    /// Whether the collider overlaps water, in which the player swims instead of walking.
+    pub swimming: bool,
+    /// Whether the player flies freely with the camera instead, without gravity or collisions.
+    pub flying: bool,
+    /// Whether the player is sneaking, crouching lower and refusing to walk off the edge of
+    /// the voxels they are standing on.
+    pub sneaking: bool,
+    /// The options the player moves with.
+    pub config: PlayerConfig,
+    /// The height of the player's eyes above their feet, which lowers while sneaking.
+    eye_height: Animated,
+    /// How far below the player's eyes the camera is, easing back up after they step up onto a
+    /// ledge so the step doesn't snap the view upwards.
+    step_offset: Animated,
+}
+
+impl Player {
+    /// Creates a player standing (or falling) with their eyes at the given position.
+    pub fn new(eye: DVec3, config: PlayerConfig) -> Self {
+        Self {
+            position: eye - DVec3::Y * EYE_HEIGHT,
+            velocity: DVec3::ZERO,
+            on_ground: false,
+            swimming: false,
+            flying: false,
+            sneaking: false,
+            config,
+            eye_height: Animated::new(EYE_HEIGHT as f32),
+            step_offset: Animated::new(0.0),
+        }
+    }
+
+    /// Returns the position of the player's eyes, where the camera sits.
+    pub fn eye(&self) -> DVec3 {
+        self.position + DVec3::Y * (self.eye_height.value + self.step_offset.value) as f64
+    }
+
+    /// Moves the player to have their eyes at the given position, stopping them. Used to follow
+    /// the camera while flying.
+    pub fn teleport(&mut self, eye: DVec3) {
+        *self = Self {
+            flying: self.flying,
+            ..Self::new(eye, self.config.clone())
+        };
+    }
+
+    /// Returns the corners of the collider with the smallest and largest coordinates.
+    pub fn bounds(&self) -> (DVec3, DVec3) {
+        let half_width = PLAYER_WIDTH / 2.0;
+
+        (
+            self.position - dvec3(half_width, 0.0, half_width),
+            self.position + dvec3(half_width, PLAYER_HEIGHT, half_width),
+        )
+    }
+
+    /// Returns whether the voxel at the given position would overlap the collider, if it were
+    /// solid.
+    pub fn intersects_voxel(&self, voxel: IVec3) -> bool {
+        let (min, max) = self.bounds();
+
+        (0..3).all(|axis| overlapped_voxels(min, max, axis).contains(&voxel[axis]))
+    }
+
+    /// Advances the player by `dt` seconds, walking with the given horizontal velocity and
+    /// jumping if `jump` is set while they are on the ground. In water, `jump` and `swim_down`
+    /// swim up and down instead. `is_solid` returns whether the voxel at a position blocks the
+    /// player, and `is_water` whether they swim in it.
+    pub fn update(
+        &mut self,
+        walk_velocity: DVec3,
+        jump: bool,
+        swim_down: bool,
+        dt: f32,
+        is_solid: impl Fn(IVec3) -> bool,
+        is_water: impl Fn(IVec3) -> bool,
+    ) {
+        self.eye_height.target = match self.sneaking {
+            true => SNEAK_EYE_HEIGHT as f32,
+            false => EYE_HEIGHT as f32,
+        };
+        self.eye_height.update(dt);
+        self.step_offset.update(dt);
+
+        let dt = (dt as f64).min(MAX_TIME_STEP);
+
+        self.push_out_of_terrain(&is_solid);
+
+        self.velocity.x = walk_velocity.x;
+        self.velocity.z = walk_velocity.z;
+        self.swimming = self.overlaps(&is_water);
+
+        if self.swimming {
+            self.swim(jump, swim_down, dt, &is_water);
+        } else {
+            if jump && self.on_ground {
+                self.velocity.y = JUMP_SPEED;
+            }
+
+            self.velocity.y = (self.velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+        }
+
+        // vertical first, so the player lands before sliding along the ground
+        let falling = self.velocity.y < 0.0;
+        let landed = self.move_along(1, self.velocity.y * dt, &is_solid);
+        self.on_ground = landed && falling;
+
+        if landed {
+            self.velocity.y = 0.0;
+        }
+
+        for axis in [0, 2] {
+            let distance = self.velocity[axis] * dt;
+
+            if self.sneaking
+                && self.on_ground
+                && !self.is_supported_after(axis, distance, &is_solid)
+            {
+                self.velocity[axis] = 0.0;
+                continue;
+            }
+
+            let start = self.clone();
+
+            if self.move_along(axis, distance, &is_solid)
+                && !self.step_up(&start, axis, distance, &is_solid)
+            {
+                self.velocity[axis] = 0.0;
+            }
+        }
+    }
+
+    /// Tries moving from `start` by `distance` along the (horizontal) axis again after rising by
+    /// the step height, then lowering back onto whatever is there, as if walking up a ledge.
+    /// Keeps the step (returning true) if it gets the player further than they got without it.
+    fn step_up(
+        &mut self,
+        start: &Self,
+        axis: usize,
+        distance: f64,
+        is_solid: impl Fn(IVec3) -> bool,
+    ) -> bool {
+        let step_height = self.config.step_height;
+
+        if !start.on_ground || step_height <= 0.0 {
+            return false;
+        }
+
+        let mut stepped = start.clone();
+        stepped.move_along(1, step_height, &is_solid);
+        let blocked = stepped.move_along(axis, distance, &is_solid);
+        stepped.move_along(1, -step_height, &is_solid);
+
+        let further = (stepped.position[axis] - start.position[axis]).abs()
+            > (self.position[axis] - start.position[axis]).abs() + EPSILON;
+
+        if !further {
+            return false;
+        }
+
+        let rise = stepped.position.y - self.position.y;
+        stepped.step_offset.value -= rise as f32;
+        *self = stepped;
+
+        !blocked
+    }
+
+    /// Changes the vertical velocity of the player in water: swimming up or down with the keys
+    /// held, floating up while their chest is under water, and otherwise sinking slowly. Falling
+    /// into water is slowed down, rather than stopped at once.
+    fn swim(&mut self, up: bool, down: bool, dt: f64, is_water: impl Fn(IVec3) -> bool) {
+        let chest = (self.position + DVec3::Y * FLOAT_HEIGHT).round().as_ivec3();
+
+        let target = match (up, down) {
+            (true, false) => SWIM_VERTICAL_SPEED,
+            (false, true) => -SWIM_VERTICAL_SPEED,
+            _ if is_water(chest) => FLOAT_SPEED,
+            _ => {
+                self.velocity.y -= SWIM_GRAVITY * dt;
+                self.velocity.y.max(-SWIM_SINK_SPEED)
+            }
+        };
+
+        self.velocity.y += (target - self.velocity.y) * (1.0 - (-WATER_DRAG * dt).exp());
+    }
+
+    /// Returns whether the player would still be standing on a solid voxel after moving by
+    /// `distance` along the (horizontal) axis.
+    fn is_supported_after(
+        &self,
+        axis: usize,
+        distance: f64,
+        is_solid: impl Fn(IVec3) -> bool,
+    ) -> bool {
+        let mut moved = self.clone();
+        moved.move_along(axis, distance, &is_solid);
+
+        let (min, max) = moved.bounds();
+        // the layer of voxels the feet rest on top of
+        let y = (min.y - 0.5 + EPSILON).floor() as i32;
+
+        overlapped_voxels(min, max, 0)
+            .any(|x| overlapped_voxels(min, max, 2).any(|z| is_solid(ivec3(x, y, z))))
+    }
+
+    /// Moves the collider by `distance` along the axis (0, 1, 2 for x, y, z), stopping against
+    /// the first solid voxel in the way. Every voxel swept over is checked, so fast movement
+    /// can't pass through thin walls. Returns whether the collider was stopped.
+    fn move_along(&mut self, axis: usize, distance: f64, is_solid: impl Fn(IVec3) -> bool) -> bool {
+        if distance == 0.0 {
+            return false;
+        }
+
+        let (min, max) = self.bounds();
+        // the voxels overlapped by the collider on the other two axes
+        let [a, b] = match axis {
+            0 => [1, 2],
+            1 => [0, 2],
+            _ => [0, 1],
+        };
+        let overlapped = |axis| overlapped_voxels(min, max, axis);
+
+        // the layers of voxels the leading face of the collider sweeps over, in order, along
+        // with where the face stops against each of them. Voxels are centered on integer
+        // positions, so they span half a voxel in each direction
+        let layers: Box<dyn Iterator<Item = (i32, f64)>> = if distance > 0.0 {
+            let first = (max[axis] + 0.5 - EPSILON).ceil() as i32;
+            let last = (max[axis] + distance + 0.5).ceil() as i32 - 1;
+            Box::new((first..=last).map(|layer| (layer, layer as f64 - 0.5 - max[axis])))
+        } else {
+            let first = (min[axis] - 0.5 + EPSILON).floor() as i32;
+            let last = (min[axis] + distance - 0.5).floor() as i32 + 1;
+            Box::new(
+                (last..=first)
+                    .rev()
+                    .map(|layer| (layer, layer as f64 + 0.5 - min[axis])),
+            )
+        };
+
+        for (layer, stop) in layers {
+            let blocked = overlapped(a).any(|i| {
+                overlapped(b).any(|j| {
+                    let mut voxel = IVec3::ZERO;
+                    voxel[axis] = layer;
+                    voxel[a] = i;
+                    voxel[b] = j;
+                    is_solid(voxel)
+                })
+            });
+
+            if blocked {
+                self.position[axis] += stop;
+                return true;
+            }
+        }
+
+        self.position[axis] += distance;
+        false
+    }
+
+    /// Returns whether any voxel overlapping the collider matches the predicate.
+    fn overlaps(&self, predicate: impl Fn(IVec3) -> bool) -> bool {
+        let (min, max) = self.bounds();
+
+        overlapped_voxels(min, max, 0).any(|x| {
+            overlapped_voxels(min, max, 1)
+                .any(|y| overlapped_voxels(min, max, 2).any(|z| predicate(ivec3(x, y, z))))
+        })
+    }
+
+    /// Returns whether any solid voxel overlaps the collider.
+    fn is_inside_terrain(&self, is_solid: impl Fn(IVec3) -> bool) -> bool {
+        self.overlaps(is_solid)
+    }
+
+    /// Lifts the player onto the nearest voxel above them they fit into, if they are stuck
+    /// inside the terrain (after spawning inside it, or a voxel being placed over them).
+    fn push_out_of_terrain(&mut self, is_solid: impl Fn(IVec3) -> bool) {
+        if !self.is_inside_terrain(&is_solid) {
+            return;
+        }
+
+        let start = self.position;
+
+        while self.is_inside_terrain(&is_solid) {
+            // standing on the bottom of the next voxel up
+            self.position.y = (self.position.y + 0.5).floor() + 0.5;
+
+            if self.position.y > CHUNK_HEIGHT as f64 {
+                self.position = start;
+                return;
+            }
+        }
+
+        self.velocity = DVec3::ZERO;
+    }
+}
+
+/// Returns the coordinates along the axis of the voxels overlapping the box between `min` and
+/// `max`. Voxels only touching the box are left out.
+fn overlapped_voxels(min: DVec3, max: DVec3, axis: usize) -> RangeInclusive<i32> {
+    let first = (min[axis] + 0.5 + EPSILON).floor() as i32;
+    let last = (max[axis] + 0.5 - EPSILON).floor() as i32;
+
+    first..=last
+}
@@ -0,0 +1,181 @@
+use std::{collections::HashMap, ops::BitOr};
+
+use wgpu::*;
+
+use crate::{
+    model::{MeshInstance, MeshVertex, ModelInstance},
+    post::SCENE_FORMAT,
+};
+
+/// Flags selecting how the terrain shader is run by a pipeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ShaderVariant(u32);
+
+impl ShaderVariant {
+    /// Shades the meshes as opaque terrain.
+    pub const NONE: Self = Self(0);
+    /// Writes only the depth of the meshes, without running a fragment shader.
+    pub const DEPTH_ONLY: Self = Self(1);
+    /// Shades the meshes as water, which doesn't write to the depth buffer and is seen from
+    /// both sides.
+    pub const WATER: Self = Self(1 << 1);
+    /// Draws instanced models, placed by the matrices of their `ModelInstance`s rather than the
+    /// offsets of `MeshInstance`s.
+    pub const INSTANCED: Self = Self(1 << 2);
+
+    /// Returns whether every flag of `other` is set.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for ShaderVariant {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// Everything a terrain pipeline differs by, which the cache keeps one pipeline for each of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    /// How the shader is run.
+    pub variant: ShaderVariant,
+    /// Whether triangles are filled in, or only their edges drawn.
+    pub polygon_mode: PolygonMode,
+    /// How the shaded fragments are blended with what has already been drawn.
+    pub blend: Option<BlendState>,
+    /// The number of samples taken of each pixel, which must match the targets drawn into.
+    pub sample_count: u32,
+}
+
+/// The pipelines drawing chunk meshes with the terrain shader, created the first time they are
+/// needed and reused from then on.
+pub struct PipelineCache {
+    /// The terrain shader every pipeline runs.
+    shader: ShaderModule,
+    /// The layout of the bind groups shared by every pipeline.
+    layout: PipelineLayout,
+    /// The pipelines created so far.
+    pipelines: HashMap<PipelineKey, RenderPipeline>,
+}
+
+impl PipelineCache {
+    /// Creates an empty cache of pipelines running the given shader with the given bind groups.
+    pub fn new(
+        device: &Device,
+        shader: ShaderModule,
+        bind_group_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            push_constant_ranges: &[],
+            bind_group_layouts,
+        });
+
+        Self {
+            shader,
+            layout,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// Creates the pipeline for the given key, unless it already exists. Pipelines must be
+    /// prepared before they are drawn with, as `PipelineCache::get` can't create them.
+    pub fn prepare(&mut self, device: &Device, key: PipelineKey) {
+        if !self.pipelines.contains_key(&key) {
+            let pipeline = Self::create_pipeline(device, &self.shader, &self.layout, key);
+            self.pipelines.insert(key, pipeline);
+        }
+    }
+
+    /// Returns the pipeline for the given key, which must have been prepared.
+    pub fn get(&self, key: &PipelineKey) -> &RenderPipeline {
+        self.pipelines
+            .get(key)
+            .unwrap_or_else(|| panic!("pipeline {key:?} was drawn with before being prepared"))
+    }
+
+    /// Creates the pipeline for the given key. Water doesn't write to the depth buffer, and
+    /// draws both sides of every face (so it can be seen from below). Opaque meshes pass the
+    /// depth test where they match the depth written by a depth prepass.
+    fn create_pipeline(
+        device: &Device,
+        shader: &ShaderModule,
+        layout: &PipelineLayout,
+        key: PipelineKey,
+    ) -> RenderPipeline {
+        let depth_only = key.variant.contains(ShaderVariant::DEPTH_ONLY);
+        let water = key.variant.contains(ShaderVariant::WATER);
+        let instanced = key.variant.contains(ShaderVariant::INSTANCED);
+
+        let fragment_entry = match (depth_only, water) {
+            (true, _) => None,
+            (false, true) => Some("fs_water"),
+            (false, false) => Some("fs_main"),
+        };
+
+        let depth_compare = match depth_only || water {
+            true => CompareFunction::Less,
+            false => CompareFunction::LessEqual,
+        };
+
+        let targets = [Some(ColorTargetState {
+            format: SCENE_FORMAT,
+            blend: key.blend,
+            write_mask: ColorWrites::ALL,
+        })];
+
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(&format!("Render Pipeline {key:?}")),
+            layout: Some(layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: match instanced {
+                    true => "vs_instanced",
+                    false => "vs_main",
+                },
+                buffers: &[
+                    MeshVertex::desc(),
+                    match instanced {
+                        true => ModelInstance::desc(),
+                        false => MeshInstance::desc(),
+                    },
+                ],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: fragment_entry.map(|entry_point| FragmentState {
+                module: shader,
+                entry_point,
+                targets: &targets,
+                compilation_options: PipelineCompilationOptions::default(),
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: match water {
+                    true => None,
+                    false => Some(Face::Back),
+                },
+                polygon_mode: key.polygon_mode,
+                conservative: false,
+                unclipped_depth: false,
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: !water,
+                depth_compare,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: key.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+}
@@ -0,0 +1,414 @@
+use wgpu::*;
+
+use crate::texture::Texture;
+
+/// A fullscreen effect applied to the scene after it has been drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostEffect {
+    /// Smooths jagged edges with fast approximate anti-aliasing, blurring each pixel along the
+    /// edge it lies on.
+    Fxaa,
+    /// Makes bright parts of the scene glow into their surroundings.
+    Bloom {
+        /// How bright (from 0 to 1) a color must be to glow.
+        threshold: f32,
+        /// How strongly bright colors glow.
+        intensity: f32,
+    },
+    /// Scales how colorful the scene is, where 0 is greyscale and 1 leaves it as is.
+    Saturation(f32),
+    /// Darkens the corners of the screen by the given amount, from 0 to 1.
+    Vignette(f32),
+    /// Brightens (above 1) or darkens (below 1) the midtones of the scene.
+    Gamma(f32),
+    /// Makes the screen wobble slightly, animated by the given time in seconds. Drawn first
+    /// while the camera is underwater, rather than being part of the stack.
+    Underwater(f32),
+}
+
+impl PostEffect {
+    /// Returns the name of the effect, shown in the settings and used as its pass name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Fxaa => "fxaa",
+            Self::Bloom { .. } => "bloom",
+            Self::Saturation(_) => "saturation",
+            Self::Vignette(_) => "vignette",
+            Self::Gamma(_) => "gamma",
+            Self::Underwater(_) => "underwater",
+        }
+    }
+}
+
+/// An effect of the post-processing stack, which can be turned off without losing its place or
+/// settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostPass {
+    pub effect: PostEffect,
+    pub enabled: bool,
+}
+
+/// Returns the default post-processing stack, where only anti-aliasing is enabled.
+pub fn default_stack() -> Vec<PostPass> {
+    let pass = |effect, enabled| PostPass { effect, enabled };
+
+    vec![
+        pass(
+            PostEffect::Bloom {
+                threshold: 0.8,
+                intensity: 0.4,
+            },
+            false,
+        ),
+        pass(PostEffect::Saturation(1.2), false),
+        pass(PostEffect::Vignette(0.3), false),
+        pass(PostEffect::Gamma(1.0), false),
+        pass(PostEffect::Fxaa, true),
+    ]
+}
+
+/// Shows the given post-processing stack, with each effect's settings, a toggle, and buttons
+/// moving it up or down the stack.
+pub fn show_stack(ui: &mut egui::Ui, stack: &mut [PostPass]) {
+    let mut swap = None;
+    let count = stack.len();
+
+    for (index, pass) in stack.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.add_enabled(index > 0, egui::Button::new("⏶")).clicked() {
+                swap = Some(index - 1);
+            }
+
+            if ui
+                .add_enabled(index + 1 < count, egui::Button::new("⏷"))
+                .clicked()
+            {
+                swap = Some(index);
+            }
+
+            ui.checkbox(&mut pass.enabled, pass.effect.name());
+
+            match &mut pass.effect {
+                PostEffect::Fxaa | PostEffect::Underwater(_) => {}
+                PostEffect::Bloom {
+                    threshold,
+                    intensity,
+                } => {
+                    ui.add(egui::Slider::new(threshold, 0.0..=1.0).text("threshold"));
+                    ui.add(egui::Slider::new(intensity, 0.0..=2.0).text("intensity"));
+                }
+                PostEffect::Saturation(saturation) => {
+                    ui.add(egui::Slider::new(saturation, 0.0..=2.0));
+                }
+                PostEffect::Vignette(vignette) => {
+                    ui.add(egui::Slider::new(vignette, 0.0..=1.0));
+                }
+                PostEffect::Gamma(gamma) => {
+                    ui.add(egui::Slider::new(gamma, 0.5..=2.0));
+                }
+            }
+        });
+    }
+
+    // swaps the effect at the index with the one after it
+    if let Some(index) = swap {
+        stack.swap(index, index + 1);
+    }
+}
+
+/// The settings of every effect, laid out as `PostUniform` in `post.wgsl`. The settings of all
+/// effects are written at once, as the passes of a frame are all recorded before it is drawn.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniform {
+    bloom_threshold: f32,
+    bloom_intensity: f32,
+    saturation: f32,
+    vignette: f32,
+    gamma: f32,
+    time: f32,
+    _padding: [f32; 2],
+}
+
+/// The format the scene is rendered in. Colors brighter than white are kept, so emissive
+/// surfaces can glow through the bloom, until the scene is presented onto the surface.
+pub const SCENE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Draws a chain of fullscreen effects over the scene. The scene is rendered into one of two
+/// offscreen textures, and each effect reads from one and writes into the other, before the
+/// result is presented onto the surface.
+pub struct PostProcessor {
+    /// The pipeline of each kind of effect.
+    fxaa_pipeline: RenderPipeline,
+    bloom_pipeline: RenderPipeline,
+    saturation_pipeline: RenderPipeline,
+    vignette_pipeline: RenderPipeline,
+    gamma_pipeline: RenderPipeline,
+    underwater_pipeline: RenderPipeline,
+    /// The pipeline copying the result of the effects onto the surface.
+    present_pipeline: RenderPipeline,
+
+    /// The layout of the bind groups the effects read their input through.
+    bind_group_layout: BindGroupLayout,
+    /// The uniform buffer holding the `PostUniform` of the current frame.
+    uniform_buffer: Buffer,
+    /// The textures the effects ping-pong between, replaced whenever the surface is resized.
+    /// The scene is rendered into the first one.
+    targets: [Texture; 2],
+    /// The bind groups reading from each of `targets`.
+    bind_groups: [BindGroup; 2],
+}
+
+impl PostProcessor {
+    /// Creates the pipelines of every effect, and the textures they are drawn between, matching
+    /// the surface.
+    pub fn new(device: &Device, surface_config: &SurfaceConfiguration) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Post Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Post Pipeline Layout"),
+            push_constant_ranges: &[],
+            bind_group_layouts: &[&bind_group_layout],
+        });
+
+        let fxaa_shader = device.create_shader_module(include_wgsl!("fxaa.wgsl"));
+        let shader = device.create_shader_module(include_wgsl!("post.wgsl"));
+
+        let create_pipeline = |label, shader, entry_point, format| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: PipelineCompilationOptions::default(),
+                },
+                fragment: Some(FragmentState {
+                    module: shader,
+                    entry_point,
+                    targets: &[Some(ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                    compilation_options: PipelineCompilationOptions::default(),
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Post Uniform Buffer"),
+            size: size_of::<PostUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let (targets, bind_groups) =
+            Self::create_targets(device, surface_config, &bind_group_layout, &uniform_buffer);
+
+        Self {
+            fxaa_pipeline: create_pipeline("FXAA Pipeline", &fxaa_shader, "fs_main", SCENE_FORMAT),
+            bloom_pipeline: create_pipeline("Bloom Pipeline", &shader, "fs_bloom", SCENE_FORMAT),
+            saturation_pipeline: create_pipeline(
+                "Saturation Pipeline",
+                &shader,
+                "fs_saturation",
+                SCENE_FORMAT,
+            ),
+            vignette_pipeline: create_pipeline(
+                "Vignette Pipeline",
+                &shader,
+                "fs_vignette",
+                SCENE_FORMAT,
+            ),
+            gamma_pipeline: create_pipeline("Gamma Pipeline", &shader, "fs_gamma", SCENE_FORMAT),
+            underwater_pipeline: create_pipeline(
+                "Underwater Pipeline",
+                &shader,
+                "fs_underwater",
+                SCENE_FORMAT,
+            ),
+            present_pipeline: create_pipeline(
+                "Present Pipeline",
+                &shader,
+                "fs_present",
+                surface_config.format,
+            ),
+            bind_group_layout,
+            uniform_buffer,
+            targets,
+            bind_groups,
+        }
+    }
+
+    /// Creates the textures the effects are drawn between, and the bind groups reading them.
+    fn create_targets(
+        device: &Device,
+        surface_config: &SurfaceConfiguration,
+        layout: &BindGroupLayout,
+        uniform_buffer: &Buffer,
+    ) -> ([Texture; 2], [BindGroup; 2]) {
+        let targets =
+            [(); 2].map(|_| Texture::create_render_target(device, surface_config, SCENE_FORMAT));
+
+        let bind_groups = [0, 1].map(|index| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Post Bind Group"),
+                layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&targets[index].view),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&targets[index].sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+
+        (targets, bind_groups)
+    }
+
+    /// Recreates the textures the effects are drawn between, after the surface has been resized.
+    pub fn resize(&mut self, device: &Device, surface_config: &SurfaceConfiguration) {
+        (self.targets, self.bind_groups) = Self::create_targets(
+            device,
+            surface_config,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+        );
+    }
+
+    /// Uploads the settings of the given effects, to be drawn this frame.
+    pub fn update(&self, queue: &Queue, effects: impl Iterator<Item = PostEffect>) {
+        let mut uniform = PostUniform::default();
+
+        for effect in effects {
+            match effect {
+                PostEffect::Fxaa => {}
+                PostEffect::Bloom {
+                    threshold,
+                    intensity,
+                } => {
+                    uniform.bloom_threshold = threshold;
+                    uniform.bloom_intensity = intensity;
+                }
+                PostEffect::Saturation(saturation) => uniform.saturation = saturation,
+                PostEffect::Vignette(vignette) => uniform.vignette = vignette,
+                PostEffect::Gamma(gamma) => uniform.gamma = gamma.max(0.01),
+                PostEffect::Underwater(time) => uniform.time = time,
+            }
+        }
+
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    /// Returns the view the scene should be rendered into.
+    pub fn scene_view(&self) -> &TextureView {
+        &self.targets[0].view
+    }
+
+    /// Returns the view the effect at the given `step` of the chain (starting from 0) should
+    /// draw into.
+    pub fn target_view(&self, step: usize) -> &TextureView {
+        &self.targets[(step + 1) % 2].view
+    }
+
+    /// Draws the given effect at the given `step` of the chain onto the given view, reading the
+    /// output of the step before it (or the scene).
+    pub fn draw(
+        &self,
+        encoder: &mut CommandEncoder,
+        effect: PostEffect,
+        step: usize,
+        view: &TextureView,
+    ) {
+        let pipeline = match effect {
+            PostEffect::Fxaa => &self.fxaa_pipeline,
+            PostEffect::Bloom { .. } => &self.bloom_pipeline,
+            PostEffect::Saturation(_) => &self.saturation_pipeline,
+            PostEffect::Vignette(_) => &self.vignette_pipeline,
+            PostEffect::Gamma(_) => &self.gamma_pipeline,
+            PostEffect::Underwater(_) => &self.underwater_pipeline,
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some(effect.name()),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.bind_groups[step % 2], &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Presents the result of the chain after the given number of `steps` (or the scene, if
+    /// none were drawn) onto the given view of the surface.
+    pub fn present(&self, encoder: &mut CommandEncoder, steps: usize, view: &TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("present"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::BLACK),
+                    store: StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.present_pipeline);
+        render_pass.set_bind_group(0, &self.bind_groups[steps % 2], &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,130 @@
+// The output of the previous effect (or the scene) being processed.
+@group(0) @binding(0) var scene: texture_2d<f32>;
+@group(0) @binding(1) var scene_sampler: sampler;
+
+struct PostUniform {
+	// How bright a color must be to glow, and how strongly it does.
+	bloom_threshold: f32,
+	bloom_intensity: f32,
+	// How colorful the scene is, where 0 is greyscale.
+	saturation: f32,
+	// How much the corners of the screen are darkened.
+	vignette: f32,
+	// The gamma the midtones are adjusted by.
+	gamma: f32,
+	// The seconds passed, animating the underwater wobble.
+	time: f32,
+}
+
+@group(0) @binding(2) var<uniform> post: PostUniform;
+
+// How far (as a fraction of the screen) and how quickly the screen wobbles underwater.
+const UNDERWATER_WOBBLE: f32 = 0.003;
+const UNDERWATER_WOBBLE_SPEED: f32 = 2.0;
+
+// The number of directions, and of rings of samples, the glow is gathered from.
+const BLOOM_DIRECTIONS: i32 = 12;
+const BLOOM_RINGS: i32 = 3;
+// The radius of the outermost ring of samples, in pixels.
+const BLOOM_RADIUS: f32 = 24.0;
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	// The position of the pixel on the screen, from (0, 0) at the top left to (1, 1).
+	@location(0) uv: vec2<f32>,
+}
+
+// Covers the screen with a single triangle.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_id: u32) -> VertexOutput {
+	var out: VertexOutput;
+
+	let uv = vec2<f32>(f32((vertex_id << 1u) & 2u), f32(vertex_id & 2u));
+	out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+	out.uv = uv;
+
+	return out;
+}
+
+fn sample_scene(uv: vec2<f32>) -> vec4<f32> {
+	return textureSampleLevel(scene, scene_sampler, uv, 0.0);
+}
+
+fn luma(color: vec3<f32>) -> f32 {
+	return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+@fragment
+fn fs_bloom(input: VertexOutput) -> @location(0) vec4<f32> {
+	let color = sample_scene(input.uv);
+	let texel = 1.0 / vec2<f32>(textureDimensions(scene));
+
+	// gather the parts of the surroundings brighter than the threshold, nearer ones weighing more
+	var glow = vec3<f32>(0.0);
+	var total = 0.0;
+
+	for (var ring = 1; ring <= BLOOM_RINGS; ring++) {
+		let radius = BLOOM_RADIUS * f32(ring) / f32(BLOOM_RINGS);
+		let weight = 1.0 / f32(ring);
+
+		for (var i = 0; i < BLOOM_DIRECTIONS; i++) {
+			// offset each ring, so the samples don't line up
+			let angle = (f32(i) + 0.5 * f32(ring)) * 6.2831853 / f32(BLOOM_DIRECTIONS);
+			let offset = vec2<f32>(cos(angle), sin(angle)) * radius * texel;
+			let bright = sample_scene(input.uv + offset).rgb;
+
+			glow += max(bright - post.bloom_threshold, vec3<f32>(0.0)) * weight;
+			total += weight;
+		}
+	}
+
+	return vec4<f32>(color.rgb + glow / total * post.bloom_intensity, color.a);
+}
+
+@fragment
+fn fs_saturation(input: VertexOutput) -> @location(0) vec4<f32> {
+	let color = sample_scene(input.uv);
+	let grey = vec3<f32>(luma(color.rgb));
+
+	return vec4<f32>(max(mix(grey, color.rgb, post.saturation), vec3<f32>(0.0)), color.a);
+}
+
+@fragment
+fn fs_vignette(input: VertexOutput) -> @location(0) vec4<f32> {
+	let color = sample_scene(input.uv);
+
+	// 0 at the center of the screen, and 1 in its corners
+	let distance = length(input.uv - 0.5) * 1.4142135;
+	let darkening = post.vignette * smoothstep(0.4, 1.0, distance);
+
+	return vec4<f32>(color.rgb * (1.0 - darkening), color.a);
+}
+
+@fragment
+fn fs_gamma(input: VertexOutput) -> @location(0) vec4<f32> {
+	let color = sample_scene(input.uv);
+
+	return vec4<f32>(pow(color.rgb, vec3<f32>(1.0 / post.gamma)), color.a);
+}
+
+@fragment
+fn fs_underwater(input: VertexOutput) -> @location(0) vec4<f32> {
+	let time = post.time * UNDERWATER_WOBBLE_SPEED;
+	let wobble = vec2<f32>(
+		sin(input.uv.y * 30.0 + time),
+		cos(input.uv.x * 25.0 + time * 0.8),
+	);
+
+	// stay on the screen at its edges
+	let uv = clamp(input.uv + wobble * UNDERWATER_WOBBLE, vec2<f32>(0.0), vec2<f32>(1.0));
+
+	return sample_scene(uv);
+}
+
+// Copies the processed scene onto the surface, clamping colors brighter than white.
+@fragment
+fn fs_present(input: VertexOutput) -> @location(0) vec4<f32> {
+	let color = sample_scene(input.uv);
+
+	return vec4<f32>(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)), 1.0);
+}
@@ -0,0 +1,65 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use glam::ivec2;
+use rayon::prelude::*;
+
+use crate::{
+    chunk::*,
+    dimension::Dimension,
+    generator::WorldgenConfig,
+    storage::{self, Compression, WorldInfo},
+};
+
+/// How many chunks are generated between each progress report.
+const PROGRESS_INTERVAL: usize = 256;
+
+/// Generates and saves every chunk of a dimension within `radius` chunks of the origin that has
+/// not already been saved, the way the world described by `world` was created, and printing
+/// progress along the way.
+pub fn pregenerate(
+    directory: &Path,
+    dimension: Dimension,
+    world: &WorldInfo,
+    compression: Compression,
+    radius: usize,
+) -> anyhow::Result<()> {
+    let generator = world
+        .world_type
+        .generator(dimension, world.seed, &WorldgenConfig::load()?);
+    let radius = radius as i32;
+
+    let positions = (-radius..=radius)
+        .flat_map(|x| (-radius..=radius).map(move |z| ivec2(x, z)))
+        .filter(|position| !storage::chunk_path(directory, dimension, *position).exists())
+        .collect::<Vec<_>>();
+
+    let total = positions.len();
+    let completed = AtomicUsize::new(0);
+    let start = Instant::now();
+
+    println!("generating {total} chunks");
+
+    positions.into_par_iter().try_for_each(|position| {
+        let mut chunk = Chunk::new(position);
+        generator.generate(&mut chunk);
+
+        storage::write_chunk(directory, dimension, &chunk, compression)?;
+
+        let completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if completed.is_multiple_of(PROGRESS_INTERVAL) || completed == total {
+            let percent = completed as f32 / total as f32 * 100.0;
+            println!("{completed}/{total} chunks ({percent:.1}%)");
+        }
+
+        anyhow::Ok(())
+    })?;
+
+    println!("done in {:.2}s", start.elapsed().as_secs_f32());
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+
+use anyhow::{bail, Context};
+use wgpu::*;
+
+/// The shader files other shaders can include, by name.
+const SHADER_FILES: &[(&str, &str)] = &[
+    ("camera.wgsl", include_str!("camera.wgsl")),
+    ("environment.wgsl", include_str!("environment.wgsl")),
+    ("fog.wgsl", include_str!("fog.wgsl")),
+];
+
+/// Resolves the directives of a WGSL shader, each on a line of its own:
+///
+/// - `#include "file.wgsl"` pastes in one of the `SHADER_FILES`, unless it has already been
+///   included, so shared declarations can be included by several files.
+/// - `#define FLAG` sets a flag, along with the flags given in `defines`.
+/// - `#ifdef FLAG` and `#ifndef FLAG` keep the lines up to the matching `#else` or `#endif` only
+///   if the flag is (or isn't) set, with the lines after `#else` kept otherwise.
+pub fn preprocess(source: &str, defines: &[&str]) -> anyhow::Result<String> {
+    let mut preprocessor = Preprocessor {
+        defines: defines.iter().map(|define| define.to_string()).collect(),
+        included: HashSet::new(),
+        output: String::new(),
+    };
+
+    preprocessor.process("shader", source)?;
+
+    Ok(preprocessor.output)
+}
+
+/// Creates a shader module from the given source, once its directives are resolved with the
+/// given flags set. Like `include_wgsl!`, an invalid shader is a bug, so this panics if the
+/// directives can't be resolved.
+pub fn create_shader_module(
+    device: &Device,
+    label: &str,
+    source: &str,
+    defines: &[&str],
+) -> ShaderModule {
+    let source = preprocess(source, defines)
+        .unwrap_or_else(|error| panic!("could not preprocess {label}: {error:?}"));
+
+    device.create_shader_module(ShaderModuleDescriptor {
+        label: Some(label),
+        source: ShaderSource::Wgsl(source.into()),
+    })
+}
+
+/// The state of a shader being preprocessed.
+struct Preprocessor {
+    /// The flags that have been set.
+    defines: HashSet<String>,
+    /// The names of the files that have already been included.
+    included: HashSet<String>,
+    /// The lines kept so far.
+    output: String,
+}
+
+impl Preprocessor {
+    /// Resolves the directives of the file with the given name and source, adding the lines
+    /// kept to the output.
+    fn process(&mut self, name: &str, source: &str) -> anyhow::Result<()> {
+        // whether the lines within each of the enclosing conditions are kept
+        let mut conditions: Vec<bool> = Vec::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let active = conditions.iter().all(|&kept| kept);
+
+            let Some(directive) = line.trim().strip_prefix('#') else {
+                if active {
+                    self.output.push_str(line);
+                    self.output.push('\n');
+                }
+
+                continue;
+            };
+
+            let (directive, argument) = directive
+                .split_once(char::is_whitespace)
+                .map(|(directive, argument)| (directive, argument.trim()))
+                .unwrap_or((directive, ""));
+            let location = || format!("{name}:{}", index + 1);
+
+            match directive {
+                "ifdef" => conditions.push(self.defines.contains(argument)),
+                "ifndef" => conditions.push(!self.defines.contains(argument)),
+                "else" => {
+                    let kept = conditions
+                        .last_mut()
+                        .with_context(|| format!("#else without #ifdef at {}", location()))?;
+                    *kept = !*kept;
+                }
+                "endif" => {
+                    conditions
+                        .pop()
+                        .with_context(|| format!("#endif without #ifdef at {}", location()))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    self.defines.insert(argument.to_string());
+                }
+                "include" => {
+                    let file = argument.trim_matches('"');
+                    let (_, source) = SHADER_FILES
+                        .iter()
+                        .find(|(name, _)| *name == file)
+                        .with_context(|| format!("unknown include {file} at {}", location()))?;
+
+                    if self.included.insert(file.to_string()) {
+                        self.process(file, source)?;
+                    }
+                }
+                _ => bail!("unknown directive #{directive} at {}", location()),
+            }
+        }
+
+        if !conditions.is_empty() {
+            bail!("#ifdef without #endif in {name}");
+        }
+
+        Ok(())
+    }
+}
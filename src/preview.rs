@@ -0,0 +1,128 @@
+use egui::{Color32, ColorImage, Context, TextureHandle, TextureOptions};
+use glam::ivec2;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    biome::{Biome, BiomeMap},
+    chunk::{create_noise_generator, terrain_height, CHUNK_HEIGHT, SEA_LEVEL},
+    generator::WorldgenConfig,
+};
+
+/// Options for the worldgen preview, part of the worldgen config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreviewConfig {
+    /// The radius (in voxels) of the area around the origin that is previewed.
+    pub radius: usize,
+    /// How many voxels wide the area each pixel of the preview covers is.
+    pub voxels_per_pixel: usize,
+}
+
+impl Default for PreviewConfig {
+    fn default() -> Self {
+        Self {
+            radius: 1024,
+            voxels_per_pixel: 8,
+        }
+    }
+}
+
+/// A top down view of the terrain and biomes around the origin, sampled straight from the noise
+/// functions the heightmap terrain is generated from, without generating or meshing any chunks.
+/// Decorations, caves and erosion are not shown.
+pub struct WorldgenPreview {
+    /// The height of the terrain, shaded from dark to light, with water drawn in blue.
+    heightmap: ColorImage,
+    /// The biome of the terrain.
+    biome_map: ColorImage,
+    /// The textures the images are uploaded to, once they have been drawn.
+    textures: Option<(TextureHandle, TextureHandle)>,
+}
+
+impl WorldgenPreview {
+    /// Samples the preview of the terrain generated with the given seed and worldgen config.
+    pub fn new(seed: u32, worldgen: &WorldgenConfig) -> Self {
+        let noise = create_noise_generator(seed, &worldgen.terrain);
+        let biomes = BiomeMap::new(seed, &worldgen.biomes);
+
+        let step = worldgen.preview.voxels_per_pixel.max(1) as i32;
+        let pixels = (worldgen.preview.radius as i32 * 2 / step).max(1);
+        let start = -(pixels * step) / 2;
+
+        let columns = (0..pixels * pixels)
+            .into_par_iter()
+            .map(|index| {
+                let position = ivec2(start, start) + ivec2(index % pixels, index / pixels) * step;
+                let height = terrain_height(&noise, &biomes, position.x, position.y);
+
+                (height, biomes.biome_at(position.x, position.y, height))
+            })
+            .collect::<Vec<_>>();
+
+        let size = [pixels as usize; 2];
+
+        Self {
+            heightmap: ColorImage {
+                size,
+                pixels: columns
+                    .iter()
+                    .map(|(height, _)| height_color(*height))
+                    .collect(),
+            },
+            biome_map: ColorImage {
+                size,
+                pixels: columns
+                    .iter()
+                    .map(|(_, biome)| biome_color(*biome))
+                    .collect(),
+            },
+            textures: None,
+        }
+    }
+
+    /// Draws the heightmap and biome map side by side.
+    pub fn show(&mut self, ctx: &Context, ui: &mut egui::Ui) {
+        let (heightmap, biome_map) = self.textures.get_or_insert_with(|| {
+            (
+                ctx.load_texture(
+                    "worldgen heightmap",
+                    self.heightmap.clone(),
+                    TextureOptions::NEAREST,
+                ),
+                ctx.load_texture(
+                    "worldgen biomes",
+                    self.biome_map.clone(),
+                    TextureOptions::NEAREST,
+                ),
+            )
+        });
+
+        let size = egui::vec2(256.0, 256.0);
+
+        ui.horizontal(|ui| {
+            ui.image((heightmap.id(), size));
+            ui.image((biome_map.id(), size));
+        });
+    }
+}
+
+/// Returns the color a column of the given height is drawn in on the heightmap.
+fn height_color(height: usize) -> Color32 {
+    if height < SEA_LEVEL {
+        let depth = (SEA_LEVEL - height) as f32 / SEA_LEVEL as f32;
+        return Color32::from_rgb(20, 60, (220.0 - depth * 140.0) as u8);
+    }
+
+    let shade = (height as f32 / CHUNK_HEIGHT as f32 * 255.0) as u8;
+    Color32::from_gray(shade)
+}
+
+/// Returns the color a biome is drawn in on the biome map.
+fn biome_color(biome: Biome) -> Color32 {
+    match biome {
+        Biome::Plains => Color32::from_rgb(96, 168, 72),
+        Biome::Desert => Color32::from_rgb(222, 204, 140),
+        Biome::Tundra => Color32::from_rgb(232, 240, 248),
+    }
+}
@@ -0,0 +1,76 @@
+use glam::{DVec3, IVec3, Vec3};
+
+/// The voxel a ray hit, and the face it entered through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RaycastHit {
+    /// The position of the voxel that was hit.
+    pub position: IVec3,
+    /// The normal of the face the ray entered the voxel through, pointing back towards the
+    /// ray's origin (zero if the ray started inside the voxel).
+    pub normal: IVec3,
+}
+
+impl RaycastHit {
+    /// Returns the position of the voxel the ray passed through just before hitting, against the
+    /// face it entered through. It wasn't solid, which makes it where a voxel placed against
+    /// that face goes. None if the ray started inside the voxel it hit.
+    pub fn adjacent(&self) -> Option<IVec3> {
+        (self.normal != IVec3::ZERO).then(|| self.position + self.normal)
+    }
+}
+
+/// Walks the voxels along a ray, starting at `origin`, in order, returning the first one
+/// `is_solid` accepts within `max_distance`. Voxels are centered on integer positions, so the
+/// voxel at `(x, y, z)` spans half a voxel in each direction around it.
+pub fn raycast(
+    origin: DVec3,
+    direction: Vec3,
+    max_distance: f32,
+    is_solid: impl Fn(IVec3) -> bool,
+) -> Option<RaycastHit> {
+    let direction = direction.try_normalize()?;
+
+    // shifted so that each voxel spans from its position to the next integer
+    let start = origin + 0.5;
+    let mut position = start.floor().as_ivec3();
+    // only the position within the starting voxel needs to be precise from here on
+    let start = (start - start.floor()).as_vec3();
+    let mut normal = IVec3::ZERO;
+
+    let step = direction.signum().as_ivec3();
+    // the distance along the ray between crossing two voxel boundaries on each axis
+    let delta = direction.recip().abs();
+
+    // the distance along the ray to the first boundary crossed on each axis
+    let next_boundary = Vec3::select(direction.cmpgt(Vec3::ZERO), 1.0 - start, start);
+    // never crossing a boundary on axes the ray runs parallel to
+    let mut distance_to = Vec3::select(
+        direction.cmpeq(Vec3::ZERO),
+        Vec3::INFINITY,
+        next_boundary * delta,
+    );
+
+    loop {
+        if is_solid(position) {
+            return Some(RaycastHit { position, normal });
+        }
+
+        // step across the nearest boundary
+        let axis = if distance_to.x < distance_to.y && distance_to.x < distance_to.z {
+            0
+        } else if distance_to.y < distance_to.z {
+            1
+        } else {
+            2
+        };
+
+        if distance_to[axis] > max_distance {
+            return None;
+        }
+
+        position[axis] += step[axis];
+        normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+        distance_to[axis] += delta[axis];
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::screenshot::{Frame, FrameSink};
+
+/// The name of the config file the recording options are loaded from.
+pub const RECORDING_CONFIG_FILE: &str = "recording.toml";
+
+/// The directory recordings written as numbered PNGs are saved to, each in a directory of its
+/// own.
+pub const RECORDING_DIRECTORY: &str = "recordings";
+
+/// The most frames read back but not yet written, after which the app waits for the writer to
+/// catch up rather than holding on to more of them.
+const MAX_QUEUED_FRAMES: usize = 8;
+
+/// Options for recording frame sequences, loaded from `RECORDING_CONFIG_FILE`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+    /// The number of frames per second of the recording. While recording, every frame advances
+    /// the world by the same step of time, however long it takes to draw and capture.
+    pub frame_rate: u32,
+    /// A command (its program, then its arguments) the raw RGBA pixels of every frame are piped
+    /// into, such as a video encoder, instead of writing them as numbered PNGs. `{width}`,
+    /// `{height}` and `{frame_rate}` within the arguments are replaced with those of the
+    /// recording.
+    pub encoder: Option<Vec<String>>,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            frame_rate: 60,
+            encoder: None,
+        }
+    }
+}
+
+/// A recording of every frame drawn while it is active, written in order on a thread of its
+/// own. Stopping the recording (by dropping it) lets the writer finish the frames still being
+/// read back.
+pub struct Recording {
+    /// Sends frames to the writer.
+    sender: SyncSender<Frame>,
+    /// The size (in pixels) of every frame.
+    width: u32,
+    height: u32,
+}
+
+impl Recording {
+    /// Starts recording frames of the given size, writing them as the config describes.
+    pub fn start(config: &RecordingConfig, width: u32, height: u32) -> anyhow::Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel(MAX_QUEUED_FRAMES);
+
+        match &config.encoder {
+            Some(command) => {
+                let [program, args @ ..] = command.as_slice() else {
+                    bail!("the encoder command is empty");
+                };
+
+                let args = args.iter().map(|arg| {
+                    arg.replace("{width}", &width.to_string())
+                        .replace("{height}", &height.to_string())
+                        .replace("{frame_rate}", &config.frame_rate.to_string())
+                });
+
+                let mut child = Command::new(program)
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .context(format!("running {program:?}"))?;
+                let stdin = child.stdin.take().context("opening the encoder's input")?;
+
+                println!("recording into {program:?}");
+
+                thread::spawn(move || {
+                    if let Err(error) = pipe_frames(receiver, stdin) {
+                        eprintln!("could not pipe the recording: {error:?}");
+                    }
+
+                    // the encoder finishes once its input is closed
+                    if let Err(error) = child.wait() {
+                        eprintln!("could not finish the recording: {error:?}");
+                    }
+                });
+            }
+
+            None => {
+                let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+                let directory = PathBuf::from(RECORDING_DIRECTORY).join(timestamp.to_string());
+
+                fs::create_dir_all(&directory).context(format!("creating {directory:?}"))?;
+                println!("recording to {directory:?}");
+
+                thread::spawn(move || {
+                    if let Err(error) = write_frames(receiver, directory) {
+                        eprintln!("could not write the recording: {error:?}");
+                    }
+                });
+            }
+        }
+
+        Ok(Self {
+            sender,
+            width,
+            height,
+        })
+    }
+
+    /// Returns the sink the next frame is captured into, to be added to the recording.
+    pub fn sink(&self) -> FrameSink {
+        FrameSink::Recording(self.sender.clone())
+    }
+
+    /// Returns whether the frames of the recording have the given size.
+    pub fn has_size(&self, width: u32, height: u32) -> bool {
+        self.width == width && self.height == height
+    }
+}
+
+/// Writes every frame received as a PNG within the directory, numbered in order.
+fn write_frames(receiver: Receiver<Frame>, directory: PathBuf) -> anyhow::Result<()> {
+    for (index, frame) in receiver.into_iter().enumerate() {
+        let path = directory.join(format!("{index:06}.png"));
+
+        frame
+            .to_image()?
+            .save(&path)
+            .context(format!("writing {path:?}"))?;
+    }
+
+    Ok(())
+}
+
+/// Writes the raw pixels of every frame received into the encoder's input.
+fn pipe_frames(receiver: Receiver<Frame>, mut input: impl Write) -> anyhow::Result<()> {
+    for frame in receiver {
+        input.write_all(frame.to_image()?.as_raw())?;
+    }
+
+    Ok(())
+}
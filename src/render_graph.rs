@@ -0,0 +1,105 @@
+use wgpu::*;
+
+use crate::gpu_timer::GpuTimer;
+
+/// A texture the passes of a frame draw into and read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attachment {
+    /// The color of the scene, before it is presented.
+    Scene,
+    /// The depth buffer of the scene.
+    Depth,
+    /// The surface the frame is presented on.
+    Surface,
+}
+
+/// The views of the attachments of the frame being drawn.
+pub struct RenderTargets<'a> {
+    pub scene: &'a TextureView,
+    pub depth: &'a TextureView,
+    pub surface: &'a TextureView,
+}
+
+/// Records a pass into the frame's encoder, drawing into the given targets.
+type RecordPass<'a> = Box<dyn FnOnce(&mut CommandEncoder, &RenderTargets) + 'a>;
+
+/// A named pass of a frame, along with the attachments it reads from and writes to.
+struct GraphPass<'a> {
+    name: &'static str,
+    reads: &'static [Attachment],
+    writes: &'static [Attachment],
+    record: RecordPass<'a>,
+}
+
+/// The passes drawing a frame, recorded in the order they were added. Each pass declares the
+/// attachments it reads and writes, so passes can be added, removed or moved around without
+/// one silently reading an attachment before anything has been drawn into it.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    passes: Vec<GraphPass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    /// Creates a graph without any passes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a pass after the ones already added.
+    pub fn add_pass(
+        &mut self,
+        name: &'static str,
+        reads: &'static [Attachment],
+        writes: &'static [Attachment],
+        record: impl FnOnce(&mut CommandEncoder, &RenderTargets) + 'a,
+    ) -> &mut Self {
+        self.passes.push(GraphPass {
+            name,
+            reads,
+            writes,
+            record: Box::new(record),
+        });
+
+        self
+    }
+
+    /// Records every pass into the encoder, in order, timing each pass with the timer if one
+    /// is given.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a pass reads an attachment no earlier pass has written to.
+    pub fn execute(
+        self,
+        encoder: &mut CommandEncoder,
+        targets: &RenderTargets,
+        mut timer: Option<&mut GpuTimer>,
+    ) {
+        let mut written = Vec::new();
+
+        for pass in self.passes {
+            if let Some(attachment) = pass.reads.iter().find(|read| !written.contains(*read)) {
+                panic!(
+                    "render pass {} reads {attachment:?} before any pass has written to it",
+                    pass.name
+                );
+            }
+
+            written.extend_from_slice(pass.writes);
+
+            encoder.push_debug_group(pass.name);
+
+            if let Some(timer) = &mut timer {
+                timer.begin_pass(encoder, pass.name);
+            }
+
+            (pass.record)(encoder, targets);
+
+            if let Some(timer) = &mut timer {
+                timer.end_pass(encoder);
+            }
+
+            encoder.pop_debug_group();
+        }
+    }
+}
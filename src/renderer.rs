@@ -1,21 +1,60 @@
-use std::{ops::Range, sync::Arc};
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use egui::Context;
-use glam::Mat4;
+use glam::{DVec3, IVec3, Vec3};
 
-use wgpu::*;
-use winit::{dpi::PhysicalSize, window::Window};
+use wgpu::{util::DrawIndexedIndirectArgs, *};
+use winit::{dpi::PhysicalSize, event::WindowEvent, window::Window};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use crate::{
-    asset_loader::load_textures,
-    camera::Camera,
+    adapter::{self, GraphicsConfig},
+    asset_loader::{load_textures, VoxelTextures},
+    camera::{Camera, Frustum},
+    chunk::{CHUNK_HEIGHT, CHUNK_WIDTH},
+    debug_lines::{DebugLine, DebugLines},
     egui_renderer::EguiRenderer,
-    model::{Mesh, MeshVertex},
+    environment::Environment,
+    god_rays::GodRays,
+    gpu_cull::GpuCuller,
+    gpu_timer::{GpuTimer, TIMER_FEATURES},
+    lighting::{LightGrid, PointLight},
+    mesh_pool::{MeshPool, PoolBuffer},
+    mesher::MeshLayer,
+    model::{self, InstancedMesh, Mesh, MeshInstance},
+    occlusion::OcclusionCuller,
+    outline::Outline,
+    pipeline_cache::{PipelineCache, PipelineKey, ShaderVariant},
+    post::{self, PostEffect, PostPass, PostProcessor, SCENE_FORMAT},
+    preprocessor,
+    recording::{Recording, RecordingConfig},
+    render_graph::{Attachment, RenderGraph, RenderTargets},
+    screenshot::{FrameSink, ScreenshotCapture},
+    sky::Sky,
+    ssao::Ssao,
     texture::Texture,
 };
 
+/// The number of mesh instances the instance buffer is created with.
+const INITIAL_INSTANCE_CAPACITY: usize = 1024;
+
+/// The features used to draw every mesh with a single call when the adapter supports them.
+/// Without them, each mesh is drawn with its own call.
+const MULTI_DRAW_FEATURES: Features =
+    Features::MULTI_DRAW_INDIRECT.union(Features::INDIRECT_FIRST_INSTANCE);
+
+/// The most validation errors kept to be shown in the debug ui, after which the oldest are
+/// dropped.
+const MAX_VALIDATION_ERRORS: usize = 16;
+
 /// A trait to be implemented by a render pass to render any arbitrary object.
 pub trait Render<'a, T> {
     /// Render a single instance of this value.
@@ -27,68 +66,355 @@ pub trait Render<'a, T> {
     fn draw_object_instanced(&mut self, value: &'a T, instances: Range<u32>);
 }
 
+/// The passes chunk meshes are drawn in, each with its own pipeline from the `PipelineCache`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TerrainPass {
+    /// Writes only the depth of the opaque meshes, so the opaque pass only shades the fragments
+    /// that end up visible.
+    DepthPrepass,
+    /// Draws the opaque meshes.
+    Opaque,
+    /// Draws the edges of the opaque meshes' triangles, in place of the opaque pass.
+    Wireframe,
+    /// Blends the transparent meshes (water) over the opaque ones.
+    Transparent,
+}
+
+impl TerrainPass {
+    /// Returns the key of the pipeline the pass is drawn with.
+    fn pipeline_key(self) -> PipelineKey {
+        let (variant, polygon_mode, blend) = match self {
+            Self::DepthPrepass => (ShaderVariant::DEPTH_ONLY, PolygonMode::Fill, None),
+            Self::Opaque => (
+                ShaderVariant::NONE,
+                PolygonMode::Fill,
+                Some(BlendState::REPLACE),
+            ),
+            Self::Wireframe => (
+                ShaderVariant::NONE,
+                PolygonMode::Line,
+                Some(BlendState::REPLACE),
+            ),
+            Self::Transparent => (
+                ShaderVariant::WATER,
+                PolygonMode::Fill,
+                Some(BlendState::ALPHA_BLENDING),
+            ),
+        };
+
+        PipelineKey {
+            variant,
+            polygon_mode,
+            blend,
+            sample_count: 1,
+        }
+    }
+
+    /// Returns the key of the pipeline instanced models are drawn with in the pass.
+    fn instanced_pipeline_key(self) -> PipelineKey {
+        let key = self.pipeline_key();
+
+        PipelineKey {
+            variant: key.variant | ShaderVariant::INSTANCED,
+            ..key
+        }
+    }
+}
+
+/// What the renderer reports to the ui while drawing a frame.
+pub struct RenderStats<'a> {
+    /// The most recent validation errors raised while creating GPU resources.
+    pub validation_errors: &'a [String],
+    /// The time the GPU spent drawing each pass of a recent frame, in order, or None if the
+    /// adapter can't time passes.
+    pub pass_timings: Option<&'a [(&'static str, Duration)]>,
+}
+
+/// The texture a frame is drawn into.
+enum FrameTarget {
+    /// The surface's texture, presented once the frame has been drawn.
+    Surface(SurfaceTexture),
+    /// A texture the given number of times larger than the surface, drawn into for a high
+    /// resolution screenshot.
+    Screenshot(wgpu::Texture, u32),
+}
+
+impl FrameTarget {
+    /// Returns the texture the frame is drawn into.
+    fn texture(&self) -> &wgpu::Texture {
+        match self {
+            Self::Surface(output) => &output.texture,
+            Self::Screenshot(texture, _) => texture,
+        }
+    }
+
+    /// Returns how many times larger than the surface the texture is.
+    fn scale(&self) -> u32 {
+        match self {
+            Self::Surface(_) => 1,
+            Self::Screenshot(_, scale) => *scale,
+        }
+    }
+}
+
+/// Options for the effects drawn by the renderer, changed from the render settings panel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSettings {
+    /// Whether shafts of light are drawn streaming from the sun past the terrain.
+    pub god_rays: bool,
+    /// Whether creases and concavities of the terrain are darkened with screen-space ambient
+    /// occlusion.
+    pub ssao: bool,
+    /// The effects drawn over the scene before presenting, in order.
+    pub post: Vec<PostPass>,
+    /// Whether the depth of the opaque terrain is drawn before it is shaded, so hidden fragments
+    /// are skipped by the depth test.
+    pub depth_prepass: bool,
+    /// Whether the camera carries a point light with it, lighting up caves and the night.
+    pub lantern: bool,
+    /// Whether only the edges of the opaque terrain's triangles are drawn, to debug its meshes.
+    pub wireframe: bool,
+    /// Whether the borders of the chunks around the player, and of its sections, are drawn.
+    pub chunk_borders: bool,
+    /// Whether the chunks around the player are marked with how far along loading they are.
+    pub chunk_load_states: bool,
+    /// The most samples taken of the voxel textures along surfaces seen at a glancing angle,
+    /// where 1 turns anisotropic filtering off (being 1, 2, 4, 8 or 16).
+    pub anisotropy: u16,
+    /// How frames are presented, which caps the frame rate to the display's refresh rate when
+    /// vsync is used.
+    pub present_mode: PresentMode,
+    /// The present modes the surface supports, being the choices for `present_mode`. Filled in
+    /// from `Renderer::present_modes` rather than chosen by the user.
+    pub supported_present_modes: Vec<PresentMode>,
+    /// Whether screenshots include the ui drawn over the frame.
+    pub screenshot_ui: bool,
+    /// How many times larger than the window high resolution screenshots are.
+    pub screenshot_scale: u32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            god_rays: true,
+            ssao: true,
+            post: post::default_stack(),
+            depth_prepass: false,
+            lantern: false,
+            wireframe: false,
+            chunk_borders: false,
+            chunk_load_states: false,
+            anisotropy: 8,
+            present_mode: PresentMode::AutoVsync,
+            supported_present_modes: vec![PresentMode::AutoVsync],
+            screenshot_ui: false,
+            screenshot_scale: 4,
+        }
+    }
+}
+
 pub struct Renderer {
     /// The actual physical device responsible for rendering things (most likely the GPU).
     pub device: wgpu::Device,
     /// The queue of commands being staged to be sent to the `device`.
-    queue: wgpu::Queue,
-    /// The series of steps that data takes while moving through the rendering process.
-    pipeline: wgpu::RenderPipeline,
+    pub queue: wgpu::Queue,
+    /// Set once the `device` has been lost (such as after the driver is reset), after which the
+    /// renderer has to be recreated.
+    device_lost: Arc<AtomicBool>,
+    /// The pipelines chunk meshes are drawn with, one for each `TerrainPass` drawn so far.
+    pipelines: PipelineCache,
+    /// The most recent validation errors raised while creating GPU resources, shown in the
+    /// debug ui.
+    validation_errors: Vec<String>,
     /// The depth texture is used to properly sort the pixels while rendering and fix z-index.
     depth_texture: Texture,
 
-    /// A reference to the surface being rendered onto.
-    surface: wgpu::Surface<'static>,
+    /// The instance the `device` was created from, kept to create another once it is lost.
+    instance: Arc<wgpu::Instance>,
+    /// Which GPU the `device` was picked from, kept to pick the same one once it is lost.
+    graphics: GraphicsConfig,
+    /// A reference to the surface being rendered onto, shared with the renderer replacing this
+    /// one if the `device` is lost.
+    surface: Arc<wgpu::Surface<'static>>,
     /// The configuration of the `surface`.
     surface_config: wgpu::SurfaceConfiguration,
+    /// The modes the `surface` can present frames with.
+    present_modes: Vec<PresentMode>,
 
     /// The renderer for egui.
     egui_renderer: crate::egui_renderer::EguiRenderer,
 
-    /// The meshes currently being rendered.
-    meshes: Vec<crate::model::Mesh>,
+    /// The shared buffers every mesh's vertices and indices are stored in.
+    pub mesh_pool: Arc<MeshPool>,
 
     /// A uniform buffer to hold the camera's view-projection matrix.
     camera_uniform: wgpu::Buffer,
     /// The uniform bind group to which the camera's uniform is stored.
     camera_bind_group: wgpu::BindGroup,
+    /// A uniform buffer to hold the lighting of the world.
+    environment_uniform: wgpu::Buffer,
+    /// The uniform bind group to which the environment's uniform is stored.
+    environment_bind_group: wgpu::BindGroup,
+    /// The point lights around the camera, sorted into clusters for the terrain to be shaded
+    /// with.
+    light_grid: LightGrid,
+    /// The color the screen is cleared to before drawing, being the color of the sky.
+    clear_color: wgpu::Color,
+    /// The time animating the underwater effect, while the camera is underwater.
+    underwater: Option<f32>,
 
-    /// The bind group to hold the array texture being rendered.
+    /// The textures of every voxel face.
+    textures: VoxelTextures,
+    /// The amount of anisotropic filtering the `textures` are sampled with.
+    anisotropy: u16,
+    /// The layout of `texture_bind_group`.
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    /// The bind group to hold the array textures (colors and normal maps) being rendered.
     texture_bind_group: wgpu::BindGroup,
+
+    /// The per-instance data of every mesh drawn in a frame, indexed by draw order.
+    instance_buffer: wgpu::Buffer,
+    /// The number of instances `instance_buffer` can hold.
+    instance_capacity: usize,
+    /// The arguments of every mesh drawn in a frame, in draw order.
+    indirect_buffer: wgpu::Buffer,
+    /// Whether the meshes are drawn from `indirect_buffer` with a single call per pipeline,
+    /// rather than one call per mesh.
+    multi_draw: bool,
+    /// Culls the opaque meshes against the camera's frustum on the GPU, if the meshes are
+    /// drawn with indirect calls.
+    gpu_cull: Option<GpuCuller>,
+
+    /// Times how long the GPU takes to draw each pass, if the adapter supports timestamps.
+    gpu_timer: Option<GpuTimer>,
+
+    /// Culls the meshes of chunks hidden behind other terrain.
+    occlusion: OcclusionCuller,
+    /// Draws shafts of light from the sun over the scene.
+    god_rays: GodRays,
+    /// Darkens the scene where it is hidden from ambient light by nearby terrain.
+    ssao: Ssao,
+    /// Draws the sky, with its stars and moon at night, behind the terrain.
+    sky: Sky,
+    /// Outlines the voxel the camera is looking at.
+    outline: Outline,
+    /// Draws lines over the world for debugging.
+    debug_lines: DebugLines,
+    /// Draws the post-processing effects, the scene being rendered into its textures when any
+    /// are enabled.
+    post: PostProcessor,
+    /// Copies frames out of the surface to be saved as screenshots.
+    screenshots: ScreenshotCapture,
+    /// The scale of the screenshot taken of the next frame, relative to the surface, if one was
+    /// requested.
+    screenshot_requested: Option<u32>,
+    /// The recording every frame drawn onto the surface is added to, while recording.
+    recording: Option<Recording>,
 }
 
 impl Renderer {
-    /// Creates a new renderer given a window as the surface.
-    pub async fn new(window: Arc<Window>, camera: &Camera) -> Result<Self> {
+    /// Creates a new renderer given a window as the surface, drawing with the GPU chosen by the
+    /// graphics config and initially lighting the world with the given environment.
+    pub async fn new(
+        window: Arc<Window>,
+        graphics: GraphicsConfig,
+        camera: &Camera,
+        environment: &Environment,
+    ) -> Result<Self> {
         let instance = Instance::new(InstanceDescriptor {
-            backends: Backends::all(),
+            backends: graphics.backends(),
             flags: InstanceFlags::empty(),
             ..Default::default()
         });
 
         let surface = instance.create_surface(Arc::clone(&window))?;
 
-        let adapter = instance
-            .request_adapter(&RequestAdapterOptions {
-                power_preference: PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .unwrap();
+        Self::with_surface(
+            Arc::new(instance),
+            Arc::new(surface),
+            graphics,
+            window,
+            camera,
+            environment,
+        )
+        .await
+    }
+
+    /// Creates a new renderer on a new device, once the device of this one has been lost. The
+    /// window's surface is kept, but everything else is created again, including an empty mesh
+    /// pool, so every mesh has to be uploaded again.
+    pub async fn recreate(
+        &self,
+        window: Arc<Window>,
+        camera: &Camera,
+        environment: &Environment,
+    ) -> Result<Self> {
+        Self::with_surface(
+            Arc::clone(&self.instance),
+            Arc::clone(&self.surface),
+            self.graphics.clone(),
+            window,
+            camera,
+            environment,
+        )
+        .await
+    }
+
+    /// Creates a new renderer drawing onto the given surface of the window, created from the
+    /// given instance, with the GPU chosen by the graphics config.
+    async fn with_surface(
+        instance: Arc<Instance>,
+        surface: Arc<Surface<'static>>,
+        graphics: GraphicsConfig,
+        window: Arc<Window>,
+        camera: &Camera,
+        environment: &Environment,
+    ) -> Result<Self> {
+        let adapter = adapter::select_adapter(&instance, &surface, &graphics).await?;
+
+        let multi_draw = adapter.features().contains(MULTI_DRAW_FEATURES);
+        let multi_draw_features = match multi_draw {
+            true => {
+                MULTI_DRAW_FEATURES | (adapter.features() & Features::MULTI_DRAW_INDIRECT_COUNT)
+            }
+            false => Features::empty(),
+        };
+
+        // passes are only timed where timestamps can be written between them
+        let timer_features = match adapter.features().contains(TIMER_FEATURES) {
+            true => TIMER_FEATURES,
+            false => Features::empty(),
+        };
 
         let (device, queue) = adapter
             .request_device(
                 &DeviceDescriptor {
                     label: Some("Request Device"),
-                    required_features: Features::POLYGON_MODE_LINE,
+                    required_features: Features::POLYGON_MODE_LINE
+                        | multi_draw_features
+                        | timer_features,
                     required_limits: Limits::default(),
                 },
                 None,
             )
             .await?;
 
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let lost = Arc::clone(&device_lost);
+
+        device.set_device_lost_callback(move |reason, message| {
+            // the callback is also called once the device is dropped, which isn't a loss
+            if matches!(
+                reason,
+                DeviceLostReason::Unknown | DeviceLostReason::DeviceInvalid
+            ) {
+                eprintln!("gpu device lost: {message}");
+                lost.store(true, Ordering::Relaxed);
+            }
+        });
+
         let surface_config = Self::get_surface_config(&adapter, &surface, window.inner_size());
+        let present_modes = Self::get_present_modes(&adapter, &surface);
         surface.configure(&device, &surface_config);
 
         let egui_renderer = EguiRenderer::new(window, &device, surface_config.format);
@@ -96,93 +422,186 @@ impl Renderer {
         let (camera_uniform, camera_bind_group_layout, camera_bind_group) =
             camera.create_buffers(&device);
 
-        let texture = load_textures(&device, &queue)?;
-        let (texture_bind_group_layout, texture_bind_group) = texture.create_bind_group(&device);
+        let (environment_uniform, environment_bind_group_layout, environment_bind_group) =
+            environment.create_buffers(&device);
+
+        let mut textures = load_textures(&device, &queue)?;
+        let anisotropy = RenderSettings::default().anisotropy;
+        textures.set_anisotropy(&device, anisotropy);
+
+        let texture_bind_group_layout = VoxelTextures::create_bind_group_layout(&device);
+        let texture_bind_group = textures.create_bind_group(&device, &texture_bind_group_layout);
 
         let depth_texture = Texture::create_depth_texture(&device, &surface_config);
 
-        let shader = device.create_shader_module(include_wgsl!("shader.wgsl"));
-        let pipeline = Self::create_pipeline(
+        let light_grid = LightGrid::new(&device);
+
+        let shader = preprocessor::create_shader_module(
             &device,
-            &surface_config,
-            shader,
-            &[&camera_bind_group_layout, &texture_bind_group_layout],
+            "shader.wgsl",
+            include_str!("shader.wgsl"),
+            &[],
         );
+        let bind_group_layouts = [
+            &camera_bind_group_layout,
+            &texture_bind_group_layout,
+            &environment_bind_group_layout,
+            &light_grid.bind_group_layout,
+        ];
+        let mut validation_errors = Vec::new();
+        let mut pipelines = PipelineCache::new(&device, shader, &bind_group_layouts);
 
-        let meshes = Vec::new();
+        // the passes drawn every frame by default, ready before the first one
+        for pass in [TerrainPass::Opaque, TerrainPass::Transparent] {
+            Self::validate(&device, &mut validation_errors, "terrain pipeline", || {
+                pipelines.prepare(&device, pass.pipeline_key())
+            });
+        }
+
+        let ssao = Ssao::new(
+            &device,
+            SCENE_FORMAT,
+            &camera_bind_group_layout,
+            &depth_texture.view,
+        );
+        let sky = Sky::new(&device, SCENE_FORMAT, &bind_group_layouts);
+        let post = PostProcessor::new(&device, &surface_config);
+        let outline = Outline::new(&device, SCENE_FORMAT, &camera_bind_group_layout);
+        let debug_lines = DebugLines::new(&device, SCENE_FORMAT, &camera_bind_group_layout);
+
+        let occlusion = OcclusionCuller::new(&device, SCENE_FORMAT, &camera_bind_group_layout);
+        let god_rays = GodRays::new(
+            &device,
+            SCENE_FORMAT,
+            &camera_bind_group_layout,
+            &environment_bind_group_layout,
+            &depth_texture.view,
+        );
+
+        let mesh_pool = Arc::new(MeshPool::new(&device));
+
+        let gpu_cull = multi_draw.then(|| {
+            let compact = device
+                .features()
+                .contains(Features::MULTI_DRAW_INDIRECT_COUNT);
+
+            GpuCuller::new(&device, compact)
+        });
+
+        let gpu_timer = device
+            .features()
+            .contains(TIMER_FEATURES)
+            .then(|| GpuTimer::new(&device, &queue));
+
+        let instance_capacity = INITIAL_INSTANCE_CAPACITY;
+        let (instance_buffer, indirect_buffer) =
+            Self::validate(&device, &mut validation_errors, "instance buffers", || {
+                (
+                    Self::create_instance_buffer(&device, instance_capacity),
+                    Self::create_indirect_buffer(&device, instance_capacity),
+                )
+            });
 
         Ok(Self {
             device,
             queue,
-            pipeline,
+            device_lost,
+            instance,
+            graphics,
+            pipelines,
+            validation_errors,
             surface,
             surface_config,
+            present_modes,
             egui_renderer,
-            meshes,
+            mesh_pool,
             camera_uniform,
             camera_bind_group,
+            environment_uniform,
+            environment_bind_group,
+            light_grid,
+            clear_color: environment.clear_color(),
+            underwater: None,
+            textures,
+            anisotropy,
+            texture_bind_group_layout,
             texture_bind_group,
             depth_texture,
+            instance_buffer,
+            instance_capacity,
+            indirect_buffer,
+            multi_draw,
+            gpu_cull,
+            gpu_timer,
+            occlusion,
+            god_rays,
+            ssao,
+            sky,
+            outline,
+            debug_lines,
+            post,
+            screenshots: ScreenshotCapture::new(),
+            screenshot_requested: None,
+            recording: None,
         })
     }
 
-    /// Creates the rendering pipeline.
-    fn create_pipeline(
-        device: &Device,
-        surface_config: &SurfaceConfiguration,
-        shader: ShaderModule,
-        bind_group_layouts: &[&BindGroupLayout],
-    ) -> RenderPipeline {
-        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            push_constant_ranges: &[],
-            bind_group_layouts,
-        });
+    /// Creates a buffer holding the given number of mesh instances.
+    fn create_instance_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (capacity * size_of::<MeshInstance>()) as BufferAddress,
+            // read as storage by the GPU culling pass
+            usage: BufferUsages::VERTEX | BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
 
-        device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[MeshVertex::desc()],
-                compilation_options: PipelineCompilationOptions::default(),
-            },
-            fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(ColorTargetState {
-                    format: surface_config.format,
-                    blend: Some(BlendState::REPLACE),
-                    write_mask: ColorWrites::ALL,
-                })],
-                compilation_options: PipelineCompilationOptions::default(),
-            }),
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
-                polygon_mode: PolygonMode::Fill,
-                conservative: false,
-                unclipped_depth: false,
-            },
-            depth_stencil: Some(DepthStencilState {
-                format: TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: CompareFunction::Less,
-                stencil: StencilState::default(),
-                bias: DepthBiasState::default(),
-            }),
-            multisample: MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
+    /// Creates a buffer holding the draw arguments of the given number of meshes.
+    fn create_indirect_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Indirect Buffer"),
+            size: (capacity * size_of::<DrawIndexedIndirectArgs>()) as BufferAddress,
+            usage: BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         })
     }
 
+    /// Uploads the given instances along with the draw arguments of the meshes using them,
+    /// growing the instance and indirect buffers if they do not fit.
+    fn write_instances(&mut self, instances: &[MeshInstance], draws: &[DrawIndexedIndirectArgs]) {
+        if instances.len() > self.instance_capacity {
+            let capacity = instances.len().next_power_of_two();
+            let device = &self.device;
+
+            (self.instance_buffer, self.indirect_buffer) = Self::validate(
+                device,
+                &mut self.validation_errors,
+                "instance buffers",
+                || {
+                    (
+                        Self::create_instance_buffer(device, capacity),
+                        Self::create_indirect_buffer(device, capacity),
+                    )
+                },
+            );
+            self.instance_capacity = capacity;
+        }
+
+        self.queue
+            .write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(instances));
+
+        if self.multi_draw && !draws.is_empty() {
+            let draws = draws
+                .iter()
+                .flat_map(|draw| draw.as_bytes())
+                .copied()
+                .collect::<Vec<_>>();
+
+            self.queue.write_buffer(&self.indirect_buffer, 0, &draws);
+        }
+    }
+
     /// Creates a surface configuration given an adapter, surface, and surface size.
     /// Does not apply the created config to the surface
     fn get_surface_config(
@@ -201,7 +620,9 @@ impl Renderer {
             .unwrap();
 
         SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
+            // copied from when taking screenshots, where supported
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | (surface_caps.usages & TextureUsages::COPY_SRC),
             format: surface_format,
             width,
             height,
@@ -212,6 +633,33 @@ impl Renderer {
         }
     }
 
+    /// Returns the present modes the surface supports out of those offered as settings. Vsync is
+    /// always supported, falling back to the display's own vsync mode where needed.
+    fn get_present_modes(adapter: &Adapter, surface: &Surface) -> Vec<PresentMode> {
+        let supported = surface.get_capabilities(adapter).present_modes;
+
+        [
+            PresentMode::AutoVsync,
+            PresentMode::Immediate,
+            PresentMode::Mailbox,
+        ]
+        .into_iter()
+        .filter(|mode| *mode == PresentMode::AutoVsync || supported.contains(mode))
+        .collect()
+    }
+
+    /// Returns the modes the surface can present frames with, which
+    /// `RenderSettings::present_mode` may be set to.
+    pub fn present_modes(&self) -> &[PresentMode] {
+        &self.present_modes
+    }
+
+    /// Passes a window event on to the ui. Returns whether the ui used it, in which case the
+    /// rest of the app should ignore it.
+    pub fn handle_ui_input(&mut self, event: &WindowEvent) -> bool {
+        self.egui_renderer.handle_input(event)
+    }
+
     /// Resizes the renderer's `config` to match the new given size.
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
         let PhysicalSize { width, height } = size;
@@ -222,28 +670,382 @@ impl Renderer {
         self.surface_config.width = width;
         self.surface_config.height = height;
 
-        self.depth_texture = Texture::create_depth_texture(&self.device, &self.surface_config);
+        // every frame of a recording has the same size
+        if self
+            .recording
+            .as_ref()
+            .is_some_and(|recording| !recording.has_size(width, height))
+        {
+            eprintln!("stopped recording, as the window was resized");
+            self.recording = None;
+        }
 
+        self.reconfigure();
+    }
+
+    /// Returns whether the device has been lost, in which case nothing drawn shows up until the
+    /// renderer is recreated with `Renderer::recreate`.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Configures the surface with the stored config again, and recreates the targets sized to
+    /// it. Called once the surface is resized, or is lost or outdated (such as after switching
+    /// away from a fullscreen window, or moving it to another monitor).
+    pub fn reconfigure(&mut self) {
+        self.resize_targets(1);
         self.surface.configure(&self.device, &self.surface_config);
     }
 
-    /// Updates the camera's uniform buffer with the given view projection matrix.
-    pub fn update_camera_buffer(&mut self, view_proj: Mat4) {
+    /// Recreates the targets the scene is drawn into, at the given scale of the surface's size.
+    fn resize_targets(&mut self, scale: u32) {
+        let config = SurfaceConfiguration {
+            width: self.surface_config.width * scale,
+            height: self.surface_config.height * scale,
+            ..self.surface_config.clone()
+        };
+
+        self.depth_texture = Texture::create_depth_texture(&self.device, &config);
+        self.god_rays.resize(&self.device, &self.depth_texture.view);
+        self.ssao.resize(&self.device, &self.depth_texture.view);
+        self.post.resize(&self.device, &config);
+    }
+
+    /// Updates the camera's uniform buffer with the given camera's view.
+    pub fn update_camera_buffer(&mut self, camera: &Camera) {
         self.queue.write_buffer(
             &self.camera_uniform,
             0,
-            bytemuck::cast_slice(&view_proj.to_cols_array()),
+            bytemuck::bytes_of(&camera.uniform()),
         );
     }
 
-    /// Renders the currently bound vertex buffer onto the `surface`.
+    /// Updates the environment's uniform buffer with the given lighting, and clears the screen
+    /// to its sky color.
+    pub fn update_environment_buffer(&mut self, environment: &Environment) {
+        self.clear_color = environment.clear_color();
+        self.underwater = environment.underwater.then_some(environment.time);
+
+        self.queue.write_buffer(
+            &self.environment_uniform,
+            0,
+            bytemuck::bytes_of(&environment.uniform()),
+        );
+    }
+
+    /// Presents frames with the given mode from the next frame on, unless the surface doesn't
+    /// support it.
+    fn set_present_mode(&mut self, present_mode: PresentMode) {
+        if !self.present_modes.contains(&present_mode) {
+            return;
+        }
+
+        self.surface_config.present_mode = present_mode;
+        self.surface.configure(&self.device, &self.surface_config);
+    }
+
+    /// Takes a screenshot of the next frame drawn, at the given scale of the surface's size,
+    /// which is saved once it has been read back. Frames drawn at a larger scale than 1 are
+    /// drawn offscreen, and not presented.
+    pub fn take_screenshot(&mut self, scale: u32) {
+        self.screenshot_requested = Some(scale.max(1));
+    }
+
+    /// Starts adding every frame drawn onto the surface to a recording, written as the config
+    /// describes, until `Renderer::stop_recording` is called.
+    pub fn start_recording(&mut self, config: &RecordingConfig) -> Result<()> {
+        if !self.surface_config.usage.contains(TextureUsages::COPY_SRC) {
+            bail!("the surface can't be copied");
+        }
+
+        self.recording = Some(Recording::start(
+            config,
+            self.surface_config.width,
+            self.surface_config.height,
+        )?);
+
+        Ok(())
+    }
+
+    /// Stops recording, once the frames already drawn have been written.
+    pub fn stop_recording(&mut self) {
+        if self.recording.take().is_some() {
+            println!("stopped recording");
+        }
+    }
+
+    /// Returns whether every frame drawn is being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Queues the frame drawn onto the surface to be captured into the given sink.
+    fn queue_capture(&mut self, sink: FrameSink) {
+        if !self.surface_config.usage.contains(TextureUsages::COPY_SRC) {
+            eprintln!("could not capture the frame: the surface can't be copied");
+            return;
+        }
+
+        self.screenshots.queue(
+            &self.device,
+            self.surface_config.width,
+            self.surface_config.height,
+            self.surface_config.format,
+            sink,
+        );
+    }
+
+    /// Resizes the targets to the given scale of the surface's size, and queues a screenshot of
+    /// the frame, returning the texture it is drawn into instead of the surface. Nothing is
+    /// queued if the screenshot would be larger than the device supports.
+    fn begin_high_res_screenshot(&mut self, scale: u32) -> Option<wgpu::Texture> {
+        let width = self.surface_config.width * scale;
+        let height = self.surface_config.height * scale;
+        let limits = self.device.limits();
+
+        let bytes =
+            (width * 4).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT) as u64 * height as u64;
+
+        if width.max(height) > limits.max_texture_dimension_2d || bytes > limits.max_buffer_size {
+            eprintln!(
+                "could not take a screenshot: {width}x{height} is larger than the gpu supports"
+            );
+            return None;
+        }
+
+        let texture = self.device.create_texture(&TextureDescriptor {
+            label: Some("Screenshot Texture"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: self.surface_config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        self.resize_targets(scale);
+        self.screenshots.queue(
+            &self.device,
+            width,
+            height,
+            self.surface_config.format,
+            FrameSink::Screenshot,
+        );
+
+        Some(texture)
+    }
+
+    /// Samples the voxel textures with up to the given amount of anisotropic filtering.
+    fn set_anisotropy(&mut self, anisotropy: u16) {
+        let device = &self.device;
+        let textures = &mut self.textures;
+        let layout = &self.texture_bind_group_layout;
+
+        self.texture_bind_group = Self::validate(
+            device,
+            &mut self.validation_errors,
+            "texture sampler",
+            || {
+                textures.set_anisotropy(device, anisotropy);
+                textures.create_bind_group(device, layout)
+            },
+        );
+        self.anisotropy = anisotropy;
+    }
+
+    /// Runs `create` within a validation error scope, so that creating an invalid GPU resource
+    /// is reported under the given label (and kept in `errors` to be shown in the debug ui),
+    /// rather than crashing the app.
+    fn validate<T>(
+        device: &Device,
+        errors: &mut Vec<String>,
+        label: &str,
+        create: impl FnOnce() -> T,
+    ) -> T {
+        device.push_error_scope(ErrorFilter::Validation);
+        let created = create();
+
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            eprintln!("could not create {label}: {error}");
+
+            if errors.len() == MAX_VALIDATION_ERRORS {
+                errors.remove(0);
+            }
+
+            errors.push(format!("{label}: {error}"));
+        }
+
+        created
+    }
+
+    /// Updates the light grid with the given point lights, around the camera at `eye`.
+    pub fn update_lights(&mut self, lights: &[PointLight], eye: DVec3) {
+        self.light_grid
+            .update(&self.device, &self.queue, lights, eye);
+    }
+
+    /// Outlines the voxel at the given position from now on (or nothing), as seen from the
+    /// camera at `eye`.
+    pub fn update_outline(&mut self, target: Option<IVec3>, eye: DVec3) {
+        self.outline.update(&self.queue, target, eye);
+    }
+
+    /// Draws the given debug lines from now on, as seen from the camera at `eye`.
+    pub fn update_debug_lines(&mut self, lines: &[DebugLine], eye: DVec3) {
+        self.debug_lines
+            .update(&self.device, &self.queue, lines, eye);
+    }
+
+    /// Renders the given opaque meshes, followed by the transparent ones, as seen from the
+    /// camera at `eye`, onto the `surface`. Meshes of chunks outside of the camera's `frustum`,
+    /// or hidden behind other terrain, are skipped. Instanced models are drawn after the meshes
+    /// of the layer they belong to, each with a single call for all of its instances. Each
+    /// pipeline's meshes are drawn with a single indirect call when the adapter supports it, in
+    /// which case the opaque meshes are culled against the `frustum` on the GPU rather than
+    /// here. The effects enabled in the `settings` are drawn over the scene, followed by the ui,
+    /// each as a pass of the frame's `RenderGraph`. The ui is given the validation errors raised
+    /// while creating GPU resources, and the time the GPU took to draw each pass.
+    #[allow(clippy::too_many_arguments)]
     pub fn render<'a>(
         &mut self,
         meshes: impl Iterator<Item = &'a Mesh>,
-        ui: impl FnOnce(&Context),
+        transparent_meshes: impl Iterator<Item = &'a Mesh>,
+        models: impl Iterator<Item = (MeshLayer, &'a InstancedMesh)>,
+        eye: DVec3,
+        frustum: &Frustum,
+        settings: &RenderSettings,
+        ui: impl FnOnce(&Context, &RenderStats),
     ) -> std::result::Result<(), SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output.texture.create_view(&TextureViewDescriptor {
+        if settings.anisotropy != self.anisotropy {
+            self.set_anisotropy(settings.anisotropy);
+        }
+
+        if settings.present_mode != self.surface_config.present_mode {
+            self.set_present_mode(settings.present_mode);
+        }
+
+        let chunk_size = Vec3::new(CHUNK_WIDTH as f32, CHUNK_HEIGHT as f32, CHUNK_WIDTH as f32);
+
+        // skip the meshes of chunks that lie entirely outside of the camera's view
+        let in_frustum = |mesh: &&Mesh| {
+            let min = mesh.offset_from(eye);
+            frustum.intersects_aabb(min, min + chunk_size)
+        };
+
+        let gpu_culling = self.gpu_cull.is_some();
+
+        let meshes = meshes
+            .filter(|mesh| gpu_culling || in_frustum(mesh))
+            .collect::<Vec<_>>();
+        let transparent_meshes = transparent_meshes.filter(in_frustum).collect::<Vec<_>>();
+
+        // test the bounding boxes of every chunk in view, and skip the ones hidden last time
+        self.occlusion.update(&self.device);
+
+        let boxes = self
+            .occlusion
+            .queue(
+                meshes
+                    .iter()
+                    .chain(&transparent_meshes)
+                    .map(|mesh| mesh.origin),
+                eye,
+                frustum,
+            )
+            .iter()
+            .map(|origin| MeshInstance {
+                offset: model::offset_from(*origin, eye),
+            })
+            .collect::<Vec<_>>();
+
+        let is_visible = |mesh: &&Mesh| !self.occlusion.is_occluded(mesh.origin);
+
+        let mut meshes = meshes
+            .into_iter()
+            .filter(is_visible)
+            .map(|mesh| (mesh, mesh.offset_from(eye)))
+            .collect::<Vec<_>>();
+
+        // opaque meshes are drawn roughly from front to back, so fragments hidden by nearer
+        // terrain fail the depth test before being shaded (their order is only kept when the
+        // gpu culling pass doesn't pack the visible draws together)
+        meshes.sort_by(|(_, a), (_, b)| a.length_squared().total_cmp(&b.length_squared()));
+
+        let mut transparent_meshes = transparent_meshes
+            .into_iter()
+            .filter(is_visible)
+            .map(|mesh| (mesh, mesh.offset_from(eye)))
+            .collect::<Vec<_>>();
+
+        // transparent meshes only blend correctly when drawn from back to front
+        transparent_meshes
+            .sort_by(|(_, a), (_, b)| b.length_squared().total_cmp(&a.length_squared()));
+
+        let instances = meshes
+            .iter()
+            .chain(&transparent_meshes)
+            .map(|(_, offset)| *offset)
+            .map(|offset| MeshInstance { offset })
+            .chain(boxes)
+            .collect::<Vec<_>>();
+
+        let draws = meshes
+            .iter()
+            .chain(&transparent_meshes)
+            .map(|(mesh, _)| *mesh)
+            .enumerate()
+            .map(|(instance, mesh)| mesh.draw_args(instance as u32..instance as u32 + 1))
+            .collect::<Vec<_>>();
+
+        self.write_instances(&instances, &draws);
+        let opaque_draws = &draws[..meshes.len()];
+
+        let (models, transparent_models): (Vec<_>, Vec<_>) =
+            models.partition(|(layer, _)| *layer == MeshLayer::Opaque);
+        let models = models
+            .into_iter()
+            .map(|(_, model)| model)
+            .collect::<Vec<_>>();
+        let transparent_models = transparent_models
+            .into_iter()
+            .map(|(_, model)| model)
+            .collect::<Vec<_>>();
+
+        self.screenshots.update(&self.device);
+
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.begin_frame(&self.device);
+        }
+
+        // screenshots larger than the surface are drawn into a texture of their own
+        let target = match self.screenshot_requested.take() {
+            Some(1) => {
+                self.queue_capture(FrameSink::Screenshot);
+                None
+            }
+            Some(scale) => self
+                .begin_high_res_screenshot(scale)
+                .map(|texture| FrameTarget::Screenshot(texture, scale)),
+            None => None,
+        };
+
+        let target = match target {
+            Some(target) => target,
+            None => FrameTarget::Surface(self.surface.get_current_texture()?),
+        };
+
+        // only the frames presented are recorded, which high resolution screenshots aren't
+        if let (Some(recording), FrameTarget::Surface(_)) = (&self.recording, &target) {
+            let sink = recording.sink();
+            self.queue_capture(sink);
+        }
+
+        let view = target.texture().create_view(&TextureViewDescriptor {
             label: Some("Rendering View"),
             ..Default::default()
         });
@@ -254,49 +1056,424 @@ impl Renderer {
                 label: Some("Render Encoder"),
             });
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Clear(Color {
-                            r: 0.01,
-                            g: 0.01,
-                            b: 0.01,
-                            a: 1.0,
-                        }),
-                        store: StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(Operations {
-                        load: LoadOp::Clear(1.0),
-                        store: StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                ..Default::default()
+        if let Some(gpu_cull) = &mut self.gpu_cull {
+            gpu_cull.prepare(
+                &self.device,
+                &self.queue,
+                frustum,
+                opaque_draws,
+                &self.instance_buffer,
+            );
+        }
+
+        let stats = RenderStats {
+            validation_errors: &self.validation_errors,
+            pass_timings: self.gpu_timer.as_ref().map(GpuTimer::timings),
+        };
+
+        encoder.push_debug_group("ui upload");
+        self.egui_renderer
+            .prepare(&self.device, &self.queue, &mut encoder, |context| {
+                ui(context, &stats)
             });
+        encoder.pop_debug_group();
 
-            render_pass.set_pipeline(&self.pipeline);
+        let effects = self
+            .underwater
+            .map(PostEffect::Underwater)
+            .into_iter()
+            .chain(
+                settings
+                    .post
+                    .iter()
+                    .filter(|pass| pass.enabled)
+                    .map(|pass| pass.effect),
+            )
+            .collect::<Vec<_>>();
 
-            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
+        self.post.update(&self.queue, effects.iter().copied());
 
-            for model in meshes {
-                render_pass.draw_object(model);
-            }
+        let opaque_pass = match settings.wireframe {
+            true => TerrainPass::Wireframe,
+            false => TerrainPass::Opaque,
         };
 
-        self.egui_renderer
-            .render(&self.device, &self.queue, &mut encoder, &view, ui);
+        let passes = [
+            settings.depth_prepass.then_some(TerrainPass::DepthPrepass),
+            Some(opaque_pass),
+            Some(TerrainPass::Transparent),
+        ];
+
+        for pass in passes.into_iter().flatten() {
+            let pipelines = &mut self.pipelines;
+            let device = &self.device;
+
+            Self::validate(
+                device,
+                &mut self.validation_errors,
+                "terrain pipeline",
+                || {
+                    pipelines.prepare(device, pass.pipeline_key());
+
+                    if pass != TerrainPass::DepthPrepass {
+                        pipelines.prepare(device, pass.instanced_pipeline_key());
+                    }
+                },
+            );
+        }
+
+        // taken out for the passes to be timed while the graph borrows the rest of the renderer
+        let mut gpu_timer = self.gpu_timer.take();
+
+        let this = &*self;
+        let (meshes, transparent_meshes) = (meshes.as_slice(), transparent_meshes.as_slice());
+        let (models, transparent_models) = (models.as_slice(), transparent_models.as_slice());
+        let mut graph = RenderGraph::new();
+
+        if let Some(gpu_cull) = &this.gpu_cull {
+            graph.add_pass("cull", &[], &[], |encoder, _| gpu_cull.dispatch(encoder));
+        }
+
+        if settings.depth_prepass {
+            graph.add_pass(
+                "depth prepass",
+                &[],
+                &[Attachment::Depth],
+                |encoder, targets| {
+                    let (vertex_buffer, index_buffer) = this.mesh_pool.buffers();
+                    let mut render_pass = this.begin_terrain_pass(
+                        encoder,
+                        targets,
+                        &vertex_buffer,
+                        &index_buffer,
+                        TerrainPass::DepthPrepass,
+                        true,
+                    );
+
+                    match &this.gpu_cull {
+                        Some(gpu_cull) => gpu_cull.draw(&mut render_pass),
+                        None => this.draw_meshes(&mut render_pass, meshes, 0),
+                    }
+                },
+            );
+        }
+
+        let depth_prepass = settings.depth_prepass;
+
+        graph.add_pass(
+            "opaque",
+            match depth_prepass {
+                true => &[Attachment::Depth],
+                false => &[],
+            },
+            &[Attachment::Scene, Attachment::Depth],
+            move |encoder, targets| {
+                let (vertex_buffer, index_buffer) = this.mesh_pool.buffers();
+                let mut render_pass = this.begin_terrain_pass(
+                    encoder,
+                    targets,
+                    &vertex_buffer,
+                    &index_buffer,
+                    opaque_pass,
+                    !depth_prepass,
+                );
+
+                match &this.gpu_cull {
+                    Some(gpu_cull) => gpu_cull.draw(&mut render_pass),
+                    None => this.draw_meshes(&mut render_pass, meshes, 0),
+                }
+
+                this.draw_models(&mut render_pass, opaque_pass, models);
+
+                // behind the opaque terrain, but seen through the transparent meshes
+                render_pass.push_debug_group("sky");
+                this.sky.draw(&mut render_pass);
+                render_pass.pop_debug_group();
+            },
+        );
+
+        graph.add_pass(
+            "transparent",
+            &[Attachment::Scene, Attachment::Depth],
+            &[Attachment::Scene],
+            |encoder, targets| {
+                let (vertex_buffer, index_buffer) = this.mesh_pool.buffers();
+                let mut render_pass = this.begin_terrain_pass(
+                    encoder,
+                    targets,
+                    &vertex_buffer,
+                    &index_buffer,
+                    TerrainPass::Transparent,
+                    false,
+                );
+
+                this.draw_meshes(&mut render_pass, transparent_meshes, meshes.len());
+                this.draw_models(
+                    &mut render_pass,
+                    TerrainPass::Transparent,
+                    transparent_models,
+                );
+
+                render_pass.push_debug_group("occlusion boxes");
+                this.occlusion.draw_boxes(
+                    &mut render_pass,
+                    &this.camera_bind_group,
+                    &this.instance_buffer,
+                    draws.len() as u32,
+                );
+                render_pass.pop_debug_group();
+            },
+        );
+
+        graph.add_pass(
+            "outline",
+            &[Attachment::Scene, Attachment::Depth],
+            &[Attachment::Scene],
+            |encoder, targets| {
+                this.outline.draw(
+                    encoder,
+                    targets.scene,
+                    targets.depth,
+                    &this.camera_bind_group,
+                )
+            },
+        );
+
+        graph.add_pass(
+            "debug lines",
+            &[Attachment::Scene, Attachment::Depth],
+            &[Attachment::Scene],
+            |encoder, targets| {
+                this.debug_lines.draw(
+                    encoder,
+                    targets.scene,
+                    targets.depth,
+                    &this.camera_bind_group,
+                )
+            },
+        );
+
+        graph.add_pass("occlusion resolve", &[], &[], |encoder, _| {
+            this.occlusion.resolve(encoder)
+        });
+
+        // before the rays, which light up the sky rather than the terrain
+        if settings.ssao {
+            graph.add_pass(
+                "ssao",
+                &[Attachment::Scene, Attachment::Depth],
+                &[Attachment::Scene],
+                |encoder, targets| {
+                    this.ssao
+                        .draw(encoder, targets.scene, &this.camera_bind_group)
+                },
+            );
+        }
+
+        if settings.god_rays {
+            graph.add_pass(
+                "god rays",
+                &[Attachment::Scene, Attachment::Depth],
+                &[Attachment::Scene],
+                |encoder, targets| {
+                    this.god_rays.draw(
+                        encoder,
+                        targets.scene,
+                        &this.camera_bind_group,
+                        &this.environment_bind_group,
+                    )
+                },
+            );
+        }
+
+        for (step, &effect) in effects.iter().enumerate() {
+            graph.add_pass(
+                effect.name(),
+                &[Attachment::Scene],
+                &[Attachment::Scene],
+                move |encoder, _| {
+                    this.post
+                        .draw(encoder, effect, step, this.post.target_view(step))
+                },
+            );
+        }
+
+        let steps = effects.len();
+
+        graph.add_pass(
+            "present",
+            &[Attachment::Scene],
+            &[Attachment::Surface],
+            move |encoder, targets| this.post.present(encoder, steps, targets.surface),
+        );
+
+        let screenshot = |encoder: &mut CommandEncoder, _: &RenderTargets| {
+            this.screenshots.copy(encoder, target.texture())
+        };
+
+        if !settings.screenshot_ui {
+            graph.add_pass("screenshot", &[Attachment::Surface], &[], screenshot);
+        }
+
+        let ui_scale = target.scale();
+
+        // the ui is drawn after post-processing, so text stays sharp
+        graph.add_pass(
+            "ui",
+            &[],
+            &[Attachment::Surface],
+            move |encoder, targets| this.egui_renderer.draw(encoder, targets.surface, ui_scale),
+        );
+
+        if settings.screenshot_ui {
+            graph.add_pass("screenshot", &[Attachment::Surface], &[], screenshot);
+        }
+
+        // the scene is drawn offscreen first, then processed and presented onto the surface
+        let targets = RenderTargets {
+            scene: self.post.scene_view(),
+            depth: &self.depth_texture.view,
+            surface: &view,
+        };
+
+        graph.execute(&mut encoder, &targets, gpu_timer.as_mut());
+
+        if let Some(gpu_timer) = &gpu_timer {
+            gpu_timer.resolve(&mut encoder);
+        }
 
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        self.occlusion.read_back();
+        self.screenshots.read_back();
+
+        self.gpu_timer = gpu_timer;
+
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.read_back();
+        }
+
+        match target {
+            FrameTarget::Surface(output) => output.present(),
+            // the targets go back to the surface's size for the next frame
+            FrameTarget::Screenshot(..) => self.resize_targets(1),
+        }
 
         Ok(())
     }
+
+    /// Begins the given pass drawing chunk meshes into the scene, with its pipeline, the
+    /// terrain's bind groups and the pool's buffers bound. The depth buffer is cleared first if
+    /// `clear_depth` is set. The opaque pass clears the scene, and the transparent pass draws
+    /// over it, counting the samples of the occlusion boxes.
+    fn begin_terrain_pass<'p>(
+        &'p self,
+        encoder: &'p mut CommandEncoder,
+        targets: &RenderTargets<'p>,
+        vertex_buffer: &'p PoolBuffer,
+        index_buffer: &'p PoolBuffer,
+        pass: TerrainPass,
+        clear_depth: bool,
+    ) -> RenderPass<'p> {
+        let (label, color_load) = match pass {
+            TerrainPass::DepthPrepass => ("Depth Prepass", None),
+            TerrainPass::Opaque => ("Opaque Pass", Some(LoadOp::Clear(self.clear_color))),
+            TerrainPass::Wireframe => ("Wireframe Pass", Some(LoadOp::Clear(self.clear_color))),
+            TerrainPass::Transparent => ("Transparent Pass", Some(LoadOp::Load)),
+        };
+
+        let color_attachment = color_load.map(|load| RenderPassColorAttachment {
+            view: targets.scene,
+            resolve_target: None,
+            ops: Operations {
+                load,
+                store: StoreOp::Store,
+            },
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some(label),
+            // the depth prepass draws no color at all
+            color_attachments: match color_attachment {
+                Some(_) => std::slice::from_ref(&color_attachment),
+                None => &[],
+            },
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: targets.depth,
+                depth_ops: Some(Operations {
+                    load: match clear_depth {
+                        true => LoadOp::Clear(1.0),
+                        false => LoadOp::Load,
+                    },
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: match pass {
+                TerrainPass::Transparent => self.occlusion.query_set(),
+                _ => None,
+            },
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(self.pipelines.get(&pass.pipeline_key()));
+
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.environment_bind_group, &[]);
+        render_pass.set_bind_group(3, self.light_grid.bind_group(), &[]);
+
+        render_pass.set_vertex_buffer(0, vertex_buffer.buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.buffer.slice(..), IndexFormat::Uint32);
+
+        render_pass
+    }
+
+    /// Draws the given meshes, whose instances and draw arguments are stored in the instance
+    /// and indirect buffers starting at the `first` draw. The pool's buffers must already be
+    /// bound.
+    fn draw_meshes<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        meshes: &[(&'a Mesh, Vec3)],
+        first: usize,
+    ) {
+        if meshes.is_empty() {
+            return;
+        }
+
+        if self.multi_draw {
+            let offset = (first * size_of::<DrawIndexedIndirectArgs>()) as BufferAddress;
+            render_pass.multi_draw_indexed_indirect(
+                &self.indirect_buffer,
+                offset,
+                meshes.len() as u32,
+            );
+            return;
+        }
+
+        for (instance, (mesh, _)) in (first as u32..).zip(meshes) {
+            render_pass.draw_object_instanced(*mesh, instance..instance + 1);
+        }
+    }
+
+    /// Draws every instance of the given models with the instanced pipeline of the pass. The
+    /// pool's buffers must already be bound, and the models' instance buffers are bound in place
+    /// of the instance buffer of the meshes.
+    fn draw_models<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        pass: TerrainPass,
+        models: &[&'a InstancedMesh],
+    ) {
+        if models.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(self.pipelines.get(&pass.instanced_pipeline_key()));
+
+        for model in models {
+            render_pass.draw_object_instanced(*model, 0..model.instances.count());
+        }
+    }
 }
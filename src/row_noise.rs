@@ -0,0 +1,413 @@
+use noise::permutationtable::{NoiseHasher, PermutationTable};
+
+use crate::chunk::CHUNK_WIDTH;
+
+/// How many points are sampled at once by `RowNoise::get_row`, one for every voxel along the x
+/// axis of a chunk.
+pub const ROW_WIDTH: usize = CHUNK_WIDTH;
+
+/// The x coordinates or noise values of a row of points.
+pub type Row = [f64; ROW_WIDTH];
+
+/// The `(x, y, z)` components of each of the 16 gradients 3d perlin noise picks from, indexed by
+/// the lowest 4 bits of a lattice corner's hash.
+const GRADIENTS: [[f64; 3]; 16] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [0.0, -1.0, 1.0],
+    [0.0, -1.0, -1.0],
+];
+
+/// 3d noise that can be sampled a whole row of points along the x axis at a time. Everything
+/// shared by the row (the y and z parts of the lattice, the hashes of its cells) is worked out
+/// once, leaving tight loops over the points that the compiler can vectorize.
+///
+/// The values match what sampling the equivalent `noise` function one point at a time gives, so
+/// switching to it doesn't change the terrain of existing seeds.
+///
+/// Only 3d noise has a row path. The 2d noise of the heightmap terrain is sampled once per
+/// column rather than once per voxel, making it a tiny fraction of the time spent generating a
+/// chunk.
+pub trait RowNoise {
+    /// Returns the noise value at each of the points `(xs[i], y, z)`.
+    fn get_row(&self, xs: &Row, y: f64, z: f64) -> Row;
+}
+
+impl<T: RowNoise + ?Sized> RowNoise for &T {
+    fn get_row(&self, xs: &Row, y: f64, z: f64) -> Row {
+        (**self).get_row(xs, y, z)
+    }
+}
+
+/// 3d perlin noise, equivalent to `noise::Perlin`.
+#[derive(Clone, Copy)]
+pub struct RowPerlin {
+    /// The table the lattice corners are hashed with.
+    table: PermutationTable,
+}
+
+impl RowPerlin {
+    /// Creates perlin noise from the given seed, matching `noise::Perlin::new(seed)`.
+    pub fn new(seed: u32) -> Self {
+        Self {
+            table: PermutationTable::new(seed),
+        }
+    }
+}
+
+impl RowNoise for RowPerlin {
+    fn get_row(&self, xs: &Row, y: f64, z: f64) -> Row {
+        /// 2/sqrt(3), scaling the noise into the -1 to 1 range.
+        const SCALE_FACTOR: f64 = 1.154_700_538_379_251_5;
+
+        let (corner_y, distance_y) = lattice(y);
+        let (corner_z, distance_z) = lattice(z);
+        let curve_y = quintic(distance_y);
+        let curve_z = quintic(distance_z);
+
+        let mut result = [0.0; ROW_WIDTH];
+        let mut start = 0;
+
+        // points next to each other usually share a lattice cell, so the corners of each cell are
+        // only hashed once, for the whole run of points within it
+        while start < ROW_WIDTH {
+            let (corner_x, _) = lattice(xs[start]);
+            let end = (start + 1..ROW_WIDTH)
+                .find(|i| lattice(xs[*i]).0 != corner_x)
+                .unwrap_or(ROW_WIDTH);
+
+            // the x component of each corner's gradient, and the rest of its dot product with the
+            // offset to the point, which is the same for every point in the run
+            let gradients: [(f64, f64); 8] = std::array::from_fn(|corner| {
+                let [x, y, z] = corner_offset(corner);
+                let hash = self.table.hash(&[
+                    corner_x + x as isize,
+                    corner_y + y as isize,
+                    corner_z + z as isize,
+                ]);
+
+                let [gradient_x, gradient_y, gradient_z] = GRADIENTS[hash & 15];
+                let rest =
+                    gradient_y * (distance_y - y as f64) + gradient_z * (distance_z - z as f64);
+
+                (gradient_x, rest)
+            });
+
+            for i in start..end {
+                let distance_x = xs[i] - corner_x as f64;
+                let curve_x = quintic(distance_x);
+
+                let dot = |corner: usize| {
+                    let (gradient_x, rest) = gradients[corner];
+                    gradient_x * (distance_x - corner_offset(corner)[0] as f64) + rest
+                };
+
+                result[i] = (linear(
+                    linear(
+                        linear(dot(0b000), dot(0b001), curve_z),
+                        linear(dot(0b010), dot(0b011), curve_z),
+                        curve_y,
+                    ),
+                    linear(
+                        linear(dot(0b100), dot(0b101), curve_z),
+                        linear(dot(0b110), dot(0b111), curve_z),
+                        curve_y,
+                    ),
+                    curve_x,
+                ) * SCALE_FACTOR)
+                    .clamp(-1.0, 1.0);
+            }
+
+            start = end;
+        }
+
+        result
+    }
+}
+
+/// Fractal brownian motion built from octaves of perlin noise, equivalent to
+/// `noise::Fbm::<Perlin>::new(seed).set_octaves(octaves)`.
+pub struct RowFbm {
+    /// The noise of each octave, from the lowest frequency to the highest.
+    octaves: Vec<RowPerlin>,
+    /// The factor the summed octaves are scaled by to bring them into the -1 to 1 range.
+    scale_factor: f64,
+}
+
+impl RowFbm {
+    /// The frequency of the first octave.
+    const FREQUENCY: f64 = 1.0;
+    /// How much the frequency grows by with each octave.
+    const LACUNARITY: f64 = std::f64::consts::PI * 2.0 / 3.0;
+    /// How much the amplitude shrinks by with each octave.
+    const PERSISTENCE: f64 = 0.5;
+
+    /// Creates fbm noise with the given number of octaves from the given seed.
+    pub fn new(seed: u32, octaves: usize) -> Self {
+        let octaves = octaves.clamp(1, 32);
+        let denominator = (1..=octaves).fold(0.0, |sum, x| sum + Self::PERSISTENCE.powi(x as i32));
+
+        Self {
+            octaves: octave_sources(seed, octaves),
+            scale_factor: 1.0 / denominator,
+        }
+    }
+}
+
+impl RowNoise for RowFbm {
+    fn get_row(&self, xs: &Row, y: f64, z: f64) -> Row {
+        let mut xs = xs.map(|x| x * Self::FREQUENCY);
+        let mut y = y * Self::FREQUENCY;
+        let mut z = z * Self::FREQUENCY;
+
+        let mut result = [0.0; ROW_WIDTH];
+        let mut attenuation = Self::PERSISTENCE;
+
+        for octave in &self.octaves {
+            let signal = octave.get_row(&xs, y, z);
+
+            for (result, signal) in result.iter_mut().zip(signal) {
+                *result += signal * attenuation;
+            }
+
+            attenuation *= Self::PERSISTENCE;
+
+            xs = xs.map(|x| x * Self::LACUNARITY);
+            y *= Self::LACUNARITY;
+            z *= Self::LACUNARITY;
+        }
+
+        result.map(|result| result * self.scale_factor)
+    }
+}
+
+/// Ridged multifractal noise built from octaves of perlin noise, equivalent to
+/// `noise::RidgedMulti::<Perlin>::new(seed).set_octaves(octaves)`.
+pub struct RowRidgedMulti {
+    /// The noise of each octave, from the lowest frequency to the highest.
+    octaves: Vec<RowPerlin>,
+    /// The factor the summed octaves are scaled by to bring them into the 0 to 2 range.
+    scale_factor: f64,
+}
+
+impl RowRidgedMulti {
+    /// The frequency of the first octave.
+    const FREQUENCY: f64 = 1.0;
+    /// How much the frequency grows by with each octave.
+    const LACUNARITY: f64 = std::f64::consts::PI * 2.0 / 3.0;
+    /// How much the amplitude shrinks by with each octave.
+    const PERSISTENCE: f64 = 1.0;
+    /// How strongly each octave is weighted by the octave before it.
+    const ATTENUATION: f64 = 2.0;
+
+    /// Creates ridged noise with the given number of octaves from the given seed.
+    pub fn new(seed: u32, octaves: usize) -> Self {
+        let octaves = octaves.clamp(1, 32);
+
+        let mut amplitude = 1.0;
+        let mut signal = 1.0;
+        let denominator = 1.0
+            + (1..=octaves).fold(0.0, |sum, x| {
+                amplitude *= Self::PERSISTENCE;
+                let weight = (signal / Self::ATTENUATION.powi(x as i32)).clamp(0.0, 1.0);
+                signal = weight * amplitude;
+                sum + signal
+            });
+
+        Self {
+            octaves: octave_sources(seed, octaves),
+            scale_factor: 2.0 / denominator,
+        }
+    }
+}
+
+impl RowNoise for RowRidgedMulti {
+    fn get_row(&self, xs: &Row, y: f64, z: f64) -> Row {
+        let mut xs = xs.map(|x| x * Self::FREQUENCY);
+        let mut y = y * Self::FREQUENCY;
+        let mut z = z * Self::FREQUENCY;
+
+        let mut result = [0.0; ROW_WIDTH];
+        let mut weights = [1.0; ROW_WIDTH];
+        let mut attenuation = 1.0;
+
+        for octave in &self.octaves {
+            let values = octave.get_row(&xs, y, z);
+
+            for i in 0..ROW_WIDTH {
+                let mut signal = 1.0 - values[i].abs();
+                signal *= signal;
+                signal *= weights[i];
+
+                weights[i] = (signal / Self::ATTENUATION).clamp(0.0, 1.0);
+                result[i] += signal * attenuation;
+            }
+
+            attenuation *= Self::PERSISTENCE;
+
+            xs = xs.map(|x| x * Self::LACUNARITY);
+            y *= Self::LACUNARITY;
+            z *= Self::LACUNARITY;
+        }
+
+        result.map(|result| result * self.scale_factor - 1.0)
+    }
+}
+
+/// The lower of two noise values, equivalent to `noise::Min`.
+pub struct RowMin<A, B> {
+    /// The first noise function.
+    a: A,
+    /// The second noise function.
+    b: B,
+}
+
+impl<A: RowNoise, B: RowNoise> RowMin<A, B> {
+    /// Creates noise taking the lower of the values of the two given noise functions.
+    pub fn new(a: A, b: B) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<A: RowNoise, B: RowNoise> RowNoise for RowMin<A, B> {
+    fn get_row(&self, xs: &Row, y: f64, z: f64) -> Row {
+        let a = self.a.get_row(xs, y, z);
+        let b = self.b.get_row(xs, y, z);
+
+        std::array::from_fn(|i| a[i].min(b[i]))
+    }
+}
+
+/// Returns the perlin noise of each octave of a fractal, seeded the same way `noise` seeds them.
+fn octave_sources(seed: u32, octaves: usize) -> Vec<RowPerlin> {
+    (0..octaves)
+        .map(|octave| RowPerlin::new(seed.wrapping_add(octave as u32)))
+        .collect()
+}
+
+/// Returns the `(x, y, z)` offset of one of the 8 corners of a lattice cell from its first
+/// corner.
+fn corner_offset(corner: usize) -> [usize; 3] {
+    [corner >> 2, corner >> 1 & 1, corner & 1]
+}
+
+/// Returns the lattice cell a coordinate lies in, and how far into it it lies. Coordinates that
+/// are zero or negative whole numbers lie at the far end of the cell below them, like in `noise`.
+fn lattice(value: f64) -> (isize, f64) {
+    let corner = if value <= 0.0 {
+        value as isize - 1
+    } else {
+        value as isize
+    };
+
+    (corner, value - corner as f64)
+}
+
+/// Eases a distance across a lattice cell, so the noise has no visible seams between cells.
+fn quintic(value: f64) -> f64 {
+    let value = value.clamp(0.0, 1.0);
+    value * value * value * (value * (value * 6.0 - 15.0) + 10.0)
+}
+
+/// Linearly interpolates from `a` to `b` by `t`.
+fn linear(a: f64, b: f64, t: f64) -> f64 {
+    b * t + a * (1.0 - t)
+}
+
+#[cfg(test)]
+mod tests {
+    use noise::{Fbm, Min, MultiFractal, NoiseFn, Perlin, RidgedMulti};
+
+    use super::*;
+
+    /// The `(start, step)` of the rows of x coordinates sampled, covering negative coordinates,
+    /// whole numbers (including zero) and the scale chunks are actually sampled at.
+    const ROWS: [(f64, f64); 4] = [
+        (-2.0, 0.25),
+        (-8.3, 0.61),
+        (-1000.0 / 96.0, 1.0 / 96.0),
+        (12345.678, 3.7),
+    ];
+
+    /// The `(y, z)` coordinates each row is sampled at.
+    const PLANES: [(f64, f64); 5] = [
+        (0.0, 0.0),
+        (-1.0, 2.5),
+        (0.73, -4.0),
+        (2.0, -0.125),
+        (-17.31, 311.9),
+    ];
+
+    /// Asserts that sampling `row` a row at a time gives exactly the same values as sampling
+    /// `point` one point at a time.
+    fn assert_matches(row: &impl RowNoise, point: &impl NoiseFn<f64, 3>) {
+        for (start, step) in ROWS {
+            let xs: Row = std::array::from_fn(|i| start + step * i as f64);
+
+            for (y, z) in PLANES {
+                let expected = xs.map(|x| point.get([x, y, z]));
+
+                assert_eq!(
+                    row.get_row(&xs, y, z),
+                    expected,
+                    "row {start} at ({y}, {z})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn perlin_matches_noise() {
+        for seed in [0, 129, u32::MAX] {
+            assert_matches(&RowPerlin::new(seed), &Perlin::new(seed));
+        }
+    }
+
+    #[test]
+    fn fbm_matches_noise() {
+        for (seed, octaves) in [(129, 1), (129, 4), (u32::MAX - 8, 6)] {
+            assert_matches(
+                &RowFbm::new(seed, octaves),
+                &Fbm::<Perlin>::new(seed).set_octaves(octaves),
+            );
+        }
+    }
+
+    #[test]
+    fn ridged_multi_matches_noise() {
+        for (seed, octaves) in [(130, 1), (130, 2), (u32::MAX - 8, 6)] {
+            assert_matches(
+                &RowRidgedMulti::new(seed, octaves),
+                &RidgedMulti::<Perlin>::new(seed).set_octaves(octaves),
+            );
+        }
+    }
+
+    #[test]
+    fn min_matches_noise() {
+        let seed = 129u32;
+
+        assert_matches(
+            &RowMin::new(
+                RowRidgedMulti::new(seed.wrapping_add(1), 2),
+                RowRidgedMulti::new(seed.wrapping_add(2), 2),
+            ),
+            &Min::new(
+                RidgedMulti::<Perlin>::new(seed.wrapping_add(1)).set_octaves(2),
+                RidgedMulti::<Perlin>::new(seed.wrapping_add(2)).set_octaves(2),
+            ),
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{mpsc::SyncSender, Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context};
+use image::RgbaImage;
+use wgpu::*;
+
+/// The directory screenshots are saved to.
+pub const SCREENSHOT_DIRECTORY: &str = "screenshots";
+
+/// The result of mapping a buffer for reading, once it is mapped.
+type MapResult = Arc<Mutex<Option<Result<(), BufferAsyncError>>>>;
+
+/// Where a captured frame goes once it has been read back.
+pub enum FrameSink {
+    /// Saved as a screenshot.
+    Screenshot,
+    /// Sent to a recording, to be written in order with the frames around it.
+    Recording(SyncSender<Frame>),
+}
+
+/// A frame read back from the GPU, as the rows of the texture it was copied from.
+pub struct Frame {
+    /// The pixels of the frame, with each row padded to `bytes_per_row`.
+    data: Vec<u8>,
+    /// The size (in pixels) of the frame.
+    width: u32,
+    height: u32,
+    /// The number of bytes of each (padded) row of the `data`.
+    bytes_per_row: u32,
+    /// The format of the texture the frame was copied from.
+    format: TextureFormat,
+}
+
+/// A frame copied into a buffer, being read back from the GPU.
+struct Capture {
+    /// The buffer the frame is copied into, with each row padded to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+    buffer: Buffer,
+    /// The size (in pixels) of the frame.
+    width: u32,
+    height: u32,
+    /// The number of bytes of each (padded) row of the buffer.
+    bytes_per_row: u32,
+    /// The format of the texture the frame was copied from.
+    format: TextureFormat,
+    /// Set once the buffer has been mapped.
+    mapped: MapResult,
+    /// Where the frame goes once it has been read back.
+    sink: FrameSink,
+}
+
+/// Copies frames out of the surface and saves them as PNGs under `SCREENSHOT_DIRECTORY`, or
+/// sends them to a recording. Frames are read back once the GPU has drawn them, and encoded on
+/// another thread, so taking a screenshot doesn't hold up the frames after it.
+#[derive(Default)]
+pub struct ScreenshotCapture {
+    /// The captures the frame being drawn is copied into.
+    queued: Vec<Capture>,
+    /// The captures of earlier frames, waiting to be read back.
+    in_flight: Vec<Capture>,
+}
+
+impl ScreenshotCapture {
+    /// Creates a capture without any screenshots queued.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues the frame being drawn, into a texture of the given size and format, to be sent to
+    /// the given sink. The texture is copied into the capture by `ScreenshotCapture::copy`.
+    pub fn queue(
+        &mut self,
+        device: &Device,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        sink: FrameSink,
+    ) {
+        let bytes_per_row = (width * 4).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Screenshot Buffer"),
+            size: (bytes_per_row * height) as BufferAddress,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        self.queued.push(Capture {
+            buffer,
+            width,
+            height,
+            bytes_per_row,
+            format,
+            mapped: Arc::default(),
+            sink,
+        });
+    }
+
+    /// Records copying the given texture into the captures queued this frame.
+    pub fn copy(&self, encoder: &mut CommandEncoder, texture: &Texture) {
+        for capture in &self.queued {
+            encoder.copy_texture_to_buffer(
+                texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer: &capture.buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(capture.bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: capture.width,
+                    height: capture.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    /// Starts reading back the captures queued this frame, once the frame copied into them has
+    /// been submitted.
+    pub fn read_back(&mut self) {
+        for capture in self.queued.drain(..) {
+            let mapped = Arc::clone(&capture.mapped);
+
+            capture
+                .buffer
+                .slice(..)
+                .map_async(MapMode::Read, move |result| {
+                    *mapped.lock().unwrap() = Some(result);
+                });
+
+            self.in_flight.push(capture);
+        }
+    }
+
+    /// Sends the captures that have been read back since the last update to their sinks, in
+    /// the order they were queued. Screenshots are saved each on a thread of its own.
+    pub fn update(&mut self, device: &Device) {
+        if self.in_flight.is_empty() {
+            return;
+        }
+
+        device.poll(Maintain::Poll);
+
+        // buffers are mapped in the order they were submitted, so the frames of a recording
+        // stay in order
+        let (read, in_flight) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|capture| capture.mapped.lock().unwrap().is_some());
+        self.in_flight = in_flight;
+
+        for capture in read {
+            let result = capture.mapped.lock().unwrap().take();
+
+            if let Some(Err(error)) = result {
+                eprintln!("could not read the captured frame: {error:?}");
+                continue;
+            }
+
+            // copied out, so the frame is converted and encoded on another thread
+            let frame = Frame {
+                data: capture.buffer.slice(..).get_mapped_range().to_vec(),
+                width: capture.width,
+                height: capture.height,
+                bytes_per_row: capture.bytes_per_row,
+                format: capture.format,
+            };
+
+            match capture.sink {
+                FrameSink::Screenshot => {
+                    std::thread::spawn(move || match frame.to_image().and_then(save) {
+                        Ok(path) => println!("saved screenshot to {path:?}"),
+                        Err(error) => eprintln!("could not save the screenshot: {error:?}"),
+                    });
+                }
+                // the recording has stopped if its writer has gone
+                FrameSink::Recording(sender) => {
+                    let _ = sender.send(frame);
+                }
+            }
+        }
+    }
+}
+
+impl Frame {
+    /// Converts the frame into an opaque RGBA image, leaving out the padding of its rows.
+    pub fn to_image(&self) -> anyhow::Result<RgbaImage> {
+        let swap_red_blue = match self.format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+            format => bail!("can't convert frames of format {format:?}"),
+        };
+
+        let mut pixels = Vec::with_capacity((self.width * self.height * 4) as usize);
+
+        for row in self.data.chunks_exact(self.bytes_per_row as usize) {
+            for pixel in row[..(self.width * 4) as usize].chunks_exact(4) {
+                let [r, g, b, _] = pixel.try_into().unwrap();
+
+                pixels.extend_from_slice(&match swap_red_blue {
+                    true => [b, g, r, 255],
+                    false => [r, g, b, 255],
+                });
+            }
+        }
+
+        RgbaImage::from_raw(self.width, self.height, pixels).context("frame doesn't match its size")
+    }
+}
+
+/// Writes the image to a PNG under `SCREENSHOT_DIRECTORY` named after the current time,
+/// returning its path.
+fn save(image: RgbaImage) -> anyhow::Result<PathBuf> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let path = PathBuf::from(SCREENSHOT_DIRECTORY).join(format!("{timestamp}.png"));
+
+    fs::create_dir_all(SCREENSHOT_DIRECTORY)
+        .context(format!("creating {SCREENSHOT_DIRECTORY:?}"))?;
+    image.save(&path).context(format!("writing {path:?}"))?;
+
+    Ok(path)
+}
@@ -1,31 +1,124 @@
 // Uniforms
-struct CameraUniform {
-	view_proj: mat4x4<f32>,	
+#include "camera.wgsl"
+#include "environment.wgsl"
+#include "fog.wgsl"
+
+@group(2) @binding(0) var<uniform> environment: EnvironmentUniform;
+
+// The width of each cluster of the light grid, in voxels, and the number of clusters along each
+// of its axes (matching `lighting.rs`).
+const LIGHT_CLUSTER_SIZE: f32 = 16.0;
+const LIGHT_GRID_SIZE: i32 = 16;
+
+struct LightGridUniform {
+	// The cluster (in world cluster coordinates) at the corner of the grid.
+	origin: vec3<i32>,
+	light_count: u32,
 }
 
-@group(0) @binding(0) var<uniform> camera: CameraUniform;
+struct PointLight {
+	// The position of the light relative to the camera.
+	offset: vec3<f32>,
+	// The distance the light reaches.
+	radius: f32,
+	color: vec3<f32>,
+}
+
+@group(3) @binding(0) var<uniform> light_grid: LightGridUniform;
+@group(3) @binding(1) var<storage, read> lights: array<PointLight>;
+// The offset and count of each cluster's lights in `light_indices`.
+@group(3) @binding(2) var<storage, read> light_clusters: array<vec2<u32>>;
+@group(3) @binding(3) var<storage, read> light_indices: array<u32>;
 
 // Textures
 @group(1) @binding(0) var texture: texture_2d_array<f32>;
 @group(1) @binding(1) var texture_sampler: sampler;
+// The normal maps of the textures, in the same layers, with green pointing up the image.
+@group(1) @binding(2) var normal_map: texture_2d_array<f32>;
+
+// How the surface of each texture reflects light, from the texture manifest.
+struct Material {
+	roughness: f32,
+	metallic: f32,
+	// How brightly the texture glows, as a multiple of its color.
+	emissive: f32,
+	// The number of frames the texture is animated through, in consecutive layers, and the
+	// seconds each is shown for.
+	frames: u32,
+	frame_time: f32,
+	// 1 if the texture is multiplied by the foliage tint of its vertices, and 0 otherwise.
+	tinted: u32,
+}
+
+@group(1) @binding(3) var<storage, read> materials: array<Material>;
+// The regions of the textures that glow (in the red channel), in the same layers.
+@group(1) @binding(4) var emissive_mask: texture_2d_array<f32>;
 
 struct VertexInput {
 	@location(0) position: vec3<f32>,	
 	@location(1) normal: vec3<f32>,
 	@location(2) texture_ambient: u32,
+	// The color foliage is tinted with by the climate. Its alpha is the opacity of faces drawn
+	// in the transparent pass that aren't water, which is 1 for every other face.
+	@location(4) tint: vec4<f32>,
+}
+
+struct InstanceInput {
+	// The offset of the mesh's origin relative to the camera.
+	@location(3) offset: vec3<f32>,
+}
+
+struct ModelInstanceInput {
+	// The columns of the matrix transforming the model's vertices into positions relative to
+	// the camera.
+	@location(5) model_0: vec4<f32>,
+	@location(6) model_1: vec4<f32>,
+	@location(7) model_2: vec4<f32>,
+	@location(8) model_3: vec4<f32>,
 }
 
 struct VertexOutput {
 	@builtin(position) clip_position: vec4<f32>,
 	@location(0) uv: vec2<f32>,
 	@location(1) texture_index: u32,
-	@location(2) ambient: f32
+	@location(2) ambient: f32,
+	@location(3) normal: vec3<f32>,
+	// The position of the vertex relative to the camera.
+	@location(4) offset: vec3<f32>,
+	@location(5) tint: vec3<f32>,
+	@location(6) opacity: f32,
 };
 
 @vertex
 fn vs_main(
 	input: VertexInput,
+	instance: InstanceInput,
+	@builtin(vertex_index) vertex_id: u32
+) -> VertexOutput {
+	return vertex_output(input, input.position + instance.offset, input.normal, vertex_id);
+}
+
+@vertex
+fn vs_instanced(
+	input: VertexInput,
+	instance: ModelInstanceInput,
 	@builtin(vertex_index) vertex_id: u32
+) -> VertexOutput {
+	let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+
+	let offset = (model * vec4<f32>(input.position, 1.0)).xyz;
+	let normal = normalize((model * vec4<f32>(input.normal, 0.0)).xyz);
+
+	return vertex_output(input, offset, normal, vertex_id);
+}
+
+// Returns the output of a vertex placed at the given offset from the camera, facing along the
+// given normal.
+fn vertex_output(
+	input: VertexInput,
+	offset: vec3<f32>,
+	normal: vec3<f32>,
+	vertex_id: u32
 ) -> VertexOutput {
 	var out: VertexOutput;
 
@@ -36,19 +129,243 @@ fn vs_main(
 		vec2<f32>(1.0, 0.0),
 	);
 
-	out.clip_position = camera.view_proj * vec4<f32>(input.position, 1.0);
+	out.offset = offset;
+	out.clip_position = camera.view_proj * vec4<f32>(out.offset, 1.0);
 	out.uv = tex_coords[vertex_id % 4];
 
 	out.texture_index = (input.texture_ambient >> 16);
 	out.ambient = f32((input.texture_ambient << 16) >> 16) / 3.0;
+	out.normal = normal;
+	out.tint = input.tint.rgb;
+	out.opacity = input.tint.a;
 
 	return out;
 }
 
+// The surface of a fragment being shaded.
+struct Surface {
+	albedo: vec3<f32>,
+	normal: vec3<f32>,
+	// The direction from the fragment towards the camera.
+	to_camera: vec3<f32>,
+	roughness: f32,
+	metallic: f32,
+}
+
+const PI: f32 = 3.14159265;
+
+// Returns the light the surface reflects towards the camera, for each unit of light arriving
+// from the given direction, using the Cook-Torrance BRDF (with the GGX distribution, Schlick's
+// Fresnel approximation and Smith's shadowing). It's scaled by pi, so a rough dielectric
+// reflects about as much as plain lambertian shading would.
+fn cook_torrance(surface: Surface, to_light: vec3<f32>) -> vec3<f32> {
+	let n_dot_l = max(dot(surface.normal, to_light), 0.0);
+
+	if n_dot_l <= 0.0 {
+		return vec3<f32>(0.0);
+	}
+
+	let half_vector = normalize(surface.to_camera + to_light);
+	let n_dot_v = max(dot(surface.normal, surface.to_camera), 0.0001);
+	let n_dot_h = max(dot(surface.normal, half_vector), 0.0);
+	let v_dot_h = max(dot(surface.to_camera, half_vector), 0.0);
+
+	let alpha = max(surface.roughness * surface.roughness, 0.002);
+	let alpha_squared = alpha * alpha;
+	let d = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+	let distribution = alpha_squared / (PI * d * d);
+
+	let k = (surface.roughness + 1.0) * (surface.roughness + 1.0) / 8.0;
+	let shadowing = n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+
+	// metals tint their reflections, and reflect (rather than scatter) all the light they take
+	let f0 = mix(vec3<f32>(0.04), surface.albedo, surface.metallic);
+	let fresnel = f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+
+	let specular = distribution * shadowing * fresnel / (4.0 * n_dot_v * n_dot_l);
+	let diffuse = (1.0 - fresnel) * (1.0 - surface.metallic) * surface.albedo / PI;
+
+	return (diffuse + specular) * n_dot_l * PI;
+}
+
+// Returns the light the point lights reaching the fragment's cluster reflect off of it.
+fn point_light(offset: vec3<f32>, surface: Surface) -> vec3<f32> {
+	let position = offset + camera.eye.xyz;
+	let cell = vec3<i32>(floor(position / LIGHT_CLUSTER_SIZE)) - light_grid.origin;
+
+	if any(cell < vec3<i32>(0)) || any(cell >= vec3<i32>(LIGHT_GRID_SIZE)) {
+		return vec3<f32>(0.0);
+	}
+
+	let cluster = light_clusters[cell.x + (cell.y + cell.z * LIGHT_GRID_SIZE) * LIGHT_GRID_SIZE];
+	var light = vec3<f32>(0.0);
+
+	for (var i = 0u; i < cluster.y; i++) {
+		let point = lights[light_indices[cluster.x + i]];
+		let to_light = point.offset - offset;
+		let distance = length(to_light);
+
+		// fades smoothly to nothing at the light's radius
+		let falloff = clamp(1.0 - (distance * distance) / (point.radius * point.radius), 0.0, 1.0);
+		let reflected = cook_torrance(surface, to_light / max(distance, 0.0001));
+
+		light += point.color * falloff * falloff * reflected;
+	}
+
+	return light;
+}
+
+// Returns where to sample the textures so their texels stay crisp squares up close, with only
+// the seams between them blended (across about a pixel), while distant surfaces still blend
+// between texels and mipmaps. The textures must be sampled with the gradients of the original
+// coordinates, so the right mipmap is still picked.
+fn crisp_uv(uv: vec2<f32>) -> vec2<f32> {
+	let size = vec2<f32>(textureDimensions(texture).xy);
+	let texel = uv * size;
+	let seam = floor(texel + 0.5);
+	let width = max(fwidth(texel), vec2<f32>(0.00001));
+	let blend = clamp((texel - seam) / width, vec2<f32>(-0.5), vec2<f32>(0.5));
+
+	return (seam + blend) / size;
+}
+
+// Bends the given (face) normal by the normal sampled from the fragment's normal map. The
+// tangent and bitangent are worked out from how the position and texture coordinates change
+// across the screen, so the mesh doesn't need to store them.
+fn apply_normal_map(input: VertexOutput, normal: vec3<f32>, mapped: vec3<f32>) -> vec3<f32> {
+	// from [0, 1] to [-1, 1], with v running down the image rather than up
+	let sampled = (mapped * 2.0 - 1.0) * vec3<f32>(1.0, -1.0, 1.0);
+
+	let dp1 = dpdx(input.offset);
+	let dp2 = dpdy(input.offset);
+	let duv1 = dpdx(input.uv);
+	let duv2 = dpdy(input.uv);
+
+	let dp2_perp = cross(dp2, normal);
+	let dp1_perp = cross(normal, dp1);
+	let tangent = dp2_perp * duv1.x + dp1_perp * duv2.x;
+	let bitangent = dp2_perp * duv1.y + dp1_perp * duv2.y;
+
+	let scale = inverseSqrt(max(max(dot(tangent, tangent), dot(bitangent, bitangent)), 1e-12));
+	let frame = mat3x3<f32>(tangent * scale, bitangent * scale, normal);
+
+	return normalize(frame * sampled);
+}
+
+// Returns the textured and shaded color of a fragment, facing the given normal (before its
+// normal map is applied).
+fn shade(input: VertexOutput, face_normal: vec3<f32>) -> vec4<f32> {
+	let uv = crisp_uv(input.uv);
+	let uv_dx = dpdx(input.uv);
+	let uv_dy = dpdy(input.uv);
+	let material = materials[input.texture_index];
+	// animated textures cycle through the layers following their first frame
+	let frame = u32(environment.time / material.frame_time) % material.frames;
+	let layer = input.texture_index + frame;
+
+	let diffuse = textureSampleGrad(texture, texture_sampler, uv, layer, uv_dx, uv_dy);
+	let mapped = textureSampleGrad(normal_map, texture_sampler, uv, layer, uv_dx, uv_dy).xyz;
+	let emissive_region =
+		textureSampleGrad(emissive_mask, texture_sampler, uv, layer, uv_dx, uv_dy).r;
+
+	let albedo = diffuse.rgb * select(vec3<f32>(1.0), input.tint, material.tinted == 1u);
+
+	var surface: Surface;
+	surface.albedo = albedo;
+	surface.normal = apply_normal_map(input, face_normal, mapped);
+	surface.to_camera = normalize(-input.offset);
+	surface.roughness = material.roughness;
+	surface.metallic = material.metallic;
+
+	// faces turned away from the sun are only lit by the ambient light
+	let sun_light = (1.0 - environment.ambient_light) * environment.daylight;
+	let light = albedo * environment.ambient_light
+		+ cook_torrance(surface, environment.sun_direction) * sun_light
+		+ point_light(input.offset, surface);
+
+	// glowing regions shine at full intensity, however dark their surroundings are
+	let emission = albedo * material.emissive * emissive_region;
+
+	return vec4<f32>(light * input.ambient + emission, diffuse.a);
+}
+
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-	var diffuse = textureSample(texture, texture_sampler, input.uv, input.texture_index);
-	var ambient = vec4<f32>(vec3<f32>(1.0) * input.ambient, 1.0);
+	let color = shade(input, normalize(input.normal));
+
+	return vec4<f32>(apply_fog(color.rgb, input.offset), color.a);
+}
+
+// The opacity of water, when looking straight down into it.
+const WATER_ALPHA: f32 = 0.7;
+// The height and steepness of the waves on the surface of water, the size of the largest
+// ones (in voxels), and how fast they move.
+const WAVE_HEIGHT: f32 = 0.06;
+const WAVE_LENGTH: f32 = 6.0;
+const WAVE_SPEED: f32 = 1.2;
+// How sharp the reflection of the sun is.
+const SUN_SHININESS: f32 = 256.0;
+const SUN_REFLECTION_COLOR: vec3<f32> = vec3<f32>(1.0, 0.9, 0.7);
+
+// Returns the slope (along x and z) of the waves at the given position on the water's surface,
+// as a sum of a few waves travelling in different directions.
+fn wave_slope(position: vec2<f32>) -> vec2<f32> {
+	let time = environment.time * WAVE_SPEED;
+	var slope = vec2<f32>(0.0);
+
+	var directions = array<vec2<f32>, 4>(
+		vec2<f32>(1.0, 0.0),
+		vec2<f32>(0.6, 0.8),
+		vec2<f32>(-0.7, 0.7),
+		vec2<f32>(0.2, -1.0),
+	);
+
+	for (var i = 0; i < 4; i++) {
+		let scale = 1.0 / f32(i + 1);
+		let frequency = 6.2831853 / (WAVE_LENGTH * scale);
+		let phase = dot(directions[i], position) * frequency + time * (1.0 + f32(i) * 0.3);
+
+		// the derivative of sin(phase) * height, with smaller waves being lower
+		slope += directions[i] * cos(phase) * frequency * WAVE_HEIGHT * scale;
+	}
+
+	return slope;
+}
+
+// Shades water, with waves rippling its surface, reflecting the sky (and sun) more strongly the
+// flatter it is looked across. Only the sky is reflected, as the rest of the scene can't be read
+// while the water is being drawn into it.
+//
+// Faces with an opacity below 1 (the ghost of a block about to be placed) are shaded like any
+// other voxel instead, only translucent.
+@fragment
+fn fs_water(input: VertexOutput) -> @location(0) vec4<f32> {
+	var normal = normalize(input.normal);
+
+	if input.opacity < 1.0 {
+		let color = shade(input, normal);
+		return vec4<f32>(apply_fog(color.rgb, input.offset), color.a * input.opacity);
+	}
+
+	// only the top of the water moves
+	if normal.y > 0.5 {
+		let slope = wave_slope(input.offset.xz + camera.eye.xz);
+		normal = normalize(vec3<f32>(-slope.x, 1.0, -slope.y));
+	}
+
+	let color = shade(input, normal);
+
+	let to_camera = normalize(-input.offset);
+	let reflected = reflect(-to_camera, normal);
+
+	// schlick's approximation, with the reflectance of water looked straight into
+	let fresnel = 0.02 + 0.98 * pow(1.0 - max(dot(normal, to_camera), 0.0), 5.0);
+
+	let sun = pow(max(dot(reflected, environment.sun_direction), 0.0), SUN_SHININESS);
+	let reflection = environment.sky_color + SUN_REFLECTION_COLOR * sun * environment.daylight;
+
+	let shaded = mix(color.rgb, reflection, fresnel);
+	let alpha = mix(color.a * WATER_ALPHA, 1.0, fresnel);
 
-	return ambient * diffuse;
+	return vec4<f32>(apply_fog(shaded, input.offset), alpha);
 }
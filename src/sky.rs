@@ -0,0 +1,71 @@
+use wgpu::*;
+
+use crate::preprocessor;
+
+/// Draws the sky behind the terrain: its color, and at night a field of stars and the moon,
+/// fading in as the sun sets and out as it rises.
+pub struct Sky {
+    /// The pipeline filling the pixels no terrain was drawn over.
+    pipeline: RenderPipeline,
+}
+
+impl Sky {
+    /// Creates the pipeline drawing the sky onto surfaces of the given format. It shares the
+    /// bind group layouts of the terrain (the camera as group 0 and the environment as group 2),
+    /// so it can be drawn in the same pass without rebinding anything.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        bind_group_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        let shader =
+            preprocessor::create_shader_module(device, "sky.wgsl", include_str!("sky.wgsl"), &[]);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Sky Pipeline Layout"),
+            push_constant_ranges: &[],
+            bind_group_layouts,
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Sky Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: PipelineCompilationOptions::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            // the sky sits on the far plane, so it only passes where the depth buffer is clear
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth32Float,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self { pipeline }
+    }
+
+    /// Draws the sky behind what has been drawn so far. The terrain's bind groups must already
+    /// be bound, and the pipeline is left changed.
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,92 @@
+// Uniforms
+#include "camera.wgsl"
+#include "environment.wgsl"
+
+@group(2) @binding(0) var<uniform> environment: EnvironmentUniform;
+
+// The number of cells across each face of the cube the sky is split into, each holding at most
+// one star.
+const STAR_CELLS: f32 = 160.0;
+// The fraction of cells holding a star.
+const STAR_CHANCE: f32 = 0.04;
+// The radius of a star, as a fraction of its cell.
+const STAR_RADIUS: f32 = 0.12;
+
+// The angular radius of the moon, in radians.
+const MOON_RADIUS: f32 = 0.035;
+// The color of the moon's disc, and of the faint glow around it.
+const MOON_COLOR: vec3<f32> = vec3<f32>(0.9, 0.92, 1.0);
+const MOON_GLOW_COLOR: vec3<f32> = vec3<f32>(0.08, 0.09, 0.12);
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	// The position of the pixel in normalized device coordinates.
+	@location(0) ndc: vec2<f32>,
+}
+
+// Covers the screen with a single triangle on the far plane.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_id: u32) -> VertexOutput {
+	var out: VertexOutput;
+
+	let uv = vec2<f32>(f32((vertex_id << 1u) & 2u), f32(vertex_id & 2u));
+	let ndc = uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+	out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+	out.ndc = ndc;
+
+	return out;
+}
+
+// Returns a pseudo-random number from 0 to 1 for the given cell.
+fn hash(cell: vec3<f32>) -> f32 {
+	let p = fract(cell * vec3<f32>(0.1031, 0.1030, 0.0973));
+	let q = p + dot(p, p.yxz + 33.33);
+	return fract((q.x + q.y) * q.z);
+}
+
+// Returns the brightness of the star field in the given direction.
+fn stars(direction: vec3<f32>) -> f32 {
+	// project onto the cube around the camera, so the cells are roughly the same size everywhere
+	let projected = direction / max(max(abs(direction.x), abs(direction.y)), abs(direction.z));
+	let position = projected * STAR_CELLS;
+	let cell = floor(position);
+
+	let seed = hash(cell);
+	if seed > STAR_CHANCE {
+		return 0.0;
+	}
+
+	// place the star somewhere inside its cell, with a random brightness
+	let center = cell + 0.25 + 0.5 * vec3<f32>(hash(cell + 1.0), hash(cell + 2.0), hash(cell + 3.0));
+	let distance = length(position - center);
+	let brightness = 0.4 + 0.6 * seed / STAR_CHANCE;
+
+	return brightness * (1.0 - smoothstep(0.0, STAR_RADIUS, distance));
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+	// the camera sits at the origin, so any point along the pixel's ray gives its direction
+	let point = camera.inverse_view_proj * vec4<f32>(input.ndc, 0.5, 1.0);
+	let direction = normalize(point.xyz / point.w);
+
+	var color = environment.sky_color;
+
+	// the night sky fades in as the sun sets, and is hidden below the horizon (and underwater)
+	let night = (1.0 - environment.daylight) * smoothstep(-0.05, 0.05, direction.y)
+		* (1.0 - environment.underwater);
+
+	if night > 0.0 {
+		color += vec3<f32>(stars(direction)) * night;
+
+		// the moon is always opposite the sun
+		let moon_direction = -environment.sun_direction;
+		let moon_angle = acos(clamp(dot(direction, moon_direction), -1.0, 1.0));
+		let disc = 1.0 - smoothstep(MOON_RADIUS * 0.9, MOON_RADIUS, moon_angle);
+		let glow = exp(-moon_angle * 8.0);
+
+		color = mix(color, MOON_COLOR, disc * night) + MOON_GLOW_COLOR * glow * night;
+	}
+
+	return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,129 @@
+// Uniforms
+#include "camera.wgsl"
+
+// The depth buffer of the scene, where the sky is left at the far plane (1).
+@group(1) @binding(0) var depth: texture_depth_2d;
+
+// The number of points tested around each pixel.
+const SAMPLES: i32 = 16;
+// The radius of the hemisphere tested around each pixel, in voxels.
+const RADIUS: f32 = 1.5;
+// How far in front of the surface a point must be covered to count, hiding self-occlusion.
+const BIAS: f32 = 0.05;
+// How strongly occlusion darkens the scene, from 0 to 1.
+const STRENGTH: f32 = 0.8;
+// The distances (in voxels) over which the effect fades out, as its detail becomes too small to
+// see, and fog takes over.
+const FADE_START: f32 = 64.0;
+const FADE_END: f32 = 128.0;
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	// The position of the pixel on the screen, from (0, 0) at the top left to (1, 1).
+	@location(0) uv: vec2<f32>,
+}
+
+// Covers the screen with a single triangle.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_id: u32) -> VertexOutput {
+	var out: VertexOutput;
+
+	let uv = vec2<f32>(f32((vertex_id << 1u) & 2u), f32(vertex_id & 2u));
+	out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+	out.uv = uv;
+
+	return out;
+}
+
+// Returns the depth of the scene at the given pixel, clamped to the screen.
+fn depth_at(pixel: vec2<i32>) -> f32 {
+	let size = vec2<i32>(textureDimensions(depth));
+	return textureLoad(depth, clamp(pixel, vec2<i32>(0), size - 1), 0);
+}
+
+// Returns the position (relative to the camera) a depth at the given position on the screen
+// lies at.
+fn position_from(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+	let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+	let position = camera.inverse_view_proj * vec4<f32>(ndc, depth, 1.0);
+
+	return position.xyz / position.w;
+}
+
+// Returns the position (relative to the camera) of the scene at the given pixel.
+fn position_at(pixel: vec2<i32>) -> vec3<f32> {
+	let uv = (vec2<f32>(pixel) + 0.5) / vec2<f32>(textureDimensions(depth));
+	return position_from(uv, depth_at(pixel));
+}
+
+// Returns the pseudo-random number from 0 to 1 for the given pixel and seed.
+fn hash(pixel: vec2<i32>, seed: f32) -> f32 {
+	let p = vec2<f32>(pixel) + seed * vec2<f32>(17.13, 31.71);
+	return fract(52.9829189 * fract(dot(p, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+	let pixel = vec2<i32>(input.clip_position.xy);
+	let center_depth = depth_at(pixel);
+
+	// the sky isn't occluded
+	if center_depth >= 1.0 {
+		return vec4<f32>(1.0);
+	}
+
+	let position = position_from(input.uv, center_depth);
+	let distance = length(position);
+	let fade = 1.0 - smoothstep(FADE_START, FADE_END, distance);
+
+	if fade <= 0.0 {
+		return vec4<f32>(1.0);
+	}
+
+	// reconstruct the normal from the neighbours closest in depth on each axis, so it doesn't
+	// blend across the silhouettes of other terrain
+	let left = position_at(pixel - vec2<i32>(1, 0)) - position;
+	let right = position_at(pixel + vec2<i32>(1, 0)) - position;
+	let up = position_at(pixel - vec2<i32>(0, 1)) - position;
+	let down = position_at(pixel + vec2<i32>(0, 1)) - position;
+
+	let horizontal = select(-left, right, dot(right, right) < dot(left, left));
+	let vertical = select(-up, down, dot(down, down) < dot(up, up));
+	var normal = normalize(cross(vertical, horizontal));
+	normal = select(-normal, normal, dot(normal, position) <= 0.0);
+
+	var occlusion = 0.0;
+
+	for (var i = 0; i < SAMPLES; i++) {
+		// a random point in the hemisphere above the surface, more of them close to it
+		let seed = f32(i);
+		let z = hash(pixel, seed);
+		let angle = hash(pixel, seed + 0.5) * 6.2831853;
+		let r = sqrt(1.0 - z * z);
+		var direction = vec3<f32>(r * cos(angle), r * sin(angle), z);
+		direction = select(-direction, direction, dot(direction, normal) >= 0.0);
+
+		let scale = (seed + 1.0) / f32(SAMPLES);
+		let point = position + direction * RADIUS * mix(0.1, 1.0, scale * scale);
+
+		let clip = camera.view_proj * vec4<f32>(point, 1.0);
+		if clip.w <= 0.0 {
+			continue;
+		}
+
+		let sample_uv = (clip.xy / clip.w) * vec2<f32>(0.5, -0.5) + 0.5;
+		let sample_pixel = vec2<i32>(sample_uv * vec2<f32>(textureDimensions(depth)));
+		let scene = position_at(sample_pixel);
+
+		// covered when the scene is closer to the camera than the point, but only by terrain
+		// within the radius, so distant terrain in front doesn't darken the background
+		let in_front = length(scene) < length(point) - BIAS;
+		let in_range = smoothstep(0.0, 1.0, RADIUS / abs(distance - length(scene)));
+
+		occlusion += select(0.0, in_range, in_front);
+	}
+
+	let ambient = 1.0 - occlusion / f32(SAMPLES) * STRENGTH * fade;
+
+	return vec4<f32>(vec3<f32>(ambient), 1.0);
+}
@@ -0,0 +1,381 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+};
+
+use anyhow::{bail, Context};
+use glam::IVec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chunk::{Chunk, DEFAULT_SEED},
+    dimension::{Dimension, WorldType},
+};
+
+/// The directory worlds are saved to by default.
+pub const DEFAULT_WORLD_DIRECTORY: &str = "world";
+
+/// The name of the config file the options for new worlds are loaded from.
+pub const WORLD_CONFIG_FILE: &str = "world.toml";
+
+/// The name of the file a world's metadata is saved to, within the world directory.
+const WORLD_INFO_FILE: &str = "world.toml";
+
+/// Options for creating new worlds, loaded from `WORLD_CONFIG_FILE`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldConfig {
+    /// The seed new worlds are generated with. `DEFAULT_SEED` is used if not set.
+    pub seed: Option<Seed>,
+    /// The type of world created. Normal worlds are created if not set.
+    pub world_type: Option<WorldType>,
+}
+
+/// A world seed as entered by the player, either a number or arbitrary text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Seed {
+    /// A numeric seed, used as-is.
+    Number(u32),
+    /// A text seed, hashed into a numeric one.
+    Text(String),
+}
+
+impl Seed {
+    /// Returns the numeric seed worlds are generated with. Text that is a (possibly negative)
+    /// number is used as that number, and any other text is hashed the same way Java's
+    /// `String::hashCode` does, so text seeds behave like they do in other voxel games.
+    pub fn value(&self) -> u32 {
+        match self {
+            Self::Number(seed) => *seed,
+            Self::Text(text) => {
+                let text = text.trim();
+
+                text.parse::<u32>()
+                    .or_else(|_| text.parse::<i32>().map(|seed| seed as u32))
+                    .unwrap_or_else(|_| {
+                        text.encode_utf16()
+                            .fold(0u32, |hash, c| hash.wrapping_mul(31).wrapping_add(c as u32))
+                    })
+            }
+        }
+    }
+}
+
+impl FromStr for Seed {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::Text(s.to_string()))
+    }
+}
+
+/// The metadata of a saved world, needed to keep generating it the same way it was created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldInfo {
+    /// The seed the world is generated with.
+    pub seed: u32,
+    /// The type of the world. Worlds saved before world types existed are normal worlds.
+    #[serde(default)]
+    pub world_type: WorldType,
+}
+
+impl WorldInfo {
+    /// Reads the metadata of the world saved in the given directory. If no world has been saved
+    /// there yet, a new one is created with the given seed (or `DEFAULT_SEED`) and world type
+    /// (or a normal world), and its metadata written out. Existing worlds always keep their own
+    /// seed and world type.
+    pub fn open_or_create(
+        directory: &Path,
+        seed: Option<u32>,
+        world_type: Option<WorldType>,
+    ) -> anyhow::Result<Self> {
+        let path = directory.join(WORLD_INFO_FILE);
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let info: Self = toml::from_str(&contents).context(format!("parsing {path:?}"))?;
+
+                if seed.is_some_and(|seed| seed != info.seed) {
+                    eprintln!(
+                        "ignoring seed, {directory:?} was already created with seed {}",
+                        info.seed
+                    );
+                }
+
+                if world_type.is_some_and(|world_type| world_type != info.world_type) {
+                    eprintln!(
+                        "ignoring world type, {directory:?} was already created as {:?}",
+                        info.world_type
+                    );
+                }
+
+                Ok(info)
+            }
+
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                let info = Self {
+                    seed: seed.unwrap_or(DEFAULT_SEED),
+                    world_type: world_type.unwrap_or_default(),
+                };
+
+                fs::create_dir_all(directory).context(format!("creating {directory:?}"))?;
+                fs::write(&path, toml::to_string(&info)?).context(format!("writing {path:?}"))?;
+
+                Ok(info)
+            }
+
+            Err(error) => Err(error).context(format!("reading {path:?}")),
+        }
+    }
+}
+
+/// The zstd level chunks are compressed with by default.
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
+
+/// How a saved chunk entry is compressed. Stored as the first byte of every entry, so worlds can
+/// mix entries saved with different settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// The entry is stored as-is.
+    None,
+    /// The entry is compressed with zstd at the given level.
+    Zstd { level: i32 },
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::Zstd {
+            level: DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+
+impl Compression {
+    /// The tag stored in front of an entry compressed this way.
+    fn tag(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Zstd { .. } => 1,
+        }
+    }
+
+    /// Compresses a serialized chunk into an entry, prefixed with the compression tag.
+    pub fn encode(&self, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut entry = vec![self.tag()];
+
+        match self {
+            Self::None => entry.extend_from_slice(bytes),
+            Self::Zstd { level } => entry.extend(zstd::encode_all(bytes, *level)?),
+        }
+
+        Ok(entry)
+    }
+
+    /// Decompresses an entry into the serialized chunk, using the compression it was saved with.
+    pub fn decode(entry: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let Some((tag, payload)) = entry.split_first() else {
+            bail!("empty chunk entry");
+        };
+
+        match tag {
+            0 => Ok(payload.to_vec()),
+            1 => Ok(zstd::decode_all(payload)?),
+            _ => bail!("unknown compression type, '{tag}'"),
+        }
+    }
+}
+
+/// A request sent to the disk I/O worker.
+#[derive(Debug)]
+pub enum IoRequest {
+    /// Read the chunk at the given position in a dimension from disk, if it has been saved.
+    Load(Dimension, glam::IVec2),
+    /// Write the given chunk of a dimension to disk, replacing any previous save of it.
+    Save(Dimension, Arc<Chunk>),
+    /// Finish all previously sent requests, then stop the worker.
+    Shutdown,
+}
+
+/// The result of a load request, being `None` if the chunk has never been saved.
+pub type IoResult = (Dimension, glam::IVec2, Option<Chunk>);
+
+/// Returns the path that the chunk at the given position in a dimension is saved to.
+pub fn chunk_path(directory: &Path, dimension: Dimension, position: IVec2) -> PathBuf {
+    directory
+        .join(dimension.name())
+        .join("chunks")
+        .join(format!("{}_{}.chunk", position.x, position.y))
+}
+
+/// Reads the chunk at the given position from disk, returning `None` if it has never been saved.
+pub fn read_chunk(
+    directory: &Path,
+    dimension: Dimension,
+    position: IVec2,
+) -> anyhow::Result<Option<Chunk>> {
+    let path = chunk_path(directory, dimension, position);
+
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error).context(format!("reading {path:?}")),
+    };
+
+    let chunk = Compression::decode(&bytes)
+        .and_then(|bytes| Chunk::from_bytes(&bytes))
+        .context(format!("decoding {path:?}"))?;
+
+    Ok(Some(chunk))
+}
+
+/// Writes a chunk to disk with the given compression. The chunk is written to a temporary file
+/// first, so a crash mid-write never leaves a corrupted save behind.
+pub fn write_chunk(
+    directory: &Path,
+    dimension: Dimension,
+    chunk: &Chunk,
+    compression: Compression,
+) -> anyhow::Result<()> {
+    let path = chunk_path(directory, dimension, chunk.position);
+    let temporary = path.with_extension("tmp");
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context(format!("creating {parent:?}"))?;
+    }
+
+    let entry = compression.encode(&chunk.to_bytes()?)?;
+
+    fs::write(&temporary, entry).context(format!("writing {temporary:?}"))?;
+    fs::rename(&temporary, &path).context(format!("replacing {path:?}"))?;
+
+    Ok(())
+}
+
+/// Runs the disk I/O worker, handling requests in the order they were sent until a
+/// `IoRequest::Shutdown` is received or the request channel disconnects. `pending_saves` is
+/// decremented after every save request is handled.
+pub fn run_io_worker(
+    directory: PathBuf,
+    compression: Compression,
+    requests: mpsc::Receiver<IoRequest>,
+    results: mpsc::Sender<IoResult>,
+    pending_saves: Arc<AtomicUsize>,
+) {
+    for request in requests {
+        match request {
+            IoRequest::Load(dimension, position) => {
+                let chunk = read_chunk(&directory, dimension, position).unwrap_or_else(|error| {
+                    eprintln!("could not load chunk {position}: {error:?}");
+                    None
+                });
+
+                if results.send((dimension, position, chunk)).is_err() {
+                    return;
+                }
+            }
+
+            IoRequest::Save(dimension, chunk) => {
+                if let Err(error) = write_chunk(&directory, dimension, &chunk, compression) {
+                    eprintln!("could not save chunk {}: {error:?}", chunk.position);
+                }
+
+                pending_saves.fetch_sub(1, Ordering::Relaxed);
+            }
+
+            IoRequest::Shutdown => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::ivec2;
+
+    use super::*;
+    use crate::chunk::Voxel;
+
+    /// Returns an empty directory to save chunks to, unique to the test calling it.
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("pig-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+
+        directory
+    }
+
+    /// Returns a chunk with a few voxels set, spread over two sections.
+    fn test_chunk() -> Chunk {
+        let mut chunk = Chunk::new(ivec2(4, -9));
+
+        chunk.set_voxel([0, 0, 0], Voxel::Bedrock);
+        chunk.set_voxel([3, 40, 12], Voxel::Water);
+        chunk.set_voxel([15, 47, 15], Voxel::Leaves);
+        chunk.decorated = true;
+
+        chunk
+    }
+
+    /// Writes the test chunk with the given compression and reads it back.
+    fn round_trip(name: &str, compression: Compression) {
+        let directory = test_directory(name);
+        let chunk = test_chunk();
+
+        write_chunk(&directory, Dimension::Overworld, &chunk, compression).unwrap();
+        let read = read_chunk(&directory, Dimension::Overworld, chunk.position).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        let read = read.expect("the chunk was saved");
+        assert_eq!(read.position, chunk.position);
+        assert_eq!(read.sections, chunk.sections);
+        assert_eq!(read.decorated, chunk.decorated);
+    }
+
+    #[test]
+    fn uncompressed_chunk_round_trips() {
+        round_trip("uncompressed", Compression::None);
+    }
+
+    #[test]
+    fn zstd_chunk_round_trips() {
+        round_trip(
+            "zstd",
+            Compression::Zstd {
+                level: DEFAULT_COMPRESSION_LEVEL,
+            },
+        );
+    }
+
+    #[test]
+    fn unsaved_chunk_is_none() {
+        let directory = test_directory("unsaved");
+
+        let read = read_chunk(&directory, Dimension::Overworld, ivec2(0, 0)).unwrap();
+        assert!(read.is_none());
+    }
+
+    #[test]
+    fn unknown_compression_is_rejected() {
+        let directory = test_directory("unknown-compression");
+        let chunk = test_chunk();
+
+        let mut entry = Compression::None
+            .encode(&chunk.to_bytes().unwrap())
+            .unwrap();
+        entry[0] = 7;
+
+        let path = chunk_path(&directory, Dimension::Overworld, chunk.position);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, entry).unwrap();
+
+        let read = read_chunk(&directory, Dimension::Overworld, chunk.position);
+        fs::remove_dir_all(&directory).unwrap();
+
+        let error = read.expect_err("the compression tag is unknown");
+        assert!(format!("{error:?}").contains("unknown compression type, '7'"));
+    }
+}
@@ -0,0 +1,130 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{bail, Context};
+use glam::{ivec3, IVec3, UVec3};
+use serde::Deserialize;
+
+use crate::{chunk::Voxel, generator::ChunkRegion};
+
+/// The directory structure templates are loaded from.
+pub const STRUCTURE_DIRECTORY: &str = "assets/structures";
+
+/// A structure template as written in a `.ron` file.
+#[derive(Debug, Deserialize)]
+struct StructureFile {
+    /// The voxel each character in `layers` stands for. Spaces are never in the palette, and
+    /// leave whatever was already in the world untouched.
+    palette: HashMap<char, Voxel>,
+    /// The horizontal layers of the structure from the bottom up, each being a list of rows
+    /// along the z axis, with each row's characters running along the x axis.
+    layers: Vec<Vec<String>>,
+}
+
+/// A reusable arrangement of voxels that can be stamped into the world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Structure {
+    /// The size of the structure along each axis.
+    size: UVec3,
+    /// The voxels of the structure, indexed by `(y * size.z + z) * size.x + x`. `None` leaves
+    /// the voxel in the world untouched.
+    voxels: Vec<Option<Voxel>>,
+}
+
+impl Structure {
+    /// Parses a structure template from the contents of a `.ron` file.
+    pub fn from_ron(source: &str) -> anyhow::Result<Self> {
+        let file: StructureFile = ron::from_str(source)?;
+
+        let height = file.layers.len();
+        let depth = file.layers.iter().map(Vec::len).max().unwrap_or(0);
+        let width = file
+            .layers
+            .iter()
+            .flatten()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let mut voxels = vec![None; width * height * depth];
+
+        for (y, layer) in file.layers.iter().enumerate() {
+            for (z, row) in layer.iter().enumerate() {
+                for (x, key) in row.chars().enumerate() {
+                    if key == ' ' {
+                        continue;
+                    }
+
+                    let Some(voxel) = file.palette.get(&key) else {
+                        bail!("'{key}' is not in the palette");
+                    };
+
+                    voxels[(y * depth + z) * width + x] = Some(*voxel);
+                }
+            }
+        }
+
+        Ok(Self {
+            size: UVec3::new(width as u32, height as u32, depth as u32),
+            voxels,
+        })
+    }
+
+    /// Loads the structure template saved at the given path.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source = fs::read_to_string(path).context(format!("reading {path:?}"))?;
+        Self::from_ron(&source).context(format!("parsing {path:?}"))
+    }
+
+    /// Returns the size of the structure along each axis.
+    pub fn size(&self) -> UVec3 {
+        self.size
+    }
+
+    /// Returns the world position and voxel of every voxel the structure sets when placed with
+    /// its minimum corner at `origin`.
+    pub fn voxels_at(&self, origin: IVec3) -> impl Iterator<Item = (IVec3, Voxel)> + '_ {
+        let size = self.size.as_ivec3();
+
+        self.voxels
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, voxel)| {
+                let index = index as i32;
+                let offset = ivec3(
+                    index % size.x,
+                    index / (size.x * size.z),
+                    index / size.x % size.z,
+                );
+
+                voxel.map(|voxel| (origin + offset, voxel))
+            })
+    }
+
+    /// Stamps the structure into a region during generation, with its minimum corner at
+    /// `origin`. Parts of the structure outside of the region are skipped.
+    pub fn place(&self, region: &mut ChunkRegion, origin: IVec3) {
+        for (position, voxel) in self.voxels_at(origin) {
+            region.set_block(position, voxel);
+        }
+    }
+}
+
+/// Loads every structure template in the given directory, keyed by its file name without the
+/// `.ron` extension.
+pub fn load_structures(directory: &Path) -> anyhow::Result<HashMap<String, Structure>> {
+    let entries = fs::read_dir(directory).context(format!("reading {directory:?}"))?;
+
+    entries
+        .filter_map(|entry| entry.map(|entry| entry.path()).ok())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .context(format!("invalid structure name, {path:?}"))?
+                .to_string();
+
+            Ok((name, Structure::load(&path)?))
+        })
+        .collect()
+}
@@ -1,12 +1,17 @@
 use image::{DynamicImage, GenericImageView};
 use wgpu::*;
 
+use crate::{
+    gpu_memory::{self, MemoryCategory, Tracked},
+    mipmap,
+};
+
 /// A texture uploaded to the GPU, which can represent image data or
 /// any arbitrary pixel data.
 #[derive(Debug)]
 pub struct Texture {
     /// The actual texture data uploaded to the GPU.
-    pub texture: wgpu::Texture,
+    pub texture: Tracked<wgpu::Texture>,
     /// A handle to the view into the texture (used during pipline creation).
     pub view: wgpu::TextureView,
     /// The sampler used to query data from the texture.
@@ -14,12 +19,13 @@ pub struct Texture {
 }
 
 impl Texture {
-    /// Creates a new texture from the given images. Each image represents a layer
-    /// in the array texture.
+    /// Creates a new texture of the given format from the given images. Each image represents a
+    /// layer in the array texture, with a full chain of mipmaps generated from it.
     pub fn from_images(
         device: &Device,
         queue: &Queue,
         images: &[DynamicImage],
+        format: TextureFormat,
         label: Option<&str>,
     ) -> anyhow::Result<Self> {
         let (width, height) = images[0].dimensions();
@@ -30,16 +36,23 @@ impl Texture {
             depth_or_array_layers: images.len() as u32,
         };
 
-        let texture = device.create_texture(&TextureDescriptor {
-            label,
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba8UnormSrgb,
-            usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        });
+        let texture = gpu_memory::create_texture(
+            device,
+            MemoryCategory::Textures,
+            &TextureDescriptor {
+                label,
+                size,
+                mip_level_count: mipmap::mip_level_count(width, height),
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                // the mipmaps are drawn into
+                usage: TextureUsages::COPY_DST
+                    | TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            },
+        );
 
         for (layer, image) in images.iter().enumerate() {
             let rgba = image.to_rgba8();
@@ -69,16 +82,10 @@ impl Texture {
             );
         }
 
+        mipmap::generate_mipmaps(device, queue, &texture, format);
+
         let view = texture.create_view(&TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&SamplerDescriptor {
-            address_mode_u: AddressMode::ClampToEdge,
-            address_mode_v: AddressMode::ClampToEdge,
-            address_mode_w: AddressMode::ClampToEdge,
-            mag_filter: FilterMode::Nearest,
-            min_filter: FilterMode::Nearest,
-            mipmap_filter: FilterMode::Nearest,
-            ..Default::default()
-        });
+        let sampler = Self::create_mipmap_sampler(device, 1);
 
         Ok(Self {
             texture,
@@ -87,48 +94,21 @@ impl Texture {
         })
     }
 
-    /// Creates a bind group and bind group layout to access the texture in the shader.
-    pub fn create_bind_group(&self, device: &Device) -> (BindGroupLayout, BindGroup) {
-        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: Some("Texture Bind Group Layout"),
-            entries: &[
-                BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Texture {
-                        sample_type: TextureSampleType::Float { filterable: true },
-                        view_dimension: TextureViewDimension::D2Array,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-                BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
-
-        let bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Texture Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[
-                // texture
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&self.view),
-                },
-                // sampler
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&self.sampler),
-                },
-            ],
-        });
-
-        (bind_group_layout, bind_group)
+    /// Creates a sampler blending between texels and mipmaps, sampling up to the given number
+    /// of times along surfaces seen at a glancing angle (anisotropic filtering), where 1 turns
+    /// anisotropic filtering off. Other amounts than 1, 2, 4, 8 and 16 are rounded up to one of
+    /// them.
+    pub fn create_mipmap_sampler(device: &Device, anisotropy: u16) -> Sampler {
+        device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            anisotropy_clamp: anisotropy.clamp(1, 16).next_power_of_two(),
+            ..Default::default()
+        })
     }
 
     /// Creates a depth texture.
@@ -141,16 +121,20 @@ impl Texture {
             depth_or_array_layers: 1,
         };
 
-        let texture = device.create_texture(&TextureDescriptor {
-            label: Some("Depth Texture"),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: TextureFormat::Depth32Float,
-            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        });
+        let texture = gpu_memory::create_texture(
+            device,
+            MemoryCategory::Depth,
+            &TextureDescriptor {
+                label: Some("Depth Texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Depth32Float,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+        );
 
         let view = texture.create_view(&TextureViewDescriptor::default());
         let sampler = device.create_sampler(&SamplerDescriptor {
@@ -172,4 +156,52 @@ impl Texture {
             sampler,
         }
     }
+
+    /// Creates a texture of the given format the size of the surface that can be rendered into,
+    /// then sampled by a later pass (like post-processing).
+    pub fn create_render_target(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        format: TextureFormat,
+    ) -> Self {
+        let (width, height) = (config.width, config.height);
+
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = gpu_memory::create_texture(
+            device,
+            MemoryCategory::RenderTargets,
+            &TextureDescriptor {
+                label: Some("Render Target Texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+        );
+
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
 }
@@ -0,0 +1,251 @@
+use std::collections::{HashSet, VecDeque};
+
+use glam::{ivec3, DVec3, IVec2, IVec3, Vec3Swizzles};
+
+use crate::{
+    camera::Frustum,
+    chunk::{Chunk, CHUNK_WIDTH, SECTIONS_PER_CHUNK, SECTION_HEIGHT},
+    model,
+};
+
+/// The size of a chunk section, in voxels.
+const SECTION_SIZE: IVec3 = IVec3::new(
+    CHUNK_WIDTH as i32,
+    SECTION_HEIGHT as i32,
+    CHUNK_WIDTH as i32,
+);
+
+/// A face of a chunk section, which the section may be seen into or out of through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionFace {
+    Up,
+    Down,
+    Right,
+    Left,
+    Front,
+    Back,
+}
+
+impl SectionFace {
+    /// Every face of a section.
+    pub const ALL: [SectionFace; 6] = [
+        Self::Up,
+        Self::Down,
+        Self::Right,
+        Self::Left,
+        Self::Front,
+        Self::Back,
+    ];
+
+    /// Returns the offset (in sections) to the neighboring section across this face.
+    pub fn offset(self) -> IVec3 {
+        match self {
+            Self::Up => ivec3(0, 1, 0),
+            Self::Down => ivec3(0, -1, 0),
+            Self::Right => ivec3(1, 0, 0),
+            Self::Left => ivec3(-1, 0, 0),
+            Self::Front => ivec3(0, 0, 1),
+            Self::Back => ivec3(0, 0, -1),
+        }
+    }
+
+    /// Returns the face on the opposite side of the section.
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+            Self::Right => Self::Left,
+            Self::Left => Self::Right,
+            Self::Front => Self::Back,
+            Self::Back => Self::Front,
+        }
+    }
+
+    /// Returns the bit representing this face in a set of faces.
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Which faces of a chunk section can be seen through to which other faces, that is which
+/// pairs of faces are joined by a path of voxels that can be seen through (air and water).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionVisibility(u64);
+
+impl SectionVisibility {
+    /// The visibility of a section that can be seen through from every face to every other.
+    pub const OPEN: SectionVisibility = SectionVisibility(u64::MAX);
+
+    /// Flood fills the voxels that can be seen through in the given section of the chunk,
+    /// connecting every pair of faces touched by the same region.
+    pub fn compute(chunk: &Chunk, section: usize) -> Self {
+        let Some(voxels) = &chunk.sections[section] else {
+            return Self::OPEN;
+        };
+
+        let index = |[x, y, z]: [usize; 3]| (y * CHUNK_WIDTH + z) * CHUNK_WIDTH + x;
+
+        let mut visited = vec![false; SECTION_HEIGHT * CHUNK_WIDTH * CHUNK_WIDTH];
+        let mut stack = Vec::new();
+        let mut visibility = Self(0);
+
+        for y in 0..SECTION_HEIGHT {
+            for z in 0..CHUNK_WIDTH {
+                for x in 0..CHUNK_WIDTH {
+                    if visited[index([x, y, z])] || voxels[y][z][x].is_opaque() {
+                        continue;
+                    }
+
+                    visited[index([x, y, z])] = true;
+                    stack.push([x, y, z]);
+
+                    let mut faces = 0u8;
+
+                    while let Some([x, y, z]) = stack.pop() {
+                        faces |= Self::faces_touched([x, y, z]);
+
+                        for face in SectionFace::ALL {
+                            let neighbor = ivec3(x as i32, y as i32, z as i32) + face.offset();
+
+                            if neighbor.cmplt(IVec3::ZERO).any()
+                                || neighbor.cmpge(SECTION_SIZE).any()
+                            {
+                                continue;
+                            }
+
+                            let [x, y, z] = neighbor.to_array().map(|n| n as usize);
+
+                            if !visited[index([x, y, z])] && !voxels[y][z][x].is_opaque() {
+                                visited[index([x, y, z])] = true;
+                                stack.push([x, y, z]);
+                            }
+                        }
+                    }
+
+                    visibility.connect_all(faces);
+                }
+            }
+        }
+
+        visibility
+    }
+
+    /// Returns whether the section can be seen through from face `a` to face `b`.
+    pub fn connects(&self, a: SectionFace, b: SectionFace) -> bool {
+        self.0 & (1 << (a as u64 * 6 + b as u64)) != 0
+    }
+
+    /// Connects every pair of faces in the given set of faces to each other.
+    fn connect_all(&mut self, faces: u8) {
+        for a in SectionFace::ALL {
+            for b in SectionFace::ALL {
+                if faces & a.bit() != 0 && faces & b.bit() != 0 {
+                    self.0 |= 1 << (a as u64 * 6 + b as u64);
+                }
+            }
+        }
+    }
+
+    /// Returns the set of faces the voxel at the given position within a section lies against.
+    fn faces_touched([x, y, z]: [usize; 3]) -> u8 {
+        let mut faces = 0;
+        let last = CHUNK_WIDTH - 1;
+
+        for (touching, face) in [
+            (y == SECTION_HEIGHT - 1, SectionFace::Up),
+            (y == 0, SectionFace::Down),
+            (x == last, SectionFace::Right),
+            (x == 0, SectionFace::Left),
+            (z == last, SectionFace::Front),
+            (z == 0, SectionFace::Back),
+        ] {
+            if touching {
+                faces |= face.bit();
+            }
+        }
+
+        faces
+    }
+}
+
+/// The visibility of each section of a chunk, from bottom to top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkVisibility {
+    pub sections: [SectionVisibility; SECTIONS_PER_CHUNK],
+}
+
+impl ChunkVisibility {
+    /// Computes the visibility of every section of the given chunk.
+    pub fn compute(chunk: &Chunk) -> Self {
+        Self {
+            sections: std::array::from_fn(|section| SectionVisibility::compute(chunk, section)),
+        }
+    }
+}
+
+/// Returns the position of the section containing the given world position, as the chunk's
+/// (x, z) position with the section's index as y.
+pub fn section_containing(position: DVec3) -> IVec3 {
+    (position / SECTION_SIZE.as_dvec3()).floor().as_ivec3()
+}
+
+/// Finds the sections that could be visible from a camera at `eye`, by walking outwards from
+/// the camera's section through the faces of each section that can be seen through, staying
+/// within the `frustum`. The walk never turns back towards the camera, so each section is only
+/// reached through sections lying between it and the camera. Sections of chunks whose
+/// visibility is unknown (`chunk_visibility` returns None) are never entered.
+///
+/// Returns None if the camera isn't within a section whose visibility is known, such as when
+/// it is above the world, in which case every section should be drawn.
+pub fn visible_sections<'a>(
+    eye: DVec3,
+    frustum: &Frustum,
+    chunk_visibility: impl Fn(IVec2) -> Option<&'a ChunkVisibility>,
+) -> Option<HashSet<IVec3>> {
+    let visibility_of = |section: IVec3| {
+        if !(0..SECTIONS_PER_CHUNK as i32).contains(&section.y) {
+            return None;
+        }
+
+        chunk_visibility(section.xz()).map(|chunk| chunk.sections[section.y as usize])
+    };
+
+    let start = section_containing(eye);
+    visibility_of(start)?;
+
+    let mut visible = HashSet::from([start]);
+    // each entry is a section, the face it was entered through and the directions walked to
+    // reach it
+    let mut queue = VecDeque::from([(start, None, 0u8)]);
+
+    while let Some((section, entered_through, directions)) = queue.pop_front() {
+        let visibility = visibility_of(section).unwrap_or(SectionVisibility::OPEN);
+
+        for face in SectionFace::ALL {
+            if directions & face.opposite().bit() != 0 {
+                continue;
+            }
+
+            if entered_through.is_some_and(|entered| !visibility.connects(entered, face)) {
+                continue;
+            }
+
+            let neighbor = section + face.offset();
+
+            if visible.contains(&neighbor) || visibility_of(neighbor).is_none() {
+                continue;
+            }
+
+            let min = model::offset_from(neighbor * SECTION_SIZE, eye);
+
+            if !frustum.intersects_aabb(min, min + SECTION_SIZE.as_vec3()) {
+                continue;
+            }
+
+            visible.insert(neighbor);
+            queue.push_back((neighbor, Some(face.opposite()), directions | face.bit()));
+        }
+    }
+
+    Some(visible)
+}
@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use winit::{
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    window::{Fullscreen, Window, WindowBuilder},
+};
+
+use crate::storage::DEFAULT_WORLD_DIRECTORY;
+
+/// The name of the config file the window state is saved to on exit.
+pub const WINDOW_STATE_FILE: &str = "window.toml";
+
+/// The size (in logical pixels) of the window when it is first opened.
+const DEFAULT_SIZE: LogicalSize<u32> = LogicalSize::new(1920, 1080);
+
+/// The state of the window and the world open in it when the app was last closed, saved to
+/// `WINDOW_STATE_FILE` so that the next launch picks up where it left off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowState {
+    /// The size (in physical pixels) of the window's contents while it isn't maximized or
+    /// fullscreen. `DEFAULT_SIZE` is used if not set.
+    pub size: Option<[u32; 2]>,
+    /// The position (in physical pixels) of the window's top left corner on the desktop while it
+    /// isn't maximized or fullscreen. The window is placed by the system if not set.
+    pub position: Option<[i32; 2]>,
+    /// Whether the window is maximized.
+    pub maximized: bool,
+    /// Whether the window covers the monitor it is on, without borders.
+    pub fullscreen: bool,
+    /// The directory of the world last opened, which is opened again unless another is chosen
+    /// with `--world`.
+    pub world: PathBuf,
+}
+
+impl Default for WindowState {
+    fn default() -> Self {
+        Self {
+            size: None,
+            position: None,
+            maximized: false,
+            fullscreen: false,
+            world: DEFAULT_WORLD_DIRECTORY.into(),
+        }
+    }
+}
+
+impl WindowState {
+    /// Returns a builder for a window restored to this state.
+    pub fn window_builder(&self) -> WindowBuilder {
+        let mut builder = WindowBuilder::new()
+            .with_maximized(self.maximized)
+            .with_fullscreen(self.fullscreen.then_some(Fullscreen::Borderless(None)));
+
+        builder = match self.size {
+            Some([width, height]) => builder.with_inner_size(PhysicalSize::new(width, height)),
+            None => builder.with_inner_size(DEFAULT_SIZE),
+        };
+
+        if let Some([x, y]) = self.position {
+            builder = builder.with_position(PhysicalPosition::new(x, y));
+        }
+
+        builder
+    }
+
+    /// Records the current state of the given window. Its size and position are only recorded
+    /// while it isn't maximized, fullscreen or minimized, so that it is restored to them once it
+    /// is none of those.
+    pub fn update(&mut self, window: &Window) {
+        self.maximized = window.is_maximized();
+        self.fullscreen = window.fullscreen().is_some();
+
+        let size = window.inner_size();
+
+        if self.maximized || self.fullscreen || size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        self.size = Some([size.width, size.height]);
+
+        // not every platform (such as Wayland) lets windows know where they are
+        if let Ok(position) = window.outer_position() {
+            self.position = Some([position.x, position.y]);
+        }
+    }
+}